@@ -0,0 +1,185 @@
+// The signed area accumulation in `CoverageAccumulator::line` and the curve flattening in
+// `CoverageAccumulator::quad` are adapted from the rasterizer in font-rs
+// (https://github.com/raphlinus/font-rs), which carries this notice:
+//
+// Copyright 2015 Google Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{Point, FillRule};
+
+/// Accumulates the signed area that line segments cover in each pixel of a bitmap. Summing the
+/// accumulated values along a row from left to right gives the winding weighted coverage of each
+/// pixel, so a whole outline can be drawn one segment at a time in any order
+pub struct CoverageAccumulator {
+    acc: Vec<f32>,
+    width: usize,
+    height: usize,
+    stride: usize
+}
+
+impl CoverageAccumulator {
+    pub fn new(width: usize, height: usize) -> CoverageAccumulator {
+        // segments are clamped to the right edge, which can put area into one column past it
+        let stride = width + 2;
+        CoverageAccumulator { acc: vec![0.0; stride * height], width, height, stride }
+    }
+
+    pub fn line(&mut self, p0: Point, p1: Point) {
+        if (p0.y - p1.y).abs() <= 1e-6 { return; }
+        let (dir, p0, p1) = if p0.y < p1.y { (1.0, p0, p1) } else { (-1.0, p1, p0) };
+        let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+        let w = self.width as f32;
+        let clamp = |x: f32| x.max(0.0).min(w);
+        let y_start = p0.y.max(0.0);
+        let mut x = p0.x + (y_start - p0.y) * dxdy;
+        for y in (y_start as usize)..self.height.min(p1.y.ceil().max(0.0) as usize) {
+            let row = y * self.stride;
+            let dy = ((y + 1) as f32).min(p1.y) - (y as f32).max(p0.y);
+            let xnext = x + dxdy * dy;
+            let d = dy * dir;
+            let (x0, x1) = if x < xnext { (clamp(x), clamp(xnext)) } else { (clamp(xnext), clamp(x)) };
+            let x0floor = x0.floor();
+            let x0i = x0floor as usize;
+            let x1ceil = x1.ceil();
+            let x1i = x1ceil as usize;
+            if x1i <= x0i + 1 {
+                // the segment stays within one pixel in this row
+                let xmf = 0.5 * (x0 + x1) - x0floor;
+                self.acc[row + x0i] += d - d * xmf;
+                self.acc[row + x0i + 1] += d * xmf;
+            } else {
+                // the segment crosses several pixels, the ones in the middle get an even share and
+                // the ends get the triangles left over
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+                self.acc[row + x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    self.acc[row + x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    self.acc[row + x0i + 1] += d * (a1 - a0);
+                    for xi in x0i + 2..x1i - 1 {
+                        self.acc[row + xi] += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    self.acc[row + x1i - 1] += d * (1.0 - a2 - am);
+                }
+                self.acc[row + x1i] += d * am;
+            }
+            x = xnext;
+        }
+    }
+
+    /// Approximates a quadratic curve with enough line segments that the error is well under a pixel
+    pub fn quad(&mut self, p0: Point, p1: Point, p2: Point) {
+        let ddx = p0.x - 2.0 * p1.x + p2.x;
+        let ddy = p0.y - 2.0 * p1.y + p2.y;
+        let devsq = ddx * ddx + ddy * ddy;
+        if devsq < 0.333 {
+            self.line(p0, p2);
+            return;
+        }
+        let n = 1 + (3.0 * devsq).sqrt().sqrt().floor() as usize;
+        let mut last = p0;
+        for i in 1..n {
+            let t = i as f32 / n as f32;
+            let mt = 1.0 - t;
+            let p = Point::new(mt * mt * p0.x + 2.0 * mt * t * p1.x + t * t * p2.x,
+                               mt * mt * p0.y + 2.0 * mt * t * p1.y + t * t * p2.y);
+            self.line(last, p);
+            last = p;
+        }
+        self.line(last, p2);
+    }
+
+    /// Sums up each row and calls `f` with the position and coverage of every pixel that is at
+    /// least partly covered
+    pub fn accumulate<F: FnMut(usize, usize, f32)>(&self, fill_rule: FillRule, mut f: F) {
+        for y in 0..self.height {
+            let mut sum = 0.0;
+            for x in 0..self.width {
+                sum += self.acc[y * self.stride + x];
+                let coverage = match fill_rule {
+                    FillRule::NonZero => sum.abs().min(1.0),
+                    // fold the winding weighted area back into 0..1, so that a pixel covered by
+                    // two contours is empty and one covered by three is full again
+                    FillRule::EvenOdd => {
+                        let c = sum.abs() % 2.0;
+                        if c > 1.0 { 2.0 - c } else { c }
+                    }
+                };
+                if coverage > 0.0 {
+                    f(x, y, coverage);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_coverage() {
+        // a square over the middle of a 3x3 bitmap, offset by half a pixel
+        let mut acc = CoverageAccumulator::new(3, 3);
+        let corners = [Point::new(0.5, 0.5), Point::new(2.5, 0.5), Point::new(2.5, 2.5), Point::new(0.5, 2.5)];
+        for i in 0..4 {
+            acc.line(corners[i], corners[(i + 1) % 4]);
+        }
+        let mut cov = [[0.0; 3]; 3];
+        acc.accumulate(FillRule::NonZero, |x, y, c| cov[y][x] = c);
+        let expected = [[0.25, 0.5, 0.25], [0.5, 1.0, 0.5], [0.25, 0.5, 0.25]];
+        for y in 0..3 {
+            for x in 0..3 {
+                assert!((cov[y][x] - expected[y][x]).abs() < 1e-4, "{:?}", cov);
+            }
+        }
+    }
+
+    #[test]
+    fn clipped_coverage() {
+        // a triangle that hangs off every edge of the bitmap still fills what is inside
+        let mut acc = CoverageAccumulator::new(4, 4);
+        let corners = [Point::new(-10.0, -10.0), Point::new(20.0, -10.0), Point::new(-10.0, 20.0)];
+        for i in 0..3 {
+            acc.quad(corners[i], corners[i], corners[(i + 1) % 3]);
+        }
+        let mut total = 0.0;
+        acc.accumulate(FillRule::NonZero, |_, _, c| total += c);
+        assert!((total - 16.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn overlapping_coverage() {
+        // two squares going the same way that overlap in the middle column
+        let mut acc = CoverageAccumulator::new(3, 1);
+        for &(x0, x1) in &[(0.0, 2.0), (1.0, 3.0)] {
+            let corners = [Point::new(x0, 0.0), Point::new(x1, 0.0), Point::new(x1, 1.0), Point::new(x0, 1.0)];
+            for i in 0..4 {
+                acc.line(corners[i], corners[(i + 1) % 4]);
+            }
+        }
+        let mut non_zero = [0.0; 3];
+        acc.accumulate(FillRule::NonZero, |x, _, c| non_zero[x] = c);
+        assert_eq!(non_zero, [1.0, 1.0, 1.0]);
+        let mut even_odd = [0.0; 3];
+        acc.accumulate(FillRule::EvenOdd, |x, _, c| even_odd[x] = c);
+        assert_eq!(even_odd, [1.0, 0.0, 1.0]);
+    }
+}
@@ -7,6 +7,10 @@ pub enum ScalerError {
     MissingTable(TableTag),
     InvalidInstruction(usize, u8),
     StackUnderflow(usize),
+    InvalidReference(usize),
+    InvalidJump(usize),
+    CallDepthExceeded(usize),
+    DivideByZero(usize),
     InvalidGlyph
 }
 
@@ -16,6 +20,10 @@ impl Error for ScalerError {
             &ScalerError::MissingTable(_) => "missing font data table",
             &ScalerError::InvalidInstruction(_,_) => "invalid instruction encountered",
             &ScalerError::StackUnderflow(_) => "stack underflow",
+            &ScalerError::InvalidReference(_) => "reference to a point, contour, zone, function or CVT entry that does not exist",
+            &ScalerError::InvalidJump(_) => "jump outside of the instructions",
+            &ScalerError::CallDepthExceeded(_) => "functions nested too deeply",
+            &ScalerError::DivideByZero(_) => "division by zero",
             &ScalerError::InvalidGlyph => "glyph data invalid"
         }
     }
@@ -27,6 +35,10 @@ impl std::fmt::Display for ScalerError {
             &ScalerError::MissingTable(t) => write!(f, "missing font data table {:?}", t),
             &ScalerError::InvalidInstruction(pc, istr) => write!(f, "invalid instruction at {:x}, code: {:2x}", pc, istr),
             &ScalerError::StackUnderflow(pc) => write!(f, "stack underflow at {:x}", pc),
+            &ScalerError::InvalidReference(pc) => write!(f, "invalid point, contour, zone, function or CVT reference at {:x}", pc),
+            &ScalerError::InvalidJump(pc) => write!(f, "jump outside of the instructions at {:x}", pc),
+            &ScalerError::CallDepthExceeded(pc) => write!(f, "functions nested too deeply at {:x}", pc),
+            &ScalerError::DivideByZero(pc) => write!(f, "division by zero at {:x}", pc),
             &ScalerError::InvalidGlyph => write!(f, "glyph data invalid"),
             _ => write!(f, "{}", self.description())
         }
@@ -47,8 +59,110 @@ impl Vector {
         let len = self.len();
         p.x * self.x / len + p.y * self.y / len
     }
+
+    /// Cosine of the angle between two vectors
+    fn dot(&self, othr: &Vector) -> f32 {
+        (self.x*othr.x + self.y*othr.y) / (self.len() * othr.len())
+    }
+
+    /// Unit vector from `a` to `b`, turned a quarter anticlockwise if `perpendicular`. Points on
+    /// top of each other give the x-axis
+    fn along(a: Point, b: Point, perpendicular: bool) -> Vector {
+        let v = Vector { x: b.x - a.x, y: b.y - a.y };
+        let len = v.len();
+        if len == 0.0 {
+            return Vector { x: 1.0, y: 0.0 };
+        }
+        let (x, y) = (v.x / len, v.y / len);
+        if perpendicular { Vector { x: -y, y: x } } else { Vector { x, y } }
+    }
+}
+
+bitflags! {
+    flags Touched: u8 {
+        const TOUCHED_X = 0b01,
+        const TOUCHED_Y = 0b10,
+    }
+}
+
+/// Points the instructions can move. Zone 0 is the twilight zone, which only exists for the
+/// instructions to use, and zone 1 holds the glyph being hinted followed by its phantom points
+#[derive(Debug, Clone, Default)]
+struct Zone {
+    current: Vec<Point>,
+    original: Vec<Point>,
+    touched: Vec<Touched>,
+    on_curve: Vec<bool>,
+    contour_ends: Vec<usize>
+}
+
+impl Zone {
+    fn new(points: Vec<Point>, on_curve: Vec<bool>, contour_ends: Vec<usize>) -> Zone {
+        Zone {
+            original: points.clone(),
+            touched: vec![Touched::empty(); points.len()],
+            current: points,
+            on_curve, contour_ends
+        }
+    }
+
+    fn twilight(len: usize) -> Zone {
+        Zone::new(vec![Point::new(0.0, 0.0); len], vec![true; len], Vec::new())
+    }
+}
+
+/// How distances get rounded to the pixel grid
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum RoundState {
+    ToHalfGrid,
+    ToGrid,
+    ToDoubleGrid,
+    DownToGrid,
+    UpToGrid,
+    Off,
+    /// Set by SROUND and S45ROUND: distances round to `phase` plus a multiple of `period`, moving
+    /// up to the next one once they are `threshold` past the one below. All in pixels
+    Super { period: f32, phase: f32, threshold: f32 }
 }
 
+impl RoundState {
+    /// Decodes the SROUND and S45ROUND selector byte against a grid of `grid_period` pixels
+    fn super_round(selector: u32, grid_period: f32) -> RoundState {
+        let period = match selector & 0xc0 {
+            0x00 => grid_period / 2.0,
+            0x80 => grid_period * 2.0,
+            _ => grid_period
+        };
+        let phase = period * ((selector & 0x30) >> 4) as f32 / 4.0;
+        let threshold = match selector & 0x0f {
+            0 => period - 1.0 / 64.0,
+            t => (t as f32 - 4.0) * period / 8.0
+        };
+        RoundState::Super { period, phase, threshold }
+    }
+
+    /// Rounds a distance after adding the engine compensation, without ever changing its sign
+    fn round(&self, d: f32, compensation: f32) -> f32 {
+        let magnitude = d.abs() + compensation;
+        let (rounded, least) = match *self {
+            RoundState::ToHalfGrid => (magnitude.floor() + 0.5, 0.5),
+            RoundState::ToGrid => ((magnitude + 0.5).floor(), 0.0),
+            RoundState::ToDoubleGrid => ((magnitude * 2.0 + 0.5).floor() / 2.0, 0.0),
+            RoundState::DownToGrid => (magnitude.floor(), 0.0),
+            RoundState::UpToGrid => (magnitude.ceil(), 0.0),
+            RoundState::Off => (magnitude, 0.0),
+            RoundState::Super { period, phase, threshold } =>
+                (((magnitude - phase + threshold) / period).floor() * period + phase, phase)
+        };
+        let rounded = if rounded < 0.0 { least } else { rounded };
+        if d >= 0.0 { rounded } else { -rounded }
+    }
+}
+
+/// What the rasterizer adds to distances of each type (gray, black, white and the reserved fourth)
+/// to make up for how it draws them. Outlines here are filled exactly, so nothing needs adding
+const ENGINE_COMPENSATION: [f32; 4] = [0.0; 4];
+
 use std::collections::{HashMap};
 
 #[derive(Debug, Clone)]
@@ -59,23 +173,30 @@ struct InterpState {
     delta_shift: u32,
     dual_prj_vec: Vector,
     freedom_vec: Vector,
+    /// Set by the pre-program to stop glyph programs running
     instruct_ctrl: bool,
     loopv: u32,
     min_dist: f32,
     project_vec: Vector,
-    round_state: u32,
+    round_state: RoundState,
     rp: [usize; 3],
-    scan_ctrl: bool,
+    /// Dropout control settings from SCANCTRL and SCANTYPE. Outlines are filled without dropout
+    /// control, so these are only kept for the program to set
+    scan_ctrl: u32,
+    scan_type: u32,
     single_width_cut_in: f32,
     single_width_value: f32,
     zp: [usize; 3],
-    twilight_zone: Vec<Point>,
-    cv_table: Vec<i16>,
-    functions: HashMap<u32, Vec<u8>>
+    twilight: Zone,
+    /// Control values scaled to the current size, in 26.6 fixed point pixels
+    cv_table: Vec<i32>,
+    storage: Vec<u32>,
+    functions: HashMap<u32, Vec<u8>>,
+    instruction_defs: HashMap<u8, Vec<u8>>
 }
 
 impl InterpState {
-    fn new(cv_table: Vec<i16>) -> InterpState {
+    fn new(cv_table: Vec<i32>, twilight_points: usize, storage_len: usize) -> InterpState {
         InterpState {
             auto_flip: true,
             cvt_cutin: 17.0 / 16.0,
@@ -87,45 +208,80 @@ impl InterpState {
             loopv: 1,
             min_dist: 1.0,
             project_vec: Vector { x: 1.0, y: 0.0 },
-            round_state: 1,
+            round_state: RoundState::ToGrid,
             rp: [0,0,0],
-            scan_ctrl: false,
+            scan_ctrl: 0,
+            scan_type: 0,
             single_width_cut_in: 0.0,
             single_width_value: 0.0,
             zp: [1,1,1],
-            twilight_zone: Vec::new(),
+            twilight: Zone::twilight(twilight_points),
             cv_table,
-            functions: HashMap::new()
+            storage: vec![0; storage_len],
+            functions: HashMap::new(),
+            instruction_defs: HashMap::new()
         }
     }
+
+    /// Puts back the vectors, zone and reference pointers and loop count that each glyph program
+    /// starts from. Everything else the pre-program sets, like the round state, carries over
+    fn reset_glyph_defaults(&mut self) {
+        self.dual_prj_vec = Vector { x: 1.0, y: 0.0 };
+        self.freedom_vec = Vector { x: 1.0, y: 0.0 };
+        self.project_vec = Vector { x: 1.0, y: 0.0 };
+        self.loopv = 1;
+        self.rp = [0,0,0];
+        self.zp = [1,1,1];
+    }
 }
 
 
 fn sign_extend(v: u16) -> u32 { 
-    0
+    v as i16 as i32 as u32
+}
+
+/// How many calls deep functions and instruction definitions may go before the program is
+/// assumed to recurse forever
+const MAX_CALL_DEPTH: usize = 32;
+
+/// Length of the instruction at `pc`, including any data pushed inline after it
+fn instruction_len(instructions: &[u8], pc: usize) -> Result<usize, ScalerError> {
+    let op = instructions[pc];
+    let len = match op {
+        0x40 => 2 + *instructions.get(pc+1).ok_or(ScalerError::InvalidInstruction(pc, op))? as usize,
+        0x41 => 2 + *instructions.get(pc+1).ok_or(ScalerError::InvalidInstruction(pc, op))? as usize * 2,
+        0xb0 ..= 0xb7 => 1 + (op - 0xaf) as usize,
+        0xb8 ..= 0xbf => 1 + (op - 0xb7) as usize * 2,
+        _ => 1
+    };
+    if pc + len > instructions.len() {
+        Err(ScalerError::InvalidInstruction(pc, op))
+    } else {
+        Ok(len)
+    }
 }
 
 struct Interp<'s, 'p> {
     stack: Vec<u32>,
     pc: usize,
     state: &'s mut InterpState,
-    original_points: Vec<Point>,
-    points: &'p mut Vec<Point>,
-    uniform_scale: f32,
+    glyph: &'p mut Zone,
+    call_depth: usize,
+    /// Converts font units to 26.6 pixels
+    scale: Fixed,
     units_per_em: f32,
     point_size: f32,
 }
 
 impl<'s, 'p> Interp<'s, 'p> {
-    fn new<'f>(scaler: &'s mut InstructedGlyphScaler<'f>, points: &'p mut Vec<Point>) -> Interp<'s, 'p> {
-        let op = points.clone();
-        let uniform_scale = scaler.uniform_scale();
+    fn new<'f>(scaler: &'s mut InstructedGlyphScaler<'f>, glyph: &'p mut Zone) -> Interp<'s, 'p> {
+        let scale = scaler.funits_scale();
         Interp {
             stack: Vec::new(),
             pc: 0,
-            state: &mut scaler.state, points,
-            original_points: op,
-            uniform_scale,
+            state: &mut scaler.state, glyph,
+            call_depth: 0,
+            scale,
             units_per_em: scaler.units_per_em,
             point_size: scaler.point_size
         }
@@ -140,16 +296,24 @@ impl<'s, 'p> Interp<'s, 'p> {
         self.stack.pop().ok_or(ScalerError::StackUnderflow(self.pc))
     }
     fn pop_f26dot6(&mut self) -> Result<F26d6, ScalerError> {
-        self.stack.pop().map(|x| F26d6::from(x as i32)).ok_or(ScalerError::StackUnderflow(self.pc))
+        self.stack.pop().map(|x| F26d6::from_bits(x as i32)).ok_or(ScalerError::StackUnderflow(self.pc))
+    }
+    /// Pops a 2.14 vector component
+    fn pop_f2dot14(&mut self) -> Result<f32, ScalerError> {
+        Ok(F2dot14::new(self.pop()? as i16).bits as f32 / 16384.0)
+    }
+    fn push_f2dot14(&mut self, v: f32) {
+        self.push(F2dot14::new((v * 16384.0).round() as i16).bits as i32 as u32)
     }
 
     fn push(&mut self, v: u32) {
         self.stack.push(v);
     }
 
-    fn compare<F: FnOnce(u32,u32)->bool>(&mut self, f: F) -> Result<(), ScalerError> {
-        let (a,b) = (self.pop()?, self.pop()?);
-        self.push(if f(a,b) { 1 } else { 0 });
+    /// Pops e2 then e1 and pushes whether `f(e1, e2)` holds, comparing them as signed values
+    fn compare<F: FnOnce(i32,i32)->bool>(&mut self, f: F) -> Result<(), ScalerError> {
+        let (e2, e1) = (self.pop()? as i32, self.pop()? as i32);
+        self.push(if f(e1, e2) { 1 } else { 0 });
         Ok(())
     }
 
@@ -163,219 +327,790 @@ impl<'s, 'p> Interp<'s, 'p> {
     }
     fn push_words(&mut self, n: usize, instructions: &Vec<u8>) -> Result<(), ScalerError> {
         println!("reading {} words", n);
-        for i in self.pc+1..self.pc+n*2+1 {
-            self.push(sign_extend((instructions[i] as u16) << 8 | instructions[i+1] as u16));
+        for i in 0..n {
+            let at = self.pc + 1 + i*2;
+            self.push(sign_extend((instructions[at] as u16) << 8 | instructions[at+1] as u16));
         }
         self.pc += n*2;
         Ok(())
     }
 
+    /// Pops a 26.6 fixed point distance, in pixels
+    fn pop_pixels(&mut self) -> Result<f32, ScalerError> {
+        Ok(self.pop_f26dot6()?.into())
+    }
+    fn push_pixels(&mut self, d: f32) {
+        self.push(F26d6::from(d).into())
+    }
+
+    fn scale_funits(&self, v: i32) -> F26d6 {
+        F26d6::from_bits(self.scale.mul_int(v))
+    }
+
+    fn pop_zone(&mut self) -> Result<usize, ScalerError> {
+        match self.pop()? {
+            z @ 0..=1 => Ok(z as usize),
+            _ => Err(ScalerError::InvalidReference(self.pc))
+        }
+    }
+
+    fn zone(&self, z: usize) -> &Zone {
+        if z == 0 { &self.state.twilight } else { &*self.glyph }
+    }
+    fn zone_mut(&mut self, z: usize) -> &mut Zone {
+        if z == 0 { &mut self.state.twilight } else { &mut *self.glyph }
+    }
+
+    fn point(&self, z: usize, p: usize) -> Result<Point, ScalerError> {
+        self.zone(z).current.get(p).cloned().ok_or(ScalerError::InvalidReference(self.pc))
+    }
+    fn original(&self, z: usize, p: usize) -> Result<Point, ScalerError> {
+        self.zone(z).original.get(p).cloned().ok_or(ScalerError::InvalidReference(self.pc))
+    }
+
+    /// First and last point of a contour
+    fn contour(&self, z: usize, c: usize) -> Result<(usize, usize), ScalerError> {
+        let ends = &self.zone(z).contour_ends;
+        let end = *ends.get(c).ok_or(ScalerError::InvalidReference(self.pc))?;
+        Ok((if c == 0 { 0 } else { ends[c-1] + 1 }, end))
+    }
+
+    /// Places a twilight point in both the original and the current outline
+    fn set_twilight(&mut self, p: usize, at: Point) -> Result<(), ScalerError> {
+        let pc = self.pc;
+        let zone = &mut self.state.twilight;
+        if p >= zone.current.len() {
+            return Err(ScalerError::InvalidReference(pc));
+        }
+        zone.original[p] = at;
+        zone.current[p] = at;
+        Ok(())
+    }
+
+    fn cvt(&self, n: usize) -> Result<f32, ScalerError> {
+        self.state.cv_table.get(n).map(|&v| v as f32 / 64.0).ok_or(ScalerError::InvalidReference(self.pc))
+    }
+
+    fn write_cvt(&mut self, n: usize, v: i32) -> Result<(), ScalerError> {
+        let pc = self.pc;
+        *self.state.cv_table.get_mut(n).ok_or(ScalerError::InvalidReference(pc))? = v;
+        Ok(())
+    }
+
+    /// The storage location popped off the stack
+    fn pop_storage(&mut self) -> Result<usize, ScalerError> {
+        let n = self.pop()? as usize;
+        if n < self.state.storage.len() { Ok(n) } else { Err(ScalerError::InvalidReference(self.pc)) }
+    }
+
+    /// Where the `k`th value down the stack is, counting the top as 1
+    fn stack_index(&self, k: u32) -> Result<usize, ScalerError> {
+        let l = self.stack.len();
+        if k == 0 || k as usize > l { Err(ScalerError::StackUnderflow(self.pc)) } else { Ok(l - k as usize) }
+    }
+
+    /// Pixels per em at the current size
+    fn ppem(&self) -> u32 {
+        let scale: f32 = self.scale.into();
+        (scale * self.units_per_em / 64.0).round() as u32
+    }
+
+    /// Pops the two points SPVTL, SFVTL and SDPVTL take a line through, p2 in zp2 and then p1 in
+    /// zp1, and gives the direction from p2 to p1 in the current and the original outline
+    fn pop_line(&mut self, perpendicular: bool) -> Result<(Vector, Vector), ScalerError> {
+        let (p2, p1) = (self.pop()? as usize, self.pop()? as usize);
+        let (z1, z2) = (self.state.zp[1], self.state.zp[2]);
+        let current = Vector::along(self.point(z2, p2)?, self.point(z1, p1)?, perpendicular);
+        let original = Vector::along(self.original(z2, p2)?, self.original(z1, p1)?, perpendicular);
+        Ok((current, original))
+    }
+
+    /// Pops a count and that many exceptions for DELTAP and DELTAC, each a point or control value
+    /// number then an argument. The argument's top four bits pick the size it applies at, counting
+    /// from the delta base plus `base`, and the bottom four how far to move in delta shift steps
+    fn deltas<F>(&mut self, base: u32, mut f: F) -> Result<(), ScalerError>
+        where F: FnMut(&mut Interp<'s, 'p>, usize, f32) -> Result<(), ScalerError> {
+        let n = self.pop()?;
+        let ppem = self.ppem();
+        for _ in 0..n {
+            let (target, arg) = (self.pop()? as usize, self.pop()?);
+            // the delta base is whatever the program set, so it can be big enough to overflow
+            if self.state.delta_base as i64 + base as i64 + (arg >> 4 & 0xf) as i64 != ppem as i64 {
+                continue;
+            }
+            // steps run from -8 to 8, skipping 0
+            let step = (arg & 0xf) as i32 - 8;
+            let step = if step >= 0 { step + 1 } else { step };
+            f(self, target, step as f32 / (1 << self.state.delta_shift.min(6)) as f32)?;
+        }
+        Ok(())
+    }
+
+    /// Distance from `a` to `b` in the current outline, along the projection vector
+    fn project(&self, a: Point, b: Point) -> f32 {
+        self.state.project_vec.project(Point::new(b.x - a.x, b.y - a.y))
+    }
+
+    /// Distance from `a` to `b` in the original outline, along the dual projection vector if one is set
+    fn dual_project(&self, a: Point, b: Point) -> f32 {
+        let v = if self.state.dual_prj_vec.len() == 0.0 { self.state.project_vec } else { self.state.dual_prj_vec };
+        v.project(Point::new(b.x - a.x, b.y - a.y))
+    }
+
+    /// Rounds a distance of the given type (the low two bits of MDRP, MIRP, ROUND and NROUND)
+    /// in the current round state
+    fn round(&self, d: f32, distance_type: u8) -> f32 {
+        self.state.round_state.round(d, ENGINE_COMPENSATION[(distance_type & 3) as usize])
+    }
+
+    /// Pops a distance for EVEN and ODD, rounds it in the current round state and gives the lowest
+    /// bit of the whole pixels in the rounded 26.6 value
+    fn pop_rounded_parity(&mut self) -> Result<i32, ScalerError> {
+        let d = self.pop_pixels()?;
+        let rounded = F26d6::from(self.round(d, 0));
+        Ok((rounded.to_bits() >> 6) & 1)
+    }
+
+    /// Only compensates a distance of the given type, for when it isn't rounded
+    fn compensate(&self, d: f32, distance_type: u8) -> f32 {
+        RoundState::Off.round(d, ENGINE_COMPENSATION[(distance_type & 3) as usize])
+    }
+
+    /// Snaps a distance onto the single width value when it is within the single width cut-in
+    fn single_width(&self, d: f32) -> f32 {
+        let w = self.state.single_width_value;
+        if (d.abs() - w).abs() < self.state.single_width_cut_in {
+            if d >= 0.0 { w } else { -w }
+        } else {
+            d
+        }
+    }
+
+    /// Keeps a distance at least the minimum distance long, on the side of the original distance
+    fn minimum_distance(&self, original: f32, d: f32) -> f32 {
+        if original >= 0.0 { d.max(self.state.min_dist) } else { d.min(-self.state.min_dist) }
+    }
+
+    /// The axes a point moved along the freedom vector counts as touched in
+    fn freedom_axes(&self) -> Touched {
+        let mut t = Touched::empty();
+        if self.state.freedom_vec.x != 0.0 { t |= TOUCHED_X; }
+        if self.state.freedom_vec.y != 0.0 { t |= TOUCHED_Y; }
+        t
+    }
+
+    /// How far a point moves along the freedom vector to cover `distance` along the projection vector
+    fn displacement(&self, distance: f32) -> (f32, f32) {
+        let (fv, pv) = (self.state.freedom_vec, self.state.project_vec);
+        let dot = fv.dot(&pv);
+        // nearly perpendicular vectors would throw the point far away, so treat them as parallel
+        let dot = if dot.abs() < 1.0 / 16.0 { 1.0 } else { dot };
+        let l = fv.len();
+        (distance * fv.x / (l * dot), distance * fv.y / (l * dot))
+    }
+
+    fn shift_point(&mut self, z: usize, p: usize, dx: f32, dy: f32, touch: Touched) -> Result<(), ScalerError> {
+        let pc = self.pc;
+        let zone = self.zone_mut(z);
+        if p >= zone.current.len() {
+            return Err(ScalerError::InvalidReference(pc));
+        }
+        zone.current[p].x += dx;
+        zone.current[p].y += dy;
+        zone.touched[p] |= touch;
+        Ok(())
+    }
+
+    /// Moves a point along the freedom vector until it has moved `distance` along the projection
+    /// vector, and marks it touched
+    fn move_point(&mut self, z: usize, p: usize, distance: f32) -> Result<(), ScalerError> {
+        let (dx, dy) = self.displacement(distance);
+        let touch = self.freedom_axes();
+        self.shift_point(z, p, dx, dy, touch)
+    }
+
+    /// Pops `loopv` points and runs `f` on each, then resets the loop counter as SLOOP asks
+    fn for_each_looped<F>(&mut self, mut f: F) -> Result<(), ScalerError>
+        where F: FnMut(&mut Interp<'s, 'p>, usize) -> Result<(), ScalerError> {
+        for _ in 0..self.state.loopv {
+            let p = self.pop()? as usize;
+            f(self, p)?;
+        }
+        self.state.loopv = 1;
+        Ok(())
+    }
+
+    fn mdap(&mut self, round: bool) -> Result<(), ScalerError> {
+        let p = self.pop()? as usize;
+        let z = self.state.zp[0];
+        let d = if round {
+            let cur = self.state.project_vec.project(self.point(z, p)?);
+            self.round(cur, 0) - cur
+        } else {
+            0.0
+        };
+        self.move_point(z, p, d)?;
+        self.state.rp[0] = p;
+        self.state.rp[1] = p;
+        Ok(())
+    }
+
+    fn miap(&mut self, round: bool) -> Result<(), ScalerError> {
+        let n = self.pop()? as usize;
+        let p = self.pop()? as usize;
+        let z = self.state.zp[0];
+        let mut d = self.cvt(n)?;
+        if z == 0 {
+            let fv = self.state.freedom_vec;
+            self.set_twilight(p, Point::new(d * fv.x / fv.len(), d * fv.y / fv.len()))?;
+        }
+        let cur = self.state.project_vec.project(self.point(z, p)?);
+        if round {
+            if (d - cur).abs() > self.state.cvt_cutin {
+                d = cur;
+            }
+            d = self.round(d, 0);
+        }
+        self.move_point(z, p, d - cur)?;
+        self.state.rp[0] = p;
+        self.state.rp[1] = p;
+        Ok(())
+    }
+
+    /// MDRP and MIRP flags: 0x10 sets rp0 to the point, 0x08 keeps the minimum distance and 0x04 rounds
+    fn mdrp(&mut self, flags: u8) -> Result<(), ScalerError> {
+        let p = self.pop()? as usize;
+        let (z0, z1, rp0) = (self.state.zp[0], self.state.zp[1], self.state.rp[0]);
+        let org = self.single_width(self.dual_project(self.original(z0, rp0)?, self.original(z1, p)?));
+        let mut d = if flags & 0x04 != 0 { self.round(org, flags) } else { self.compensate(org, flags) };
+        if flags & 0x08 != 0 {
+            d = self.minimum_distance(org, d);
+        }
+        let cur = self.project(self.point(z0, rp0)?, self.point(z1, p)?);
+        self.move_point(z1, p, d - cur)?;
+        self.state.rp[1] = rp0;
+        self.state.rp[2] = p;
+        if flags & 0x10 != 0 {
+            self.state.rp[0] = p;
+        }
+        Ok(())
+    }
+
+    fn mirp(&mut self, flags: u8) -> Result<(), ScalerError> {
+        let n = self.pop()? as usize;
+        let p = self.pop()? as usize;
+        let (z0, z1, rp0) = (self.state.zp[0], self.state.zp[1], self.state.rp[0]);
+        let mut cvt = self.single_width(self.cvt(n)?);
+        if z1 == 0 {
+            let (o, fv) = (self.original(z0, rp0)?, self.state.freedom_vec);
+            self.set_twilight(p, Point::new(o.x + cvt * fv.x / fv.len(), o.y + cvt * fv.y / fv.len()))?;
+        }
+        let org = self.dual_project(self.original(z0, rp0)?, self.original(z1, p)?);
+        let cur = self.project(self.point(z0, rp0)?, self.point(z1, p)?);
+        if self.state.auto_flip && (org < 0.0) != (cvt < 0.0) {
+            cvt = -cvt;
+        }
+        let mut d = if flags & 0x04 != 0 {
+            if z0 == z1 && (cvt - org).abs() > self.state.cvt_cutin {
+                cvt = org;
+            }
+            self.round(cvt, flags)
+        } else {
+            self.compensate(cvt, flags)
+        };
+        if flags & 0x08 != 0 {
+            d = self.minimum_distance(org, d);
+        }
+        self.move_point(z1, p, d - cur)?;
+        self.state.rp[1] = rp0;
+        self.state.rp[2] = p;
+        if flags & 0x10 != 0 {
+            self.state.rp[0] = p;
+        }
+        Ok(())
+    }
+
+    fn msirp(&mut self, set_rp0: bool) -> Result<(), ScalerError> {
+        let d = self.pop_pixels()?;
+        let p = self.pop()? as usize;
+        let (z0, z1, rp0) = (self.state.zp[0], self.state.zp[1], self.state.rp[0]);
+        if z1 == 0 {
+            let o = self.original(z0, rp0)?;
+            self.set_twilight(p, o)?;
+        }
+        let cur = self.project(self.point(z0, rp0)?, self.point(z1, p)?);
+        self.move_point(z1, p, d - cur)?;
+        self.state.rp[1] = rp0;
+        self.state.rp[2] = p;
+        if set_rp0 {
+            self.state.rp[0] = p;
+        }
+        Ok(())
+    }
+
+    fn align_points(&mut self) -> Result<(), ScalerError> {
+        let p2 = self.pop()? as usize;
+        let p1 = self.pop()? as usize;
+        let (z0, z1) = (self.state.zp[0], self.state.zp[1]);
+        let d = self.project(self.point(z1, p1)?, self.point(z0, p2)?) / 2.0;
+        self.move_point(z1, p1, d)?;
+        self.move_point(z0, p2, -d)
+    }
+
+    fn interpolate_points(&mut self) -> Result<(), ScalerError> {
+        let (z0, z1, z2) = (self.state.zp[0], self.state.zp[1], self.state.zp[2]);
+        let (rp1, rp2) = (self.state.rp[1], self.state.rp[2]);
+        let (org1, cur1) = (self.original(z0, rp1)?, self.point(z0, rp1)?);
+        let org_range = self.dual_project(org1, self.original(z1, rp2)?);
+        let cur_range = self.project(cur1, self.point(z1, rp2)?);
+        self.for_each_looped(|s, p| {
+            let org = s.dual_project(org1, s.original(z2, p)?);
+            let cur = s.project(cur1, s.point(z2, p)?);
+            let d = if org_range != 0.0 { org * cur_range / org_range } else { org };
+            s.move_point(z2, p, d - cur)
+        })
+    }
+
+    /// The reference point SHP, SHC and SHZ use, and how far it has moved along the projection vector
+    fn shift_reference(&self, use_rp1: bool) -> Result<(usize, usize, f32), ScalerError> {
+        let (z, p) = if use_rp1 { (self.state.zp[0], self.state.rp[1]) } else { (self.state.zp[1], self.state.rp[2]) };
+        Ok((z, p, self.project(self.original(z, p)?, self.point(z, p)?)))
+    }
+
+    fn shift_contour(&mut self, use_rp1: bool) -> Result<(), ScalerError> {
+        let c = self.pop()? as usize;
+        let (rz, rp, d) = self.shift_reference(use_rp1)?;
+        let z = self.state.zp[2];
+        let (start, end) = self.contour(z, c)?;
+        for p in start..end+1 {
+            if z != rz || p != rp {
+                self.move_point(z, p, d)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn shift_zone(&mut self, use_rp1: bool) -> Result<(), ScalerError> {
+        let z = self.pop_zone()?;
+        let (rz, rp, d) = self.shift_reference(use_rp1)?;
+        let (dx, dy) = self.displacement(d);
+        // the phantom points at the end of the glyph zone aren't part of any contour and stay put
+        let len = if z == 0 { self.state.twilight.current.len() } else { self.glyph.contour_ends.last().map_or(0, |&e| e + 1) };
+        for p in 0..len {
+            if z != rz || p != rp {
+                self.shift_point(z, p, dx, dy, Touched::empty())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn intersect(&mut self) -> Result<(), ScalerError> {
+        let (b1, b0) = (self.pop()? as usize, self.pop()? as usize);
+        let (a1, a0) = (self.pop()? as usize, self.pop()? as usize);
+        let p = self.pop()? as usize;
+        let (z0, z1, z2) = (self.state.zp[0], self.state.zp[1], self.state.zp[2]);
+        let (a0, a1) = (self.point(z1, a0)?, self.point(z1, a1)?);
+        let (b0, b1) = (self.point(z0, b0)?, self.point(z0, b1)?);
+        let (da, db) = (Point::new(a1.x - a0.x, a1.y - a0.y), Point::new(b1.x - b0.x, b1.y - b0.y));
+        let denom = da.x * db.y - da.y * db.x;
+        let at = if denom.abs() < 1e-6 {
+            // parallel lines never meet, so settle for the middle of all four points
+            Point::new((a0.x + a1.x + b0.x + b1.x) / 4.0, (a0.y + a1.y + b0.y + b1.y) / 4.0)
+        } else {
+            let t = ((b0.x - a0.x) * db.y - (b0.y - a0.y) * db.x) / denom;
+            Point::new(a0.x + t * da.x, a0.y + t * da.y)
+        };
+        let pc = self.pc;
+        let zone = self.zone_mut(z2);
+        if p >= zone.current.len() {
+            return Err(ScalerError::InvalidReference(pc));
+        }
+        zone.current[p] = at;
+        zone.touched[p] |= TOUCHED_X | TOUCHED_Y;
+        Ok(())
+    }
+
+    fn flip_range(&mut self, on_curve: bool) -> Result<(), ScalerError> {
+        let hi = self.pop()? as usize;
+        let lo = self.pop()? as usize;
+        if lo > hi || hi >= self.glyph.on_curve.len() {
+            return Err(ScalerError::InvalidReference(self.pc));
+        }
+        for c in &mut self.glyph.on_curve[lo..hi+1] {
+            *c = on_curve;
+        }
+        Ok(())
+    }
+
+    /// Moves the points of each contour that weren't touched along an axis so they keep their
+    /// place relative to the touched points on either side of them
+    fn interpolate_untouched(&mut self, axis: Touched) -> Result<(), ScalerError> {
+        let pc = self.pc;
+        let zone = &mut *self.glyph;
+        let along_x = axis == TOUCHED_X;
+        let coord = |p: Point| if along_x { p.x } else { p.y };
+        let mut start = 0;
+        for &end in &zone.contour_ends {
+            if end >= zone.current.len() {
+                return Err(ScalerError::InvalidReference(pc));
+            }
+            let touched: Vec<usize> = (start..end+1).filter(|&p| zone.touched[p].contains(axis)).collect();
+            for (i, &t1) in touched.iter().enumerate() {
+                let t2 = touched[(i + 1) % touched.len()];
+                let (o1, o2) = (coord(zone.original[t1]), coord(zone.original[t2]));
+                let (c1, c2) = (coord(zone.current[t1]), coord(zone.current[t2]));
+                let (lo_o, lo_c, hi_o, hi_c) = if o1 <= o2 { (o1, c1, o2, c2) } else { (o2, c2, o1, c1) };
+                // walk the untouched points from t1 to t2, wrapping around the end of the contour
+                let mut p = if t1 == end { start } else { t1 + 1 };
+                while p != t2 {
+                    let o = coord(zone.original[p]);
+                    let c = if o <= lo_o {
+                        o + lo_c - lo_o
+                    } else if o >= hi_o {
+                        o + hi_c - hi_o
+                    } else {
+                        lo_c + (o - lo_o) * (hi_c - lo_c) / (hi_o - lo_o)
+                    };
+                    if along_x { zone.current[p].x = c; } else { zone.current[p].y = c; }
+                    p = if p == end { start } else { p + 1 };
+                }
+            }
+            start = end + 1;
+        }
+        Ok(())
+    }
+
+    fn function(&self, f: u32) -> Result<Vec<u8>, ScalerError> {
+        self.state.functions.get(&f).cloned().ok_or(ScalerError::InvalidReference(self.pc))
+    }
+
+    /// Runs a function or instruction definition `count` times in a new call frame, then picks
+    /// up again after the instruction that called it
+    fn call(&mut self, body: &Vec<u8>, count: u32) -> Result<(), ScalerError> {
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err(ScalerError::CallDepthExceeded(self.pc));
+        }
+        let return_pc = self.pc;
+        self.call_depth += 1;
+        for _ in 0..count {
+            self.pc = 0;
+            self.interpret(body)?;
+        }
+        self.call_depth -= 1;
+        self.pc = return_pc;
+        Ok(())
+    }
+
+    /// Moves `offset` bytes from the current instruction. Jumping in place would never end
+    fn jump(&mut self, offset: i32, instructions: &[u8]) -> Result<(), ScalerError> {
+        let target = self.pc as i64 + offset as i64;
+        if offset == 0 || target < 0 || target > instructions.len() as i64 {
+            return Err(ScalerError::InvalidJump(self.pc));
+        }
+        self.pc = target as usize;
+        Ok(())
+    }
+
+    /// Finds the ENDF closing the FDEF or IDEF at the current instruction
+    fn definition_end(&self, instructions: &[u8]) -> Result<usize, ScalerError> {
+        let mut pc = self.pc + 1;
+        while pc < instructions.len() {
+            match instructions[pc] {
+                0x2d => return Ok(pc),
+                0x2c | 0x89 => break,
+                _ => pc += instruction_len(instructions, pc)?
+            }
+        }
+        Err(ScalerError::InvalidInstruction(self.pc, instructions[self.pc]))
+    }
+
+    /// Finds the EIF, or the ELSE too if `stop_at_else`, matching the IF or ELSE at the current
+    /// instruction, stepping over nested blocks and pushed data that could look like either
+    fn block_end(&self, instructions: &[u8], stop_at_else: bool) -> Result<usize, ScalerError> {
+        let mut depth = 0;
+        let mut pc = self.pc + 1;
+        while pc < instructions.len() {
+            match instructions[pc] {
+                0x58 => depth += 1,
+                0x1b if depth == 0 && stop_at_else => return Ok(pc),
+                0x59 if depth == 0 => return Ok(pc),
+                0x59 => depth -= 1,
+                _ => {}
+            }
+            pc += instruction_len(instructions, pc)?;
+        }
+        Err(ScalerError::InvalidInstruction(self.pc, instructions[self.pc]))
+    }
 
     fn interpret(&mut self, instructions: &Vec<u8>) -> Result<(), ScalerError> {
         while self.pc < instructions.len() {
-            let l = self.stack.len();
-            print!("pc = {:x}, current instruction = {:2x}, stack = [ ", self.pc, instructions[self.pc]);
-            for i in 1..11 {
-                if l >= i { print!("{:x} ", self.stack[l-i]); }
-            }
-            println!("]");
             match instructions[self.pc] {
                 0x7f => {self.pop()?;},
                 0x64 => { let v = self.pop_f26dot6()?.abs().into(); self.push(v) },
                 0x60 => { let v = (self.pop_f26dot6()? + self.pop_f26dot6()?).into(); self.push(v) },
-                0x27 => { /* ALIGN */ },
-                0x3c => { /* ALIGNRP */ },
+                0x27 => self.align_points()?,
+                0x3c => self.for_each_looped(|s, p| {
+                    let (z0, z1, rp0) = (s.state.zp[0], s.state.zp[1], s.state.rp[0]);
+                    let d = s.project(s.point(z0, rp0)?, s.point(z1, p)?);
+                    s.move_point(z1, p, -d)
+                })?,
                 0x5a => {
                     let (a, b) = (self.pop()?, self.pop()?);
-                    self.push(if (a == 1) && (b == 1) { 1 } else { 0 })
+                    self.push(if (a != 0) && (b != 0) { 1 } else { 0 })
+                },
+                0x2b => { /* CALL */
+                    let f = self.pop()?;
+                    let body = self.function(f)?;
+                    self.call(&body, 1)?
                 },
-                0x2b => { /* CALL */ },
                 0x67 => { let v = self.pop_f26dot6()?.ceil().into(); self.push(v) },
-                0x25 => { /* CINDEX */ },
+                0x25 => { /* CINDEX */ let k = self.pop()?; let i = self.stack_index(k)?; let v = self.stack[i]; self.push(v) },
                 0x22 => self.stack.clear(),
                 0x4f => println!("debug value: {:x}", self.pop()?),
-                0x73 => { /* DELTAC1 */ },
-                0x74 => { /* DELTAC2 */ },
-                0x75 => { /* DELTAC3 */ },
-                0x5d => { /* DELTAP1 */ },
-                0x71 => { /* DELTAP2 */ },
-                0x72 => { /* DELTAP3 */ },
+                0x73 ..= 0x75 => { /* DELTAC[123] */
+                    let base = (instructions[self.pc] - 0x73) as u32 * 16;
+                    self.deltas(base, |s, n, d| { let v = s.cvt(n)?; s.write_cvt(n, F26d6::from(v + d).to_bits()) })?
+                },
+                0x5d | 0x71 | 0x72 => { /* DELTAP[123] */
+                    let base = match instructions[self.pc] { 0x5d => 0, 0x71 => 16, _ => 32 };
+                    let z = self.state.zp[0];
+                    self.deltas(base, |s, p, d| s.move_point(z, p, d))?
+                },
                 0x24 => { let l = self.stack.len() as u32; self.push(l) },
-                0x62 => { let v = (self.pop_f26dot6()? / self.pop_f26dot6()?).into(); self.push(v) },
-                0x20 => { let t = self.stack[self.stack.len()-1]; self.push(t) }
+                0x62 => {
+                    let (n2, n1) = (self.pop_f26dot6()?, self.pop_f26dot6()?);
+                    if n2 == F26d6::from(0) {
+                        return Err(ScalerError::DivideByZero(self.pc));
+                    }
+                    self.push((n1 / n2).into())
+                },
+                0x20 => { let i = self.stack_index(1)?; let t = self.stack[i]; self.push(t) }
                 0x59 => { /* EIF */ /* nop */ },
                 0x1b => { /* ELSE */ 
                     // only way to execute this instruction is if the true side of an IF branch
                     // was exectuted, so skip past EIF
-                    while instructions[self.pc] != 0x59 {
-                        self.pc += 1;
-                    }
+                    self.pc = self.block_end(instructions, false)?;
                 },
-                0x2d => { /* ENDF */ println!("ENDF without FDEF"); },
+                0x2d => { /* ENDF */ return Err(ScalerError::InvalidInstruction(self.pc, 0x2d)) },
                 0x54 => self.compare(|a,b| a == b)?,
-                0x57 => { /* EVEN */ },
+                0x57 => { let v = self.pop_rounded_parity()? == 0; self.push(v as u32) },
                 0x2c => { /* FDEF */
                     let start = self.pc+1;
-                    let mut end = start;
-                    while instructions[end] != 0x2d {
-                        end += match instructions[end] {
-                            0x40 => instructions[end+1] as usize+1,
-                            0x41 => instructions[end+1] as usize*2+1,
-                            0xb0 ... 0xb7 => instructions[end] as usize - 0xaf + 1,
-                            0xb8 ... 0xbf => (instructions[end] as usize - 0xb7) * 2 + 1,
-                            _ => 1
-                        };
-                    }
+                    let end = self.definition_end(instructions)?;
                     let id = self.pop()?;
                     println!("function def #{} = [{:?}]", id, &instructions[start..end]);
                     self.state.functions.insert(id, instructions[start..end].into());
-                    self.pc = end+1; //skip ENDF
+                    self.pc = end; //skip ENDF
                 },
                 0x4e => { self.state.auto_flip = false; },
                 0x4d => { self.state.auto_flip = true; },
-                0x80 => { /* FLIPPT */ },
-                0x82 => { /* FLIPRGOFF */ },
-                0x81 => { /* FLIPRGON */ },
+                0x80 => self.for_each_looped(|s, p| {
+                    let pc = s.pc;
+                    match s.glyph.on_curve.get_mut(p) {
+                        Some(c) => { *c = !*c; Ok(()) },
+                        None => Err(ScalerError::InvalidReference(pc))
+                    }
+                })?,
+                0x82 => self.flip_range(false)?,
+                0x81 => self.flip_range(true)?,
                 0x66 => { let v = self.pop_f26dot6()?.floor().into(); self.push(v) },
-                0x46 => { /* GC[0] */ },
-                0x47 => { /* GC[1] */ },
+                0x46 => { /* GC[0] */
+                    let p = self.pop()? as usize;
+                    let d = self.state.project_vec.project(self.point(self.state.zp[2], p)?);
+                    self.push_pixels(d)
+                },
+                0x47 => { /* GC[1] */
+                    let p = self.pop()? as usize;
+                    let d = self.dual_project(Point::new(0.0, 0.0), self.original(self.state.zp[2], p)?);
+                    self.push_pixels(d)
+                },
                 0x88 => { println!("info req: {:b}", self.pop()?); self.push(0) },
-                0x0d => { let (x,y) = (F26d6::from(self.state.freedom_vec.x), F26d6::from(self.state.freedom_vec.y)); self.push(x.into()); self.push(y.into()) },
-                0x0c => { let (x,y) = (F26d6::from(self.state.project_vec.x), F26d6::from(self.state.project_vec.y)); self.push(x.into()); self.push(y.into()) },
+                0x0d => { let v = self.state.freedom_vec; self.push_f2dot14(v.x / v.len()); self.push_f2dot14(v.y / v.len()) },
+                0x0c => { let v = self.state.project_vec; self.push_f2dot14(v.x / v.len()); self.push_f2dot14(v.y / v.len()) },
                 0x52 => self.compare(|a,b| a > b)?,
                 0x53 => self.compare(|a,b| a >= b)?,
                 0x89 => { /* IDEF */
                     let start = self.pc+1;
-                    let mut end = start;
-                    while instructions[end] != 0x2d {
-                        end += 1;
-                    }
+                    let end = self.definition_end(instructions)?;
                     let id = self.pop()?;
+                    if id > 0xff {
+                        return Err(ScalerError::InvalidReference(self.pc));
+                    }
                     println!("instruction def #{} = [{:?}]", id, &instructions[start..end]);
-                    self.pc = end+1; //skip ENDF
+                    self.state.instruction_defs.insert(id as u8, instructions[start..end].into());
+                    self.pc = end; //skip ENDF
                 }, 
                 0x58 => { /* IF */
                     let cond = self.pop()?;
                     if cond == 0 {
-                        // move to next ELSE or EIF instruction
-                        while instructions[self.pc] != 0x1b || instructions[self.pc] != 0x59 {
-                            self.pc += 1;
-                        }
+                        // move to the matching ELSE or EIF instruction
+                        self.pc = self.block_end(instructions, true)?;
                     }
                 },
-                0x8e => { /* INSTCTRL [cvt only] */ panic!("INSTCTRL only in CVT programs"); },
-                0x39 => { /* IP */ },
-                0x0f => { /* ISECT */ },
-                0x30 => { /* IUP[0] */ },
-                0x31 => { /* IUP[1] */ },
-                0x1c => { self.pc += (self.pop()? - 1) as usize; }
-                0x79 => { let (e, offset) = (self.pop()?, self.pop()?); if e == 0 { self.pc += (offset-1) as usize; } }
-                0x78 => { let (e, offset) = (self.pop()?, self.pop()?); if e == 1 { self.pc += (offset-1) as usize; } }
-                0x2a => { /* LOOPCALL */ },
+                0x8e => { /* INSTCTRL */
+                    let (selector, v) = (self.pop()?, self.pop()?);
+                    if selector == 1 {
+                        self.state.instruct_ctrl = v & 1 != 0;
+                    }
+                },
+                0x39 => self.interpolate_points()?,
+                0x0f => self.intersect()?,
+                0x30 => self.interpolate_untouched(TOUCHED_Y)?,
+                0x31 => self.interpolate_untouched(TOUCHED_X)?,
+                0x1c => { let offset = self.pop()? as i32; self.jump(offset, instructions)?; continue }
+                0x79 => { let (e, offset) = (self.pop()?, self.pop()? as i32); if e == 0 { self.jump(offset, instructions)?; continue } }
+                0x78 => { let (e, offset) = (self.pop()?, self.pop()? as i32); if e != 0 { self.jump(offset, instructions)?; continue } }
+                0x2a => { /* LOOPCALL */
+                    let f = self.pop()?;
+                    let count = self.pop()?;
+                    let body = self.function(f)?;
+                    self.call(&body, count)?
+                },
                 0x50 => self.compare(|a,b| a < b)?,
                 0x51 => self.compare(|a,b| a <= b)?,
-                0x8b => { let v = self.pop()?.max(self.pop()?); self.push(v); },
+                0x8b => { let v = (self.pop()? as i32).max(self.pop()? as i32); self.push(v as u32); },
                 0x49 => { /* MD[0] */
                     let (p1, p2) = (self.pop()? as usize, self.pop()? as usize);
-                    let (d1, d2) = (self.state.project_vec.project(self.points[p1]), self.state.project_vec.project(self.points[p2])); 
-                    self.push(F26d6::from(d2-d1).into())
+                    let (z0, z1) = (self.state.zp[0], self.state.zp[1]);
+                    let d = self.project(self.point(z1, p1)?, self.point(z0, p2)?);
+                    self.push_pixels(d)
                 },
                 0x4a => { /* MD[1] */
                     let (p1, p2) = (self.pop()? as usize, self.pop()? as usize);
-                    let (d1, d2) = (self.state.project_vec.project(self.original_points[p1]), self.state.project_vec.project(self.original_points[p2])); 
-                    self.push(F26d6::from(d2-d1).into())
+                    let (z0, z1) = (self.state.zp[0], self.state.zp[1]);
+                    let d = self.dual_project(self.original(z1, p1)?, self.original(z0, p2)?);
+                    self.push_pixels(d)
                 },
-                0x2e => { /* MDAP[0] */ },
-                0x2f => { /* MDAP[1] */ },
-                0xc0 ... 0xdf => { /* MDRP[abcde] */ },
-                0x3e => { /* MIAP[0] */ },
-                0x3f => { /* MIAP[1] */ },
-                0x8c => { let v = self.pop()?.min(self.pop()?); self.push(v); },
-                0x26 => { /* MINDEX */ },
-                0xe0 ... 0xff => { /* MIRP[abcde] */ },
-                0x4b => { let s = self.uniform_scale as u32; self.push(s) },
+                0x2e => self.mdap(false)?,
+                0x2f => self.mdap(true)?,
+                0xc0 ..= 0xdf => { let flags = instructions[self.pc] & 0x1f; self.mdrp(flags)? },
+                0x3e => self.miap(false)?,
+                0x3f => self.miap(true)?,
+                0x8c => { let v = (self.pop()? as i32).min(self.pop()? as i32); self.push(v as u32); },
+                0x26 => { /* MINDEX */ let k = self.pop()?; let i = self.stack_index(k)?; let v = self.stack.remove(i); self.push(v) },
+                0xe0 ..= 0xff => { let flags = instructions[self.pc] & 0x1f; self.mirp(flags)? },
+                0x4b => { let ppem = self.ppem(); self.push(ppem) },
                 0x4c => { let s = self.point_size as u32; self.push(s) },
-                0x3a ... 0x3b => { /* MSIRP[a] */ },
+                0x3a ..= 0x3b => { let set_rp0 = instructions[self.pc] == 0x3b; self.msirp(set_rp0)? },
                 0x63 => { let v = (self.pop_f26dot6()? * self.pop_f26dot6()?).into(); self.push(v) },
                 0x65 => { let v = (-self.pop_f26dot6()?).into(); self.push(v) },
                 0x55 => self.compare(|a,b| a != b)?,
                 0x5c => { let v = if self.pop()? == 0 { 1 } else { 0 }; self.push(v) },
                 0x40 => { self.pc += 1; let len = instructions[self.pc] as usize; self.push_bytes(len, &instructions)? },
                 0x41 => { self.pc += 1; let len = instructions[self.pc] as usize; self.push_words(len, &instructions)? },
-                0x6c ... 0x6f => { /* NROUND[a] */ },
-                0x56 => { /* ODD */ },
+                0x6c ..= 0x6f => { /* NROUND[ab] */
+                    let d = self.pop_pixels()?;
+                    let d = self.compensate(d, instructions[self.pc]);
+                    self.push_pixels(d)
+                },
+                0x56 => { let v = self.pop_rounded_parity()? == 1; self.push(v as u32) },
                 0x5b => {
                     let (a, b) = (self.pop()?, self.pop()?);
-                    self.push(if (a == 1) || (b == 1) { 1 } else { 0 })
+                    self.push(if (a != 0) || (b != 0) { 1 } else { 0 })
                 },
                 0x21 => { self.pop()?; }
                 0xb0 ... 0xb7 => { let len = instructions[self.pc] as usize - 0xaf; self.push_bytes(len,  &instructions)? },
                 0xb8 ... 0xbf => { let len = instructions[self.pc] as usize - 0xb7; self.push_words(len, &instructions)? },
-                0x45 => { /* RCVT */ },
-                0x7d => { /* RDTG */ },
-                0x7a => { /* ROFF */ },
-                0x8a => {
-                    let l = self.stack.len();
-                    let a = self.stack[l-1];
-                    self.stack[l-1] = self.stack[l-3];
-                    self.stack[l-3] = a;
+                0x45 => { /* RCVT */
+                    let n = self.pop()? as usize;
+                    let v = self.cvt(n)?;
+                    self.push_pixels(v)
                 },
-                0x68 ... 0x6b => { /* ROUND[ab] */ },
-                0x43 => { /* RS */ },
-                0x3d => { /* RTDG */ },
-                0x18 => { /* RTG */ },
-                0x19 => { /* RTHG */ },
-                0x7c => { /* RUTG */ },
-                0x77 => { /* S45ROUND */ },
+                0x7d => { self.state.round_state = RoundState::DownToGrid; },
+                0x7a => { self.state.round_state = RoundState::Off; },
+                0x8a => { let i = self.stack_index(3)?; let v = self.stack.remove(i); self.push(v) },
+                0x68 ..= 0x6b => { /* ROUND[ab] */
+                    let d = self.pop_pixels()?;
+                    let d = self.round(d, instructions[self.pc]);
+                    self.push_pixels(d)
+                },
+                0x43 => { let n = self.pop_storage()?; let v = self.state.storage[n]; self.push(v) },
+                0x3d => { self.state.round_state = RoundState::ToDoubleGrid; },
+                0x18 => { self.state.round_state = RoundState::ToGrid; },
+                0x19 => { self.state.round_state = RoundState::ToHalfGrid; },
+                0x7c => { self.state.round_state = RoundState::UpToGrid; },
+                0x77 => { let n = self.pop()?; self.state.round_state = RoundState::super_round(n, std::f32::consts::SQRT_2 / 2.0); },
                 0x7e => { self.pop()?; },
-                0x85 => { /* SCANCTRL */ },
-                0x8d => { /* SCANTYPE */ },
-                0x48 => { /* SCFS */ },
-                0x1d => { /* SCVTCI */ },
+                0x85 => { self.state.scan_ctrl = self.pop()?; },
+                0x8d => { self.state.scan_type = self.pop()?; },
+                0x48 => { /* SCFS */
+                    let (d, p) = (self.pop_pixels()?, self.pop()? as usize);
+                    let z = self.state.zp[2];
+                    let cur = self.state.project_vec.project(self.point(z, p)?);
+                    self.move_point(z, p, d - cur)?;
+                    // twilight points are placed in the original outline too
+                    if z == 0 {
+                        let at = self.point(z, p)?;
+                        self.set_twilight(p, at)?;
+                    }
+                },
+                0x1d => { self.state.cvt_cutin = self.pop_pixels()?; },
                 0x5e => { self.state.delta_base = self.pop()?; },
-                0x86 ... 0x87 => { /* SDPVTL */ },
+                0x86 ..= 0x87 => { /* SDPVTL */
+                    let (current, original) = self.pop_line(instructions[self.pc] == 0x87)?;
+                    self.state.project_vec = current;
+                    self.state.dual_prj_vec = original;
+                },
                 0x5f => { self.state.delta_shift = self.pop()?; },
-                0x0b => { self.state.freedom_vec = Vector { x: self.pop_f26dot6()?.into(), y: self.pop_f26dot6()?.into() }; },
+                0x0b => { let (y, x) = (self.pop_f2dot14()?, self.pop_f2dot14()?); self.state.freedom_vec = Vector { x, y }; },
                 0x04 => { self.state.freedom_vec = Vector { x: 0.0, y: 1.0 }; },
                 0x05 => { self.state.freedom_vec = Vector { x: 1.0, y: 0.0 }; },
-                0x08 => { /* SFVTL[0] */ },
-                0x09 => { /* SFVTL[1] */ },
+                0x08 ..= 0x09 => { /* SFVTL */ let (v, _) = self.pop_line(instructions[self.pc] == 0x09)?; self.state.freedom_vec = v; },
                 0x0e => { self.state.freedom_vec = self.state.project_vec; },
-                0x34 ... 0x35 => { /* SHC[a] */ },
-                0x32 ... 0x33 => { /* SHP[a] */ },
-                0x38 => { /* SHPIX */ },
-                0x36 ... 0x37 => { /* SHZ */ },
+                0x34 ..= 0x35 => { let use_rp1 = instructions[self.pc] == 0x35; self.shift_contour(use_rp1)? },
+                0x32 ..= 0x33 => {
+                    let (_, _, d) = self.shift_reference(instructions[self.pc] == 0x33)?;
+                    let z = self.state.zp[2];
+                    self.for_each_looped(|s, p| s.move_point(z, p, d))?
+                },
+                0x38 => {
+                    let d = self.pop_pixels()?;
+                    let (fv, z) = (self.state.freedom_vec, self.state.zp[2]);
+                    let (dx, dy, touch) = (d * fv.x / fv.len(), d * fv.y / fv.len(), self.freedom_axes());
+                    self.for_each_looped(|s, p| s.shift_point(z, p, dx, dy, touch))?
+                },
+                0x36 ..= 0x37 => { let use_rp1 = instructions[self.pc] == 0x37; self.shift_zone(use_rp1)? },
                 0x17 => { self.state.loopv = self.pop()?; },
-                0x1a => { self.state.min_dist = self.pop_f26dot6()?.into(); },
-                0x0a => { self.state.project_vec = Vector { x: self.pop_f26dot6()?.into(), y: self.pop_f26dot6()?.into() }; },
+                0x1a => { self.state.min_dist = self.pop_pixels()?; },
+                0x0a => { let (y, x) = (self.pop_f2dot14()?, self.pop_f2dot14()?); self.state.project_vec = Vector { x, y }; },
                 0x02 => { self.state.project_vec = Vector { x: 0.0, y: 1.0 }; },
                 0x03 => { self.state.project_vec = Vector { x: 1.0, y: 0.0 }; },
-                0x06 ... 0x07 => { /* SPVTL */ },
-                0x76 => { /* SROUND */ },
+                0x06 ..= 0x07 => { /* SPVTL */ let (v, _) = self.pop_line(instructions[self.pc] == 0x07)?; self.state.project_vec = v; },
+                0x76 => { let n = self.pop()?; self.state.round_state = RoundState::super_round(n, 1.0); },
                 0x10 => { self.state.rp[0] = self.pop()? as usize; },
                 0x11 => { self.state.rp[1] = self.pop()? as usize; },
                 0x12 => { self.state.rp[2] = self.pop()? as usize; },
-                0x1f => { self.state.single_width_value = self.pop()? as f32; },
-                0x1e => { self.state.single_width_cut_in = self.pop()? as f32; },
-                0x61 => { let v = (self.pop_f26dot6()? - self.pop_f26dot6()?).into(); self.push(v) },
+                0x1f => { let v = self.pop()? as i32; self.state.single_width_value = self.scale_funits(v).into(); },
+                0x1e => { self.state.single_width_cut_in = self.pop_pixels()?; },
+                0x61 => { let (n2, n1) = (self.pop_f26dot6()?, self.pop_f26dot6()?); self.push((n1 - n2).into()) },
                 0x00 => { self.state.freedom_vec = Vector { x: 0.0, y: 1.0 }; self.state.project_vec = Vector { x: 0.0, y: 1.0 }; },
-                0x01 => { self.state.freedom_vec = Vector { x: 0.0, y: 1.0 }; self.state.project_vec = Vector { x: 1.0, y: 0.0 }; },
-                0x23 => {
-                    let l = self.stack.len();
-                    let a = self.stack[l-1];
-                    self.stack[l-1] = self.stack[l-2];
-                    self.stack[l-2] = a;
+                0x01 => { self.state.freedom_vec = Vector { x: 1.0, y: 0.0 }; self.state.project_vec = Vector { x: 1.0, y: 0.0 }; },
+                0x23 => { let i = self.stack_index(2)?; let l = self.stack.len(); self.stack.swap(i, l - 1); },
+                0x13 => { self.state.zp[0] = self.pop_zone()?; },
+                0x14 => { self.state.zp[1] = self.pop_zone()?; },
+                0x15 => { self.state.zp[2] = self.pop_zone()?; },
+                0x16 => { let p = self.pop_zone()?; self.state.zp[0] = p; self.state.zp[1] = p; self.state.zp[2] = p; },
+                0x29 => { /* UTP */
+                    let p = self.pop()? as usize;
+                    let (z, axes, pc) = (self.state.zp[0], self.freedom_axes(), self.pc);
+                    match self.zone_mut(z).touched.get_mut(p) {
+                        Some(t) => t.remove(axes),
+                        None => return Err(ScalerError::InvalidReference(pc))
+                    }
+                },
+                0x70 => { /* WCVTF */
+                    let v = self.pop()? as i32;
+                    let n = self.pop()? as usize;
+                    let v = self.scale_funits(v).to_bits();
+                    self.write_cvt(n, v)?
                 },
-                0x13 => { self.state.zp[0] = self.pop()? as usize; },
-                0x14 => { self.state.zp[1] = self.pop()? as usize; },
-                0x15 => { self.state.zp[2] = self.pop()? as usize; },
-                0x16 => { let p = self.pop()? as usize; self.state.zp[0] = p; self.state.zp[1] = p; self.state.zp[2] = p; },
-                0x29 => { /* UTP */ },
-                0x70 => { /* WCVTF */ },
-                0x44 => { /* WCVTP */ },
-                0x42 => { /* WS */ },
-
-                _ => return Err(ScalerError::InvalidInstruction(self.pc, instructions[self.pc]))
+                0x44 => { let (v, n) = (self.pop()? as i32, self.pop()? as usize); self.write_cvt(n, v)? },
+                0x42 => { let v = self.pop()?; let n = self.pop_storage()?; self.state.storage[n] = v; },
+
+                op => match self.state.instruction_defs.get(&op).cloned() {
+                    Some(body) => self.call(&body, 1)?,
+                    None => return Err(ScalerError::InvalidInstruction(self.pc, op))
+                }
             }
             self.pc += 1;
         }
@@ -386,10 +1121,18 @@ impl<'s, 'p> Interp<'s, 'p> {
 
 
 pub struct InstructedGlyphScaler<'f> {
-    glyph_table: &'f GlyphDataTable,
+    glyph_table: &'f GlyphDataTable<'f>,
+    hmtx_table: &'f HorizMetricsTable<'f>,
+    line_metrics: Option<LineMetrics>,
+    max_component_depth: u16,
     output_dpi: f32,
     units_per_em: f32,
     point_size: f32,
+    cvt_units: Vec<i16>,
+    prep_program: Option<&'f FontProgram>,
+    /// The state after the font program, which every size starts from
+    font_state: InterpState,
+    /// The state after the pre-program for the current size, which every glyph starts from
     state: InterpState
 }
 
@@ -399,20 +1142,52 @@ impl<'f> InstructedGlyphScaler<'f> {
     pub fn new(font: &'f SfntFont, dpi: f32, point_size: f32) -> Result<InstructedGlyphScaler<'f>, ScalerError> {
         let mut slf = InstructedGlyphScaler {
             glyph_table: font.glyf_table.as_ref().ok_or(ScalerError::MissingTable(TableTag::GlyphData))?,
+            hmtx_table: font.hmtx_table.as_ref().ok_or(ScalerError::MissingTable(TableTag::HorizMetrics))?,
+            line_metrics: font.line_metrics(),
+            max_component_depth: font.max_component_depth(),
             output_dpi: dpi, point_size,
             units_per_em: font.head_table.ok_or(ScalerError::MissingTable(TableTag::FontHeader))?.units_per_em as f32,
-            state: InterpState::new(font.cval_table.as_ref().ok_or(ScalerError::MissingTable(TableTag::ControlValue))?.0.clone())
+            cvt_units: font.cval_table.as_ref().ok_or(ScalerError::MissingTable(TableTag::ControlValue))?.0.clone(),
+            prep_program: font.prep_table.as_ref(),
+            font_state: InterpState::new(Vec::new(), 0, 0),
+            state: InterpState::new(Vec::new(), font.max_twilight_points() as usize, font.max_storage() as usize)
         };
-        println!("font program");
         if let Some(ref fprg) = font.fprg_table {
-            Interp::new(&mut slf, &mut Vec::new()).interpret(&fprg.0)?;
-        }
-        println!("preprogram");
-        if let Some(ref prep) = font.prep_table {
-            Interp::new(&mut slf, &mut Vec::new()).interpret(&prep.0)?;
+            Interp::new(&mut slf, &mut Zone::default()).interpret(&fprg.0)?;
         }
+        slf.font_state = slf.state.clone();
+        slf.set_point_size(point_size)?;
         Ok(slf)
     }
+
+    /// Switches to another size: the control values are scaled to it and the pre-program runs again
+    pub fn set_point_size(&mut self, point_size: f32) -> Result<(), ScalerError> {
+        self.point_size = point_size;
+        let scale = self.funits_scale();
+        self.state = self.font_state.clone();
+        self.state.cv_table = self.cvt_units.iter().map(|&v| scale.mul_int(v as i32)).collect();
+        if let Some(prep) = self.prep_program {
+            Interp::new(self, &mut Zone::default()).interpret(&prep.0)?;
+        }
+        self.state.reset_glyph_defaults();
+        Ok(())
+    }
+
+    /// Multiplies font units to give 26.6 pixels at the current size
+    fn funits_scale(&self) -> Fixed {
+        Fixed::from(self.uniform_scale() * 64.0)
+    }
+
+    /// The four phantom points that follow a glyph's outline so its instructions can hint the
+    /// metrics too: the left side bearing and advance width, then the top and bottom of the line
+    fn phantom_points(&self, glyph_index: usize, x_min: i16) -> Result<[Point; 4], FontError> {
+        let scale = self.uniform_scale();
+        let metric = self.hmtx_table.metric(glyph_index)?;
+        let (ascender, descender) = self.line_metrics.map_or((0, 0), |m| (m.ascender, m.descender));
+        let left = (x_min as f32 - metric.left_side_bearing as f32) * scale;
+        Ok([Point::new(left, 0.0), Point::new(left + metric.advance_width as f32 * scale, 0.0),
+            Point::new(0.0, ascender as f32 * scale), Point::new(0.0, descender as f32 * scale)])
+    }
 }
 
 impl<'f> GlyphScaler for InstructedGlyphScaler<'f> {
@@ -421,18 +1196,277 @@ impl<'f> GlyphScaler for InstructedGlyphScaler<'f> {
     }
     fn scale_glyph(&mut self, glyph_index: usize, offset: Point) -> Result<Glyph, Box<Error>> {
         let scale = self.uniform_scale();
-        if let GlyphDescription::Simple { num_contours, ref end_points_of_contours, ref instructions, ref points, .. } = self.glyph_table.glyphs[glyph_index] {
-            let mut points: Vec<Point> = points.iter().map(|&p| Point { x: p.x as f32*scale, y: p.y as f32*scale }).collect();
-            
-            Interp::new(self, &mut points).interpret(instructions)?;
-
-            for p in points.iter_mut() {
-                p.x += offset.x; 
-                p.y += offset.y;
+        let mut glyph = self.glyph_table.resolved_glyph(glyph_index, self.max_component_depth, Some(scale))?;
+        let (mut points, on_curve) = match glyph {
+            GlyphDescription::Simple { x_min, ref end_points_of_contours, ref instructions, ref points, .. } => {
+                let mut outline: Vec<Point> = points.iter().map(|&p| Point { x: p.x as f32*scale, y: p.y as f32*scale }).collect();
+                outline.extend_from_slice(&self.phantom_points(glyph_index, x_min)?);
+                let mut zone = Zone::new(outline, points.iter().map(|p| p.on_curve).collect(),
+                                         end_points_of_contours.iter().map(|&e| e as usize).collect());
+
+                // each glyph starts from the state the pre-program left, whatever earlier glyphs changed
+                let size_state = self.state.clone();
+                let hinted = if self.state.instruct_ctrl { Ok(()) } else { Interp::new(self, &mut zone).interpret(instructions) };
+                self.state = size_state;
+                hinted?;
+
+                zone.current.truncate(points.len());
+                (zone.current, zone.on_curve)
+            },
+            _ => return Err(Box::new(ScalerError::InvalidGlyph))
+        };
+        // FLIPPT, FLIPRGON and FLIPRGOFF can change which points are on the curve
+        if let GlyphDescription::Simple { ref mut points, .. } = glyph {
+            for (p, on) in points.iter_mut().zip(on_curve) {
+                p.on_curve = on;
             }
-            Glyph::from_truetype_with_points(&self.glyph_table.glyphs[glyph_index], points).ok_or(Box::new(ScalerError::InvalidGlyph))
-        } else {
-            Err(Box::new(ScalerError::InvalidGlyph))
+        }
+
+        for p in points.iter_mut() {
+            p.x += offset.x; 
+            p.y += offset.y;
+        }
+        Glyph::from_truetype_with_points(&glyph, points).ok_or(Box::new(ScalerError::InvalidGlyph))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(program: &[u8], glyph: &mut Zone, cv_table: Vec<i32>) -> Result<(), ScalerError> {
+        run_with_stack(program, glyph, cv_table, Vec::new()).map(|_| ())
+    }
+
+    /// Runs a program starting from `stack` and returns the stack it leaves behind
+    fn run_with_stack(program: &[u8], glyph: &mut Zone, cv_table: Vec<i32>, stack: Vec<u32>) -> Result<Vec<u32>, ScalerError> {
+        run_in(&mut InterpState::new(cv_table, 2, 4), program, glyph, stack)
+    }
+
+    fn run_in(state: &mut InterpState, program: &[u8], glyph: &mut Zone, stack: Vec<u32>) -> Result<Vec<u32>, ScalerError> {
+        let mut interp = Interp {
+            stack, pc: 0, state, glyph, call_depth: 0,
+            scale: Fixed::from(64.0), units_per_em: 2048.0, point_size: 12.0
+        };
+        interp.interpret(&program.to_vec())?;
+        Ok(interp.stack)
+    }
+
+    fn zone(points: &[(f32, f32)], contour_ends: Vec<usize>) -> Zone {
+        Zone::new(points.iter().map(|&(x, y)| Point::new(x, y)).collect(), vec![true; points.len()], contour_ends)
+    }
+
+    #[test]
+    fn iup_interpolates_untouched_points() {
+        let mut glyph = zone(&[(0.0, 0.0), (5.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)], vec![4]);
+        // SVTCA[x], touch point 0 in place, shift point 2 right by one pixel, then IUP[x]
+        run(&[0x01, 0xb0, 0, 0x2e, 0xb1, 2, 64, 0x38, 0x31], &mut glyph, vec![]).unwrap();
+        let xs: Vec<f32> = glyph.current.iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![0.0, 5.5, 11.0, 11.0, 0.0]);
+        assert!(glyph.current.iter().all(|p| p.y == 0.0 || p.y == 10.0));
+    }
+
+    #[test]
+    fn mdrp_keeps_minimum_distance() {
+        let mut glyph = zone(&[(0.0, 0.0), (0.25, 0.0), (0.0, 3.0)], vec![2]);
+        // SVTCA[x], SRP0 0, MDRP[min dist, round] 1
+        run(&[0x01, 0xb1, 1, 0, 0x10, 0xcc], &mut glyph, vec![]).unwrap();
+        assert_eq!(glyph.current[1].x, 1.0);
+        assert!(glyph.touched[1].contains(TOUCHED_X) && !glyph.touched[1].contains(TOUCHED_Y));
+    }
+
+    #[test]
+    fn alignpts_across_zones() {
+        let mut glyph = zone(&[(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)], vec![2]);
+        let mut state = InterpState::new(Vec::new(), 2, 4);
+        // SVTCA[x], SZP0 twilight, ALIGNPTS glyph point 1 with twilight point 0
+        run_in(&mut state, &[0x01, 0xb0, 0, 0x13, 0xb1, 1, 0, 0x27], &mut glyph, Vec::new()).unwrap();
+        assert_eq!(glyph.current[1].x, 2.0);
+        assert_eq!(state.twilight.current[0].x, 2.0);
+        assert_eq!(glyph.current[0].x, 0.0);
+    }
+
+    #[test]
+    fn miap_and_ip() {
+        let mut glyph = zone(&[(0.0, 0.0), (0.0, 1.0), (0.0, 2.6)], vec![2]);
+        // SVTCA[y], MIAP[round] 2 to CVT entry 1, SRP1 0, SRP2 2, IP 1
+        run(&[0x00, 0xb1, 2, 1, 0x3f, 0xb2, 1, 0, 2, 0x12, 0x11, 0x39], &mut glyph, vec![0, 192]).unwrap();
+        assert_eq!(glyph.current[2].y, 3.0);
+        assert!((glyph.current[1].y - 3.0 / 2.6).abs() < 1e-4);
+    }
+
+    #[test]
+    fn flip_and_bad_references() {
+        let mut glyph = zone(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], vec![2]);
+        run(&[0xb1, 0, 2, 0x82, 0xb0, 1, 0x80], &mut glyph, vec![]).unwrap();
+        assert_eq!(glyph.on_curve, vec![false, true, false]);
+        match run(&[0xb0, 7, 0x2e], &mut glyph, vec![]) {
+            Err(ScalerError::InvalidReference(_)) => {},
+            r => panic!("expected an invalid reference, got {:?}", r)
+        }
+    }
+
+    #[test]
+    fn if_else_blocks() {
+        let mut glyph = Zone::default();
+        // IF skips a nested IF and pushed bytes that look like IF and ELSE to reach its own ELSE
+        let program = [0xb0, 0, 0x58, 0xb1, 0x58, 0x1b, 0x58, 0xb0, 1, 0x59, 0x1b, 0xb0, 7, 0x59];
+        assert_eq!(run_with_stack(&program, &mut glyph, vec![], vec![]).unwrap(), vec![7]);
+        let program = [0xb0, 1, 0x58, 0xb0, 5, 0x1b, 0x58, 0x59, 0xb0, 7, 0x59];
+        assert_eq!(run_with_stack(&program, &mut glyph, vec![], vec![]).unwrap(), vec![5]);
+        // LT compares the second value popped against the first, as signed numbers
+        assert_eq!(run_with_stack(&[0x50], &mut glyph, vec![], vec![-1i32 as u32, 2]).unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn calls_and_idefs() {
+        let mut glyph = Zone::default();
+        // FDEF 0 pushes 3, FDEF 1 calls 0, IDEF 0x93 pushes 4, then LOOPCALL 0 twice, CALL 1 and run 0x93
+        let program = [0xb0, 0, 0x2c, 0xb0, 3, 0x2d,
+                       0xb0, 1, 0x2c, 0xb0, 0, 0x2b, 0x2d,
+                       0xb0, 0x93, 0x89, 0xb0, 4, 0x2d,
+                       0xb1, 2, 0, 0x2a, 0xb0, 1, 0x2b, 0x93];
+        assert_eq!(run_with_stack(&program, &mut glyph, vec![], vec![]).unwrap(), vec![3, 3, 3, 4]);
+        match run(&[0xb0, 0, 0x2c, 0xb0, 0, 0x2b, 0x2d, 0xb0, 0, 0x2b], &mut glyph, vec![]) {
+            Err(ScalerError::CallDepthExceeded(_)) => {},
+            r => panic!("expected runaway recursion to stop, got {:?}", r)
+        }
+        match run(&[0xb0, 9, 0x2b], &mut glyph, vec![]) {
+            Err(ScalerError::InvalidReference(_)) => {},
+            r => panic!("expected a missing function, got {:?}", r)
+        }
+    }
+
+    #[test]
+    fn relative_jumps() {
+        let mut glyph = Zone::default();
+        // JROF with a false condition skips the first push
+        assert_eq!(run_with_stack(&[0xb1, 3, 0, 0x79, 0xb0, 1, 0xb0, 2], &mut glyph, vec![], vec![]).unwrap(), vec![2]);
+        // jump forward to a JMPR that goes back five bytes, then jump to the end
+        let program = [0xb0, 6, 0x1c, 0xb0, 7, 0xb0, 4, 0x1c, 0x1c, 0xb0, 9];
+        assert_eq!(run_with_stack(&program, &mut glyph, vec![], vec![-5i32 as u32]).unwrap(), vec![7]);
+        match run(&[0xb0, 20, 0x1c], &mut glyph, vec![]) {
+            Err(ScalerError::InvalidJump(_)) => {},
+            r => panic!("expected a jump past the end to fail, got {:?}", r)
+        }
+    }
+
+    #[test]
+    fn storage_and_cvt() {
+        let mut glyph = Zone::default();
+        // WS 200 to location 1, WCVTP half a pixel and WCVTF three font units, then read them back
+        let program = [0xb1, 1, 200, 0x42, 0xb1, 0, 32, 0x44, 0xb1, 1, 3, 0x70, 0xb0, 1, 0x43, 0xb0, 0, 0x45, 0xb0, 1, 0x45];
+        assert_eq!(run_with_stack(&program, &mut glyph, vec![0, 0], vec![]).unwrap(), vec![200, 32, 192]);
+        match run(&[0xb1, 4, 1, 0x42], &mut glyph, vec![]) {
+            Err(ScalerError::InvalidReference(_)) => {},
+            r => panic!("expected storage past the end to fail, got {:?}", r)
+        }
+    }
+
+    #[test]
+    fn stack_instructions() {
+        let mut glyph = Zone::default();
+        // ROLL, CINDEX, MINDEX
+        assert_eq!(run_with_stack(&[0x8a], &mut glyph, vec![], vec![1, 2, 3]).unwrap(), vec![2, 3, 1]);
+        assert_eq!(run_with_stack(&[0x25], &mut glyph, vec![], vec![10, 20, 30, 2]).unwrap(), vec![10, 20, 30, 20]);
+        assert_eq!(run_with_stack(&[0x26], &mut glyph, vec![], vec![10, 20, 30, 3]).unwrap(), vec![20, 30, 10]);
+        // SCANCTRL and SCANTYPE take their operands even though they don't change the outline
+        assert_eq!(run_with_stack(&[0x85, 0x8d], &mut glyph, vec![], vec![5, 4, 0x1ff]).unwrap(), vec![5]);
+        match run_with_stack(&[0x25], &mut glyph, vec![], vec![1, 5]) {
+            Err(ScalerError::StackUnderflow(_)) => {},
+            r => panic!("expected CINDEX past the bottom of the stack to fail, got {:?}", r)
+        }
+    }
+
+    #[test]
+    fn vectors_from_lines_and_deltas() {
+        let mut glyph = zone(&[(0.0, 0.0), (3.0, 4.0), (0.0, 4.0)], vec![2]);
+        // SPVTL[0] from point 0 to 1, GC[0] 1; then the same across the line
+        assert_eq!(run_with_stack(&[0xb1, 1, 0, 0x06, 0xb0, 1, 0x46], &mut glyph, vec![], vec![]).unwrap(), vec![320]);
+        assert_eq!(run_with_stack(&[0xb1, 1, 0, 0x07, 0xb0, 1, 0x46], &mut glyph, vec![], vec![]).unwrap(), vec![0]);
+
+        // SVTCA[x], SCFS point 2 to two pixels
+        run(&[0x01, 0xb1, 2, 128, 0x48], &mut glyph, vec![]).unwrap();
+        assert_eq!(glyph.current[2].x, 2.0);
+
+        // at 2048ppem with the delta base at 2040, DELTAP1 moves point 0 eight steps of an eighth
+        // of a pixel, and DELTAP2 is for 16ppem bigger so leaves it alone
+        let program = [0x01, 0xb8, 0x07, 0xf8, 0x5e, 0xb2, 0x8f, 0, 1, 0x5d, 0xb2, 0x8f, 0, 1, 0x71];
+        run(&program, &mut glyph, vec![]).unwrap();
+        assert_eq!(glyph.current[0].x, 1.0);
+        assert!(glyph.touched[0].contains(TOUCHED_X));
+
+        // a delta base at the top of the range is never reached rather than wrapping around
+        run(&[0x01, 0xb8, 0xff, 0xff, 0x5e, 0xb2, 0xff, 0, 1, 0x5d], &mut glyph, vec![]).unwrap();
+        assert_eq!(glyph.current[0].x, 1.0);
+    }
+
+    #[test]
+    fn deltac_and_instctrl() {
+        let mut state = InterpState::new(vec![0, 192], 2, 4);
+        // at 2048ppem with the delta base at 2040, DELTAC1 adds a pixel to CVT entry 1, then RCVT 1
+        let program = [0xb8, 0x07, 0xf8, 0x5e, 0xb2, 0x8f, 1, 1, 0x73, 0xb0, 1, 0x45];
+        assert_eq!(run_in(&mut state, &program, &mut Zone::default(), vec![]).unwrap(), vec![256]);
+        // INSTCTRL selector 1 turns glyph programs off
+        run_in(&mut state, &[0xb1, 1, 1, 0x8e], &mut Zone::default(), vec![]).unwrap();
+        assert!(state.instruct_ctrl);
+    }
+
+    #[test]
+    fn prep_state_carries_into_glyphs() {
+        let mut state = InterpState::new(vec![], 2, 4);
+        // the pre-program stores a value, turns rounding off and points the vectors along y
+        run_in(&mut state, &[0xb1, 1, 200, 0x42, 0x7a, 0x00], &mut Zone::default(), vec![]).unwrap();
+        state.reset_glyph_defaults();
+        // MDAP[round] then touches the point along x and leaves it where it is, and the stored
+        // value is still there
+        let mut glyph = zone(&[(0.25, 0.25)], vec![0]);
+        assert_eq!(run_in(&mut state, &[0xb0, 0, 0x2f, 0xb0, 1, 0x43], &mut glyph, vec![]).unwrap(), vec![200]);
+        assert_eq!((glyph.current[0].x, glyph.current[0].y), (0.25, 0.25));
+        assert_eq!(glyph.touched[0], TOUCHED_X);
+    }
+
+    #[test]
+    fn round_states() {
+        let mut glyph = Zone::default();
+        let program = [0x18, 0x68,                          // RTG, -1.5 rounds away from zero
+                       0x19, 0xb0, 96, 0x68,                // RTHG, 1.5
+                       0x7d, 0xb0, 96, 0x68,                // RDTG, 1.5
+                       0x7c, 0xb0, 65, 0x68,                // RUTG, 1 + 1/64
+                       0x3d, 0xb0, 80, 0x68,                // RTDG, 1.25
+                       0x7a, 0xb0, 81, 0x68,                // ROFF
+                       0xb0, 0x58, 0x76, 0xb0, 96, 0x68,    // SROUND period 1, phase 1/4, threshold 1/2
+                       0xb0, 0x48, 0x77, 0xb0, 64, 0x68,    // S45ROUND period sqrt(2)/2
+                       0xb0, 0x00, 0x76, 0xb0, 10, 0x6c];   // NROUND leaves the value alone
+        let stack = run_with_stack(&program, &mut glyph, vec![], vec![-96i32 as u32]).unwrap();
+        assert_eq!(stack, vec![-128i32 as u32, 96, 64, 128, 96, 81, 80, 45, 10]);
+    }
+
+    #[test]
+    fn even_and_odd_round_first() {
+        let mut glyph = Zone::default();
+        let program = [0x7a, 0xb8, 0xff, 0xa0, 0x56,    // ROFF, -1.5 is in pixel -2 so isn't odd
+                       0xb8, 0xff, 0xa0, 0x57,          // but is even
+                       0x7d, 0xb0, 112, 0x56,           // RDTG, 1.75 rounds down to 1
+                       0x18, 0xb0, 112, 0x56];          // RTG, and up to 2
+        let stack = run_with_stack(&program, &mut glyph, vec![], vec![]).unwrap();
+        assert_eq!(stack, vec![0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut glyph = Zone::default();
+        let program = [0xb8, 0xff, 0x80, 0xb0, 64, 0x61,          // PUSHW -2, then -2 - 1
+                       0xb0, 96, 0xb8, 0xff, 0x80, 0x63,          // 1.5 * -2
+                       0xb8, 0xff, 0x80, 0xb0, 192, 0x62,         // -2 / 3
+                       0xb8, 0xff, 0xc0, 0xb0, 1, 0x8b,           // MAX of -1 and 1/64
+                       0xb0, 96, 0x56,                            // ODD rounds 1.5 up to 2
+                       0xb9, 0, 0, 0x40, 0, 0x0a, 0x0c];          // SPVFS (0, 1), then GPV
+        let stack = run_with_stack(&program, &mut glyph, vec![], vec![]).unwrap();
+        assert_eq!(stack, vec![-192i32 as u32, -192i32 as u32, -43i32 as u32, 1, 0, 0, 0x4000]);
+        match run(&[0xb1, 64, 0, 0x62], &mut glyph, vec![]) {
+            Err(ScalerError::DivideByZero(_)) => {},
+            r => panic!("expected division by zero to fail, got {:?}", r)
         }
     }
 }
@@ -8,6 +8,12 @@ extern crate typenum;
 mod truetype_loader;
 mod numerics;
 mod interp_instructor;
+mod coverage;
+mod shaping;
+
+pub use truetype_loader::{FontError, LineMetrics, SfntFont};
+pub use shaping::{layout_paragraph, line_breaks, paragraph_direction, shape, shape_paragraph, Alignment, Break, Direction, Feature,
+                  GlyphRun, LayoutOptions, Line, LineGlyph, ParagraphLayout, PositionedGlyph, Script};
 
 use std::error::Error;
 
@@ -43,28 +49,91 @@ pub struct Glyph {
     points: Vec<Point>
 }
 
-struct CharMap<'fontdata> {
-    id_map: &'fontdata [u8; 256]
+/// Maps characters to glyph indices using the best Unicode subtable in a font's 'cmap' table
+pub struct CharMap {
+    subtable: truetype_loader::CharGlyphMappingEncodingTableFormat,
+    encoding: CharMapEncoding,
+    variations: Option<truetype_loader::CharGlyphMappingEncodingTableFormat>
 }
 
-impl<'fontdata> CharMap<'fontdata> {
-    fn from_truetype<'f>(font: &'f truetype_loader::SfntFont) -> CharMap<'f> {
-        CharMap {
-            id_map: font.cmap_table.as_ref().and_then(|table| {
-                for enc_tbl in &table.encoding_tables {
-                    match &enc_tbl.subtable {
-                        &truetype_loader::CharGlyphMappingEncodingTableFormat::ByteEncoding { glyph_ids: ref ids } => { return Some(ids) },
-                        _ => {}
-                    }
-                }
-                None
-            }).expect("font has format 0 table")
-        }
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CharMapEncoding {
+    Unicode,
+    /// Microsoft symbol fonts put their characters in the private use area at U+F000 to U+F0FF
+    Symbol,
+    /// Only the ASCII part of Mac Roman lines up with Unicode
+    MacRoman,
+    /// Multibyte encodings like Shift-JIS or Big5, which only line up with Unicode in ASCII too
+    Legacy
+}
+
+impl CharMap {
+    pub fn from_truetype(font: &truetype_loader::SfntFont) -> Result<CharMap, truetype_loader::FontError> {
+        use truetype_loader::{FontError, TableTag};
+        let table = font.cmap_table.as_ref().ok_or(FontError::MissingTable(TableTag::CharGlyphMapping))?;
+        let (enc_tbl, subtable) = table.best_unicode_subtable()
+            .ok_or(FontError::UnknownFormat(TableTag::CharGlyphMapping, table.encoding_tables.first().map_or(0, |t| t.format as u32)))?;
+        let encoding = match (enc_tbl.platform_id, enc_tbl.platform_encoding_id) {
+            (3, 0) => CharMapEncoding::Symbol,
+            (1, _) => CharMapEncoding::MacRoman,
+            _ => CharMapEncoding::Unicode
+        };
+        Ok(CharMap { subtable, encoding, variations: table.variation_subtable() })
     }
-    fn map(&self, c: char) -> usize {
+
+    /// Use the subtable for a particular platform and encoding, like (3, 2) for Shift-JIS. Codes in
+    /// that encoding can then be looked up with `map_code` and `map_bytes`
+    pub fn from_truetype_encoding(font: &truetype_loader::SfntFont, platform_id: u16, platform_encoding_id: u16) -> Result<CharMap, truetype_loader::FontError> {
+        use truetype_loader::{FontError, TableTag};
+        let table = font.cmap_table.as_ref().ok_or(FontError::MissingTable(TableTag::CharGlyphMapping))?;
+        let subtable = table.encoding_subtable(platform_id, platform_encoding_id)
+            .ok_or(FontError::MissingTable(TableTag::CharGlyphMapping))??;
+        let encoding = match (platform_id, platform_encoding_id) {
+            (0, _) | (3, 1) | (3, 10) => CharMapEncoding::Unicode,
+            (3, 0) => CharMapEncoding::Symbol,
+            (1, 0) => CharMapEncoding::MacRoman,
+            _ => CharMapEncoding::Legacy
+        };
+        Ok(CharMap { subtable, encoding, variations: table.variation_subtable() })
+    }
+
+    /// Look up the glyph for a character. Characters the font doesn't have get the missing glyph, 0
+    pub fn map(&self, c: char) -> usize {
         let ci = c as u32;
-        if ci > 256 { return 0 }
-        self.id_map[ci as usize] as usize
+        let gid = match self.encoding {
+            CharMapEncoding::Unicode => self.subtable.glyph_index(ci),
+            CharMapEncoding::Symbol => self.subtable.glyph_index(ci)
+                .or_else(|| if ci <= 0xff { self.subtable.glyph_index(0xf000 + ci) } else { None }),
+            CharMapEncoding::MacRoman | CharMapEncoding::Legacy => if ci < 0x80 { self.subtable.glyph_index(ci) } else { None }
+        };
+        gid.unwrap_or(0) as usize
+    }
+
+    /// Look up the glyph for a character code in the encoding of the subtable this map uses
+    pub fn map_code(&self, code: u32) -> usize {
+        self.subtable.glyph_index(code).unwrap_or(0) as usize
+    }
+
+    /// Look up the glyphs for a string of bytes in the encoding of the subtable this map uses,
+    /// splitting multibyte codes apart as the subtable describes. A lead byte at the very end of
+    /// the string is dropped
+    pub fn map_bytes(&self, mut bytes: &[u8]) -> Vec<usize> {
+        let mut glyphs = Vec::new();
+        while let Some((code, len)) = self.subtable.next_code(bytes) {
+            glyphs.push(self.map_code(code));
+            bytes = &bytes[len..];
+        }
+        glyphs
+    }
+
+    /// Look up the glyph for a character followed by a variation selector, like U+FE0E to ask for
+    /// the text presentation of an emoji. Sequences the font doesn't know get the glyph for the
+    /// character on its own
+    pub fn map_variation(&self, c: char, selector: char) -> usize {
+        match self.variations.as_ref().and_then(|v| v.variation_glyph_index(c as u32, selector as u32)) {
+            Some(truetype_loader::VariationGlyph::Glyph(gid)) => gid as usize,
+            _ => self.map(c)
+        }
     }
 }
 
@@ -139,6 +208,12 @@ impl Glyph {
             _ => None
         }
     }
+
+    /// Builds the outline of a glyph in a font, resolving composite glyphs into a single outline.
+    /// Glyphs without an outline, like spaces, give `None`
+    pub fn from_truetype_font(font: &truetype_loader::SfntFont, glyph_index: usize) -> Result<Option<Glyph>, truetype_loader::FontError> {
+        Ok(Glyph::from_truetype(&font.resolved_glyph(glyph_index, None)?))
+    }
 }
 
 pub trait GlyphScaler {
@@ -147,7 +222,8 @@ pub trait GlyphScaler {
 }
 
 pub struct SimpleGlyphScaler<'f> {
-    glyph_table: &'f truetype_loader::GlyphDataTable,
+    glyph_table: &'f truetype_loader::GlyphDataTable<'f>,
+    max_component_depth: u16,
     output_dpi: f32,
     units_per_em: f32,
     point_size: f32
@@ -158,7 +234,8 @@ impl<'f> SimpleGlyphScaler<'f> {
         Ok(SimpleGlyphScaler {
             output_dpi: dpi, point_size,
             units_per_em: font.head_table.ok_or("font missnig head table")?.units_per_em as f32,
-            glyph_table: font.glyf_table.as_ref().ok_or("font missing glyph table")?
+            glyph_table: font.glyf_table.as_ref().ok_or("font missing glyph table")?,
+            max_component_depth: font.max_component_depth()
         })
     }
 }
@@ -169,7 +246,8 @@ impl<'f> GlyphScaler for SimpleGlyphScaler<'f> {
     }
     fn scale_glyph(&mut self, glyph_index: usize, offset: Point) -> Result<Glyph, Box<Error>> {
         let scale = self.uniform_scale();
-        let mut g = Glyph::from_truetype(&self.glyph_table.glyphs[glyph_index]).ok_or("glyph from truetype")?;
+        let ttf_glyph = self.glyph_table.resolved_glyph(glyph_index, self.max_component_depth, Some(scale))?;
+        let mut g = Glyph::from_truetype(&ttf_glyph).ok_or("glyph from truetype")?;
         for p in g.points.iter_mut() {
             p.x = p.x * scale + 8.0 + offset.x; 
             p.y = (self.units_per_em-p.y) * scale + offset.y;
@@ -178,8 +256,49 @@ impl<'f> GlyphScaler for SimpleGlyphScaler<'f> {
     }
 }
 
+/// How a `Rasterizer` turns outlines into pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RasterMode {
+    /// Pixels are either fully on or off, depending on whether the center of their row is inside
+    /// the outline
+    Binary,
+    /// Pixels get an 8-bit grayscale value for how much of them the outline covers
+    Coverage
+}
+
+/// Decides which parts of an outline are inside it, which matters where contours overlap or
+/// are nested
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    /// A point is inside if the contours around it wind around it a non-zero number of times.
+    /// This is what TrueType expects, so overlapping contours are filled once
+    NonZero,
+    /// A point is inside if a ray from it crosses the outline an odd number of times, so
+    /// overlapping parts of contours cancel out
+    EvenOdd
+}
+
+impl FillRule {
+    fn is_inside(&self, winding: i32) -> bool {
+        match *self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0
+        }
+    }
+}
+
+/// A point where an outline crosses a scanline. `winding` is +1 if the outline is heading down
+/// the bitmap there and -1 if it is heading up
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Crossing {
+    x: f32,
+    winding: i32
+}
+
 pub struct Rasterizer<S: GlyphScaler> {
-    scaler: S
+    scaler: S,
+    mode: RasterMode,
+    fill_rule: FillRule
 }
 
 fn inside<T: PartialOrd>(x: T, min: T, max: T) -> bool {
@@ -229,34 +348,53 @@ impl Curve {
         }
     }
 
-    fn intersect_scanline(&self, points: &Vec<Point>, y: f32, result: &mut Vec<f32>) {
+    // finds where this curve crosses the horizontal line at y, along with which way it is going
+    // there. A crossing right at an end point only counts for the curve that heads down the bitmap
+    // from it, so the two curves sharing that point don't both count it and the tips of contours
+    // that just touch the line cancel out
+    fn intersect_scanline(&self, points: &[Point], y: f32, result: &mut Vec<Crossing>) {
+        const T_EPSILON: f32 = 1e-6;
+        let mut push = |t: f32, dydt: f32, x: f32| {
+            let counts = if t < T_EPSILON {
+                t > -T_EPSILON && dydt > 0.0
+            } else if t > 1.0 - T_EPSILON {
+                t < 1.0 + T_EPSILON && dydt < 0.0
+            } else {
+                dydt != 0.0
+            };
+            if counts {
+                result.push(Crossing { x, winding: if dydt > 0.0 { 1 } else { -1 } });
+            }
+        };
         match self {
             &Curve::Line(start, end) => {
                 let Point{x: startx, y: starty} = points[start];
                 let Point{x: endx, y: endy} = points[end];
-                if !inside(y, starty, endy) { return; }
-                if (startx-endx).abs() < 0.001 {
-                    result.push(startx); 
-                } else if (starty-endy).abs() < 0.001 {
-                    result.push(startx); result.push(endx); 
-                } else {
-                    let m = (endy-starty)/(endx-startx);
-                    result.push((y-starty)/m + startx); 
-                }
+                let dy = endy - starty;
+                // horizontal lines run along the scanline rather than crossing it
+                if dy == 0.0 { return; }
+                let t = (y - starty) / dy;
+                push(t, dy, startx + (endx - startx) * t);
             },
             &Curve::Quad(start, ctrl, end) => {
+                // y(t) = (a - 2b + c)t² + 2(b - a)t + a
                 let a = points[start].y; let b = points[ctrl].y; let c = points[end].y;
-                let det = -a*c + a*y + b*b - 2.0*b*y + c*y;
-                if det < 0.0 { return; }
-                let sqrt_det = det.sqrt();
-                let denom = -a + 2.0*b - c;
-                let t1 = (-sqrt_det - a + b) / denom;
-                let t2 = (sqrt_det - a + b) / denom;
-                if inside(t1, 0.0, 1.0) {
-                    result.push((1.0-t1)*(1.0-t1)*points[start].x + 2.0*(1.0-t1)*t1*points[ctrl].x + t1*t1*points[end].x);
-                }
-                if inside(t2, 0.0, 1.0) {
-                    result.push((1.0-t2)*(1.0-t2)*points[start].x + 2.0*(1.0-t2)*t2*points[ctrl].x + t2*t2*points[end].x);
+                let qa = a - 2.0*b + c;
+                let qb = 2.0*(b - a);
+                let qc = a - y;
+                let x_at = |t: f32| (1.0-t)*(1.0-t)*points[start].x + 2.0*(1.0-t)*t*points[ctrl].x + t*t*points[end].x;
+                if qa.abs() < 1e-6 {
+                    // the control point is halfway between the ends in y, so y is linear in t
+                    if qb == 0.0 { return; }
+                    let t = -qc / qb;
+                    push(t, qb, x_at(t));
+                } else {
+                    let det = qb*qb - 4.0*qa*qc;
+                    if det < 0.0 { return; }
+                    let sqrt_det = det.sqrt();
+                    for &t in &[(-qb - sqrt_det) / (2.0*qa), (-qb + sqrt_det) / (2.0*qa)] {
+                        push(t, 2.0*qa*t + qb, x_at(t));
+                    }
                 }
             }
         }
@@ -264,6 +402,30 @@ impl Curve {
 }
 
 impl<S: GlyphScaler> Rasterizer<S> {
+    pub fn new(scaler: S) -> Rasterizer<S> {
+        Rasterizer { scaler, mode: RasterMode::Binary, fill_rule: FillRule::NonZero }
+    }
+
+    pub fn with_mode(scaler: S, mode: RasterMode) -> Rasterizer<S> {
+        Rasterizer { scaler, mode, fill_rule: FillRule::NonZero }
+    }
+
+    pub fn mode(&self) -> RasterMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: RasterMode) {
+        self.mode = mode;
+    }
+
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    pub fn set_fill_rule(&mut self, fill_rule: FillRule) {
+        self.fill_rule = fill_rule;
+    }
+
     pub fn scale(&self) -> f32 {
         self.scaler.uniform_scale()
         //point_size * self.output_dpi / (72f32 * self.units_per_em)
@@ -274,26 +436,70 @@ impl<S: GlyphScaler> Rasterizer<S> {
         //scale & grid fit the outline
         // this involves interpreting some instructions
         let glyph = self.scaler.scale_glyph(glyph_index, offset)?;
+        match self.mode {
+            RasterMode::Binary => Rasterizer::<S>::raster_scanlines(&glyph, self.fill_rule, bitmap, width, height),
+            RasterMode::Coverage => Rasterizer::<S>::raster_coverage(&glyph, self.fill_rule, bitmap, width, height)
+        }
+        /*for p in points {
+            println!("{:?}", p);
+            bitmap[(p.x as usize) + (p.y.abs() as usize)*width] = 128;
+        }*/
+        Ok(bitmap)
+    }
+
+    fn raster_scanlines(glyph: &Glyph, fill_rule: FillRule, bitmap: &mut [u8], width: usize, height: usize) {
         //rasterize by scan line
+        let mut xs = Vec::new();
         for y in 0..height {
-            let mut xs = Vec::new();
+            xs.clear();
             for curve in &glyph.curves {
                 curve.intersect_scanline(&glyph.points, y as f32 + 0.5, &mut xs);
             }
             use std::cmp::Ordering;
-            xs.sort_unstable_by(|a, b| if *a < *b { Ordering::Less } else { Ordering::Greater });
-            for px in xs.chunks(2) {
-                if px.len() != 2 { continue; }
-                for x in (px[0] as usize)..(px[1] as usize) {
+            xs.sort_unstable_by(|a, b| if a.x < b.x { Ordering::Less } else { Ordering::Greater });
+            // walk the crossings left to right, keeping track of how many times the outline winds
+            // around the span between each pair of them
+            let mut winding = 0;
+            for px in xs.windows(2) {
+                winding += px[0].winding;
+                if !fill_rule.is_inside(winding) { continue; }
+                let x0 = (px[0].x.max(0.0) as usize).min(width);
+                let x1 = (px[1].x.max(0.0) as usize).min(width);
+                for x in x0..x1 {
                     bitmap[x + (y as usize)*width] = 255;
                 }
             }
         }
-        /*for p in points {
-            println!("{:?}", p);
-            bitmap[(p.x as usize) + (p.y.abs() as usize)*width] = 128;
-        }*/
-        Ok(bitmap)
+    }
+
+    fn raster_coverage(glyph: &Glyph, fill_rule: FillRule, bitmap: &mut [u8], width: usize, height: usize) {
+        if glyph.points.is_empty() { return; }
+        // only accumulate over the part of the bitmap the glyph can touch. Quadratic curves stay
+        // inside their control points, so the bounds of the points are enough
+        let (mut x0, mut y0, mut x1, mut y1) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for p in &glyph.points {
+            x0 = x0.min(p.x); y0 = y0.min(p.y);
+            x1 = x1.max(p.x); y1 = y1.max(p.y);
+        }
+        let bx0 = (x0.floor().max(0.0) as usize).min(width);
+        let by0 = (y0.floor().max(0.0) as usize).min(height);
+        let bx1 = (x1.ceil().max(0.0) as usize).min(width);
+        let by1 = (y1.ceil().max(0.0) as usize).min(height);
+        if bx0 >= bx1 || by0 >= by1 { return; }
+
+        let mut acc = coverage::CoverageAccumulator::new(bx1-bx0, by1-by0);
+        let p = |i: usize| Point::new(glyph.points[i].x - bx0 as f32, glyph.points[i].y - by0 as f32);
+        for curve in &glyph.curves {
+            match *curve {
+                Curve::Line(start, end) => acc.line(p(start), p(end)),
+                Curve::Quad(start, ctrl, end) => acc.quad(p(start), p(ctrl), p(end))
+            }
+        }
+        acc.accumulate(fill_rule, |x, y, coverage| {
+            let px = &mut bitmap[(x+bx0) + (y+by0)*width];
+            // glyphs next to each other can share pixels, so keep whatever is darker
+            *px = (*px).max((coverage * 255.0 + 0.5) as u8);
+        });
     }
 }
 
@@ -348,11 +554,14 @@ mod tests {
 
     const test_glyph_index: usize = 9;
     #[cfg(target_os="windows")]
-    const FONT_PATH: &'static str = 
+    const FONT_PATH: &str = 
         "C:\\Windows\\Fonts\\arial.ttf";
     #[cfg(target_os="macos")]
-    const FONT_PATH: &'static str = 
+    const FONT_PATH: &str = 
         "/Library/Fonts/Arial.ttf";
+    #[cfg(target_os="linux")]
+    const FONT_PATH: &str = 
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
 
     #[test]
     fn load_truetype_svg_out() {
@@ -360,18 +569,33 @@ mod tests {
         let mut font_file = File::open(FONT_PATH).unwrap();
         let font = SfntFont::from_binary(&mut font_file).expect("load font data");
 
-        let g = Glyph::from_truetype(font.glyf_table.as_ref().map(|t| &t.glyphs[test_glyph_index]).expect("load glyph")).unwrap();
+        let g = Glyph::from_truetype(&font.glyph(test_glyph_index).expect("load glyph")).unwrap();
         let doc = glyph_to_svg(&g, 0.5f32);
         svg::save("glyph_conv.svg", &doc).unwrap();
     }
 
+    #[test]
+    fn load_truetype_composite_svg() {
+        use truetype_loader::*;
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+
+        let glyf = font.glyf_table.as_ref().unwrap();
+        let gi = (0..glyf.len()).find(|&i| matches!(glyf.glyph(i).unwrap(), GlyphDescription::Composite { .. }))
+            .expect("font has a composite glyph");
+        let g = Glyph::from_truetype_font(&font, gi).expect("resolve glyph").expect("glyph has an outline");
+        assert!(!g.curves.is_empty());
+        let doc = glyph_to_svg(&g, 0.5f32);
+        svg::save("glyph_composite.svg", &doc).unwrap();
+    }
+
     #[test]
     fn load_truetype_read_instructions() {
         use truetype_loader::*;
         let mut font_file = File::open(FONT_PATH).unwrap();
         let font = SfntFont::from_binary(&mut font_file).expect("load font data");
 
-        font.glyf_table.as_ref().map(|glyf_table| match glyf_table.glyphs[test_glyph_index] {
+        match font.glyph(test_glyph_index).expect("load glyph") {
             GlyphDescription::Simple { ref instructions, .. } => {
                 for is in instructions.chunks(8) {
                     for i in is {
@@ -381,7 +605,7 @@ mod tests {
                 }
             },
             _ => println!("!")
-        });
+        }
     }
 
 
@@ -391,8 +615,7 @@ mod tests {
         use truetype_loader::*;
         let mut font_file = File::open(FONT_PATH).unwrap();
         let font = SfntFont::from_binary(&mut font_file).expect("load font data");
-        let g = Glyph::from_truetype(font.glyf_table.as_ref()
-                                     .map(|t| &t.glyphs[test_glyph_index]).expect("load glyph")).unwrap();
+        let g = Glyph::from_truetype(&font.glyph(test_glyph_index).expect("load glyph")).unwrap();
         let mut doc = glyph_to_svg(&g, 1.0f32);
         for iy in (0u32..90u32) {
             let y = (iy as f32) * 32.0;
@@ -402,8 +625,8 @@ mod tests {
             }
             doc.append(Line::new().set("x1", 0).set("y1", y)
                        .set("x2", 2048.0).set("y2", y).set("stroke", "blue").set("stroke-width", 4));
-            for x in ipoints {
-                doc.append(Circle::new().set("cx",x).set("cy",y).set("r",6).set("fill","blue"));
+            for Crossing { x, winding } in ipoints {
+                doc.append(Circle::new().set("cx",x).set("cy",y).set("r",6).set("fill", if winding > 0 { "blue" } else { "green" }));
             }
         }
         svg::save("glyph_intersect.svg", &doc).unwrap();
@@ -415,9 +638,7 @@ mod tests {
         let mut font_file = File::open(FONT_PATH).unwrap();
         let font = SfntFont::from_binary(&mut font_file).expect("load font data");
         
-        let mut rr = Rasterizer {
-            scaler: SimpleGlyphScaler::new(&font, 144.0, 140.0).expect("create scaler")
-        };
+        let mut rr = Rasterizer::new(SimpleGlyphScaler::new(&font, 144.0, 140.0).expect("create scaler"));
         let mut bm = Vec::new();
         bm.resize(512*512, 0u8);
 
@@ -429,6 +650,68 @@ mod tests {
 
     }
 
+    #[test]
+    fn load_truetype_raster_coverage() {
+        use truetype_loader::*;
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+
+        let mut bm = vec![0u8; 256*64];
+        let mut offset = Point::new(4.0, 4.0);
+        let mut point_size = 6.0;
+        for _ in 0..6 {
+            let mut rr = Rasterizer::with_mode(SimpleGlyphScaler::new(&font, 144.0, point_size).expect("create scaler"), RasterMode::Coverage);
+            rr.raster_glyph(test_glyph_index, &mut bm[..], 256, offset).expect("rasterized glyph");
+            offset.x += point_size * 2.0 + 4.0;
+            point_size *= 1.5;
+        }
+        assert!(bm.iter().any(|&v| v > 0 && v < 255), "coverage rasterizer should produce gray pixels");
+
+        let im = ImageBuffer::from_raw(256,64,bm).unwrap();
+        let mut fout = File::create(Path::new("lglcov.png")).expect("creating output file");
+        let _ = image::ImageLuma8(im).save(&mut fout, image::PNG);
+    }
+
+    #[test]
+    fn char_map_lookup() {
+        use truetype_loader::*;
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+        let cm = CharMap::from_truetype(&font).expect("char map");
+        assert!(cm.map('A') != 0);
+        assert!(cm.map('\u{3a9}') != 0);
+        assert!(cm.map('A') != cm.map('B'));
+        assert_eq!(cm.map('\u{10fffd}'), 0);
+        assert_eq!(cm.map_variation('A', '\u{fe0f}'), cm.map('A'));
+    }
+
+    #[test]
+    fn raster_overlapping_contours() {
+        // two squares wound the same way that overlap in the middle, like the contours of an
+        // overlapping composite glyph
+        let mut points = Vec::new();
+        let mut curves = Vec::new();
+        for &(x0, x1) in &[(1.0, 5.0), (3.0, 7.0)] {
+            let s = points.len();
+            points.extend_from_slice(&[Point::new(x0, 1.0), Point::new(x1, 1.0), Point::new(x1, 3.0), Point::new(x0, 3.0)]);
+            for i in 0..4 {
+                curves.push(Curve::Line(s + i, s + (i + 1) % 4));
+            }
+        }
+        let g = Glyph { curves, points };
+        for &mode in &[RasterMode::Binary, RasterMode::Coverage] {
+            for &(fill_rule, middle) in &[(FillRule::NonZero, 255), (FillRule::EvenOdd, 0)] {
+                let mut bm = vec![0u8; 8*4];
+                match mode {
+                    RasterMode::Binary => Rasterizer::<SimpleGlyphScaler>::raster_scanlines(&g, fill_rule, &mut bm, 8, 4),
+                    RasterMode::Coverage => Rasterizer::<SimpleGlyphScaler>::raster_coverage(&g, fill_rule, &mut bm, 8, 4)
+                }
+                let row = &bm[8..16];
+                assert_eq!(row, &[0, 255, 255, middle, middle, 255, 255, 0], "{:?} {:?}", mode, fill_rule);
+            }
+        }
+    }
+
     #[test]
     fn load_truetype_raster_string() {
         use truetype_loader::*;
@@ -443,20 +726,21 @@ mod tests {
         let mut point_size = 8.0;
 
         for i in 0..4 {
-            let s = "@Test~String!$&";
-            let cm = CharMap::from_truetype(&font);
-            let mut offset = Point::new(8.0, 8.0 + (i as f32) * 50.0);
+            let s = "@Test~String!$& AVATAR";
+            let top = 8.0 + (i as f32) * 50.0;
 
-            let mut rr = Rasterizer {
-                scaler: SimpleGlyphScaler::new(&font, 144.0, point_size).expect("create scaler")
-            };
+            let mut rr = Rasterizer::new(SimpleGlyphScaler::new(&font, 144.0, point_size).expect("create scaler"));
 
-            for c in s.chars() {
-                let gi = cm.map(c);
-                //let g = Glyph::from_truetype(&font, gi).expect("load glyph");
-                rr.raster_glyph(gi, &mut bm[..], 1024, offset);
-                offset.x += font.hmtx_table.as_ref()
-                    .map(|hmtx| hmtx.metrics[gi].advance_width as f32 * rr.scaler.uniform_scale()).unwrap();
+            let layout = layout_paragraph(&font, s, &LayoutOptions::new(point_size * 144.0 / 72.0, 1008.0));
+            for line in &layout.lines {
+                for g in &line.glyphs {
+                    // spaces have no outline for the scaler to draw
+                    if Glyph::from_truetype_font(&font, g.glyph_index).expect("load glyph").is_none() {
+                        continue;
+                    }
+                    rr.raster_glyph(g.glyph_index, &mut bm[..], 1024, Point::new(8.0 + g.x, top + g.y - layout.lines[0].baseline))
+                        .expect("rasterized glyph");
+                }
             }
 
             point_size *= 2.0;
@@ -483,17 +767,15 @@ mod tests {
 
         for i in 0..4 {
             let s = "@Test~String!$&";
-            let cm = CharMap::from_truetype(&font);
+            let cm = CharMap::from_truetype(&font).expect("char map");
             let mut offset = Point::new(8.0, 8.0 + (i as f32) * 50.0);
-            let mut rr = Rasterizer {
-                scaler: interp_instructor::InstructedGlyphScaler::new(&font, 144.0, point_size).expect("create scaler")
-            };
+            let mut rr = Rasterizer::new(interp_instructor::InstructedGlyphScaler::new(&font, 144.0, point_size).expect("create scaler"));
             for c in s.chars() {
                 let gi = cm.map(c);
                 //let g = Glyph::from_truetype(&font, gi).expect("load glyph");
                 rr.raster_glyph(gi, &mut bm[..], 1024, offset).expect("rasterized glyph");
                 offset.x += font.hmtx_table.as_ref()
-                    .map(|hmtx| hmtx.metrics[gi].advance_width as f32 * rr.scaler.uniform_scale()).unwrap();
+                    .map(|hmtx| hmtx.metric(gi).unwrap().advance_width as f32 * rr.scaler.uniform_scale()).unwrap();
             }
 
             point_size *= 2.0;
@@ -6,8 +6,59 @@ use typenum;
 
 pub type F2dot14 = fix::aliases::binary::IFix16<typenum::N14>;
 
+/// Divides rounding half away from zero, the way TrueType rounds fixed point products and quotients
+fn div_round(a: i64, b: i64) -> i64 {
+    let q = (a.abs() + b.abs() / 2) / b.abs();
+    if (a < 0) != (b < 0) { -q } else { q }
+}
+
+/// Clamps a wide intermediate to what fits in an i32
+fn saturate(v: i64) -> i32 {
+    v.max(i32::MIN as i64).min(i32::MAX as i64) as i32
+}
+
+/// Divides two fixed point numbers whose value of one is `one`, saturating the quotient
+fn fixed_div(a: i32, b: i32, one: i64) -> i32 {
+    saturate(div_round(a as i64 * one, b as i64))
+}
+
+/// A 26.6 fixed point number, the unit of distances in pixels that TrueType instructions use
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct F26d6(i32);
 
+impl F26d6 {
+    pub fn from_bits(bits: i32) -> F26d6 {
+        F26d6(bits)
+    }
+
+    pub fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    pub fn abs(self) -> F26d6 {
+        F26d6(self.0.abs())
+    }
+
+    pub fn floor(self) -> F26d6 {
+        F26d6(self.0 & !63)
+    }
+
+    pub fn ceil(self) -> F26d6 {
+        F26d6(self.0.wrapping_add(63) & !63)
+    }
+
+    pub fn round(self) -> F26d6 {
+        F26d6(self.0.wrapping_add(32) & !63)
+    }
+
+    /// Converts to 2.14, saturating values that don't fit
+    pub fn to_f2dot14(self) -> F2dot14 {
+        let v = (self.0 as i64) << 8;
+        F2dot14::new(v.max(i16::MIN as i64).min(i16::MAX as i64) as i16)
+    }
+}
+
+/// Whole pixels
 impl From<i32> for F26d6 {
     fn from(v: i32) -> F26d6 {
         F26d6(v << 6)
@@ -16,12 +67,17 @@ impl From<i32> for F26d6 {
 
 impl From<f32> for F26d6 {
     fn from(v: f32) -> F26d6 {
-        let i = v.floor() as i32;
-        let f = (v.fract().abs() * 64.0).ceil() as i32;
-        F26d6(i << 6 | (f & 0x0000_004f))
+        F26d6((v * 64.0).round() as i32)
     }
 }
 
+impl From<F2dot14> for F26d6 {
+    fn from(v: F2dot14) -> F26d6 {
+        F26d6(div_round(v.bits as i64, 256) as i32)
+    }
+}
+
+/// The bits as they sit on the interpreter stack
 impl Into<u32> for F26d6 {
     fn into(self) -> u32 {
         self.0 as u32
@@ -30,21 +86,7 @@ impl Into<u32> for F26d6 {
 
 impl Into<f32> for F26d6 {
     fn into(self) -> f32 {
-        self.0 as f32
-    }
-}
-
-impl F26d6 {
-    pub fn abs(self) -> F26d6 {
-        F26d6(self.0.abs())
-    }
-
-    pub fn floor(self) -> F26d6 {
-        F26d6(self.0 & 0xffff_ffc0)
-    }
-
-    pub fn ceil(self) -> F26d6 {
-        F26d6(self.0 & 0xffff_ffc0)
+        self.0 as f32 / 64.0
     }
 }
 
@@ -53,52 +95,152 @@ use std::ops::*;
 impl Add<F26d6> for F26d6 {
     type Output = F26d6;
     fn add(self, othr: F26d6) -> F26d6 {
-        F26d6(self.0 + othr.0)
+        F26d6(self.0.wrapping_add(othr.0))
     }
 }
 impl Sub<F26d6> for F26d6 {
     type Output = F26d6;
     fn sub(self, othr: F26d6) -> F26d6 {
-        F26d6(self.0 - othr.0)
+        F26d6(self.0.wrapping_sub(othr.0))
     }
 }
 impl Mul<F26d6> for F26d6 {
     type Output = F26d6;
     fn mul(self, othr: F26d6) -> F26d6 {
-        F26d6(self.0 * othr.0)
+        F26d6(saturate(div_round(self.0 as i64 * othr.0 as i64, 64)))
     }
 }
+/// Panics when dividing by zero, like integer division
 impl Div<F26d6> for F26d6 {
     type Output = F26d6;
     fn div(self, othr: F26d6) -> F26d6 {
-        F26d6(self.0 / othr.0)
+        F26d6(fixed_div(self.0, othr.0, 64))
     }
 }
 
 impl Neg for F26d6 {
     type Output = F26d6;
     fn neg(self) -> F26d6 {
-        F26d6(-self.0)
+        F26d6(self.0.wrapping_neg())
     }
 }
 
+/// A 16.16 fixed point number, used for table versions and for scale factors
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed(i32);
 
-#[cfg(test)]
-mod tests {
+impl Fixed {
+    pub fn from_binary<R: Read, E: ByteOrder>(r: &mut R) -> IOResult<Fixed> {
+        Ok(Fixed(r.read_i32::<E>()?))
+    }
+
+    pub fn from_bits(bits: i32) -> Fixed {
+        Fixed(bits)
+    }
+
+    pub fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// Multiplies an integer, like a distance in font units, rounding the product
+    pub fn mul_int(self, v: i32) -> i32 {
+        saturate(div_round(self.0 as i64 * v as i64, 0x10000))
+    }
 }
 
-/*#[derive(Copy, Clone, Debug)]
-pub struct F2dot14(u16);
+impl From<f32> for Fixed {
+    fn from(v: f32) -> Fixed {
+        Fixed((v * 65536.0).round() as i32)
+    }
+}
+
+impl From<Fixed> for f32 {
+    fn from(v: Fixed) -> f32 {
+        v.0 as f32 / 65536.0
+    }
+}
 
-impl From<u16> for F2dot14 {
-    fn from(v: u16) -> F2dot14 {
-        F2dot14(v)
+/// Saturates distances of 32768 pixels or more, which 16.16 can't hold
+impl From<F26d6> for Fixed {
+    fn from(v: F26d6) -> Fixed {
+        Fixed(saturate((v.0 as i64) << 10))
     }
 }
 
-//add, sub, mul, div
+impl From<Fixed> for F26d6 {
+    fn from(v: Fixed) -> F26d6 {
+        F26d6(div_round(v.0 as i64, 1024) as i32)
+    }
+}
+
+impl Mul<Fixed> for Fixed {
+    type Output = Fixed;
+    fn mul(self, othr: Fixed) -> Fixed {
+        Fixed(saturate(div_round(self.0 as i64 * othr.0 as i64, 0x10000)))
+    }
+}
+/// Panics when dividing by zero, like integer division
+impl Div<Fixed> for Fixed {
+    type Output = Fixed;
+    fn div(self, othr: Fixed) -> Fixed {
+        Fixed(fixed_div(self.0, othr.0, 0x10000))
+    }
+}
 
-#[derive(Copy, Clone, Debug)]
-pub struct F26dot6(i32);*/
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f26d6_arithmetic() {
+        let (a, b) = (F26d6::from(1.5), F26d6::from(-2.25));
+        assert_eq!((a * b).to_bits(), -216);   // -3.375
+        assert_eq!((b / a).to_bits(), -96);    // -1.5
+        assert_eq!((F26d6::from_bits(1) * F26d6::from_bits(32)).to_bits(), 1);
+        assert_eq!((F26d6::from(1) / F26d6::from(3)).to_bits(), 21);
+        assert_eq!((F26d6::from(2) / F26d6::from(3)).to_bits(), 43);
+        assert_eq!(b.floor(), F26d6::from(-3));
+        assert_eq!(b.ceil(), F26d6::from(-2));
+        assert_eq!(b.round(), F26d6::from(-2));
+        assert_eq!(a.round(), F26d6::from(2));
+        assert_eq!(a.floor(), a.ceil() - F26d6::from(1));
+        let f: f32 = b.into();
+        assert_eq!(f, -2.25);
+        // results that don't fit saturate instead of wrapping around
+        let big = F26d6::from(0x10_0000);
+        assert_eq!((big * big).to_bits(), i32::MAX);
+        assert_eq!((big * -big).to_bits(), i32::MIN);
+        assert_eq!((big / F26d6::from_bits(1)).to_bits(), i32::MAX);
+        let (big, tiny) = (Fixed::from(30000.0), Fixed::from_bits(1));
+        assert_eq!((big * big).to_bits(), i32::MAX);
+        assert_eq!((Fixed::from(-30000.0) / tiny).to_bits(), i32::MIN);
+        assert_eq!(big.mul_int(-0x10_0000), i32::MIN);
+    }
 
+    #[test]
+    fn conversions() {
+        assert_eq!(F26d6::from(F2dot14::new(0x4000)), F26d6::from(1));
+        assert_eq!(F26d6::from(F2dot14::new(-0x2000)).to_bits(), -32);
+        assert_eq!(F26d6::from(0.5).to_f2dot14().bits, 0x2000);
+        assert_eq!(F26d6::from(3).to_f2dot14().bits, 0x7fff);
+        let scale = Fixed::from(0.375);
+        assert_eq!(scale.to_bits(), 0x6000);
+        assert_eq!(scale.mul_int(-3), -1);
+        assert_eq!((scale * Fixed::from(2.0)).to_bits(), 0xc000);
+        assert_eq!((scale / Fixed::from(0.5)).to_bits(), 0xc000);
+        let d: F26d6 = scale.into();
+        assert_eq!(d.to_bits(), 24);
+        assert_eq!(Fixed::from(d), scale);
+        // past what the narrower type holds, both ways saturate rather than wrap
+        assert_eq!(F26d6::from_bits(0x0100_0000).to_f2dot14().bits, 0x7fff);
+        assert_eq!(F26d6::from_bits(-0x0100_0000).to_f2dot14().bits, -0x8000);
+        assert_eq!(F26d6::from_bits(-0x80).to_f2dot14().bits, -0x8000);
+        assert_eq!(Fixed::from(F26d6::from(32767)).to_bits(), 32767 << 16);
+        assert_eq!(Fixed::from(F26d6::from(32768)).to_bits(), i32::MAX);
+        assert_eq!(Fixed::from(F26d6::from(-32768)).to_bits(), i32::MIN);
+        assert_eq!(Fixed::from(F26d6::from(-40000)).to_bits(), i32::MIN);
+        let version = Fixed::from_binary::<_, BigEndian>(&mut &[0x00, 0x02, 0x50, 0x00][..]).unwrap();
+        assert_eq!(version.to_bits(), 0x0002_5000);
+    }
+}
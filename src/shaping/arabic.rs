@@ -0,0 +1,257 @@
+//! Joining for the cursive scripts, Arabic, Syriac, N'Ko and Mongolian. Each letter takes an
+//! isolated, initial, medial or final form depending on whether the letters around it join to it,
+//! and the font's 'isol', 'init', 'medi' and 'fina' features substitute the forms
+
+use super::find_range;
+
+/// How a character joins to its neighbours, from Unicode's ArabicShaping.txt. Left and right are
+/// visual, so a right joining letter joins to the letter before it in the text
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JoiningType {
+    NonJoining,
+    RightJoining,
+    LeftJoining,
+    DualJoining,
+    /// Joins on both sides without changing shape itself, like the tatweel and ZWJ
+    JoinCausing,
+    /// Marks and format characters, which letters join across
+    Transparent
+}
+
+use self::JoiningType::*;
+
+/// Joining types, sorted by their first character. Characters not in here don't join
+const JOINING_TYPES: &[(u32, u32, JoiningType)] = &[
+    (0x0300, 0x036f, Transparent),
+    (0x0483, 0x0489, Transparent),
+    (0x0591, 0x05bd, Transparent),
+    (0x05bf, 0x05bf, Transparent),
+    (0x05c1, 0x05c2, Transparent),
+    (0x05c4, 0x05c5, Transparent),
+    (0x05c7, 0x05c7, Transparent),
+    (0x0610, 0x061a, Transparent),
+    (0x061c, 0x061c, Transparent),
+    (0x0620, 0x0620, DualJoining),
+    (0x0622, 0x0625, RightJoining),
+    (0x0626, 0x0626, DualJoining),
+    (0x0627, 0x0627, RightJoining),
+    (0x0628, 0x0628, DualJoining),
+    (0x0629, 0x0629, RightJoining),
+    (0x062a, 0x062e, DualJoining),
+    (0x062f, 0x0632, RightJoining),
+    (0x0633, 0x063f, DualJoining),
+    (0x0640, 0x0640, JoinCausing),
+    (0x0641, 0x0647, DualJoining),
+    (0x0648, 0x0648, RightJoining),
+    (0x0649, 0x064a, DualJoining),
+    (0x064b, 0x065f, Transparent),
+    (0x066e, 0x066f, DualJoining),
+    (0x0670, 0x0670, Transparent),
+    (0x0671, 0x0673, RightJoining),
+    (0x0675, 0x0677, RightJoining),
+    (0x0678, 0x0687, DualJoining),
+    (0x0688, 0x0699, RightJoining),
+    (0x069a, 0x06bf, DualJoining),
+    (0x06c0, 0x06c0, RightJoining),
+    (0x06c1, 0x06c2, DualJoining),
+    (0x06c3, 0x06cb, RightJoining),
+    (0x06cc, 0x06cc, DualJoining),
+    (0x06cd, 0x06cd, RightJoining),
+    (0x06ce, 0x06ce, DualJoining),
+    (0x06cf, 0x06cf, RightJoining),
+    (0x06d0, 0x06d1, DualJoining),
+    (0x06d2, 0x06d3, RightJoining),
+    (0x06d5, 0x06d5, RightJoining),
+    (0x06d6, 0x06dc, Transparent),
+    (0x06df, 0x06e4, Transparent),
+    (0x06e7, 0x06e8, Transparent),
+    (0x06ea, 0x06ed, Transparent),
+    (0x06ee, 0x06ef, RightJoining),
+    (0x06fa, 0x06fc, DualJoining),
+    (0x06ff, 0x06ff, DualJoining),
+    (0x070f, 0x070f, Transparent),
+    (0x0710, 0x0710, RightJoining),
+    (0x0711, 0x0711, Transparent),
+    (0x0712, 0x0714, DualJoining),
+    (0x0715, 0x0719, RightJoining),
+    (0x071a, 0x071d, DualJoining),
+    (0x071e, 0x071e, RightJoining),
+    (0x071f, 0x0727, DualJoining),
+    (0x0728, 0x0728, RightJoining),
+    (0x0729, 0x0729, DualJoining),
+    (0x072a, 0x072a, RightJoining),
+    (0x072b, 0x072b, DualJoining),
+    (0x072c, 0x072c, RightJoining),
+    (0x072d, 0x072e, DualJoining),
+    (0x072f, 0x072f, RightJoining),
+    (0x0730, 0x074a, Transparent),
+    (0x074d, 0x074d, RightJoining),
+    (0x074e, 0x0758, DualJoining),
+    (0x0759, 0x075b, RightJoining),
+    (0x075c, 0x076a, DualJoining),
+    (0x076b, 0x076c, RightJoining),
+    (0x076d, 0x0770, DualJoining),
+    (0x0771, 0x0771, RightJoining),
+    (0x0772, 0x0772, DualJoining),
+    (0x0773, 0x0774, RightJoining),
+    (0x0775, 0x0777, DualJoining),
+    (0x0778, 0x0779, RightJoining),
+    (0x077a, 0x077f, DualJoining),
+    (0x07a6, 0x07b0, Transparent),
+    (0x07ca, 0x07ea, DualJoining),
+    (0x07eb, 0x07f3, Transparent),
+    (0x07fa, 0x07fa, JoinCausing),
+    (0x07fd, 0x07fd, Transparent),
+    (0x0860, 0x0860, DualJoining),
+    (0x0862, 0x0865, DualJoining),
+    (0x0867, 0x0867, RightJoining),
+    (0x0868, 0x0868, DualJoining),
+    (0x0869, 0x086a, RightJoining),
+    (0x08a0, 0x08a9, DualJoining),
+    (0x08aa, 0x08ac, RightJoining),
+    (0x08ae, 0x08ae, RightJoining),
+    (0x08af, 0x08b0, DualJoining),
+    (0x08b1, 0x08b2, RightJoining),
+    (0x08b3, 0x08b8, DualJoining),
+    (0x08b9, 0x08b9, RightJoining),
+    (0x08ba, 0x08c8, DualJoining),
+    (0x08ca, 0x08e1, Transparent),
+    (0x08e3, 0x08ff, Transparent),
+    (0x1807, 0x1807, DualJoining),
+    (0x180a, 0x180a, JoinCausing),
+    (0x180b, 0x180d, Transparent),
+    (0x180f, 0x180f, Transparent),
+    (0x1820, 0x1878, DualJoining),
+    (0x1885, 0x1886, Transparent),
+    (0x1887, 0x18a8, DualJoining),
+    (0x18a9, 0x18a9, Transparent),
+    (0x18aa, 0x18aa, DualJoining),
+    (0x1ab0, 0x1aff, Transparent),
+    (0x1dc0, 0x1dff, Transparent),
+    (0x200b, 0x200b, Transparent),
+    (0x200d, 0x200d, JoinCausing),
+    (0x200e, 0x200f, Transparent),
+    (0x202a, 0x202e, Transparent),
+    (0x2060, 0x2064, Transparent),
+    (0x20d0, 0x20ff, Transparent),
+    (0xa872, 0xa872, LeftJoining),
+    (0xfe00, 0xfe0f, Transparent),
+    (0xfe20, 0xfe2f, Transparent),
+    (0xfeff, 0xfeff, Transparent),
+    (0xe0100, 0xe01ef, Transparent)
+];
+
+pub fn joining_type(c: char) -> JoiningType {
+    find_range(JOINING_TYPES, c).unwrap_or(NonJoining)
+}
+
+impl JoiningType {
+    /// Whether this joins to the character after it in the text
+    fn joins_next(&self) -> bool {
+        matches!(*self, LeftJoining | DualJoining | JoinCausing)
+    }
+
+    /// Whether this joins to the character before it in the text
+    fn joins_previous(&self) -> bool {
+        matches!(*self, RightJoining | DualJoining | JoinCausing)
+    }
+}
+
+/// The shape a joining letter takes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JoiningForm {
+    Isolated,
+    Initial,
+    Medial,
+    Final
+}
+
+impl JoiningForm {
+    /// The feature that substitutes this form
+    pub fn feature(&self) -> &'static [u8; 4] {
+        match *self {
+            JoiningForm::Isolated => b"isol",
+            JoiningForm::Initial => b"init",
+            JoiningForm::Medial => b"medi",
+            JoiningForm::Final => b"fina"
+        }
+    }
+}
+
+/// The features that substitute joining forms, in the order they are applied. Each one is a stage
+/// of its own, so a glyph one of them changes isn't changed again by a later one
+pub const FORM_FEATURES: &[&[u8; 4]] = &[b"isol", b"fina", b"medi", b"init"];
+
+/// Works out the form of each character in a run. Transparent characters, like the vowel marks,
+/// are skipped over and don't get a form, and neither do characters that don't join at all
+pub fn joining_forms(chars: &[char]) -> Vec<Option<JoiningForm>> {
+    let mut forms = vec![None; chars.len()];
+    // the last character that wasn't transparent, and whether it joins to what comes next
+    let mut previous: Option<(usize, bool)> = None;
+    for (i, &c) in chars.iter().enumerate() {
+        let joining = joining_type(c);
+        if joining == Transparent {
+            continue;
+        }
+        if joining == NonJoining {
+            previous = None;
+            continue;
+        }
+        match previous {
+            Some((p, true)) if joining.joins_previous() => {
+                forms[p] = match forms[p] {
+                    Some(JoiningForm::Isolated) => Some(JoiningForm::Initial),
+                    Some(JoiningForm::Final) => Some(JoiningForm::Medial),
+                    f => f
+                };
+                forms[i] = Some(JoiningForm::Final);
+            },
+            _ => forms[i] = Some(JoiningForm::Isolated)
+        }
+        previous = Some((i, joining.joins_next()));
+    }
+    // join causing characters join their neighbours but keep their own shape
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '\u{200d}' {
+            forms[i] = None;
+        }
+    }
+    forms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::JoiningForm::*;
+
+    #[test]
+    fn joining() {
+        assert_eq!(joining_type('\u{628}'), DualJoining);
+        assert_eq!(joining_type('\u{627}'), RightJoining);
+        assert_eq!(joining_type('\u{64e}'), Transparent);
+        assert_eq!(joining_type('a'), NonJoining);
+        assert_eq!(joining_type('\u{7ca}'), DualJoining);
+        assert_eq!(joining_type('\u{1820}'), DualJoining);
+        assert_eq!(joining_type('\u{a872}'), LeftJoining);
+
+        // beh alef beh: the alef doesn't join to what follows it
+        let forms = joining_forms(&['\u{628}', '\u{627}', '\u{628}']);
+        assert_eq!(forms, vec![Some(Initial), Some(Final), Some(Isolated)]);
+
+        // beh beh beh with a fatha in the middle, and a space breaking off the last word
+        let forms = joining_forms(&['\u{628}', '\u{628}', '\u{64e}', '\u{628}', ' ', '\u{628}']);
+        assert_eq!(forms, vec![Some(Initial), Some(Medial), None, Some(Final), None, Some(Isolated)]);
+
+        // a tatweel or ZWJ makes the letter before it join forward
+        let forms = joining_forms(&['\u{628}', '\u{640}']);
+        assert_eq!(forms[0], Some(Initial));
+        let forms = joining_forms(&['\u{628}', '\u{200d}']);
+        assert_eq!(forms, vec![Some(Initial), None]);
+
+        // Syriac and N'Ko join the same way
+        let forms = joining_forms(&['\u{712}', '\u{710}']);
+        assert_eq!(forms, vec![Some(Initial), Some(Final)]);
+        let forms = joining_forms(&['\u{7ca}', '\u{7cb}', '\u{7cc}']);
+        assert_eq!(forms, vec![Some(Initial), Some(Medial), Some(Final)]);
+    }
+}
@@ -0,0 +1,1366 @@
+//! The Unicode Bidirectional Algorithm (UAX #9). It works out which way each character in a
+//! paragraph mixing left to right and right to left text goes, as an embedding level that is odd
+//! for right to left, and the order the characters of a line are drawn in
+
+use super::find_range;
+
+/// The bidirectional class of a character, from Unicode's DerivedBidiClass.txt
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BidiClass {
+    /// Left to right letters, like Latin
+    L,
+    /// Right to left letters, like Hebrew
+    R,
+    /// Arabic letters, which are right to left and make the digits after them Arabic numbers
+    AL,
+    /// European digits
+    EN,
+    /// Plus and minus signs
+    ES,
+    /// Currency and percent signs, which go with the number next to them
+    ET,
+    /// Arabic digits
+    AN,
+    /// Separators inside numbers, like the comma and full stop
+    CS,
+    /// Combining marks, which take the class of the character they are on
+    NSM,
+    /// Controls and format characters that are ignored
+    BN,
+    /// Paragraph separators
+    B,
+    /// Tabs
+    S,
+    /// Spaces
+    WS,
+    /// Everything else that has no direction of its own, like most punctuation
+    ON,
+    LRE,
+    LRO,
+    RLE,
+    RLO,
+    PDF,
+    LRI,
+    RLI,
+    FSI,
+    PDI
+}
+
+use self::BidiClass::*;
+
+/// Bidi classes, sorted by their first character. Characters not in here are `L`
+const BIDI_CLASSES: &[(u32, u32, BidiClass)] = &[
+    (0x0000, 0x0008, BN),
+    (0x0009, 0x0009, S),
+    (0x000a, 0x000a, B),
+    (0x000b, 0x000b, S),
+    (0x000c, 0x000c, WS),
+    (0x000d, 0x000d, B),
+    (0x000e, 0x001b, BN),
+    (0x001c, 0x001e, B),
+    (0x001f, 0x001f, S),
+    (0x0020, 0x0020, WS),
+    (0x0021, 0x0022, ON),
+    (0x0023, 0x0025, ET),
+    (0x0026, 0x002a, ON),
+    (0x002b, 0x002b, ES),
+    (0x002c, 0x002c, CS),
+    (0x002d, 0x002d, ES),
+    (0x002e, 0x002f, CS),
+    (0x0030, 0x0039, EN),
+    (0x003a, 0x003a, CS),
+    (0x003b, 0x0040, ON),
+    (0x005b, 0x0060, ON),
+    (0x007b, 0x007e, ON),
+    (0x007f, 0x0084, BN),
+    (0x0085, 0x0085, B),
+    (0x0086, 0x009f, BN),
+    (0x00a0, 0x00a0, CS),
+    (0x00a1, 0x00a1, ON),
+    (0x00a2, 0x00a5, ET),
+    (0x00a6, 0x00a9, ON),
+    (0x00ab, 0x00ac, ON),
+    (0x00ad, 0x00ad, BN),
+    (0x00ae, 0x00af, ON),
+    (0x00b0, 0x00b1, ET),
+    (0x00b2, 0x00b3, EN),
+    (0x00b4, 0x00b4, ON),
+    (0x00b6, 0x00b8, ON),
+    (0x00b9, 0x00b9, EN),
+    (0x00bb, 0x00bf, ON),
+    (0x00d7, 0x00d7, ON),
+    (0x00f7, 0x00f7, ON),
+    (0x02b9, 0x02ba, ON),
+    (0x02c2, 0x02cf, ON),
+    (0x02d2, 0x02df, ON),
+    (0x02e5, 0x02ed, ON),
+    (0x02ef, 0x02ff, ON),
+    (0x0300, 0x036f, NSM),
+    (0x0374, 0x0375, ON),
+    (0x037e, 0x037e, ON),
+    (0x0384, 0x0385, ON),
+    (0x0387, 0x0387, ON),
+    (0x03f6, 0x03f6, ON),
+    (0x0483, 0x0489, NSM),
+    (0x058a, 0x058e, ON),
+    (0x058f, 0x058f, ET),
+    (0x0590, 0x0590, R),
+    (0x0591, 0x05bd, NSM),
+    (0x05be, 0x05be, R),
+    (0x05bf, 0x05bf, NSM),
+    (0x05c0, 0x05c0, R),
+    (0x05c1, 0x05c2, NSM),
+    (0x05c3, 0x05c3, R),
+    (0x05c4, 0x05c5, NSM),
+    (0x05c6, 0x05c6, R),
+    (0x05c7, 0x05c7, NSM),
+    (0x05c8, 0x05ff, R),
+    (0x0600, 0x0605, AN),
+    (0x0606, 0x0607, ON),
+    (0x0608, 0x0608, AL),
+    (0x0609, 0x060a, ET),
+    (0x060b, 0x060b, AL),
+    (0x060c, 0x060c, CS),
+    (0x060d, 0x060d, AL),
+    (0x060e, 0x060f, ON),
+    (0x0610, 0x061a, NSM),
+    (0x061b, 0x064a, AL),
+    (0x064b, 0x065f, NSM),
+    (0x0660, 0x0669, AN),
+    (0x066a, 0x066a, ET),
+    (0x066b, 0x066c, AN),
+    (0x066d, 0x066f, AL),
+    (0x0670, 0x0670, NSM),
+    (0x0671, 0x06d5, AL),
+    (0x06d6, 0x06dc, NSM),
+    (0x06dd, 0x06dd, AN),
+    (0x06de, 0x06de, ON),
+    (0x06df, 0x06e4, NSM),
+    (0x06e5, 0x06e6, AL),
+    (0x06e7, 0x06e8, NSM),
+    (0x06e9, 0x06e9, ON),
+    (0x06ea, 0x06ed, NSM),
+    (0x06ee, 0x06ef, AL),
+    (0x06f0, 0x06f9, EN),
+    (0x06fa, 0x0710, AL),
+    (0x0711, 0x0711, NSM),
+    (0x0712, 0x072f, AL),
+    (0x0730, 0x074a, NSM),
+    (0x074b, 0x07a5, AL),
+    (0x07a6, 0x07b0, NSM),
+    (0x07b1, 0x07bf, AL),
+    (0x07c0, 0x07ea, R),
+    (0x07eb, 0x07f3, NSM),
+    (0x07f4, 0x07f5, R),
+    (0x07f6, 0x07f9, ON),
+    (0x07fa, 0x07fc, R),
+    (0x07fd, 0x07fd, NSM),
+    (0x07fe, 0x0815, R),
+    (0x0816, 0x0819, NSM),
+    (0x081a, 0x081a, R),
+    (0x081b, 0x0823, NSM),
+    (0x0824, 0x0824, R),
+    (0x0825, 0x0827, NSM),
+    (0x0828, 0x0828, R),
+    (0x0829, 0x082d, NSM),
+    (0x082e, 0x0858, R),
+    (0x0859, 0x085b, NSM),
+    (0x085c, 0x085f, R),
+    (0x0860, 0x088f, AL),
+    (0x0890, 0x0891, AN),
+    (0x0892, 0x0897, AL),
+    (0x0898, 0x089f, NSM),
+    (0x08a0, 0x08c9, AL),
+    (0x08ca, 0x08e1, NSM),
+    (0x08e2, 0x08e2, AN),
+    (0x08e3, 0x0902, NSM),
+    (0x093a, 0x093a, NSM),
+    (0x093c, 0x093c, NSM),
+    (0x0941, 0x0948, NSM),
+    (0x094d, 0x094d, NSM),
+    (0x0951, 0x0957, NSM),
+    (0x0962, 0x0963, NSM),
+    (0x0981, 0x0981, NSM),
+    (0x09bc, 0x09bc, NSM),
+    (0x09c1, 0x09c4, NSM),
+    (0x09cd, 0x09cd, NSM),
+    (0x09e2, 0x09e3, NSM),
+    (0x09f2, 0x09f3, ET),
+    (0x09fb, 0x09fb, ET),
+    (0x09fe, 0x0a02, NSM),
+    (0x0a3c, 0x0a3c, NSM),
+    (0x0a41, 0x0a51, NSM),
+    (0x0a70, 0x0a71, NSM),
+    (0x0a75, 0x0a75, NSM),
+    (0x0a81, 0x0a82, NSM),
+    (0x0abc, 0x0abc, NSM),
+    (0x0ac1, 0x0ac8, NSM),
+    (0x0acd, 0x0acd, NSM),
+    (0x0ae2, 0x0ae3, NSM),
+    (0x0af1, 0x0af1, ET),
+    (0x0afa, 0x0b01, NSM),
+    (0x0b3c, 0x0b3c, NSM),
+    (0x0b3f, 0x0b3f, NSM),
+    (0x0b41, 0x0b44, NSM),
+    (0x0b4d, 0x0b56, NSM),
+    (0x0b62, 0x0b63, NSM),
+    (0x0b82, 0x0b82, NSM),
+    (0x0bc0, 0x0bc0, NSM),
+    (0x0bcd, 0x0bcd, NSM),
+    (0x0bf3, 0x0bf8, ON),
+    (0x0bf9, 0x0bf9, ET),
+    (0x0bfa, 0x0bfa, ON),
+    (0x0c00, 0x0c00, NSM),
+    (0x0c04, 0x0c04, NSM),
+    (0x0c3c, 0x0c3c, NSM),
+    (0x0c3e, 0x0c40, NSM),
+    (0x0c46, 0x0c56, NSM),
+    (0x0c62, 0x0c63, NSM),
+    (0x0c78, 0x0c7e, ON),
+    (0x0c81, 0x0c81, NSM),
+    (0x0cbc, 0x0cbc, NSM),
+    (0x0ccc, 0x0ccd, NSM),
+    (0x0ce2, 0x0ce3, NSM),
+    (0x0d00, 0x0d01, NSM),
+    (0x0d3b, 0x0d3c, NSM),
+    (0x0d41, 0x0d44, NSM),
+    (0x0d4d, 0x0d4d, NSM),
+    (0x0d62, 0x0d63, NSM),
+    (0x0d81, 0x0d81, NSM),
+    (0x0dca, 0x0dca, NSM),
+    (0x0dd2, 0x0dd6, NSM),
+    (0x0e31, 0x0e31, NSM),
+    (0x0e34, 0x0e3a, NSM),
+    (0x0e3f, 0x0e3f, ET),
+    (0x0e47, 0x0e4e, NSM),
+    (0x0eb1, 0x0eb1, NSM),
+    (0x0eb4, 0x0ebc, NSM),
+    (0x0ec8, 0x0ecd, NSM),
+    (0x0f18, 0x0f19, NSM),
+    (0x0f35, 0x0f35, NSM),
+    (0x0f37, 0x0f37, NSM),
+    (0x0f39, 0x0f39, NSM),
+    (0x0f3a, 0x0f3d, ON),
+    (0x0f71, 0x0f7e, NSM),
+    (0x0f80, 0x0f84, NSM),
+    (0x0f86, 0x0f87, NSM),
+    (0x0f8d, 0x0fbc, NSM),
+    (0x0fc6, 0x0fc6, NSM),
+    (0x102d, 0x1030, NSM),
+    (0x1032, 0x1037, NSM),
+    (0x1039, 0x103a, NSM),
+    (0x103d, 0x103e, NSM),
+    (0x1058, 0x1059, NSM),
+    (0x105e, 0x1060, NSM),
+    (0x1071, 0x1074, NSM),
+    (0x1082, 0x1082, NSM),
+    (0x1085, 0x1086, NSM),
+    (0x108d, 0x108d, NSM),
+    (0x109d, 0x109d, NSM),
+    (0x135d, 0x135f, NSM),
+    (0x1390, 0x1399, ON),
+    (0x1400, 0x1400, ON),
+    (0x1680, 0x1680, WS),
+    (0x169b, 0x169c, ON),
+    (0x1712, 0x1714, NSM),
+    (0x1732, 0x1733, NSM),
+    (0x1752, 0x1753, NSM),
+    (0x1772, 0x1773, NSM),
+    (0x17b4, 0x17b5, NSM),
+    (0x17b7, 0x17bd, NSM),
+    (0x17c6, 0x17c6, NSM),
+    (0x17c9, 0x17d3, NSM),
+    (0x17db, 0x17db, ET),
+    (0x17dd, 0x17dd, NSM),
+    (0x17f0, 0x180a, ON),
+    (0x180b, 0x180d, NSM),
+    (0x180e, 0x180e, BN),
+    (0x180f, 0x180f, NSM),
+    (0x1885, 0x1886, NSM),
+    (0x18a9, 0x18a9, NSM),
+    (0x1920, 0x1922, NSM),
+    (0x1927, 0x1928, NSM),
+    (0x1932, 0x1932, NSM),
+    (0x1939, 0x193b, NSM),
+    (0x1940, 0x1945, ON),
+    (0x19de, 0x19ff, ON),
+    (0x1a17, 0x1a18, NSM),
+    (0x1a1b, 0x1a1b, NSM),
+    (0x1a56, 0x1a56, NSM),
+    (0x1a58, 0x1a60, NSM),
+    (0x1a62, 0x1a62, NSM),
+    (0x1a65, 0x1a6c, NSM),
+    (0x1a73, 0x1a7f, NSM),
+    (0x1ab0, 0x1b03, NSM),
+    (0x1b34, 0x1b34, NSM),
+    (0x1b36, 0x1b3a, NSM),
+    (0x1b3c, 0x1b3c, NSM),
+    (0x1b42, 0x1b42, NSM),
+    (0x1b6b, 0x1b73, NSM),
+    (0x1b80, 0x1b81, NSM),
+    (0x1ba2, 0x1ba5, NSM),
+    (0x1ba8, 0x1ba9, NSM),
+    (0x1bab, 0x1bad, NSM),
+    (0x1be6, 0x1be6, NSM),
+    (0x1be8, 0x1be9, NSM),
+    (0x1bed, 0x1bed, NSM),
+    (0x1bef, 0x1bf1, NSM),
+    (0x1c2c, 0x1c33, NSM),
+    (0x1c36, 0x1c37, NSM),
+    (0x1cd0, 0x1cd2, NSM),
+    (0x1cd4, 0x1ce0, NSM),
+    (0x1ce2, 0x1ce8, NSM),
+    (0x1ced, 0x1ced, NSM),
+    (0x1cf4, 0x1cf4, NSM),
+    (0x1cf8, 0x1cf9, NSM),
+    (0x1dc0, 0x1dff, NSM),
+    (0x1fbd, 0x1fbd, ON),
+    (0x1fbf, 0x1fc1, ON),
+    (0x1fcd, 0x1fcf, ON),
+    (0x1fdd, 0x1fdf, ON),
+    (0x1fed, 0x1fef, ON),
+    (0x1ffd, 0x1ffe, ON),
+    (0x2000, 0x200a, WS),
+    (0x200b, 0x200d, BN),
+    (0x200f, 0x200f, R),
+    (0x2010, 0x2027, ON),
+    (0x2028, 0x2028, WS),
+    (0x2029, 0x2029, B),
+    (0x202a, 0x202a, LRE),
+    (0x202b, 0x202b, RLE),
+    (0x202c, 0x202c, PDF),
+    (0x202d, 0x202d, LRO),
+    (0x202e, 0x202e, RLO),
+    (0x202f, 0x202f, CS),
+    (0x2030, 0x2034, ET),
+    (0x2035, 0x2043, ON),
+    (0x2044, 0x2044, CS),
+    (0x2045, 0x205e, ON),
+    (0x205f, 0x205f, WS),
+    (0x2060, 0x2064, BN),
+    (0x2066, 0x2066, LRI),
+    (0x2067, 0x2067, RLI),
+    (0x2068, 0x2068, FSI),
+    (0x2069, 0x2069, PDI),
+    (0x206a, 0x206f, BN),
+    (0x2070, 0x2070, EN),
+    (0x2074, 0x2079, EN),
+    (0x207a, 0x207b, ES),
+    (0x207c, 0x207e, ON),
+    (0x2080, 0x2089, EN),
+    (0x208a, 0x208b, ES),
+    (0x208c, 0x208e, ON),
+    (0x20a0, 0x20cf, ET),
+    (0x20d0, 0x20f0, NSM),
+    (0x2100, 0x2101, ON),
+    (0x2103, 0x2106, ON),
+    (0x2108, 0x2109, ON),
+    (0x2114, 0x2114, ON),
+    (0x2116, 0x2118, ON),
+    (0x211e, 0x2123, ON),
+    (0x2125, 0x2125, ON),
+    (0x2127, 0x2127, ON),
+    (0x2129, 0x2129, ON),
+    (0x212e, 0x212e, ET),
+    (0x213a, 0x213b, ON),
+    (0x2140, 0x2144, ON),
+    (0x214a, 0x214d, ON),
+    (0x2150, 0x215f, ON),
+    (0x2189, 0x2211, ON),
+    (0x2212, 0x2212, ES),
+    (0x2213, 0x2213, ET),
+    (0x2214, 0x2335, ON),
+    (0x237b, 0x2394, ON),
+    (0x2396, 0x2487, ON),
+    (0x2488, 0x249b, EN),
+    (0x24ea, 0x26ab, ON),
+    (0x26ad, 0x27ff, ON),
+    (0x2900, 0x2bff, ON),
+    (0x2ce5, 0x2cea, ON),
+    (0x2cef, 0x2cf1, NSM),
+    (0x2cf9, 0x2cff, ON),
+    (0x2d7f, 0x2d7f, NSM),
+    (0x2de0, 0x2dff, NSM),
+    (0x2e00, 0x2ffb, ON),
+    (0x3000, 0x3000, WS),
+    (0x3001, 0x3004, ON),
+    (0x3008, 0x3020, ON),
+    (0x302a, 0x302d, NSM),
+    (0x3030, 0x3030, ON),
+    (0x3036, 0x3037, ON),
+    (0x303d, 0x303f, ON),
+    (0x3099, 0x309a, NSM),
+    (0x309b, 0x309c, ON),
+    (0x30a0, 0x30a0, ON),
+    (0x30fb, 0x30fb, ON),
+    (0x31c0, 0x31e3, ON),
+    (0x321d, 0x321e, ON),
+    (0x3250, 0x325f, ON),
+    (0x327c, 0x327e, ON),
+    (0x32b1, 0x32bf, ON),
+    (0x32cc, 0x32cf, ON),
+    (0x3377, 0x337a, ON),
+    (0x33de, 0x33df, ON),
+    (0x33ff, 0x33ff, ON),
+    (0x4dc0, 0x4dff, ON),
+    (0xa490, 0xa4c6, ON),
+    (0xa60d, 0xa60f, ON),
+    (0xa66f, 0xa672, NSM),
+    (0xa673, 0xa673, ON),
+    (0xa674, 0xa67d, NSM),
+    (0xa67e, 0xa67f, ON),
+    (0xa69e, 0xa69f, NSM),
+    (0xa6f0, 0xa6f1, NSM),
+    (0xa700, 0xa721, ON),
+    (0xa788, 0xa788, ON),
+    (0xa802, 0xa802, NSM),
+    (0xa806, 0xa806, NSM),
+    (0xa80b, 0xa80b, NSM),
+    (0xa825, 0xa826, NSM),
+    (0xa828, 0xa82b, ON),
+    (0xa82c, 0xa82c, NSM),
+    (0xa838, 0xa839, ET),
+    (0xa874, 0xa877, ON),
+    (0xa8c4, 0xa8c5, NSM),
+    (0xa8e0, 0xa8f1, NSM),
+    (0xa8ff, 0xa8ff, NSM),
+    (0xa926, 0xa92d, NSM),
+    (0xa947, 0xa951, NSM),
+    (0xa980, 0xa982, NSM),
+    (0xa9b3, 0xa9b3, NSM),
+    (0xa9b6, 0xa9b9, NSM),
+    (0xa9bc, 0xa9bd, NSM),
+    (0xa9e5, 0xa9e5, NSM),
+    (0xaa29, 0xaa2e, NSM),
+    (0xaa31, 0xaa32, NSM),
+    (0xaa35, 0xaa36, NSM),
+    (0xaa43, 0xaa43, NSM),
+    (0xaa4c, 0xaa4c, NSM),
+    (0xaa7c, 0xaa7c, NSM),
+    (0xaab0, 0xaab0, NSM),
+    (0xaab2, 0xaab4, NSM),
+    (0xaab7, 0xaab8, NSM),
+    (0xaabe, 0xaabf, NSM),
+    (0xaac1, 0xaac1, NSM),
+    (0xaaec, 0xaaed, NSM),
+    (0xaaf6, 0xaaf6, NSM),
+    (0xab6a, 0xab6b, ON),
+    (0xabe5, 0xabe5, NSM),
+    (0xabe8, 0xabe8, NSM),
+    (0xabed, 0xabed, NSM),
+    (0xfb1d, 0xfb1d, R),
+    (0xfb1e, 0xfb1e, NSM),
+    (0xfb1f, 0xfb28, R),
+    (0xfb29, 0xfb29, ES),
+    (0xfb2a, 0xfb4f, R),
+    (0xfb50, 0xfd3d, AL),
+    (0xfd3e, 0xfd4f, ON),
+    (0xfd50, 0xfdce, AL),
+    (0xfdcf, 0xfdcf, ON),
+    (0xfdd0, 0xfdef, BN),
+    (0xfdf0, 0xfdfc, AL),
+    (0xfdfd, 0xfdff, ON),
+    (0xfe00, 0xfe0f, NSM),
+    (0xfe10, 0xfe19, ON),
+    (0xfe20, 0xfe2f, NSM),
+    (0xfe30, 0xfe4f, ON),
+    (0xfe50, 0xfe50, CS),
+    (0xfe51, 0xfe51, ON),
+    (0xfe52, 0xfe52, CS),
+    (0xfe54, 0xfe54, ON),
+    (0xfe55, 0xfe55, CS),
+    (0xfe56, 0xfe5e, ON),
+    (0xfe5f, 0xfe5f, ET),
+    (0xfe60, 0xfe61, ON),
+    (0xfe62, 0xfe63, ES),
+    (0xfe64, 0xfe68, ON),
+    (0xfe69, 0xfe6a, ET),
+    (0xfe6b, 0xfe6b, ON),
+    (0xfe70, 0xfefe, AL),
+    (0xfeff, 0xfeff, BN),
+    (0xff01, 0xff02, ON),
+    (0xff03, 0xff05, ET),
+    (0xff06, 0xff0a, ON),
+    (0xff0b, 0xff0b, ES),
+    (0xff0c, 0xff0c, CS),
+    (0xff0d, 0xff0d, ES),
+    (0xff0e, 0xff0f, CS),
+    (0xff10, 0xff19, EN),
+    (0xff1a, 0xff1a, CS),
+    (0xff1b, 0xff20, ON),
+    (0xff3b, 0xff40, ON),
+    (0xff5b, 0xff65, ON),
+    (0xffe0, 0xffe1, ET),
+    (0xffe2, 0xffe4, ON),
+    (0xffe5, 0xffe6, ET),
+    (0xffe8, 0xfffd, ON),
+    (0xfffe, 0xffff, BN),
+    (0x10101, 0x10101, ON),
+    (0x10140, 0x1018c, ON),
+    (0x10190, 0x101a0, ON),
+    (0x101fd, 0x101fd, NSM),
+    (0x102e0, 0x102e0, NSM),
+    (0x102e1, 0x102fb, EN),
+    (0x10376, 0x1037a, NSM),
+    (0x10800, 0x1091e, R),
+    (0x1091f, 0x1091f, ON),
+    (0x10920, 0x10a00, R),
+    (0x10a01, 0x10a03, NSM),
+    (0x10a04, 0x10a04, R),
+    (0x10a05, 0x10a06, NSM),
+    (0x10a07, 0x10a0b, R),
+    (0x10a0c, 0x10a0f, NSM),
+    (0x10a10, 0x10a37, R),
+    (0x10a38, 0x10a3a, NSM),
+    (0x10a3b, 0x10a3e, R),
+    (0x10a3f, 0x10a3f, NSM),
+    (0x10a40, 0x10ae4, R),
+    (0x10ae5, 0x10ae6, NSM),
+    (0x10ae7, 0x10b38, R),
+    (0x10b39, 0x10b3f, ON),
+    (0x10b40, 0x10cff, R),
+    (0x10d00, 0x10d23, AL),
+    (0x10d24, 0x10d27, NSM),
+    (0x10d28, 0x10d2f, AL),
+    (0x10d30, 0x10d39, AN),
+    (0x10d3a, 0x10d3f, AL),
+    (0x10d40, 0x10e5f, R),
+    (0x10e60, 0x10e7e, AN),
+    (0x10e7f, 0x10eaa, R),
+    (0x10eab, 0x10eac, NSM),
+    (0x10ead, 0x10ebf, R),
+    (0x10ec0, 0x10eff, AL),
+    (0x10f00, 0x10f2f, R),
+    (0x10f30, 0x10f45, AL),
+    (0x10f46, 0x10f50, NSM),
+    (0x10f51, 0x10f6f, AL),
+    (0x10f70, 0x10f81, R),
+    (0x10f82, 0x10f85, NSM),
+    (0x10f86, 0x10fff, R),
+    (0x11001, 0x11001, NSM),
+    (0x11038, 0x11046, NSM),
+    (0x11052, 0x11065, ON),
+    (0x11070, 0x11070, NSM),
+    (0x11073, 0x11074, NSM),
+    (0x1107f, 0x11081, NSM),
+    (0x110b3, 0x110b6, NSM),
+    (0x110b9, 0x110ba, NSM),
+    (0x110c2, 0x110c2, NSM),
+    (0x11100, 0x11102, NSM),
+    (0x11127, 0x1112b, NSM),
+    (0x1112d, 0x11134, NSM),
+    (0x11173, 0x11173, NSM),
+    (0x11180, 0x11181, NSM),
+    (0x111b6, 0x111be, NSM),
+    (0x111c9, 0x111cc, NSM),
+    (0x111cf, 0x111cf, NSM),
+    (0x1122f, 0x11231, NSM),
+    (0x11234, 0x11234, NSM),
+    (0x11236, 0x11237, NSM),
+    (0x1123e, 0x1123e, NSM),
+    (0x112df, 0x112df, NSM),
+    (0x112e3, 0x112ea, NSM),
+    (0x11300, 0x11301, NSM),
+    (0x1133b, 0x1133c, NSM),
+    (0x11340, 0x11340, NSM),
+    (0x11366, 0x11374, NSM),
+    (0x11438, 0x1143f, NSM),
+    (0x11442, 0x11444, NSM),
+    (0x11446, 0x11446, NSM),
+    (0x1145e, 0x1145e, NSM),
+    (0x114b3, 0x114b8, NSM),
+    (0x114ba, 0x114ba, NSM),
+    (0x114bf, 0x114c0, NSM),
+    (0x114c2, 0x114c3, NSM),
+    (0x115b2, 0x115b5, NSM),
+    (0x115bc, 0x115bd, NSM),
+    (0x115bf, 0x115c0, NSM),
+    (0x115dc, 0x115dd, NSM),
+    (0x11633, 0x1163a, NSM),
+    (0x1163d, 0x1163d, NSM),
+    (0x1163f, 0x11640, NSM),
+    (0x11660, 0x1166c, ON),
+    (0x116ab, 0x116ab, NSM),
+    (0x116ad, 0x116ad, NSM),
+    (0x116b0, 0x116b5, NSM),
+    (0x116b7, 0x116b7, NSM),
+    (0x1171d, 0x1171f, NSM),
+    (0x11722, 0x11725, NSM),
+    (0x11727, 0x1172b, NSM),
+    (0x1182f, 0x11837, NSM),
+    (0x11839, 0x1183a, NSM),
+    (0x1193b, 0x1193c, NSM),
+    (0x1193e, 0x1193e, NSM),
+    (0x11943, 0x11943, NSM),
+    (0x119d4, 0x119db, NSM),
+    (0x119e0, 0x119e0, NSM),
+    (0x11a01, 0x11a06, NSM),
+    (0x11a09, 0x11a0a, NSM),
+    (0x11a33, 0x11a38, NSM),
+    (0x11a3b, 0x11a3e, NSM),
+    (0x11a47, 0x11a47, NSM),
+    (0x11a51, 0x11a56, NSM),
+    (0x11a59, 0x11a5b, NSM),
+    (0x11a8a, 0x11a96, NSM),
+    (0x11a98, 0x11a99, NSM),
+    (0x11c30, 0x11c3d, NSM),
+    (0x11c92, 0x11ca7, NSM),
+    (0x11caa, 0x11cb0, NSM),
+    (0x11cb2, 0x11cb3, NSM),
+    (0x11cb5, 0x11cb6, NSM),
+    (0x11d31, 0x11d45, NSM),
+    (0x11d47, 0x11d47, NSM),
+    (0x11d90, 0x11d91, NSM),
+    (0x11d95, 0x11d95, NSM),
+    (0x11d97, 0x11d97, NSM),
+    (0x11ef3, 0x11ef4, NSM),
+    (0x11fd5, 0x11fdc, ON),
+    (0x11fdd, 0x11fe0, ET),
+    (0x11fe1, 0x11ff1, ON),
+    (0x16af0, 0x16af4, NSM),
+    (0x16b30, 0x16b36, NSM),
+    (0x16f4f, 0x16f4f, NSM),
+    (0x16f8f, 0x16f92, NSM),
+    (0x16fe2, 0x16fe2, ON),
+    (0x16fe4, 0x16fe4, NSM),
+    (0x1bc9d, 0x1bc9e, NSM),
+    (0x1bca0, 0x1bca3, BN),
+    (0x1cf00, 0x1cf46, NSM),
+    (0x1d167, 0x1d169, NSM),
+    (0x1d173, 0x1d17a, BN),
+    (0x1d17b, 0x1d182, NSM),
+    (0x1d185, 0x1d18b, NSM),
+    (0x1d1aa, 0x1d1ad, NSM),
+    (0x1d1e9, 0x1d241, ON),
+    (0x1d242, 0x1d244, NSM),
+    (0x1d245, 0x1d245, ON),
+    (0x1d300, 0x1d356, ON),
+    (0x1d6db, 0x1d6db, ON),
+    (0x1d715, 0x1d715, ON),
+    (0x1d74f, 0x1d74f, ON),
+    (0x1d789, 0x1d789, ON),
+    (0x1d7c3, 0x1d7c3, ON),
+    (0x1d7ce, 0x1d7ff, EN),
+    (0x1da00, 0x1da36, NSM),
+    (0x1da3b, 0x1da6c, NSM),
+    (0x1da75, 0x1da75, NSM),
+    (0x1da84, 0x1da84, NSM),
+    (0x1da9b, 0x1daaf, NSM),
+    (0x1e000, 0x1e02a, NSM),
+    (0x1e130, 0x1e136, NSM),
+    (0x1e2ae, 0x1e2ae, NSM),
+    (0x1e2ec, 0x1e2ef, NSM),
+    (0x1e2ff, 0x1e2ff, ET),
+    (0x1e800, 0x1e8cf, R),
+    (0x1e8d0, 0x1e8d6, NSM),
+    (0x1e8d7, 0x1e943, R),
+    (0x1e944, 0x1e94a, NSM),
+    (0x1e94b, 0x1ec6f, R),
+    (0x1ec70, 0x1ecbf, AL),
+    (0x1ecc0, 0x1ecff, R),
+    (0x1ed00, 0x1ed4f, AL),
+    (0x1ed50, 0x1edff, R),
+    (0x1ee00, 0x1eeef, AL),
+    (0x1eef0, 0x1eef1, ON),
+    (0x1eef2, 0x1eeff, AL),
+    (0x1ef00, 0x1efff, R),
+    (0x1f000, 0x1f0f5, ON),
+    (0x1f100, 0x1f10a, EN),
+    (0x1f10b, 0x1f10f, ON),
+    (0x1f12f, 0x1f12f, ON),
+    (0x1f16a, 0x1f16f, ON),
+    (0x1f1ad, 0x1f1ad, ON),
+    (0x1f260, 0x1fbca, ON),
+    (0x1fbf0, 0x1fbf9, EN),
+    (0x1fffe, 0x1ffff, BN),
+    (0x2fffe, 0x2ffff, BN),
+    (0x3fffe, 0xe00ff, BN),
+    (0xe0100, 0xe01ef, NSM),
+    (0xe01f0, 0xeffff, BN),
+    (0xffffe, 0xfffff, BN),
+    (0x10fffe, 0x10ffff, BN)
+];
+
+/// Characters and the character that is their mirror image, like the brackets, sorted by the first
+const MIRRORS: &[(u32, u32)] = &[
+    (0x0028, 0x0029), (0x0029, 0x0028), (0x003c, 0x003e), (0x003e, 0x003c),
+    (0x005b, 0x005d), (0x005d, 0x005b), (0x007b, 0x007d), (0x007d, 0x007b),
+    (0x00ab, 0x00bb), (0x00bb, 0x00ab), (0x0f3a, 0x0f3b), (0x0f3b, 0x0f3a),
+    (0x0f3c, 0x0f3d), (0x0f3d, 0x0f3c), (0x169b, 0x169c), (0x169c, 0x169b),
+    (0x2039, 0x203a), (0x203a, 0x2039), (0x2045, 0x2046), (0x2046, 0x2045),
+    (0x207d, 0x207e), (0x207e, 0x207d), (0x208d, 0x208e), (0x208e, 0x208d),
+    (0x2208, 0x220b), (0x220a, 0x220d), (0x220b, 0x2208), (0x220d, 0x220a),
+    (0x2264, 0x2265), (0x2265, 0x2264), (0x2266, 0x2267), (0x2267, 0x2266),
+    (0x2268, 0x2269), (0x2269, 0x2268), (0x226a, 0x226b), (0x226b, 0x226a),
+    (0x226e, 0x226f), (0x226f, 0x226e), (0x2270, 0x2271), (0x2271, 0x2270),
+    (0x2272, 0x2273), (0x2273, 0x2272), (0x2274, 0x2275), (0x2275, 0x2274),
+    (0x2276, 0x2277), (0x2277, 0x2276), (0x2278, 0x2279), (0x2279, 0x2278),
+    (0x227a, 0x227b), (0x227b, 0x227a), (0x227c, 0x227d), (0x227d, 0x227c),
+    (0x227e, 0x227f), (0x227f, 0x227e), (0x2282, 0x2283), (0x2283, 0x2282),
+    (0x2284, 0x2285), (0x2285, 0x2284), (0x2286, 0x2287), (0x2287, 0x2286),
+    (0x2288, 0x2289), (0x2289, 0x2288), (0x228a, 0x228b), (0x228b, 0x228a),
+    (0x22a2, 0x22a3), (0x22a3, 0x22a2), (0x22b0, 0x22b1), (0x22b1, 0x22b0),
+    (0x22b2, 0x22b3), (0x22b3, 0x22b2), (0x22b4, 0x22b5), (0x22b5, 0x22b4),
+    (0x22c9, 0x22ca), (0x22ca, 0x22c9), (0x22cb, 0x22cc), (0x22cc, 0x22cb),
+    (0x22d0, 0x22d1), (0x22d1, 0x22d0), (0x22d6, 0x22d7), (0x22d7, 0x22d6),
+    (0x22d8, 0x22d9), (0x22d9, 0x22d8), (0x22da, 0x22db), (0x22db, 0x22da),
+    (0x22dc, 0x22dd), (0x22dd, 0x22dc), (0x22de, 0x22df), (0x22df, 0x22de),
+    (0x22e6, 0x22e7), (0x22e7, 0x22e6), (0x22e8, 0x22e9), (0x22e9, 0x22e8),
+    (0x2308, 0x2309), (0x2309, 0x2308), (0x230a, 0x230b), (0x230b, 0x230a),
+    (0x2329, 0x232a), (0x232a, 0x2329), (0x2768, 0x2769), (0x2769, 0x2768),
+    (0x276a, 0x276b), (0x276b, 0x276a), (0x276c, 0x276d), (0x276d, 0x276c),
+    (0x276e, 0x276f), (0x276f, 0x276e), (0x2770, 0x2771), (0x2771, 0x2770),
+    (0x2772, 0x2773), (0x2773, 0x2772), (0x2774, 0x2775), (0x2775, 0x2774),
+    (0x27c3, 0x27c4), (0x27c4, 0x27c3), (0x27c5, 0x27c6), (0x27c6, 0x27c5),
+    (0x27d5, 0x27d6), (0x27d6, 0x27d5), (0x27dd, 0x27de), (0x27de, 0x27dd),
+    (0x27e2, 0x27e3), (0x27e3, 0x27e2), (0x27e4, 0x27e5), (0x27e5, 0x27e4),
+    (0x27e6, 0x27e7), (0x27e7, 0x27e6), (0x27e8, 0x27e9), (0x27e9, 0x27e8),
+    (0x27ea, 0x27eb), (0x27eb, 0x27ea), (0x27ec, 0x27ed), (0x27ed, 0x27ec),
+    (0x27ee, 0x27ef), (0x27ef, 0x27ee), (0x2983, 0x2984), (0x2984, 0x2983),
+    (0x2985, 0x2986), (0x2986, 0x2985), (0x2987, 0x2988), (0x2988, 0x2987),
+    (0x2989, 0x298a), (0x298a, 0x2989), (0x298b, 0x298c), (0x298c, 0x298b),
+    (0x298d, 0x2990), (0x298e, 0x298f), (0x298f, 0x298e), (0x2990, 0x298d),
+    (0x2991, 0x2992), (0x2992, 0x2991), (0x2997, 0x2998), (0x2998, 0x2997),
+    (0x29a8, 0x29a9), (0x29a9, 0x29a8), (0x29aa, 0x29ab), (0x29ab, 0x29aa),
+    (0x29ac, 0x29ad), (0x29ad, 0x29ac), (0x29ae, 0x29af), (0x29af, 0x29ae),
+    (0x29c0, 0x29c1), (0x29c1, 0x29c0), (0x29d1, 0x29d2), (0x29d2, 0x29d1),
+    (0x29d4, 0x29d5), (0x29d5, 0x29d4), (0x29d8, 0x29d9), (0x29d9, 0x29d8),
+    (0x29da, 0x29db), (0x29db, 0x29da), (0x29e8, 0x29e9), (0x29e9, 0x29e8),
+    (0x29fc, 0x29fd), (0x29fd, 0x29fc), (0x2a2d, 0x2a2e), (0x2a2e, 0x2a2d),
+    (0x2a34, 0x2a35), (0x2a35, 0x2a34), (0x2a79, 0x2a7a), (0x2a7a, 0x2a79),
+    (0x2a7b, 0x2a7c), (0x2a7c, 0x2a7b), (0x2a7d, 0x2a7e), (0x2a7e, 0x2a7d),
+    (0x2a7f, 0x2a80), (0x2a80, 0x2a7f), (0x2a81, 0x2a82), (0x2a82, 0x2a81),
+    (0x2a85, 0x2a86), (0x2a86, 0x2a85), (0x2a87, 0x2a88), (0x2a88, 0x2a87),
+    (0x2a89, 0x2a8a), (0x2a8a, 0x2a89), (0x2a8b, 0x2a8c), (0x2a8c, 0x2a8b),
+    (0x2a8d, 0x2a8e), (0x2a8e, 0x2a8d), (0x2a8f, 0x2a90), (0x2a90, 0x2a8f),
+    (0x2a91, 0x2a92), (0x2a92, 0x2a91), (0x2a93, 0x2a94), (0x2a94, 0x2a93),
+    (0x2a95, 0x2a96), (0x2a96, 0x2a95), (0x2a97, 0x2a98), (0x2a98, 0x2a97),
+    (0x2a99, 0x2a9a), (0x2a9a, 0x2a99), (0x2a9b, 0x2a9c), (0x2a9c, 0x2a9b),
+    (0x2a9d, 0x2a9e), (0x2a9e, 0x2a9d), (0x2a9f, 0x2aa0), (0x2aa0, 0x2a9f),
+    (0x2aa1, 0x2aa2), (0x2aa2, 0x2aa1), (0x2aa6, 0x2aa7), (0x2aa7, 0x2aa6),
+    (0x2aa8, 0x2aa9), (0x2aa9, 0x2aa8), (0x2aaf, 0x2ab0), (0x2ab0, 0x2aaf),
+    (0x2ab1, 0x2ab2), (0x2ab2, 0x2ab1), (0x2ab3, 0x2ab4), (0x2ab4, 0x2ab3),
+    (0x2ab5, 0x2ab6), (0x2ab6, 0x2ab5), (0x2ab7, 0x2ab8), (0x2ab8, 0x2ab7),
+    (0x2ab9, 0x2aba), (0x2aba, 0x2ab9), (0x2abb, 0x2abc), (0x2abc, 0x2abb),
+    (0x2abd, 0x2abe), (0x2abe, 0x2abd), (0x2abf, 0x2ac0), (0x2ac0, 0x2abf),
+    (0x2ac1, 0x2ac2), (0x2ac2, 0x2ac1), (0x2ac3, 0x2ac4), (0x2ac4, 0x2ac3),
+    (0x2ac5, 0x2ac6), (0x2ac6, 0x2ac5), (0x2ac7, 0x2ac8), (0x2ac8, 0x2ac7),
+    (0x2ac9, 0x2aca), (0x2aca, 0x2ac9), (0x2acb, 0x2acc), (0x2acc, 0x2acb),
+    (0x2acd, 0x2ace), (0x2ace, 0x2acd), (0x2acf, 0x2ad0), (0x2ad0, 0x2acf),
+    (0x2ad1, 0x2ad2), (0x2ad2, 0x2ad1), (0x2ad3, 0x2ad4), (0x2ad4, 0x2ad3),
+    (0x2ad5, 0x2ad6), (0x2ad6, 0x2ad5), (0x2af7, 0x2af8), (0x2af8, 0x2af7),
+    (0x2af9, 0x2afa), (0x2afa, 0x2af9), (0x2e02, 0x2e03), (0x2e03, 0x2e02),
+    (0x2e04, 0x2e05), (0x2e05, 0x2e04), (0x2e09, 0x2e0a), (0x2e0a, 0x2e09),
+    (0x2e0c, 0x2e0d), (0x2e0d, 0x2e0c), (0x2e1c, 0x2e1d), (0x2e1d, 0x2e1c),
+    (0x2e20, 0x2e21), (0x2e21, 0x2e20), (0x2e22, 0x2e23), (0x2e23, 0x2e22),
+    (0x2e24, 0x2e25), (0x2e25, 0x2e24), (0x2e26, 0x2e27), (0x2e27, 0x2e26),
+    (0x2e28, 0x2e29), (0x2e29, 0x2e28), (0x2e55, 0x2e56), (0x2e56, 0x2e55),
+    (0x2e57, 0x2e58), (0x2e58, 0x2e57), (0x2e59, 0x2e5a), (0x2e5a, 0x2e59),
+    (0x2e5b, 0x2e5c), (0x2e5c, 0x2e5b), (0x3008, 0x3009), (0x3009, 0x3008),
+    (0x300a, 0x300b), (0x300b, 0x300a), (0x300c, 0x300d), (0x300d, 0x300c),
+    (0x300e, 0x300f), (0x300f, 0x300e), (0x3010, 0x3011), (0x3011, 0x3010),
+    (0x3014, 0x3015), (0x3015, 0x3014), (0x3016, 0x3017), (0x3017, 0x3016),
+    (0x3018, 0x3019), (0x3019, 0x3018), (0x301a, 0x301b), (0x301b, 0x301a),
+    (0xfe59, 0xfe5a), (0xfe5a, 0xfe59), (0xfe5b, 0xfe5c), (0xfe5c, 0xfe5b),
+    (0xfe5d, 0xfe5e), (0xfe5e, 0xfe5d), (0xfe64, 0xfe65), (0xfe65, 0xfe64),
+    (0xff08, 0xff09), (0xff09, 0xff08), (0xff1c, 0xff1e), (0xff1e, 0xff1c),
+    (0xff3b, 0xff3d), (0xff3d, 0xff3b), (0xff5b, 0xff5d), (0xff5d, 0xff5b),
+    (0xff5f, 0xff60), (0xff60, 0xff5f), (0xff62, 0xff63), (0xff63, 0xff62)
+];
+
+/// Opening brackets and the closing bracket that goes with them, sorted by the opening bracket
+const BRACKETS: &[(u32, u32)] = &[
+    (0x0028, 0x0029), (0x005b, 0x005d), (0x007b, 0x007d), (0x0f3a, 0x0f3b),
+    (0x0f3c, 0x0f3d), (0x169b, 0x169c), (0x2045, 0x2046), (0x207d, 0x207e),
+    (0x208d, 0x208e), (0x2308, 0x2309), (0x230a, 0x230b), (0x2329, 0x232a),
+    (0x2768, 0x2769), (0x276a, 0x276b), (0x276c, 0x276d), (0x276e, 0x276f),
+    (0x2770, 0x2771), (0x2772, 0x2773), (0x2774, 0x2775), (0x27c5, 0x27c6),
+    (0x27e6, 0x27e7), (0x27e8, 0x27e9), (0x27ea, 0x27eb), (0x27ec, 0x27ed),
+    (0x27ee, 0x27ef), (0x2983, 0x2984), (0x2985, 0x2986), (0x2987, 0x2988),
+    (0x2989, 0x298a), (0x298b, 0x298c), (0x298d, 0x2990), (0x298f, 0x298e),
+    (0x2991, 0x2992), (0x2997, 0x2998), (0x29d8, 0x29d9), (0x29da, 0x29db),
+    (0x29fc, 0x29fd), (0x2e22, 0x2e23), (0x2e24, 0x2e25), (0x2e26, 0x2e27),
+    (0x2e28, 0x2e29), (0x2e55, 0x2e56), (0x2e57, 0x2e58), (0x2e59, 0x2e5a),
+    (0x2e5b, 0x2e5c), (0x3008, 0x3009), (0x300a, 0x300b), (0x300c, 0x300d),
+    (0x300e, 0x300f), (0x3010, 0x3011), (0x3014, 0x3015), (0x3016, 0x3017),
+    (0x3018, 0x3019), (0x301a, 0x301b), (0xfe59, 0xfe5a), (0xfe5b, 0xfe5c),
+    (0xfe5d, 0xfe5e), (0xff08, 0xff09), (0xff3b, 0xff3d), (0xff5b, 0xff5d),
+    (0xff5f, 0xff60), (0xff62, 0xff63)
+];
+
+/// The deepest an embedding can go. Embeddings and isolates past this are ignored
+const MAX_DEPTH: u8 = 125;
+/// How many brackets can be open at once before bracket pairs stop being looked for
+const MAX_BRACKETS: usize = 63;
+
+pub fn bidi_class(c: char) -> BidiClass {
+    find_range(BIDI_CLASSES, c).unwrap_or(L)
+}
+
+/// The character that looks like `c` flipped left to right, which is drawn in its place when it is
+/// in right to left text
+pub fn mirror(c: char) -> Option<char> {
+    MIRRORS.binary_search_by_key(&(c as u32), |&(from, _)| from).ok().and_then(|i| ::std::char::from_u32(MIRRORS[i].1))
+}
+
+/// If `c` is a bracket, the opening bracket of its pair and whether `c` is the opening one
+fn bracket(c: char) -> Option<(u32, bool)> {
+    // the angle brackets at U+2329 and U+232A are canonically the same as U+3008 and U+3009
+    let c = match c as u32 {
+        0x2329 => 0x3008,
+        0x232a => 0x3009,
+        c => c
+    };
+    BRACKETS.iter().find(|&&(open, close)| c == open || c == close).map(|&(open, _)| (open, c == open))
+}
+
+/// The direction of a paragraph, or of a run of text in it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft
+}
+
+impl Direction {
+    /// The direction of text at an embedding level
+    pub fn of_level(level: u8) -> Direction {
+        if level & 1 == 1 { Direction::RightToLeft } else { Direction::LeftToRight }
+    }
+
+    fn level(&self) -> u8 {
+        match *self {
+            Direction::LeftToRight => 0,
+            Direction::RightToLeft => 1
+        }
+    }
+}
+
+/// The direction of a paragraph going by its first strong character, skipping over isolates.
+/// Paragraphs without any are left to right
+pub fn paragraph_direction(text: &str) -> Direction {
+    let classes: Vec<BidiClass> = text.chars().map(bidi_class).collect();
+    first_strong(&classes).unwrap_or(Direction::LeftToRight)
+}
+
+fn first_strong(classes: &[BidiClass]) -> Option<Direction> {
+    let mut isolates = 0;
+    for &class in classes {
+        match class {
+            LRI | RLI | FSI => isolates += 1,
+            PDI if isolates > 0 => isolates -= 1,
+            L if isolates == 0 => return Some(Direction::LeftToRight),
+            R | AL if isolates == 0 => return Some(Direction::RightToLeft),
+            B => break,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether rule X9 takes a character out of the text before the implicit levels are resolved
+fn is_removed(class: BidiClass) -> bool {
+    matches!(class, LRE | RLE | LRO | RLO | PDF | BN)
+}
+
+fn is_neutral(class: BidiClass) -> bool {
+    matches!(class, B | S | WS | ON | LRI | RLI | FSI | PDI)
+}
+
+/// The strong direction a resolved class counts as next to neutrals, where numbers count as right
+/// to left
+fn strong_direction(class: BidiClass) -> Option<BidiClass> {
+    match class {
+        L => Some(L),
+        R | AL | EN | AN => Some(R),
+        _ => None
+    }
+}
+
+/// The strong class of an embedding level's direction
+fn level_class(level: u8) -> BidiClass {
+    if level & 1 == 1 { R } else { L }
+}
+
+/// The embedding levels of a paragraph. The text is taken to be a single paragraph, so text with
+/// paragraph separators in it should be split up first
+#[derive(Clone, Debug)]
+pub struct BidiParagraph {
+    /// The class each character had before any were resolved
+    classes: Vec<BidiClass>,
+    /// The resolved level of each character
+    pub levels: Vec<u8>,
+    /// The paragraph's own level, 0 for left to right and 1 for right to left
+    pub level: u8
+}
+
+#[derive(Copy, Clone, Debug)]
+struct StackEntry {
+    level: u8,
+    /// The class characters are overridden to, if any
+    override_class: Option<BidiClass>,
+    isolate: bool
+}
+
+impl BidiParagraph {
+    /// Resolve the levels of a paragraph. The direction is worked out from its text unless it is
+    /// given
+    pub fn new(chars: &[char], direction: Option<Direction>) -> BidiParagraph {
+        let classes: Vec<BidiClass> = chars.iter().map(|&c| bidi_class(c)).collect();
+        let level = direction.or_else(|| first_strong(&classes)).unwrap_or(Direction::LeftToRight).level();
+        let matching_pdi = match_isolates(&classes);
+        let (mut levels, mut types) = explicit_levels(&classes, &matching_pdi, level);
+
+        for sequence in isolating_run_sequences(&classes, &levels, &matching_pdi) {
+            let first = sequence[0];
+            let last = sequence[sequence.len() - 1];
+            let run_level = levels[first];
+            let before = (0..first).rev().find(|&i| !is_removed(classes[i])).map(|i| levels[i]).unwrap_or(level);
+            let after = match matching_pdi[last] {
+                None if is_isolate_initiator(classes[last]) => level,
+                _ => (last + 1..classes.len()).find(|&i| !is_removed(classes[i])).map(|i| levels[i]).unwrap_or(level)
+            };
+            let sos = level_class(run_level.max(before));
+            let eos = level_class(run_level.max(after));
+            resolve_weak_types(&sequence, &classes, &mut types, sos);
+            resolve_brackets(&sequence, chars, &classes, &mut types, run_level, sos);
+            resolve_neutral_types(&sequence, &mut types, run_level, sos, eos);
+            for &i in &sequence {
+                levels[i] = match (levels[i] & 1, types[i]) {
+                    (0, R) => levels[i] + 1,
+                    (0, AN) | (0, EN) => levels[i] + 2,
+                    (1, L) | (1, EN) | (1, AN) => levels[i] + 1,
+                    _ => levels[i]
+                };
+            }
+        }
+
+        // the characters X9 took out go with whatever is before them
+        for i in 0..classes.len() {
+            if is_removed(classes[i]) {
+                levels[i] = if i == 0 { level } else { levels[i - 1] };
+            }
+        }
+        BidiParagraph { classes, levels, level }
+    }
+
+    /// The levels of the characters in `start..end` when they make up a line. Tabs, and whitespace
+    /// at the end of the line or before a tab, go back to the paragraph's level
+    pub fn line_levels(&self, start: usize, end: usize) -> Vec<u8> {
+        let mut levels = self.levels[start..end].to_vec();
+        let mut trailing = true;
+        for i in (start..end).rev() {
+            match self.classes[i] {
+                S | B => {
+                    levels[i - start] = self.level;
+                    trailing = true;
+                },
+                WS | LRI | RLI | FSI | PDI if trailing => levels[i - start] = self.level,
+                c if trailing && is_removed(c) => levels[i - start] = self.level,
+                _ => trailing = false
+            }
+        }
+        levels
+    }
+}
+
+fn is_isolate_initiator(class: BidiClass) -> bool {
+    matches!(class, LRI | RLI | FSI)
+}
+
+/// The index of the PDI that closes each isolate initiator, for the ones that are closed
+fn match_isolates(classes: &[BidiClass]) -> Vec<Option<usize>> {
+    let mut matching = vec![None; classes.len()];
+    let mut open = Vec::new();
+    for (i, &class) in classes.iter().enumerate() {
+        match class {
+            LRI | RLI | FSI => open.push(i),
+            PDI => if let Some(initiator) = open.pop() {
+                matching[initiator] = Some(i);
+            },
+            _ => {}
+        }
+    }
+    matching
+}
+
+/// Rules X1 to X8, which set the level of each character from the embeddings, overrides and
+/// isolates around it. Gives the levels and the classes after overrides
+fn explicit_levels(classes: &[BidiClass], matching_pdi: &[Option<usize>], level: u8) -> (Vec<u8>, Vec<BidiClass>) {
+    let mut levels = vec![level; classes.len()];
+    let mut types = classes.to_vec();
+    let mut stack = vec![StackEntry { level, override_class: None, isolate: false }];
+    let mut overflow_isolates = 0;
+    let mut overflow_embeddings = 0;
+    let mut valid_isolates = 0;
+    for (i, &class) in classes.iter().enumerate() {
+        let last = stack[stack.len() - 1];
+        match class {
+            RLE | LRE | RLO | LRO => {
+                levels[i] = last.level;
+                let next = if class == RLE || class == RLO { (last.level + 1) | 1 } else { (last.level + 2) & !1 };
+                if next <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    let override_class = match class {
+                        RLO => Some(R),
+                        LRO => Some(L),
+                        _ => None
+                    };
+                    stack.push(StackEntry { level: next, override_class, isolate: false });
+                } else if overflow_isolates == 0 {
+                    overflow_embeddings += 1;
+                }
+            },
+            RLI | LRI | FSI => {
+                levels[i] = last.level;
+                if let Some(o) = last.override_class {
+                    types[i] = o;
+                }
+                let right_to_left = match class {
+                    RLI => true,
+                    LRI => false,
+                    _ => first_strong(&classes[i + 1..matching_pdi[i].unwrap_or(classes.len())]) == Some(Direction::RightToLeft)
+                };
+                let next = if right_to_left { (last.level + 1) | 1 } else { (last.level + 2) & !1 };
+                if next <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    valid_isolates += 1;
+                    stack.push(StackEntry { level: next, override_class: None, isolate: true });
+                } else {
+                    overflow_isolates += 1;
+                }
+            },
+            PDI => {
+                if overflow_isolates > 0 {
+                    overflow_isolates -= 1;
+                } else if valid_isolates > 0 {
+                    overflow_embeddings = 0;
+                    while !stack[stack.len() - 1].isolate {
+                        stack.pop();
+                    }
+                    stack.pop();
+                    valid_isolates -= 1;
+                }
+                let last = stack[stack.len() - 1];
+                levels[i] = last.level;
+                if let Some(o) = last.override_class {
+                    types[i] = o;
+                }
+            },
+            PDF => {
+                levels[i] = last.level;
+                if overflow_isolates > 0 {
+                } else if overflow_embeddings > 0 {
+                    overflow_embeddings -= 1;
+                } else if !last.isolate && stack.len() > 1 {
+                    stack.pop();
+                }
+            },
+            B => levels[i] = level,
+            BN => levels[i] = last.level,
+            _ => {
+                levels[i] = last.level;
+                if let Some(o) = last.override_class {
+                    types[i] = o;
+                }
+            }
+        }
+    }
+    (levels, types)
+}
+
+/// Rule X10. Splits the characters X9 leaves into runs of the same level, and joins the runs on
+/// either side of an isolate into one sequence, so the text around an isolate is resolved as if
+/// the isolate weren't there
+fn isolating_run_sequences(classes: &[BidiClass], levels: &[u8], matching_pdi: &[Option<usize>]) -> Vec<Vec<usize>> {
+    let mut runs: Vec<Vec<usize>> = Vec::new();
+    let mut last_level = None;
+    for i in (0..classes.len()).filter(|&i| !is_removed(classes[i])) {
+        if last_level == Some(levels[i]) {
+            let n = runs.len();
+            runs[n - 1].push(i);
+        } else {
+            runs.push(vec![i]);
+        }
+        last_level = Some(levels[i]);
+    }
+
+    let mut run_starting_at = vec![None; classes.len()];
+    for (n, run) in runs.iter().enumerate() {
+        run_starting_at[run[0]] = Some(n);
+    }
+    let matched_pdis: Vec<usize> = matching_pdi.iter().filter_map(|&m| m).collect();
+
+    let mut sequences = Vec::new();
+    for run in &runs {
+        // runs starting with a PDI that closes an isolate carry on the sequence before the isolate
+        if classes[run[0]] == PDI && matched_pdis.contains(&run[0]) {
+            continue;
+        }
+        let mut sequence = run.clone();
+        loop {
+            let last = sequence[sequence.len() - 1];
+            match matching_pdi[last].and_then(|pdi| run_starting_at[pdi]) {
+                Some(n) if is_isolate_initiator(classes[last]) => sequence.extend_from_slice(&runs[n]),
+                _ => break
+            }
+        }
+        sequences.push(sequence);
+    }
+    sequences
+}
+
+/// Rules W1 to W7, which resolve the classes of marks, numbers and the separators in numbers
+fn resolve_weak_types(sequence: &[usize], classes: &[BidiClass], types: &mut [BidiClass], sos: BidiClass) {
+    // W1: marks take the class of the character they are on
+    let mut previous = sos;
+    for &i in sequence {
+        if types[i] == NSM {
+            types[i] = previous;
+        }
+        previous = if is_isolate_initiator(classes[i]) || classes[i] == PDI { ON } else { types[i] };
+    }
+
+    // W2 and W3: European digits after Arabic letters are Arabic numbers, and Arabic letters are
+    // then just right to left
+    let mut strong = sos;
+    for &i in sequence {
+        match types[i] {
+            L | R | AL => strong = types[i],
+            EN if strong == AL => types[i] = AN,
+            _ => {}
+        }
+    }
+    for &i in sequence {
+        if types[i] == AL {
+            types[i] = R;
+        }
+    }
+
+    // W4: a single separator between two numbers of the same kind is part of the number
+    for k in 1..sequence.len().saturating_sub(1) {
+        let (before, after) = (types[sequence[k - 1]], types[sequence[k + 1]]);
+        match types[sequence[k]] {
+            ES if before == EN && after == EN => types[sequence[k]] = EN,
+            CS if before == after && (before == EN || before == AN) => types[sequence[k]] = before,
+            _ => {}
+        }
+    }
+
+    // W5: currency and percent signs next to a European number are part of it
+    let mut k = 0;
+    while k < sequence.len() {
+        if types[sequence[k]] != ET {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        while k < sequence.len() && types[sequence[k]] == ET {
+            k += 1;
+        }
+        if (start > 0 && types[sequence[start - 1]] == EN) || (k < sequence.len() && types[sequence[k]] == EN) {
+            for &i in &sequence[start..k] {
+                types[i] = EN;
+            }
+        }
+    }
+
+    // W6 and W7: other separators are neutral, and European numbers in left to right text are
+    // left to right
+    let mut strong = sos;
+    for &i in sequence {
+        match types[i] {
+            ES | ET | CS => types[i] = ON,
+            L | R => strong = types[i],
+            EN if strong == L => types[i] = L,
+            _ => {}
+        }
+    }
+}
+
+/// Rule N0. Both brackets of a pair take the direction of the text inside them, so they match each
+/// other and enclose it the right way around
+fn resolve_brackets(sequence: &[usize], chars: &[char], classes: &[BidiClass], types: &mut [BidiClass], level: u8, sos: BidiClass) {
+    let mut open: Vec<(u32, usize)> = Vec::new();
+    let mut pairs = Vec::new();
+    for (k, &i) in sequence.iter().enumerate() {
+        if types[i] != ON {
+            continue;
+        }
+        match bracket(chars[i]) {
+            Some((bracket, true)) => {
+                if open.len() == MAX_BRACKETS {
+                    break;
+                }
+                open.push((bracket, k));
+            },
+            Some((bracket, false)) => {
+                if let Some(depth) = open.iter().rposition(|&(b, _)| b == bracket) {
+                    pairs.push((open[depth].1, k));
+                    open.truncate(depth);
+                }
+            },
+            None => {}
+        }
+    }
+    pairs.sort();
+
+    let embedding = level_class(level);
+    for (opening, closing) in pairs {
+        let inside: Vec<BidiClass> = sequence[opening + 1..closing].iter().filter_map(|&i| strong_direction(types[i])).collect();
+        let resolved = if inside.contains(&embedding) {
+            embedding
+        } else if let Some(&opposite) = inside.first() {
+            // only the opposite direction is inside, so the brackets go that way too if the text
+            // before them does
+            let before = sequence[..opening].iter().rev().filter_map(|&i| strong_direction(types[i])).next().unwrap_or(sos);
+            if before == opposite { opposite } else { embedding }
+        } else {
+            continue;
+        };
+        for &k in &[opening, closing] {
+            types[sequence[k]] = resolved;
+            // marks on a bracket go with it
+            for &i in sequence[k + 1..].iter().take_while(|&&i| classes[i] == NSM) {
+                types[i] = resolved;
+            }
+        }
+    }
+}
+
+/// Rules N1 and N2. Neutrals between text going the same way go that way too, and the others take
+/// the direction of their embedding
+fn resolve_neutral_types(sequence: &[usize], types: &mut [BidiClass], level: u8, sos: BidiClass, eos: BidiClass) {
+    let mut k = 0;
+    while k < sequence.len() {
+        if !is_neutral(types[sequence[k]]) {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        while k < sequence.len() && is_neutral(types[sequence[k]]) {
+            k += 1;
+        }
+        let before = if start == 0 { sos } else { strong_direction(types[sequence[start - 1]]).unwrap_or(sos) };
+        let after = if k == sequence.len() { eos } else { strong_direction(types[sequence[k]]).unwrap_or(eos) };
+        let resolved = if before == after { before } else { level_class(level) };
+        for &i in &sequence[start..k] {
+            types[i] = resolved;
+        }
+    }
+}
+
+/// The order to draw things with these levels in, left to right, as indices into `levels`. Each
+/// sequence at or above a level is reversed, from the highest level down to the lowest odd one.
+/// This works on the characters of a line, or on runs that each have a single level
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = match levels.iter().cloned().max() {
+        Some(l) => l,
+        None => return order
+    };
+    let lowest_odd = levels.iter().cloned().min().unwrap_or(0) | 1;
+    let mut level = highest;
+    while level >= lowest_odd {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+        level -= 1;
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(text: &str, direction: Option<Direction>) -> Vec<u8> {
+        let chars: Vec<char> = text.chars().collect();
+        let paragraph = BidiParagraph::new(&chars, direction);
+        paragraph.line_levels(0, chars.len())
+    }
+
+    fn visual(text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let levels = levels(text, None);
+        visual_order(&levels).into_iter().map(|i| chars[i]).collect()
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(bidi_class('a'), L);
+        assert_eq!(bidi_class('\u{5d0}'), R);
+        assert_eq!(bidi_class('\u{628}'), AL);
+        assert_eq!(bidi_class('7'), EN);
+        assert_eq!(bidi_class('\u{663}'), AN);
+        assert_eq!(bidi_class(' '), WS);
+        assert_eq!(bidi_class('$'), ET);
+        assert_eq!(bidi_class('\u{301}'), NSM);
+        assert_eq!(bidi_class('\u{2067}'), RLI);
+        assert_eq!(bidi_class('\u{4e2d}'), L);
+        // unassigned characters in the Hebrew block are right to left
+        assert_eq!(bidi_class('\u{5ff}'), R);
+
+        assert_eq!(mirror('('), Some(')'));
+        assert_eq!(mirror(']'), Some('['));
+        assert_eq!(mirror('\u{226a}'), Some('\u{226b}'));
+        assert_eq!(mirror('a'), None);
+        assert_eq!(bracket('\u{2329}'), bracket('\u{3008}'));
+        assert_eq!(bracket(')'), Some(('(' as u32, false)));
+
+        assert_eq!(paragraph_direction("abc \u{5d0}"), Direction::LeftToRight);
+        assert_eq!(paragraph_direction("123 \u{5d0} abc"), Direction::RightToLeft);
+        assert_eq!(paragraph_direction("\u{2066}abc\u{2069}\u{628}"), Direction::RightToLeft);
+        assert_eq!(paragraph_direction("123"), Direction::LeftToRight);
+    }
+
+    #[test]
+    fn resolve_levels() {
+        // Hebrew in English, and the space between them goes with the paragraph
+        assert_eq!(levels("ab \u{5d0}\u{5d1}", None), vec![0, 0, 0, 1, 1]);
+        assert_eq!(levels("\u{5d0}\u{5d1} ab", None), vec![1, 1, 1, 2, 2]);
+
+        // numbers in right to left text go left to right, a level up
+        assert_eq!(levels("\u{5d0} 12", None), vec![1, 1, 2, 2]);
+        // a separator between digits is part of the number, and digits after Arabic are Arabic
+        assert_eq!(levels("\u{5d0} 1.5", None), vec![1, 1, 2, 2, 2]);
+        assert_eq!(levels("\u{628}12", None), vec![1, 2, 2]);
+        // percent signs go with the number
+        assert_eq!(levels("\u{5d0} 5%", None), vec![1, 1, 2, 2]);
+
+        // the direction can be forced, and trailing whitespace goes back to the paragraph level
+        assert_eq!(levels("ab ", Some(Direction::RightToLeft)), vec![2, 2, 1]);
+        assert_eq!(levels("\u{5d0}\u{5d1} ", Some(Direction::LeftToRight)), vec![1, 1, 0]);
+
+        // brackets around right to left text in a left to right paragraph go with the text in them
+        assert_eq!(levels("a (\u{5d0}) \u{5d1}", None), vec![0, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(levels("\u{5d0} (\u{5d1}) a", Some(Direction::LeftToRight)), vec![1, 1, 1, 1, 1, 0, 0]);
+        assert_eq!(levels("\u{5d0} (a) \u{5d1}", None), vec![1, 1, 1, 2, 1, 1, 1]);
+
+        // an embedding makes English inside Hebrew go up two levels, and an override reverses
+        // letters that would otherwise go left to right
+        assert_eq!(levels("\u{5d0}\u{202a}ab\u{202c}", None), vec![1, 1, 2, 2, 1]);
+        assert_eq!(levels("\u{202e}ab\u{202c}", None), vec![0, 1, 1, 0]);
+
+        // an isolate doesn't affect the text around it: the space goes with the Hebrew on both sides
+        assert_eq!(levels("\u{5d0} \u{2066}a\u{2069} \u{5d1}", Some(Direction::LeftToRight)), vec![1, 1, 1, 2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn reordering() {
+        assert_eq!(visual("abc"), "abc");
+        assert_eq!(visual("\u{5d0}\u{5d1}\u{5d2}"), "\u{5d2}\u{5d1}\u{5d0}");
+        assert_eq!(visual("ab \u{5d0}\u{5d1} cd"), "ab \u{5d1}\u{5d0} cd");
+        // numbers keep their order inside right to left text
+        assert_eq!(visual("\u{5d0} 12 \u{5d1}"), "\u{5d1} 12 \u{5d0}");
+        assert_eq!(visual("\u{5d0}\u{5d1} ab"), "ab \u{5d1}\u{5d0}");
+        assert_eq!(visual_order(&[0, 1, 1, 2, 2, 1, 0]), vec![0, 5, 3, 4, 2, 1, 6]);
+        assert!(visual_order(&[]).is_empty());
+    }
+}
@@ -0,0 +1,598 @@
+//! Shaping for the Brahmic scripts of India and Southeast Asia. Text is split into syllables
+//! around a base consonant, the characters of each syllable are put into the order the font's
+//! features expect, like vowel signs that are written before the consonant they follow, and the
+//! glyphs of each syllable are marked with the features that apply to them
+
+use std::collections::BTreeMap;
+use truetype_loader::LayoutGlyph;
+use truetype_loader::TableTag;
+use super::{Script, feature_mask, GLOBAL_MASK};
+
+/// Where a dependent vowel or medial is drawn relative to the base consonant
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Position {
+    Pre,
+    Above,
+    Below,
+    Post
+}
+
+/// The part a character plays in a syllable, after Unicode's Indic syllabic categories
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Category {
+    Consonant,
+    /// The consonant that forms a reph when it starts a syllable with a virama after it
+    Ra,
+    /// An independent vowel, which starts a syllable of its own
+    Vowel,
+    /// A dot that changes the sound of the consonant it follows
+    Nukta,
+    /// The virama, or in Khmer the coeng, which joins consonants into conjuncts
+    Halant,
+    Joiner,
+    NonJoiner,
+    /// A dependent vowel sign
+    Matra(Position),
+    /// Consonant signs that follow the base, like the Myanmar medials
+    Medial(Position),
+    /// Syllable modifiers, like the anusvara and visarga, that go at the end of a syllable
+    Modifier,
+    /// Characters that marks can be shown on by themselves, like the dotted circle
+    Placeholder,
+    Other
+}
+
+use self::Category::*;
+use self::Position::*;
+
+/// How each script builds its syllables
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Model {
+    /// The scripts of India, whose base is the last consonant of a syllable
+    Indic,
+    /// Khmer and Myanmar, whose base is the first consonant, with the rest stacked below it
+    FirstConsonantBase
+}
+
+/// Where a reph ends up once it is formed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RephPosition {
+    /// Right after the base consonant
+    AfterMain,
+    /// At the end of the syllable, before any syllable modifiers
+    AfterPost
+}
+
+/// The first character of each of the Indic blocks, which are all laid out alike
+const INDIC_BLOCKS: &[(Script, u32)] = &[
+    (Script::Devanagari, 0x0900),
+    (Script::Bengali, 0x0980),
+    (Script::Gurmukhi, 0x0a00),
+    (Script::Gujarati, 0x0a80),
+    (Script::Oriya, 0x0b00),
+    (Script::Tamil, 0x0b80),
+    (Script::Telugu, 0x0c00),
+    (Script::Kannada, 0x0c80),
+    (Script::Malayalam, 0x0d00)
+];
+
+/// The 'GSUB' features for these scripts, in stages. The first `BASIC_STAGE_COUNT` stages form
+/// conjuncts and other forms, then the syllables are reordered again before the rest pick the
+/// presentation forms
+pub const SUBSTITUTION_STAGES: &[&[&[u8; 4]]] = &[
+    &[b"locl", b"ccmp"],
+    &[b"nukt"],
+    &[b"akhn"],
+    &[b"rphf"],
+    &[b"rkrf"],
+    &[b"pref"],
+    &[b"blwf"],
+    &[b"abvf"],
+    &[b"half"],
+    &[b"pstf"],
+    &[b"vatu"],
+    &[b"cjct"],
+    &[b"cfar"],
+    &[b"pres", b"abvs", b"blws", b"psts", b"haln", b"calt", b"clig", b"rlig"]
+];
+pub const BASIC_STAGE_COUNT: usize = 13;
+
+/// Features that only apply to some of the glyphs of a syllable
+pub const MASKED_FEATURES: &[&[u8; 4]] = &[b"rphf", b"pref", b"half", b"blwf", b"abvf", b"pstf", b"vatu", b"cfar"];
+
+pub fn model(script: Script) -> Option<Model> {
+    match script {
+        Script::Khmer | Script::Myanmar => Some(Model::FirstConsonantBase),
+        s if INDIC_BLOCKS.iter().any(|&(b, _)| b == s) => Some(Model::Indic),
+        _ => None
+    }
+}
+
+fn reph_position(script: Script) -> Option<RephPosition> {
+    match script {
+        Script::Devanagari | Script::Bengali | Script::Gujarati | Script::Telugu | Script::Kannada => Some(RephPosition::AfterPost),
+        Script::Oriya | Script::Malayalam => Some(RephPosition::AfterMain),
+        _ => None
+    }
+}
+
+/// Telugu and Kannada only form a reph when a ZWJ follows the virama
+fn reph_needs_joiner(script: Script) -> bool {
+    script == Script::Telugu || script == Script::Kannada
+}
+
+pub fn category(script: Script, c: char) -> Category {
+    let cp = c as u32;
+    match cp {
+        0x200c => return NonJoiner,
+        0x200d => return Joiner,
+        0x00a0 | 0x25cc => return Placeholder,
+        _ => {}
+    }
+    match script {
+        Script::Khmer => khmer_category(cp),
+        Script::Myanmar => myanmar_category(cp),
+        _ => match INDIC_BLOCKS.iter().find(|&&(s, start)| s == script && cp >= start && cp < start + 0x80) {
+            Some(&(_, start)) => indic_category(script, cp - start),
+            None => Other
+        }
+    }
+}
+
+/// Categories in the Indic blocks, by where in the block a character is
+fn indic_category(script: Script, offset: u32) -> Category {
+    use super::Script::*;
+    match (script, offset) {
+        (_, 0x00..=0x03) => Modifier,
+        (_, 0x04..=0x14) | (_, 0x60..=0x61) => Vowel,
+        (_, 0x30) => Ra,
+        (Bengali, 0x70) => Ra,
+        (_, 0x15..=0x39) | (_, 0x58..=0x5f) => Consonant,
+        (Bengali, 0x4e) | (Bengali, 0x71) => Consonant,
+        (Devanagari, 0x72..=0x77) => Vowel,
+        (Devanagari, 0x78..=0x7f) | (Malayalam, 0x54..=0x56) | (Malayalam, 0x7a..=0x7f) => Consonant,
+        (Malayalam, 0x3b..=0x3c) => Halant,
+        (_, 0x3c) => Nukta,
+        (_, 0x4d) => Halant,
+        (Gurmukhi, 0x70..=0x71) => Modifier,
+        (_, 0x51..=0x53) | (Devanagari, 0x54) => Modifier,
+
+        // vowel signs drawn before the consonant
+        (Devanagari, 0x3f) | (Devanagari, 0x4e) | (Bengali, 0x3f) | (Gurmukhi, 0x3f) | (Gujarati, 0x3f) => Matra(Pre),
+        (Bengali, 0x47..=0x48) | (Oriya, 0x47) => Matra(Pre),
+        (Tamil, 0x46..=0x48) | (Malayalam, 0x46..=0x48) => Matra(Pre),
+
+        // above
+        (Devanagari, 0x3a) | (Devanagari, 0x45..=0x48) | (Devanagari, 0x55) => Matra(Above),
+        (Gurmukhi, 0x47..=0x48) | (Gurmukhi, 0x4b..=0x4c) => Matra(Above),
+        (Gujarati, 0x45) | (Gujarati, 0x47..=0x48) => Matra(Above),
+        (Oriya, 0x3f) | (Oriya, 0x56) | (Tamil, 0x40) => Matra(Above),
+        (Telugu, 0x3e..=0x40) | (Telugu, 0x46..=0x4c) | (Telugu, 0x55) => Matra(Above),
+        (Kannada, 0x3f) | (Kannada, 0x46) => Matra(Above),
+
+        // below, except in the scripts that write the u signs after the consonant
+        (Tamil, 0x41..=0x42) | (Telugu, 0x41..=0x44) | (Kannada, 0x41..=0x44) => Matra(Post),
+        (_, 0x41..=0x44) | (_, 0x56) | (_, 0x62..=0x63) => Matra(Below),
+
+        // everything else is drawn after
+        (_, 0x3d) => Other,
+        (_, 0x3a..=0x4f) | (_, 0x55..=0x57) => Matra(Post),
+        _ => Other
+    }
+}
+
+fn khmer_category(cp: u32) -> Category {
+    match cp {
+        0x179a => Ra,
+        0x1780..=0x17a2 => Consonant,
+        0x17a3..=0x17b3 => Vowel,
+        0x17b6 => Matra(Post),
+        0x17b7..=0x17ba => Matra(Above),
+        0x17bb..=0x17bd => Matra(Below),
+        // these are split into a pre-base part and this, which wraps around the consonant
+        0x17be..=0x17c0 | 0x17c4..=0x17c5 => Matra(Post),
+        0x17c1..=0x17c3 => Matra(Pre),
+        0x17c6..=0x17d1 | 0x17d3 | 0x17dd => Modifier,
+        0x17d2 => Halant,
+        _ => Other
+    }
+}
+
+fn myanmar_category(cp: u32) -> Category {
+    match cp {
+        0x1000..=0x1020 | 0x103f | 0x1050..=0x1051 | 0x105a..=0x105d | 0x1061 | 0x1065..=0x1066 |
+        0x106e..=0x1070 | 0x1075..=0x1081 | 0x108e => Consonant,
+        0x1021..=0x102a | 0x1052..=0x1055 => Vowel,
+        0x1031 | 0x1084 => Matra(Pre),
+        0x102b..=0x102c | 0x1056..=0x1057 | 0x1062 | 0x1067..=0x1068 | 0x1083 | 0x109b..=0x109c => Matra(Post),
+        0x102d..=0x102e | 0x1032..=0x1035 | 0x1071..=0x1074 | 0x1085..=0x1086 | 0x109d => Matra(Above),
+        0x102f..=0x1030 | 0x1058..=0x1059 => Matra(Below),
+        0x1036..=0x1038 | 0x1063..=0x1064 | 0x1069..=0x106d | 0x1087..=0x108d | 0x108f | 0x109a => Modifier,
+        0x1039 => Halant,
+        // the asat kills the vowel of the consonant before it, much like a nukta
+        0x103a => Nukta,
+        0x103c => Medial(Pre),
+        0x103b | 0x105e..=0x1060 => Medial(Post),
+        0x103d..=0x103e | 0x1082 => Medial(Below),
+        _ => Other
+    }
+}
+
+/// Vowel signs that are drawn in two parts, on either side of the consonant, are split up so that
+/// the part before it can be moved there
+pub fn decompose(c: char) -> Option<(char, char)> {
+    Some(match c {
+        '\u{9cb}' => ('\u{9c7}', '\u{9be}'),
+        '\u{9cc}' => ('\u{9c7}', '\u{9d7}'),
+        '\u{b48}' => ('\u{b47}', '\u{b56}'),
+        '\u{b4b}' => ('\u{b47}', '\u{b3e}'),
+        '\u{b4c}' => ('\u{b47}', '\u{b57}'),
+        '\u{bca}' => ('\u{bc6}', '\u{bbe}'),
+        '\u{bcb}' => ('\u{bc7}', '\u{bbe}'),
+        '\u{bcc}' => ('\u{bc6}', '\u{bd7}'),
+        '\u{d4a}' => ('\u{d46}', '\u{d3e}'),
+        '\u{d4b}' => ('\u{d47}', '\u{d3e}'),
+        '\u{d4c}' => ('\u{d46}', '\u{d57}'),
+        // Khmer keeps the whole vowel as the part after, since fonts draw it that way
+        '\u{17be}' | '\u{17bf}' | '\u{17c0}' | '\u{17c4}' | '\u{17c5}' => ('\u{17c1}', c),
+        _ => return None
+    })
+}
+
+fn mask(tag: &[u8; 4]) -> u32 {
+    feature_mask(TableTag::new(tag))
+}
+
+fn is_consonant(c: Category) -> bool {
+    matches!(c, Consonant | Ra | Placeholder)
+}
+
+/// Whether a character is part of the syllable it follows
+fn is_dependent(c: Category) -> bool {
+    matches!(c, Nukta | Halant | Joiner | NonJoiner | Matra(_) | Medial(_) | Modifier)
+}
+
+/// Splits a run into syllables, giving the index each one ends at. A consonant syllable is a chain
+/// of consonants joined by viramas followed by its signs, and anything else is a syllable by
+/// itself along with any signs after it
+fn find_syllables(categories: &[Category]) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut i = 0;
+    while i < categories.len() {
+        let first = categories[i];
+        i += 1;
+        if is_consonant(first) {
+            loop {
+                while i < categories.len() && categories[i] == Nukta { i += 1; }
+                let mut j = i;
+                if j < categories.len() && categories[j] == Halant {
+                    j += 1;
+                    if j < categories.len() && (categories[j] == Joiner || categories[j] == NonJoiner) { j += 1; }
+                    if j < categories.len() && is_consonant(categories[j]) {
+                        i = j + 1;
+                        continue;
+                    }
+                }
+                break;
+            }
+        }
+        if first != Other {
+            while i < categories.len() && is_dependent(categories[i]) { i += 1; }
+        }
+        ends.push(i);
+    }
+    ends
+}
+
+/// A syllable of a run, by the clusters of its characters
+#[derive(Clone, Debug)]
+struct Syllable {
+    /// The cluster of the first character, and of the first character after the syllable
+    start: usize,
+    end: usize,
+    /// Syllables without a base, like independent vowels, are left as they are
+    base: Option<usize>,
+    /// The clusters of the ra and virama that should become a reph
+    reph: Option<(usize, usize)>,
+    /// Whether anything was moved, in which case the whole syllable becomes one cluster
+    reordered: bool
+}
+
+/// What the second reordering needs to know about a run after the first
+#[derive(Clone, Debug)]
+pub struct Syllables {
+    script: Script,
+    syllables: Vec<Syllable>,
+    categories: BTreeMap<usize, Category>
+}
+
+/// The order characters are put in within a syllable before the features are applied
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Slot {
+    Reph,
+    PreMatra,
+    PreBaseForm,
+    PreBase,
+    Base,
+    /// The Myanmar kinzi, which is written before the base but drawn above it
+    Kinzi,
+    PostBase
+}
+
+/// Finds the syllables in a run, reorders the glyphs in each and marks them with the features
+/// that apply to them. `chars` are the characters the glyphs were mapped from, one per glyph
+pub fn setup(script: Script, chars: &[char], glyphs: &mut [LayoutGlyph]) -> Syllables {
+    let model = model(script).unwrap_or(Model::Indic);
+    let categories: Vec<Category> = chars.iter().map(|&c| category(script, c)).collect();
+    // the glyphs get moved around, so the second reordering finds their categories by cluster
+    let mut by_cluster = BTreeMap::new();
+    for (g, &c) in glyphs.iter().zip(categories.iter()) {
+        by_cluster.entry(g.cluster).or_insert(c);
+    }
+    let mut syllables = Vec::new();
+    let mut start = 0;
+    for end in find_syllables(&categories) {
+        let cats = &categories[start..end];
+        let clusters: Vec<usize> = glyphs[start..end].iter().map(|g| g.cluster).collect();
+
+        // a ra and virama at the start of a syllable with more consonants after it becomes a reph,
+        // and so does the Myanmar kinzi, nga asat virama
+        let joiner = reph_needs_joiner(script) as usize;
+        let reph_len = if reph_position(script).is_some() && cats.len() > 2 + joiner && cats[0] == Ra && cats[1] == Halant
+            && (joiner == 0 || cats[2] == Joiner) && is_consonant(cats[2 + joiner]) {
+            2 + joiner
+        } else if script == Script::Myanmar && cats.len() > 3 && chars[start] == '\u{1004}' && cats[1] == Nukta && cats[2] == Halant
+            && is_consonant(cats[3]) {
+            3
+        } else {
+            0
+        };
+
+        let consonants = (reph_len..cats.len()).filter(|&i| is_consonant(cats[i]));
+        let base = match model {
+            Model::FirstConsonantBase => consonants.clone().next(),
+            Model::Indic => {
+                let mut base = consonants.clone().next_back();
+                // a ra after a virama at the end of a cluster takes a form below the consonant
+                // before it instead of being the base
+                if let Some(b) = base {
+                    if cats[b] == Ra && b > reph_len + 1 && cats[b - 1] == Halant && is_consonant(cats[b - 2]) {
+                        base = consonants.clone().rfind(|&i| i < b - 1);
+                    }
+                }
+                base
+            }
+        };
+
+        let mut slots = vec![Slot::PostBase; cats.len()];
+        if let Some(base) = base {
+            for i in 0..cats.len() {
+                slots[i] = if i < reph_len {
+                    if script == Script::Myanmar { Slot::Kinzi } else { Slot::Reph }
+                } else if i < base {
+                    Slot::PreBase
+                } else if i == base || (i > base && cats[i] == Nukta && slots[i - 1] == Slot::Base) {
+                    Slot::Base
+                } else {
+                    match cats[i] {
+                        Matra(Pre) => Slot::PreMatra,
+                        Medial(Pre) => Slot::PreBaseForm,
+                        // the Khmer coeng ro is drawn before the base
+                        Ra if model == Model::FirstConsonantBase && cats[i - 1] == Halant => Slot::PreBaseForm,
+                        Halant if model == Model::FirstConsonantBase && i + 1 < cats.len() && cats[i + 1] == Ra => Slot::PreBaseForm,
+                        _ => Slot::PostBase
+                    }
+                };
+            }
+        }
+
+        for (i, g) in glyphs[start..end].iter_mut().enumerate() {
+            g.mask = GLOBAL_MASK | match slots[i] {
+                Slot::Reph | Slot::Kinzi => mask(b"rphf"),
+                Slot::PreBaseForm => mask(b"pref"),
+                Slot::PreBase if model == Model::Indic => mask(b"half"),
+                Slot::PostBase if !is_matra_or_modifier(cats[i]) => {
+                    mask(b"blwf") | mask(b"abvf") | mask(b"pstf") | mask(b"pref") |
+                    mask(b"vatu") | mask(b"cfar")
+                },
+                _ => 0
+            };
+        }
+
+        // stable, so characters in the same slot keep their order
+        let mut order: Vec<usize> = (0..cats.len()).collect();
+        order.sort_by_key(|&i| slots[i]);
+        let reordered = order.iter().enumerate().any(|(i, &o)| i != o);
+        if reordered {
+            let moved: Vec<LayoutGlyph> = order.iter().map(|&i| glyphs[start + i]).collect();
+            glyphs[start..end].copy_from_slice(&moved);
+        }
+
+        syllables.push(Syllable {
+            start: clusters[0],
+            end: usize::MAX,
+            base: base.map(|b| clusters[b]),
+            reph: if reph_len > 0 && script != Script::Myanmar { Some((clusters[0], clusters[1])) } else { None },
+            reordered
+        });
+        start = end;
+    }
+
+    // each syllable ends where the next starts, and the last runs to the end of the text
+    for i in 1..syllables.len() {
+        syllables[i - 1].end = syllables[i].start;
+    }
+    Syllables { script, syllables, categories: by_cluster }
+}
+
+fn is_matra_or_modifier(c: Category) -> bool {
+    matches!(c, Matra(_) | Modifier)
+}
+
+impl Syllables {
+    fn category(&self, cluster: usize) -> Category {
+        self.categories.get(&cluster).cloned().unwrap_or(Other)
+    }
+
+    /// Moves the reph and pre-base vowel signs to where they belong now that the consonants have
+    /// taken their forms, then puts the glyphs of each reordered syllable into one cluster
+    pub fn reorder(&self, glyphs: &mut [LayoutGlyph]) {
+        let mut i = 0;
+        while i < glyphs.len() {
+            let syllable = match self.syllables.iter().find(|s| glyphs[i].cluster >= s.start && glyphs[i].cluster < s.end) {
+                Some(s) => s,
+                None => { i += 1; continue; }
+            };
+            let mut end = i + 1;
+            while end < glyphs.len() && glyphs[end].cluster >= syllable.start && glyphs[end].cluster < syllable.end {
+                end += 1;
+            }
+            let moved = self.reorder_syllable(syllable, &mut glyphs[i..end]);
+            if syllable.reordered || moved {
+                for g in &mut glyphs[i..end] {
+                    g.cluster = syllable.start;
+                }
+            }
+            i = end;
+        }
+    }
+
+    /// Gives whether anything was moved
+    fn reorder_syllable(&self, syllable: &Syllable, glyphs: &mut [LayoutGlyph]) -> bool {
+        // the reph formed if the virama it was made with is gone
+        let reph = match syllable.reph {
+            Some((ra, halant)) if !glyphs.iter().any(|g| g.cluster == halant) => glyphs.iter().position(|g| g.cluster == ra),
+            _ => None
+        };
+        let base_cluster = match syllable.base {
+            Some(b) => b,
+            None => return false
+        };
+        let is_base = |g: &LayoutGlyph| g.cluster <= base_cluster && !is_matra_or_modifier(self.category(g.cluster));
+        let base = match (0..glyphs.len()).rfind(|&j| Some(j) != reph && is_base(&glyphs[j])) {
+            Some(b) => b,
+            None => return false
+        };
+        let mut moved = false;
+
+        // pre-base vowel signs go after the last virama before the base that didn't become part of
+        // a half form, since the consonants before that are drawn as full letters. When the base is
+        // the first consonant the only virama before it is the Khmer coeng of a coeng ro, which the
+        // vowel sign is drawn in front of
+        let pre_matras: Vec<usize> = (0..base).filter(|&j| self.category(glyphs[j].cluster) == Matra(Pre)).collect();
+        if let (Some(&first), Model::Indic) = (pre_matras.first(), model(self.script).unwrap_or(Model::Indic)) {
+            if let Some(halant) = (first..base).rfind(|&j| self.category(glyphs[j].cluster) == Halant) {
+                let count = pre_matras.len();
+                glyphs[first..halant + 1].rotate_left(count);
+                moved = true;
+            }
+        }
+
+        if let Some(reph) = reph {
+            let base = (0..glyphs.len()).rfind(|&j| j != reph && is_base(&glyphs[j])).unwrap_or(base);
+            let target = match reph_position(self.script) {
+                Some(RephPosition::AfterMain) => base,
+                _ => {
+                    let mut t = glyphs.len() - 1;
+                    while t > base && self.category(glyphs[t].cluster) == Modifier { t -= 1; }
+                    t
+                }
+            };
+            if target > reph {
+                glyphs[reph..target + 1].rotate_left(1);
+                moved = true;
+            }
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str) -> (Vec<char>, Vec<LayoutGlyph>) {
+        // the character stands in for the glyph, so the order can be checked
+        let chars: Vec<char> = text.chars().collect();
+        let glyphs = text.char_indices().map(|(i, c)| LayoutGlyph::new(c as u32 as u16, i)).collect();
+        (chars, glyphs)
+    }
+
+    fn text(glyphs: &[LayoutGlyph]) -> String {
+        glyphs.iter().map(|g| ::std::char::from_u32(g.glyph as u32).unwrap()).collect()
+    }
+
+    #[test]
+    fn categories() {
+        assert_eq!(category(Script::Devanagari, '\u{915}'), Consonant);
+        assert_eq!(category(Script::Devanagari, '\u{930}'), Ra);
+        assert_eq!(category(Script::Devanagari, '\u{93f}'), Matra(Pre));
+        assert_eq!(category(Script::Devanagari, '\u{94d}'), Halant);
+        assert_eq!(category(Script::Devanagari, '\u{902}'), Modifier);
+        assert_eq!(category(Script::Tamil, '\u{bc6}'), Matra(Pre));
+        assert_eq!(category(Script::Khmer, '\u{17d2}'), Halant);
+        assert_eq!(category(Script::Myanmar, '\u{1031}'), Matra(Pre));
+        assert_eq!(category(Script::Devanagari, 'a'), Other);
+        assert_eq!(find_syllables(&[Consonant, Halant, Consonant, Matra(Pre), Modifier, Consonant, Vowel, Modifier]), vec![5, 6, 8]);
+    }
+
+    #[test]
+    fn reordering() {
+        // ki: the i sign moves before the ka
+        let (chars, mut glyphs) = run("\u{915}\u{93f}");
+        setup(Script::Devanagari, &chars, &mut glyphs);
+        assert_eq!(text(&glyphs), "\u{93f}\u{915}");
+
+        // rki: the reph stays in front for 'rphf', and the i sign goes after it
+        let (chars, mut glyphs) = run("\u{930}\u{94d}\u{915}\u{93f}");
+        let syllables = setup(Script::Devanagari, &chars, &mut glyphs);
+        assert_eq!(text(&glyphs), "\u{930}\u{94d}\u{93f}\u{915}");
+        assert!(glyphs[0].mask & mask(b"rphf") != 0 && glyphs[3].mask & mask(b"rphf") == 0);
+
+        // once the ra and virama form a reph, it moves to the end of the syllable, and all of the
+        // glyphs end up in one cluster
+        glyphs.remove(1);
+        glyphs[0].glyph = 'R' as u16;
+        syllables.reorder(&mut glyphs);
+        assert_eq!(text(&glyphs), "\u{93f}\u{915}R");
+        assert!(glyphs.iter().all(|g| g.cluster == 0));
+
+        // kra: the ra after the virama takes its below form instead of being the base
+        let (chars, mut glyphs) = run("\u{915}\u{94d}\u{930}");
+        setup(Script::Devanagari, &chars, &mut glyphs);
+        assert!(glyphs[0].mask & mask(b"half") == 0);
+        assert!(glyphs[2].mask & mask(b"blwf") != 0);
+        let (chars, mut glyphs) = run("\u{915}\u{94d}\u{937}");
+        setup(Script::Devanagari, &chars, &mut glyphs);
+        assert!(glyphs[0].mask & mask(b"half") != 0 && glyphs[2].mask & mask(b"half") == 0);
+
+        // ski without a half form: the i sign ends up after the virama, next to the base
+        let (chars, mut glyphs) = run("\u{938}\u{94d}\u{915}\u{93f}");
+        let syllables = setup(Script::Devanagari, &chars, &mut glyphs);
+        assert_eq!(text(&glyphs), "\u{93f}\u{938}\u{94d}\u{915}");
+        syllables.reorder(&mut glyphs);
+        assert_eq!(text(&glyphs), "\u{938}\u{94d}\u{93f}\u{915}");
+
+        // a syllable without a base doesn't take the glyphs after it along when it is reordered
+        let (chars, mut glyphs) = run("\u{930}\u{94d}\u{915} \u{905}");
+        let syllables = setup(Script::Devanagari, &chars, &mut glyphs);
+        glyphs.remove(1);
+        syllables.reorder(&mut glyphs);
+        assert_eq!(text(&glyphs), "\u{915}\u{930} \u{905}");
+        assert_eq!(glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![0, 0, 9, 10]);
+
+        // Khmer: the pre-base vowel and coeng ro go before the base, and the vowel stays in front of
+        // the coeng when the syllable is reordered again
+        let (chars, mut glyphs) = run("\u{1780}\u{17d2}\u{179a}\u{17c1}");
+        let syllables = setup(Script::Khmer, &chars, &mut glyphs);
+        assert_eq!(text(&glyphs), "\u{17c1}\u{17d2}\u{179a}\u{1780}");
+        syllables.reorder(&mut glyphs);
+        assert_eq!(text(&glyphs), "\u{17c1}\u{17d2}\u{179a}\u{1780}");
+        assert!(glyphs.iter().all(|g| g.cluster == 0));
+
+        // Myanmar: the kinzi goes after the base and the e vowel before it
+        let (chars, mut glyphs) = run("\u{1004}\u{103a}\u{1039}\u{1000}\u{1031}");
+        setup(Script::Myanmar, &chars, &mut glyphs);
+        assert_eq!(text(&glyphs), "\u{1031}\u{1000}\u{1004}\u{103a}\u{1039}");
+    }
+}
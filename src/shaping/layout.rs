@@ -0,0 +1,359 @@
+//! Lays out a paragraph on lines no wider than a maximum width, wrapping it at the places the
+//! line breaking algorithm allows and lining up the glyphs of each line in the order they are drawn
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use truetype_loader::*;
+use CharMap;
+use super::{shape_levels, Direction, Feature, FALLBACK_UNITS_PER_EM};
+use super::bidi::BidiParagraph;
+use super::line_break::{line_breaks, line_break_class, Break, LineBreakClass};
+
+/// Where the text of a line goes between the edges of the paragraph
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    Right,
+    Center,
+    /// Widen the spaces so each line fills the width. The last line of a paragraph isn't widened,
+    /// it goes on the side the paragraph starts from
+    Justify
+}
+
+/// How to lay out a paragraph
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutOptions {
+    /// The size of the text in pixels per em
+    pub size: f32,
+    /// How wide a line can be in pixels. A word that is wider than this gets a line of its own and
+    /// sticks out past it
+    pub max_width: f32,
+    pub alignment: Alignment,
+    /// The direction of the paragraph, or `None` to work it out from the text
+    pub direction: Option<Direction>,
+    /// Features to apply on top of the defaults, see `shape`
+    pub features: Vec<Feature>
+}
+
+impl LayoutOptions {
+    /// Left aligned text in the direction its first strong character goes, with the default
+    /// features
+    pub fn new(size: f32, max_width: f32) -> LayoutOptions {
+        LayoutOptions { size, max_width, alignment: Alignment::Left, direction: None, features: Vec::new() }
+    }
+}
+
+/// A glyph placed on a line, in pixels from the top left of the paragraph with y going down
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineGlyph {
+    pub glyph_index: usize,
+    /// The byte offset in the text of the first character this glyph came from
+    pub cluster: usize,
+    pub x: f32,
+    pub y: f32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// The bytes of the text on this line, including the spaces and newline at its end
+    pub range: Range<usize>,
+    /// How far down from the top of the paragraph the baseline is, in pixels
+    pub baseline: f32,
+    /// The width of the text on the line in pixels, not counting spaces at its end
+    pub width: f32,
+    /// The glyphs in visual order, left to right
+    pub glyphs: Vec<LineGlyph>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParagraphLayout {
+    pub lines: Vec<Line>,
+    /// The distance from one baseline to the next, in pixels
+    pub line_height: f32,
+    /// The height of all the lines together
+    pub height: f32
+}
+
+/// Lay out a paragraph, wrapping it onto as many lines as it needs to fit `options.max_width`.
+/// Newlines always start a new line, and each piece of text between them is its own bidi
+/// paragraph. Line heights come from the font's ascender, descender and line gap
+pub fn layout_paragraph(font: &SfntFont, text: &str, options: &LayoutOptions) -> ParagraphLayout {
+    let units_per_em = font.head_table.map(|h| h.units_per_em).unwrap_or(FALLBACK_UNITS_PER_EM) as f32;
+    let scale = options.size / units_per_em;
+    // fonts without any vertical metrics get the usual split of the em above and below the baseline
+    let metrics = font.line_metrics().unwrap_or(LineMetrics {
+        ascender: (units_per_em * 0.8) as i16,
+        descender: -(units_per_em * 0.2) as i16,
+        line_gap: 0
+    });
+    let ascent = metrics.ascender as f32 * scale;
+    let line_height = (metrics.ascender as f32 - metrics.descender as f32 + metrics.line_gap as f32) * scale;
+
+    let mut breaks = line_breaks(text);
+    // empty text is still a line
+    if breaks.is_empty() {
+        breaks.push((0, Break::Mandatory));
+    }
+    let char_map = CharMap::from_truetype(font).ok();
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for &(end, kind) in &breaks {
+        if kind != Break::Mandatory {
+            continue;
+        }
+        let opportunities: Vec<usize> = breaks.iter().filter(|&&(b, _)| b > start && b < end).map(|&(b, _)| b).collect();
+        layout_lines(font, char_map.as_ref(), text, start..end, &opportunities, options, ascent, line_height, &mut lines);
+        start = end;
+    }
+    let height = lines.len() as f32 * line_height;
+    ParagraphLayout { lines, line_height, height }
+}
+
+/// Wrap the text between two mandatory breaks, adding its lines to `lines`
+fn layout_lines(font: &SfntFont, char_map: Option<&CharMap>, text: &str, range: Range<usize>, opportunities: &[usize],
+                options: &LayoutOptions, ascent: f32, line_height: f32, lines: &mut Vec<Line>) {
+    // the newline at the end isn't drawn
+    let mut content_end = range.end;
+    while let Some(c) = text[range.start..content_end].chars().next_back() {
+        if !is_newline(c) {
+            break;
+        }
+        content_end -= c.len_utf8();
+    }
+    let segment = &text[range.start..content_end];
+    let chars: Vec<char> = segment.chars().collect();
+    let paragraph = BidiParagraph::new(&chars, options.direction);
+
+    // how wide each cluster is when the whole paragraph is on one line
+    let mut advances = BTreeMap::new();
+    for run in shape_levels(font, char_map, segment, &paragraph.levels, options.size, &options.features) {
+        for g in run.glyphs {
+            *advances.entry(range.start + g.cluster).or_insert(0.0) += g.x_advance;
+        }
+    }
+    let width = |start: usize, end: usize| -> f32 {
+        advances.range(start..trim_spaces(text, start, end)).map(|(_, &w)| w).sum()
+    };
+
+    // fill each line with as many words as fit
+    let mut line_ends = Vec::new();
+    let mut line_start = range.start;
+    let mut last_fit = None;
+    for &end in opportunities.iter().filter(|&&b| b < content_end).chain(Some(content_end).iter()) {
+        if width(line_start, end) > options.max_width {
+            if let Some(fit) = last_fit {
+                line_ends.push(fit);
+                line_start = fit;
+                last_fit = None;
+            }
+            if width(line_start, end) > options.max_width {
+                line_ends.push(end);
+                line_start = end;
+                continue;
+            }
+        }
+        last_fit = Some(end);
+    }
+    line_ends.extend(last_fit);
+    // so is a blank one
+    if line_ends.is_empty() {
+        line_ends.push(content_end);
+    }
+
+    let mut line_start = range.start;
+    let count = line_ends.len();
+    for (n, end) in line_ends.into_iter().enumerate() {
+        let last = n + 1 == count;
+        let baseline = lines.len() as f32 * line_height + ascent;
+        let mut line = position_line(font, char_map, text, line_start..end, range.start, &paragraph, options, baseline, last);
+        if last {
+            line.range.end = range.end;
+        }
+        lines.push(line);
+        line_start = end;
+    }
+}
+
+/// Shape and place the glyphs of one line
+fn position_line(font: &SfntFont, char_map: Option<&CharMap>, text: &str, range: Range<usize>, paragraph_start: usize,
+                 paragraph: &BidiParagraph, options: &LayoutOptions, baseline: f32, last: bool) -> Line {
+    let first_char = text[paragraph_start..range.start].chars().count();
+    let char_count = text[range.clone()].chars().count();
+    let levels = paragraph.line_levels(first_char, first_char + char_count);
+    let runs = shape_levels(font, char_map, &text[range.clone()], &levels, options.size, &options.features);
+
+    let content_end = trim_spaces(text, range.start, range.end);
+    let is_space = |cluster: usize| text[cluster..].chars().next().map(|c| c.is_whitespace()).unwrap_or(false);
+    let mut total = 0.0;
+    let mut trailing = 0.0;
+    let mut spaces = 0;
+    for g in runs.iter().flat_map(|r| r.glyphs.iter()) {
+        let cluster = range.start + g.cluster;
+        total += g.x_advance;
+        if cluster >= content_end {
+            trailing += g.x_advance;
+        } else if is_space(cluster) {
+            spaces += 1;
+        }
+    }
+    let width = total - trailing;
+
+    // the spaces at the end of a line go on the side the paragraph starts from, so they are on the
+    // left of a right to left line
+    let right_to_left = paragraph.level & 1 == 1;
+    let alignment = match options.alignment {
+        Alignment::Justify if last => if right_to_left { Alignment::Right } else { Alignment::Left },
+        a => a
+    };
+    let extra = options.max_width - width;
+    let stretch = if alignment == Alignment::Justify && spaces > 0 && extra > 0.0 { extra / spaces as f32 } else { 0.0 };
+    let mut x = match alignment {
+        Alignment::Left | Alignment::Justify => 0.0,
+        Alignment::Right => extra,
+        Alignment::Center => extra / 2.0
+    } - if right_to_left { trailing } else { 0.0 };
+    let mut y = baseline;
+
+    let mut glyphs = Vec::new();
+    for g in runs.iter().flat_map(|r| r.glyphs.iter()) {
+        let cluster = range.start + g.cluster;
+        glyphs.push(LineGlyph { glyph_index: g.glyph_index, cluster, x: x + g.x_offset, y: y - g.y_offset });
+        x += g.x_advance;
+        y -= g.y_advance;
+        if cluster < content_end && is_space(cluster) {
+            x += stretch;
+        }
+    }
+    Line { range, baseline, width, glyphs }
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(line_break_class(c), LineBreakClass::BK | LineBreakClass::CR | LineBreakClass::LF | LineBreakClass::NL)
+}
+
+/// Where the text in `start..end` ends without the whitespace at its end
+fn trim_spaces(text: &str, start: usize, end: usize) -> usize {
+    let mut end = end;
+    while let Some(c) = text[start..end].chars().next_back() {
+        if !c.is_whitespace() {
+            break;
+        }
+        end -= c.len_utf8();
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::shape;
+    use std::fs::File;
+
+    #[cfg(target_os="windows")]
+    const FONT_PATH: &str =
+        "C:\\Windows\\Fonts\\arial.ttf";
+    #[cfg(target_os="macos")]
+    const FONT_PATH: &str =
+        "/Library/Fonts/Arial.ttf";
+    #[cfg(target_os="linux")]
+    const FONT_PATH: &str =
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+    fn text_width(font: &SfntFont, text: &str) -> f32 {
+        shape(font, text, 16.0, &[]).iter().map(|g| g.x_advance).sum()
+    }
+
+    fn line_texts<'t>(text: &'t str, layout: &ParagraphLayout) -> Vec<&'t str> {
+        layout.lines.iter().map(|l| &text[l.range.clone()]).collect()
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+        let metrics = font.line_metrics().unwrap();
+        let scale = 16.0 / font.head_table.unwrap().units_per_em as f32;
+
+        let text = "The quick brown fox jumps over the lazy dog";
+        let layout = layout_paragraph(&font, text, &LayoutOptions::new(16.0, 1000.0));
+        assert_eq!(line_texts(text, &layout), vec![text]);
+        let line = &layout.lines[0];
+        assert!((line.width - text_width(&font, text)).abs() < 1e-3);
+        assert!((line.baseline - metrics.ascender as f32 * scale).abs() < 1e-3);
+        assert_eq!(line.glyphs.len(), text.len());
+        assert_eq!(line.glyphs[0].x, 0.0);
+        assert!(line.glyphs.windows(2).all(|g| g[0].x < g[1].x && g[0].y == g[1].y));
+
+        // wrapping where the first three words only just fit
+        let max_width = text_width(&font, "The quick brown") + 0.5;
+        let layout = layout_paragraph(&font, text, &LayoutOptions::new(16.0, max_width));
+        assert_eq!(line_texts(text, &layout), vec!["The quick brown ", "fox jumps over ", "the lazy dog"]);
+        assert!(layout.lines.iter().all(|l| l.width <= max_width));
+        let line_height = (metrics.ascender - metrics.descender + metrics.line_gap) as f32 * scale;
+        assert!((layout.lines[1].baseline - layout.lines[0].baseline - line_height).abs() < 1e-3);
+        assert!((layout.height - 3.0 * line_height).abs() < 1e-3);
+
+        // words wider than a line stick out on a line of their own
+        let layout = layout_paragraph(&font, "a verylongword b", &LayoutOptions::new(16.0, 20.0));
+        assert_eq!(line_texts("a verylongword b", &layout), vec!["a ", "verylongword ", "b"]);
+
+        // newlines always break, and blank lines are still lines
+        let layout = layout_paragraph(&font, "one\n\ntwo", &LayoutOptions::new(16.0, 1000.0));
+        assert_eq!(line_texts("one\n\ntwo", &layout), vec!["one\n", "\n", "two"]);
+        assert_eq!(layout.lines[1].glyphs.len(), 0);
+        assert_eq!(layout.lines[2].glyphs[0].cluster, 5);
+        assert_eq!(layout_paragraph(&font, "", &LayoutOptions::new(16.0, 100.0)).lines.len(), 1);
+    }
+
+    #[test]
+    fn alignment() {
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+
+        let text = "The quick brown fox jumps over the lazy dog";
+        let mut options = LayoutOptions::new(16.0, text_width(&font, "The quick brown") + 10.0);
+        let last_x = |line: &Line| {
+            let g = line.glyphs.iter().rfind(|g| !text[g.cluster..].starts_with(' ')).unwrap();
+            g.x + shape(&font, &text[g.cluster..g.cluster + 1], 16.0, &[])[0].x_advance
+        };
+
+        options.alignment = Alignment::Right;
+        let layout = layout_paragraph(&font, text, &options);
+        for line in &layout.lines {
+            assert!((line.glyphs[0].x - (options.max_width - line.width)).abs() < 1e-3);
+        }
+
+        options.alignment = Alignment::Center;
+        let layout = layout_paragraph(&font, text, &options);
+        for line in &layout.lines {
+            assert!((line.glyphs[0].x - (options.max_width - line.width) / 2.0).abs() < 1e-3);
+        }
+
+        // justified lines fill the width apart from the last one
+        options.alignment = Alignment::Justify;
+        let layout = layout_paragraph(&font, text, &options);
+        let (last, lines) = layout.lines.split_last().unwrap();
+        for line in lines {
+            assert_eq!(line.glyphs[0].x, 0.0);
+            assert!((last_x(line) - options.max_width).abs() < 0.1);
+        }
+        assert_eq!(last.glyphs[0].x, 0.0);
+        assert!(last_x(last) < options.max_width - 1.0);
+
+        // right to left paragraphs start on the right, with their trailing spaces out of the way
+        let hebrew = "\u{5d0}\u{5d1}\u{5d2} \u{5d3}\u{5d4}\u{5d5}";
+        let mut options = LayoutOptions::new(16.0, 1000.0);
+        options.alignment = Alignment::Right;
+        let layout = layout_paragraph(&font, hebrew, &options);
+        let line = &layout.lines[0];
+        assert_eq!(line.glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![11, 9, 7, 6, 4, 2, 0]);
+        assert!((line.glyphs[0].x - (1000.0 - line.width)).abs() < 1e-3);
+        options.max_width = text_width(&font, "\u{5d0}\u{5d1}\u{5d2}") + 1.0;
+        let layout = layout_paragraph(&font, hebrew, &options);
+        assert_eq!(line_texts(hebrew, &layout), vec!["\u{5d0}\u{5d1}\u{5d2} ", "\u{5d3}\u{5d4}\u{5d5}"]);
+        let line = &layout.lines[0];
+        // the space is at the visual start of the line, to the left of the text
+        assert_eq!(line.glyphs[0].cluster, 6);
+        assert!((line.glyphs[1].x - (options.max_width - line.width)).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,1302 @@
+//! Line breaking from the Unicode Line Breaking Algorithm (UAX #14), which finds the places text
+//! may be wrapped onto a new line and the places it has to be
+
+use super::find_range;
+
+/// The line breaking class of a character, from Unicode's LineBreak.txt. The classes that rule LB1
+/// leaves up to the implementation are already resolved: ambiguous, unknown and South East Asian
+/// letters are `AL`, South East Asian marks are `CM`, and conditional Japanese starters are `NS`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineBreakClass {
+    /// Mandatory breaks, like the line and paragraph separators
+    BK,
+    CR,
+    LF,
+    /// Next line
+    NL,
+    /// Combining marks, which go with the character before them
+    CM,
+    ZWJ,
+    /// Word joiners, which stop a break on either side
+    WJ,
+    /// Zero width spaces, which allow a break
+    ZW,
+    /// Non-breaking glue, like the no-break space
+    GL,
+    SP,
+    /// Break opportunities before and after, like the em dash
+    B2,
+    /// Break opportunities after, like most spaces that aren't `SP`
+    BA,
+    /// Break opportunities before
+    BB,
+    /// Hyphens
+    HY,
+    /// Contingent break opportunities, for embedded objects
+    CB,
+    /// Closing punctuation
+    CL,
+    /// Closing parentheses
+    CP,
+    /// Exclamation and question marks
+    EX,
+    /// Inseparable characters, like the ellipsis
+    IN,
+    /// Nonstarters, which a line can't start with
+    NS,
+    /// Opening punctuation
+    OP,
+    /// Quotation marks
+    QU,
+    /// Separators inside numbers
+    IS,
+    NU,
+    /// Postfixes on numbers, like the percent sign
+    PO,
+    /// Prefixes on numbers, like currency signs
+    PR,
+    /// The slash
+    SY,
+    /// Ordinary letters and symbols
+    AL,
+    /// Emoji that take a skin tone modifier
+    EB,
+    /// Emoji skin tone modifiers
+    EM,
+    /// Hangul syllables of a leading and a vowel jamo
+    H2,
+    /// Hangul syllables of a leading, a vowel and a trailing jamo
+    H3,
+    /// Hebrew letters
+    HL,
+    /// Ideographs, which can be broken between
+    ID,
+    JL,
+    JV,
+    JT,
+    /// Regional indicators, which make up flags in pairs
+    RI
+}
+
+use self::LineBreakClass::*;
+
+/// Line breaking classes, sorted by their first character. Characters not in here are `AL`, apart
+/// from Hangul syllables which are worked out from where they are in their block
+const LINE_BREAK_CLASSES: &[(u32, u32, LineBreakClass)] = &[
+    (0x0000, 0x0008, CM),
+    (0x0009, 0x0009, BA),
+    (0x000a, 0x000a, LF),
+    (0x000b, 0x000c, BK),
+    (0x000d, 0x000d, CR),
+    (0x000e, 0x001f, CM),
+    (0x0020, 0x0020, SP),
+    (0x0021, 0x0021, EX),
+    (0x0022, 0x0022, QU),
+    (0x0024, 0x0024, PR),
+    (0x0025, 0x0025, PO),
+    (0x0027, 0x0027, QU),
+    (0x0028, 0x0028, OP),
+    (0x0029, 0x0029, CP),
+    (0x002b, 0x002b, PR),
+    (0x002c, 0x002c, IS),
+    (0x002d, 0x002d, HY),
+    (0x002e, 0x002e, IS),
+    (0x002f, 0x002f, SY),
+    (0x0030, 0x0039, NU),
+    (0x003a, 0x003b, IS),
+    (0x003f, 0x003f, EX),
+    (0x005b, 0x005b, OP),
+    (0x005c, 0x005c, PR),
+    (0x005d, 0x005d, CP),
+    (0x007b, 0x007b, OP),
+    (0x007c, 0x007c, BA),
+    (0x007d, 0x007d, CL),
+    (0x007f, 0x0084, CM),
+    (0x0085, 0x0085, NL),
+    (0x0086, 0x009f, CM),
+    (0x00a0, 0x00a0, GL),
+    (0x00a1, 0x00a1, OP),
+    (0x00a2, 0x00a2, PO),
+    (0x00a3, 0x00a5, PR),
+    (0x00ab, 0x00ab, QU),
+    (0x00ad, 0x00ad, BA),
+    (0x00b0, 0x00b0, PO),
+    (0x00b1, 0x00b1, PR),
+    (0x00b4, 0x00b4, BB),
+    (0x00bb, 0x00bb, QU),
+    (0x00bf, 0x00bf, OP),
+    (0x02c8, 0x02c8, BB),
+    (0x02cc, 0x02cc, BB),
+    (0x02df, 0x02df, BB),
+    (0x0300, 0x034e, CM),
+    (0x034f, 0x034f, GL),
+    (0x0350, 0x035b, CM),
+    (0x035c, 0x0362, GL),
+    (0x0363, 0x036f, CM),
+    (0x037e, 0x037e, IS),
+    (0x0483, 0x0489, CM),
+    (0x0589, 0x0589, IS),
+    (0x058a, 0x058a, BA),
+    (0x058f, 0x058f, PR),
+    (0x0591, 0x05bd, CM),
+    (0x05be, 0x05be, BA),
+    (0x05bf, 0x05bf, CM),
+    (0x05c1, 0x05c2, CM),
+    (0x05c4, 0x05c5, CM),
+    (0x05c6, 0x05c6, EX),
+    (0x05c7, 0x05c7, CM),
+    (0x05d0, 0x05f2, HL),
+    (0x0609, 0x060b, PO),
+    (0x060c, 0x060d, IS),
+    (0x0610, 0x061a, CM),
+    (0x061b, 0x061b, EX),
+    (0x061c, 0x061c, CM),
+    (0x061d, 0x061f, EX),
+    (0x064b, 0x065f, CM),
+    (0x0660, 0x0669, NU),
+    (0x066a, 0x066a, PO),
+    (0x066b, 0x066c, NU),
+    (0x0670, 0x0670, CM),
+    (0x06d4, 0x06d4, EX),
+    (0x06d6, 0x06dc, CM),
+    (0x06df, 0x06e4, CM),
+    (0x06e7, 0x06e8, CM),
+    (0x06ea, 0x06ed, CM),
+    (0x06f0, 0x06f9, NU),
+    (0x0711, 0x0711, CM),
+    (0x0730, 0x074a, CM),
+    (0x07a6, 0x07b0, CM),
+    (0x07c0, 0x07c9, NU),
+    (0x07eb, 0x07f3, CM),
+    (0x07f8, 0x07f8, IS),
+    (0x07f9, 0x07f9, EX),
+    (0x07fd, 0x07fd, CM),
+    (0x07fe, 0x07ff, PR),
+    (0x0816, 0x0819, CM),
+    (0x081b, 0x0823, CM),
+    (0x0825, 0x0827, CM),
+    (0x0829, 0x082d, CM),
+    (0x0859, 0x085b, CM),
+    (0x0898, 0x089f, CM),
+    (0x08ca, 0x08e1, CM),
+    (0x08e3, 0x0903, CM),
+    (0x093a, 0x093c, CM),
+    (0x093e, 0x094f, CM),
+    (0x0951, 0x0957, CM),
+    (0x0962, 0x0963, CM),
+    (0x0964, 0x0965, BA),
+    (0x0966, 0x096f, NU),
+    (0x0981, 0x0983, CM),
+    (0x09bc, 0x09bc, CM),
+    (0x09be, 0x09cd, CM),
+    (0x09d7, 0x09d7, CM),
+    (0x09e2, 0x09e3, CM),
+    (0x09e6, 0x09ef, NU),
+    (0x09f2, 0x09f3, PO),
+    (0x09f9, 0x09f9, PO),
+    (0x09fb, 0x09fb, PR),
+    (0x09fe, 0x0a03, CM),
+    (0x0a3c, 0x0a51, CM),
+    (0x0a66, 0x0a6f, NU),
+    (0x0a70, 0x0a71, CM),
+    (0x0a75, 0x0a75, CM),
+    (0x0a81, 0x0a83, CM),
+    (0x0abc, 0x0abc, CM),
+    (0x0abe, 0x0acd, CM),
+    (0x0ae2, 0x0ae3, CM),
+    (0x0ae6, 0x0aef, NU),
+    (0x0af1, 0x0af1, PR),
+    (0x0afa, 0x0b03, CM),
+    (0x0b3c, 0x0b3c, CM),
+    (0x0b3e, 0x0b57, CM),
+    (0x0b62, 0x0b63, CM),
+    (0x0b66, 0x0b6f, NU),
+    (0x0b82, 0x0b82, CM),
+    (0x0bbe, 0x0bcd, CM),
+    (0x0bd7, 0x0bd7, CM),
+    (0x0be6, 0x0bef, NU),
+    (0x0bf9, 0x0bf9, PR),
+    (0x0c00, 0x0c04, CM),
+    (0x0c3c, 0x0c3c, CM),
+    (0x0c3e, 0x0c56, CM),
+    (0x0c62, 0x0c63, CM),
+    (0x0c66, 0x0c6f, NU),
+    (0x0c77, 0x0c77, BB),
+    (0x0c81, 0x0c83, CM),
+    (0x0c84, 0x0c84, BB),
+    (0x0cbc, 0x0cbc, CM),
+    (0x0cbe, 0x0cd6, CM),
+    (0x0ce2, 0x0ce3, CM),
+    (0x0ce6, 0x0cef, NU),
+    (0x0cf3, 0x0d03, CM),
+    (0x0d3b, 0x0d3c, CM),
+    (0x0d3e, 0x0d4d, CM),
+    (0x0d57, 0x0d57, CM),
+    (0x0d62, 0x0d63, CM),
+    (0x0d66, 0x0d6f, NU),
+    (0x0d79, 0x0d79, PO),
+    (0x0d81, 0x0d83, CM),
+    (0x0dca, 0x0ddf, CM),
+    (0x0de6, 0x0def, NU),
+    (0x0df2, 0x0df3, CM),
+    (0x0e31, 0x0e31, CM),
+    (0x0e34, 0x0e3a, CM),
+    (0x0e3f, 0x0e3f, PR),
+    (0x0e47, 0x0e4e, CM),
+    (0x0e50, 0x0e59, NU),
+    (0x0e5a, 0x0e5b, BA),
+    (0x0eb1, 0x0eb1, CM),
+    (0x0eb4, 0x0ebc, CM),
+    (0x0ec8, 0x0ecd, CM),
+    (0x0ed0, 0x0ed9, NU),
+    (0x0f01, 0x0f04, BB),
+    (0x0f06, 0x0f07, BB),
+    (0x0f08, 0x0f08, GL),
+    (0x0f09, 0x0f0a, BB),
+    (0x0f0b, 0x0f0b, BA),
+    (0x0f0c, 0x0f0c, GL),
+    (0x0f0d, 0x0f11, EX),
+    (0x0f12, 0x0f12, GL),
+    (0x0f14, 0x0f14, EX),
+    (0x0f18, 0x0f19, CM),
+    (0x0f20, 0x0f29, NU),
+    (0x0f34, 0x0f34, BA),
+    (0x0f35, 0x0f35, CM),
+    (0x0f37, 0x0f37, CM),
+    (0x0f39, 0x0f39, CM),
+    (0x0f3a, 0x0f3a, OP),
+    (0x0f3b, 0x0f3b, CL),
+    (0x0f3c, 0x0f3c, OP),
+    (0x0f3d, 0x0f3d, CL),
+    (0x0f3e, 0x0f3f, CM),
+    (0x0f71, 0x0f7e, CM),
+    (0x0f7f, 0x0f7f, BA),
+    (0x0f80, 0x0f84, CM),
+    (0x0f85, 0x0f85, BA),
+    (0x0f86, 0x0f87, CM),
+    (0x0f8d, 0x0fbc, CM),
+    (0x0fbe, 0x0fbf, BA),
+    (0x0fc6, 0x0fc6, CM),
+    (0x0fd0, 0x0fd1, BB),
+    (0x0fd2, 0x0fd2, BA),
+    (0x0fd3, 0x0fd3, BB),
+    (0x0fd9, 0x0fda, GL),
+    (0x102b, 0x103e, CM),
+    (0x1040, 0x1049, NU),
+    (0x104a, 0x104b, BA),
+    (0x1056, 0x1059, CM),
+    (0x105e, 0x1060, CM),
+    (0x1062, 0x1064, CM),
+    (0x1067, 0x106d, CM),
+    (0x1071, 0x1074, CM),
+    (0x1082, 0x108d, CM),
+    (0x108f, 0x108f, CM),
+    (0x1090, 0x1099, NU),
+    (0x109a, 0x109d, CM),
+    (0x1100, 0x115f, JL),
+    (0x1160, 0x11a7, JV),
+    (0x11a8, 0x11ff, JT),
+    (0x135d, 0x135f, CM),
+    (0x1361, 0x1361, BA),
+    (0x1400, 0x1400, BA),
+    (0x1680, 0x1680, BA),
+    (0x169b, 0x169b, OP),
+    (0x169c, 0x169c, CL),
+    (0x16eb, 0x16ed, BA),
+    (0x1712, 0x1715, CM),
+    (0x1732, 0x1734, CM),
+    (0x1735, 0x1736, BA),
+    (0x1752, 0x1753, CM),
+    (0x1772, 0x1773, CM),
+    (0x17b4, 0x17d3, CM),
+    (0x17d4, 0x17d5, BA),
+    (0x17d6, 0x17d6, NS),
+    (0x17d8, 0x17d8, BA),
+    (0x17da, 0x17da, BA),
+    (0x17db, 0x17db, PR),
+    (0x17dd, 0x17dd, CM),
+    (0x17e0, 0x17e9, NU),
+    (0x1802, 0x1803, EX),
+    (0x1804, 0x1805, BA),
+    (0x1806, 0x1806, BB),
+    (0x1808, 0x1809, EX),
+    (0x180b, 0x180d, CM),
+    (0x180e, 0x180e, GL),
+    (0x180f, 0x180f, CM),
+    (0x1810, 0x1819, NU),
+    (0x1885, 0x1886, CM),
+    (0x18a9, 0x18a9, CM),
+    (0x1920, 0x193b, CM),
+    (0x1944, 0x1945, EX),
+    (0x1946, 0x194f, NU),
+    (0x19d0, 0x19d9, NU),
+    (0x1a17, 0x1a1b, CM),
+    (0x1a55, 0x1a7f, CM),
+    (0x1a80, 0x1a99, NU),
+    (0x1ab0, 0x1b04, CM),
+    (0x1b34, 0x1b44, CM),
+    (0x1b50, 0x1b59, NU),
+    (0x1b5a, 0x1b5b, BA),
+    (0x1b5d, 0x1b60, BA),
+    (0x1b6b, 0x1b73, CM),
+    (0x1b7d, 0x1b7e, BA),
+    (0x1b80, 0x1b82, CM),
+    (0x1ba1, 0x1bad, CM),
+    (0x1bb0, 0x1bb9, NU),
+    (0x1be6, 0x1bf3, CM),
+    (0x1c24, 0x1c37, CM),
+    (0x1c3b, 0x1c3f, BA),
+    (0x1c40, 0x1c49, NU),
+    (0x1c50, 0x1c59, NU),
+    (0x1c7e, 0x1c7f, BA),
+    (0x1cd0, 0x1cd2, CM),
+    (0x1cd4, 0x1ce8, CM),
+    (0x1ced, 0x1ced, CM),
+    (0x1cf4, 0x1cf4, CM),
+    (0x1cf7, 0x1cf9, CM),
+    (0x1dc0, 0x1dcc, CM),
+    (0x1dcd, 0x1dcd, GL),
+    (0x1dce, 0x1dfb, CM),
+    (0x1dfc, 0x1dfc, GL),
+    (0x1dfd, 0x1dff, CM),
+    (0x1ffd, 0x1ffd, BB),
+    (0x2000, 0x2006, BA),
+    (0x2007, 0x2007, GL),
+    (0x2008, 0x200a, BA),
+    (0x200b, 0x200b, ZW),
+    (0x200c, 0x200c, CM),
+    (0x200d, 0x200d, ZWJ),
+    (0x200e, 0x200f, CM),
+    (0x2010, 0x2010, BA),
+    (0x2011, 0x2011, GL),
+    (0x2012, 0x2013, BA),
+    (0x2014, 0x2014, B2),
+    (0x2018, 0x2019, QU),
+    (0x201a, 0x201a, OP),
+    (0x201b, 0x201d, QU),
+    (0x201e, 0x201e, OP),
+    (0x201f, 0x201f, QU),
+    (0x2024, 0x2026, IN),
+    (0x2027, 0x2027, BA),
+    (0x2028, 0x2029, BK),
+    (0x202a, 0x202e, CM),
+    (0x202f, 0x202f, GL),
+    (0x2030, 0x2037, PO),
+    (0x2039, 0x203a, QU),
+    (0x203c, 0x203d, NS),
+    (0x2044, 0x2044, IS),
+    (0x2045, 0x2045, OP),
+    (0x2046, 0x2046, CL),
+    (0x2047, 0x2049, NS),
+    (0x2056, 0x2056, BA),
+    (0x2057, 0x2057, PO),
+    (0x2058, 0x205b, BA),
+    (0x205d, 0x205f, BA),
+    (0x2060, 0x2060, WJ),
+    (0x2066, 0x206f, CM),
+    (0x207d, 0x207d, OP),
+    (0x207e, 0x207e, CL),
+    (0x208d, 0x208d, OP),
+    (0x208e, 0x208e, CL),
+    (0x20a0, 0x20a6, PR),
+    (0x20a7, 0x20a7, PO),
+    (0x20a8, 0x20b5, PR),
+    (0x20b6, 0x20b6, PO),
+    (0x20b7, 0x20ba, PR),
+    (0x20bb, 0x20bb, PO),
+    (0x20bc, 0x20bd, PR),
+    (0x20be, 0x20be, PO),
+    (0x20bf, 0x20bf, PR),
+    (0x20c0, 0x20c0, PO),
+    (0x20c1, 0x20cf, PR),
+    (0x20d0, 0x20f0, CM),
+    (0x2103, 0x2103, PO),
+    (0x2109, 0x2109, PO),
+    (0x2116, 0x2116, PR),
+    (0x2212, 0x2213, PR),
+    (0x22ef, 0x22ef, IN),
+    (0x2308, 0x2308, OP),
+    (0x2309, 0x2309, CL),
+    (0x230a, 0x230a, OP),
+    (0x230b, 0x230b, CL),
+    (0x231a, 0x231b, ID),
+    (0x2329, 0x2329, OP),
+    (0x232a, 0x232a, CL),
+    (0x23f0, 0x23f3, ID),
+    (0x2600, 0x2603, ID),
+    (0x2614, 0x2615, ID),
+    (0x2618, 0x2618, ID),
+    (0x261a, 0x261c, ID),
+    (0x261d, 0x261d, EB),
+    (0x261e, 0x261f, ID),
+    (0x2639, 0x263b, ID),
+    (0x2668, 0x2668, ID),
+    (0x267f, 0x267f, ID),
+    (0x26bd, 0x26c8, ID),
+    (0x26cd, 0x26cd, ID),
+    (0x26cf, 0x26d1, ID),
+    (0x26d3, 0x26d4, ID),
+    (0x26d8, 0x26d9, ID),
+    (0x26dc, 0x26dc, ID),
+    (0x26df, 0x26e1, ID),
+    (0x26ea, 0x26ea, ID),
+    (0x26f1, 0x26f5, ID),
+    (0x26f7, 0x26f8, ID),
+    (0x26f9, 0x26f9, EB),
+    (0x26fa, 0x26fa, ID),
+    (0x26fd, 0x2704, ID),
+    (0x2708, 0x2709, ID),
+    (0x270a, 0x270d, EB),
+    (0x275b, 0x2760, QU),
+    (0x2762, 0x2763, EX),
+    (0x2764, 0x2764, ID),
+    (0x2768, 0x2768, OP),
+    (0x2769, 0x2769, CL),
+    (0x276a, 0x276a, OP),
+    (0x276b, 0x276b, CL),
+    (0x276c, 0x276c, OP),
+    (0x276d, 0x276d, CL),
+    (0x276e, 0x276e, OP),
+    (0x276f, 0x276f, CL),
+    (0x2770, 0x2770, OP),
+    (0x2771, 0x2771, CL),
+    (0x2772, 0x2772, OP),
+    (0x2773, 0x2773, CL),
+    (0x2774, 0x2774, OP),
+    (0x2775, 0x2775, CL),
+    (0x27c5, 0x27c5, OP),
+    (0x27c6, 0x27c6, CL),
+    (0x27e6, 0x27e6, OP),
+    (0x27e7, 0x27e7, CL),
+    (0x27e8, 0x27e8, OP),
+    (0x27e9, 0x27e9, CL),
+    (0x27ea, 0x27ea, OP),
+    (0x27eb, 0x27eb, CL),
+    (0x27ec, 0x27ec, OP),
+    (0x27ed, 0x27ed, CL),
+    (0x27ee, 0x27ee, OP),
+    (0x27ef, 0x27ef, CL),
+    (0x2983, 0x2983, OP),
+    (0x2984, 0x2984, CL),
+    (0x2985, 0x2985, OP),
+    (0x2986, 0x2986, CL),
+    (0x2987, 0x2987, OP),
+    (0x2988, 0x2988, CL),
+    (0x2989, 0x2989, OP),
+    (0x298a, 0x298a, CL),
+    (0x298b, 0x298b, OP),
+    (0x298c, 0x298c, CL),
+    (0x298d, 0x298d, OP),
+    (0x298e, 0x298e, CL),
+    (0x298f, 0x298f, OP),
+    (0x2990, 0x2990, CL),
+    (0x2991, 0x2991, OP),
+    (0x2992, 0x2992, CL),
+    (0x2993, 0x2993, OP),
+    (0x2994, 0x2994, CL),
+    (0x2995, 0x2995, OP),
+    (0x2996, 0x2996, CL),
+    (0x2997, 0x2997, OP),
+    (0x2998, 0x2998, CL),
+    (0x29d8, 0x29d8, OP),
+    (0x29d9, 0x29d9, CL),
+    (0x29da, 0x29da, OP),
+    (0x29db, 0x29db, CL),
+    (0x29fc, 0x29fc, OP),
+    (0x29fd, 0x29fd, CL),
+    (0x2cef, 0x2cf1, CM),
+    (0x2cf9, 0x2cf9, EX),
+    (0x2cfa, 0x2cfc, BA),
+    (0x2cfe, 0x2cfe, EX),
+    (0x2cff, 0x2cff, BA),
+    (0x2d70, 0x2d70, BA),
+    (0x2d7f, 0x2d7f, CM),
+    (0x2de0, 0x2dff, CM),
+    (0x2e00, 0x2e0d, QU),
+    (0x2e0e, 0x2e15, BA),
+    (0x2e17, 0x2e17, BA),
+    (0x2e18, 0x2e18, OP),
+    (0x2e19, 0x2e19, BA),
+    (0x2e1c, 0x2e1d, QU),
+    (0x2e20, 0x2e21, QU),
+    (0x2e22, 0x2e22, OP),
+    (0x2e23, 0x2e23, CL),
+    (0x2e24, 0x2e24, OP),
+    (0x2e25, 0x2e25, CL),
+    (0x2e26, 0x2e26, OP),
+    (0x2e27, 0x2e27, CL),
+    (0x2e28, 0x2e28, OP),
+    (0x2e29, 0x2e29, CL),
+    (0x2e2a, 0x2e2d, BA),
+    (0x2e2e, 0x2e2e, EX),
+    (0x2e30, 0x2e31, BA),
+    (0x2e33, 0x2e34, BA),
+    (0x2e3a, 0x2e3b, B2),
+    (0x2e3c, 0x2e3e, BA),
+    (0x2e40, 0x2e41, BA),
+    (0x2e42, 0x2e42, OP),
+    (0x2e43, 0x2e4a, BA),
+    (0x2e4c, 0x2e4c, BA),
+    (0x2e4e, 0x2e4f, BA),
+    (0x2e53, 0x2e54, EX),
+    (0x2e55, 0x2e55, OP),
+    (0x2e56, 0x2e56, CL),
+    (0x2e57, 0x2e57, OP),
+    (0x2e58, 0x2e58, CL),
+    (0x2e59, 0x2e59, OP),
+    (0x2e5a, 0x2e5a, CL),
+    (0x2e5b, 0x2e5b, OP),
+    (0x2e5c, 0x2e5c, CL),
+    (0x2e5d, 0x2e5d, BA),
+    (0x2e80, 0x2ffb, ID),
+    (0x3000, 0x3000, BA),
+    (0x3001, 0x3002, CL),
+    (0x3003, 0x3004, ID),
+    (0x3005, 0x3005, NS),
+    (0x3006, 0x3007, ID),
+    (0x3008, 0x3008, OP),
+    (0x3009, 0x3009, CL),
+    (0x300a, 0x300a, OP),
+    (0x300b, 0x300b, CL),
+    (0x300c, 0x300c, OP),
+    (0x300d, 0x300d, CL),
+    (0x300e, 0x300e, OP),
+    (0x300f, 0x300f, CL),
+    (0x3010, 0x3010, OP),
+    (0x3011, 0x3011, CL),
+    (0x3012, 0x3013, ID),
+    (0x3014, 0x3014, OP),
+    (0x3015, 0x3015, CL),
+    (0x3016, 0x3016, OP),
+    (0x3017, 0x3017, CL),
+    (0x3018, 0x3018, OP),
+    (0x3019, 0x3019, CL),
+    (0x301a, 0x301a, OP),
+    (0x301b, 0x301b, CL),
+    (0x301c, 0x301c, NS),
+    (0x301d, 0x301d, OP),
+    (0x301e, 0x301f, CL),
+    (0x3020, 0x3029, ID),
+    (0x302a, 0x302f, CM),
+    (0x3030, 0x3034, ID),
+    (0x3035, 0x3035, CM),
+    (0x3036, 0x303a, ID),
+    (0x303b, 0x303c, NS),
+    (0x303d, 0x303f, ID),
+    (0x3041, 0x3041, NS),
+    (0x3042, 0x3042, ID),
+    (0x3043, 0x3043, NS),
+    (0x3044, 0x3044, ID),
+    (0x3045, 0x3045, NS),
+    (0x3046, 0x3046, ID),
+    (0x3047, 0x3047, NS),
+    (0x3048, 0x3048, ID),
+    (0x3049, 0x3049, NS),
+    (0x304a, 0x3062, ID),
+    (0x3063, 0x3063, NS),
+    (0x3064, 0x3082, ID),
+    (0x3083, 0x3083, NS),
+    (0x3084, 0x3084, ID),
+    (0x3085, 0x3085, NS),
+    (0x3086, 0x3086, ID),
+    (0x3087, 0x3087, NS),
+    (0x3088, 0x308d, ID),
+    (0x308e, 0x308e, NS),
+    (0x308f, 0x3094, ID),
+    (0x3095, 0x3096, NS),
+    (0x3099, 0x309a, CM),
+    (0x309b, 0x309e, NS),
+    (0x309f, 0x309f, ID),
+    (0x30a0, 0x30a1, NS),
+    (0x30a2, 0x30a2, ID),
+    (0x30a3, 0x30a3, NS),
+    (0x30a4, 0x30a4, ID),
+    (0x30a5, 0x30a5, NS),
+    (0x30a6, 0x30a6, ID),
+    (0x30a7, 0x30a7, NS),
+    (0x30a8, 0x30a8, ID),
+    (0x30a9, 0x30a9, NS),
+    (0x30aa, 0x30c2, ID),
+    (0x30c3, 0x30c3, NS),
+    (0x30c4, 0x30e2, ID),
+    (0x30e3, 0x30e3, NS),
+    (0x30e4, 0x30e4, ID),
+    (0x30e5, 0x30e5, NS),
+    (0x30e6, 0x30e6, ID),
+    (0x30e7, 0x30e7, NS),
+    (0x30e8, 0x30ed, ID),
+    (0x30ee, 0x30ee, NS),
+    (0x30ef, 0x30f4, ID),
+    (0x30f5, 0x30f6, NS),
+    (0x30f7, 0x30fa, ID),
+    (0x30fb, 0x30fe, NS),
+    (0x30ff, 0x31e3, ID),
+    (0x31f0, 0x31ff, NS),
+    (0x3200, 0x3247, ID),
+    (0x3250, 0x4dbf, ID),
+    (0x4e00, 0xa014, ID),
+    (0xa015, 0xa015, NS),
+    (0xa016, 0xa4c6, ID),
+    (0xa4fe, 0xa4ff, BA),
+    (0xa60d, 0xa60d, BA),
+    (0xa60e, 0xa60e, EX),
+    (0xa60f, 0xa60f, BA),
+    (0xa620, 0xa629, NU),
+    (0xa66f, 0xa672, CM),
+    (0xa674, 0xa67d, CM),
+    (0xa69e, 0xa69f, CM),
+    (0xa6f0, 0xa6f1, CM),
+    (0xa6f3, 0xa6f7, BA),
+    (0xa802, 0xa802, CM),
+    (0xa806, 0xa806, CM),
+    (0xa80b, 0xa80b, CM),
+    (0xa823, 0xa827, CM),
+    (0xa82c, 0xa82c, CM),
+    (0xa838, 0xa838, PO),
+    (0xa874, 0xa875, BB),
+    (0xa876, 0xa877, EX),
+    (0xa880, 0xa881, CM),
+    (0xa8b4, 0xa8c5, CM),
+    (0xa8ce, 0xa8cf, BA),
+    (0xa8d0, 0xa8d9, NU),
+    (0xa8e0, 0xa8f1, CM),
+    (0xa8fc, 0xa8fc, BB),
+    (0xa8ff, 0xa8ff, CM),
+    (0xa900, 0xa909, NU),
+    (0xa926, 0xa92d, CM),
+    (0xa92e, 0xa92f, BA),
+    (0xa947, 0xa953, CM),
+    (0xa960, 0xa97c, JL),
+    (0xa980, 0xa983, CM),
+    (0xa9b3, 0xa9c0, CM),
+    (0xa9c7, 0xa9c9, BA),
+    (0xa9d0, 0xa9d9, NU),
+    (0xa9e5, 0xa9e5, CM),
+    (0xa9f0, 0xa9f9, NU),
+    (0xaa29, 0xaa36, CM),
+    (0xaa43, 0xaa43, CM),
+    (0xaa4c, 0xaa4d, CM),
+    (0xaa50, 0xaa59, NU),
+    (0xaa5d, 0xaa5f, BA),
+    (0xaa7b, 0xaa7d, CM),
+    (0xaab0, 0xaab0, CM),
+    (0xaab2, 0xaab4, CM),
+    (0xaab7, 0xaab8, CM),
+    (0xaabe, 0xaabf, CM),
+    (0xaac1, 0xaac1, CM),
+    (0xaaeb, 0xaaef, CM),
+    (0xaaf0, 0xaaf1, BA),
+    (0xaaf5, 0xaaf6, CM),
+    (0xabe3, 0xabea, CM),
+    (0xabeb, 0xabeb, BA),
+    (0xabec, 0xabed, CM),
+    (0xabf0, 0xabf9, NU),
+    (0xd7b0, 0xd7c6, JV),
+    (0xd7cb, 0xd7fb, JT),
+    (0xf900, 0xfaff, ID),
+    (0xfb1d, 0xfb1d, HL),
+    (0xfb1e, 0xfb1e, CM),
+    (0xfb1f, 0xfb28, HL),
+    (0xfb2a, 0xfb4f, HL),
+    (0xfd3e, 0xfd3e, CL),
+    (0xfd3f, 0xfd3f, OP),
+    (0xfdfc, 0xfdfc, PO),
+    (0xfe00, 0xfe0f, CM),
+    (0xfe10, 0xfe10, IS),
+    (0xfe11, 0xfe12, CL),
+    (0xfe13, 0xfe14, IS),
+    (0xfe15, 0xfe16, EX),
+    (0xfe17, 0xfe17, OP),
+    (0xfe18, 0xfe18, CL),
+    (0xfe19, 0xfe19, IN),
+    (0xfe20, 0xfe2f, CM),
+    (0xfe30, 0xfe34, ID),
+    (0xfe35, 0xfe35, OP),
+    (0xfe36, 0xfe36, CL),
+    (0xfe37, 0xfe37, OP),
+    (0xfe38, 0xfe38, CL),
+    (0xfe39, 0xfe39, OP),
+    (0xfe3a, 0xfe3a, CL),
+    (0xfe3b, 0xfe3b, OP),
+    (0xfe3c, 0xfe3c, CL),
+    (0xfe3d, 0xfe3d, OP),
+    (0xfe3e, 0xfe3e, CL),
+    (0xfe3f, 0xfe3f, OP),
+    (0xfe40, 0xfe40, CL),
+    (0xfe41, 0xfe41, OP),
+    (0xfe42, 0xfe42, CL),
+    (0xfe43, 0xfe43, OP),
+    (0xfe44, 0xfe44, CL),
+    (0xfe45, 0xfe46, ID),
+    (0xfe47, 0xfe47, OP),
+    (0xfe48, 0xfe48, CL),
+    (0xfe49, 0xfe4f, ID),
+    (0xfe50, 0xfe50, CL),
+    (0xfe51, 0xfe51, ID),
+    (0xfe52, 0xfe52, CL),
+    (0xfe54, 0xfe55, NS),
+    (0xfe56, 0xfe57, EX),
+    (0xfe58, 0xfe58, ID),
+    (0xfe59, 0xfe59, OP),
+    (0xfe5a, 0xfe5a, CL),
+    (0xfe5b, 0xfe5b, OP),
+    (0xfe5c, 0xfe5c, CL),
+    (0xfe5d, 0xfe5d, OP),
+    (0xfe5e, 0xfe5e, CL),
+    (0xfe5f, 0xfe68, ID),
+    (0xfe69, 0xfe69, PR),
+    (0xfe6a, 0xfe6a, PO),
+    (0xfe6b, 0xfe6b, ID),
+    (0xfeff, 0xfeff, WJ),
+    (0xff01, 0xff01, EX),
+    (0xff02, 0xff03, ID),
+    (0xff04, 0xff04, PR),
+    (0xff05, 0xff05, PO),
+    (0xff06, 0xff07, ID),
+    (0xff08, 0xff08, OP),
+    (0xff09, 0xff09, CL),
+    (0xff0a, 0xff0b, ID),
+    (0xff0c, 0xff0c, CL),
+    (0xff0d, 0xff0d, ID),
+    (0xff0e, 0xff0e, CL),
+    (0xff0f, 0xff19, ID),
+    (0xff1a, 0xff1b, NS),
+    (0xff1c, 0xff1e, ID),
+    (0xff1f, 0xff1f, EX),
+    (0xff20, 0xff3a, ID),
+    (0xff3b, 0xff3b, OP),
+    (0xff3c, 0xff3c, ID),
+    (0xff3d, 0xff3d, CL),
+    (0xff3e, 0xff5a, ID),
+    (0xff5b, 0xff5b, OP),
+    (0xff5c, 0xff5c, ID),
+    (0xff5d, 0xff5d, CL),
+    (0xff5e, 0xff5e, ID),
+    (0xff5f, 0xff5f, OP),
+    (0xff60, 0xff61, CL),
+    (0xff62, 0xff62, OP),
+    (0xff63, 0xff64, CL),
+    (0xff65, 0xff65, NS),
+    (0xff66, 0xff66, ID),
+    (0xff67, 0xff70, NS),
+    (0xff71, 0xff9d, ID),
+    (0xff9e, 0xff9f, NS),
+    (0xffa0, 0xffdc, ID),
+    (0xffe0, 0xffe0, PO),
+    (0xffe1, 0xffe1, PR),
+    (0xffe2, 0xffe4, ID),
+    (0xffe5, 0xffe6, PR),
+    (0xfff9, 0xfffb, CM),
+    (0xfffc, 0xfffc, CB),
+    (0x10100, 0x10102, BA),
+    (0x101fd, 0x101fd, CM),
+    (0x102e0, 0x102e0, CM),
+    (0x10376, 0x1037a, CM),
+    (0x1039f, 0x1039f, BA),
+    (0x103d0, 0x103d0, BA),
+    (0x104a0, 0x104a9, NU),
+    (0x10857, 0x10857, BA),
+    (0x1091f, 0x1091f, BA),
+    (0x10a01, 0x10a0f, CM),
+    (0x10a38, 0x10a3f, CM),
+    (0x10a50, 0x10a57, BA),
+    (0x10ae5, 0x10ae6, CM),
+    (0x10af0, 0x10af5, BA),
+    (0x10af6, 0x10af6, IN),
+    (0x10b39, 0x10b3f, BA),
+    (0x10d24, 0x10d27, CM),
+    (0x10d30, 0x10d39, NU),
+    (0x10eab, 0x10eac, CM),
+    (0x10ead, 0x10ead, BA),
+    (0x10efd, 0x10eff, CM),
+    (0x10f46, 0x10f50, CM),
+    (0x10f82, 0x10f85, CM),
+    (0x11000, 0x11002, CM),
+    (0x11038, 0x11046, CM),
+    (0x11047, 0x11048, BA),
+    (0x11066, 0x1106f, NU),
+    (0x11070, 0x11070, CM),
+    (0x11073, 0x11074, CM),
+    (0x1107f, 0x11082, CM),
+    (0x110b0, 0x110ba, CM),
+    (0x110be, 0x110c1, BA),
+    (0x110c2, 0x110c2, CM),
+    (0x110f0, 0x110f9, NU),
+    (0x11100, 0x11102, CM),
+    (0x11127, 0x11134, CM),
+    (0x11136, 0x1113f, NU),
+    (0x11140, 0x11143, BA),
+    (0x11145, 0x11146, CM),
+    (0x11173, 0x11173, CM),
+    (0x11175, 0x11175, BB),
+    (0x11180, 0x11182, CM),
+    (0x111b3, 0x111c0, CM),
+    (0x111c5, 0x111c6, BA),
+    (0x111c8, 0x111c8, BA),
+    (0x111c9, 0x111cc, CM),
+    (0x111ce, 0x111cf, CM),
+    (0x111d0, 0x111d9, NU),
+    (0x111db, 0x111db, BB),
+    (0x111dd, 0x111df, BA),
+    (0x1122c, 0x11237, CM),
+    (0x11238, 0x11239, BA),
+    (0x1123b, 0x1123c, BA),
+    (0x1123e, 0x1123e, CM),
+    (0x11241, 0x11241, CM),
+    (0x112a9, 0x112a9, BA),
+    (0x112df, 0x112ea, CM),
+    (0x112f0, 0x112f9, NU),
+    (0x11300, 0x11303, CM),
+    (0x1133b, 0x1133c, CM),
+    (0x1133e, 0x1134d, CM),
+    (0x11357, 0x11357, CM),
+    (0x11362, 0x11374, CM),
+    (0x11435, 0x11446, CM),
+    (0x1144b, 0x1144e, BA),
+    (0x11450, 0x11459, NU),
+    (0x1145a, 0x1145b, BA),
+    (0x1145e, 0x1145e, CM),
+    (0x114b0, 0x114c3, CM),
+    (0x114d0, 0x114d9, NU),
+    (0x115af, 0x115c0, CM),
+    (0x115c1, 0x115c1, BB),
+    (0x115c2, 0x115c3, BA),
+    (0x115c4, 0x115c5, EX),
+    (0x115c9, 0x115d7, BA),
+    (0x115dc, 0x115dd, CM),
+    (0x11630, 0x11640, CM),
+    (0x11641, 0x11642, BA),
+    (0x11650, 0x11659, NU),
+    (0x11660, 0x1166c, BB),
+    (0x116ab, 0x116b7, CM),
+    (0x116c0, 0x116c9, NU),
+    (0x1171d, 0x1172b, CM),
+    (0x11730, 0x11739, NU),
+    (0x1173c, 0x1173e, BA),
+    (0x1182c, 0x1183a, CM),
+    (0x118e0, 0x118e9, NU),
+    (0x11930, 0x1193e, CM),
+    (0x11940, 0x11940, CM),
+    (0x11942, 0x11943, CM),
+    (0x11944, 0x11946, BA),
+    (0x11950, 0x11959, NU),
+    (0x119d1, 0x119e0, CM),
+    (0x119e2, 0x119e2, BB),
+    (0x119e4, 0x119e4, CM),
+    (0x11a01, 0x11a0a, CM),
+    (0x11a33, 0x11a39, CM),
+    (0x11a3b, 0x11a3e, CM),
+    (0x11a3f, 0x11a3f, BB),
+    (0x11a41, 0x11a44, BA),
+    (0x11a45, 0x11a45, BB),
+    (0x11a47, 0x11a47, CM),
+    (0x11a51, 0x11a5b, CM),
+    (0x11a8a, 0x11a99, CM),
+    (0x11a9a, 0x11a9c, BA),
+    (0x11a9e, 0x11aa0, BB),
+    (0x11aa1, 0x11aa2, BA),
+    (0x11b00, 0x11b09, BB),
+    (0x11c2f, 0x11c3f, CM),
+    (0x11c41, 0x11c45, BA),
+    (0x11c50, 0x11c59, NU),
+    (0x11c70, 0x11c70, BB),
+    (0x11c71, 0x11c71, EX),
+    (0x11c92, 0x11cb6, CM),
+    (0x11d31, 0x11d45, CM),
+    (0x11d47, 0x11d47, CM),
+    (0x11d50, 0x11d59, NU),
+    (0x11d8a, 0x11d97, CM),
+    (0x11da0, 0x11da9, NU),
+    (0x11ef3, 0x11ef6, CM),
+    (0x11f00, 0x11f01, CM),
+    (0x11f03, 0x11f03, CM),
+    (0x11f34, 0x11f42, CM),
+    (0x11f43, 0x11f44, BA),
+    (0x11f45, 0x11f4f, ID),
+    (0x11f50, 0x11f59, NU),
+    (0x11fdd, 0x11fe0, PO),
+    (0x11fff, 0x11fff, BA),
+    (0x12470, 0x12474, BA),
+    (0x13258, 0x1325a, OP),
+    (0x1325b, 0x1325d, CL),
+    (0x13282, 0x13282, CL),
+    (0x13286, 0x13286, OP),
+    (0x13287, 0x13287, CL),
+    (0x13288, 0x13288, OP),
+    (0x13289, 0x13289, CL),
+    (0x13379, 0x13379, OP),
+    (0x1337a, 0x1337b, CL),
+    (0x13430, 0x13436, GL),
+    (0x13437, 0x13437, OP),
+    (0x13438, 0x13438, CL),
+    (0x13439, 0x1343b, GL),
+    (0x1343c, 0x1343c, OP),
+    (0x1343d, 0x1343d, CL),
+    (0x1343e, 0x1343e, OP),
+    (0x1343f, 0x1343f, CL),
+    (0x13440, 0x13440, CM),
+    (0x13447, 0x13455, CM),
+    (0x145ce, 0x145ce, OP),
+    (0x145cf, 0x145cf, CL),
+    (0x16a60, 0x16a69, NU),
+    (0x16a6e, 0x16a6f, BA),
+    (0x16ac0, 0x16ac9, NU),
+    (0x16af0, 0x16af4, CM),
+    (0x16af5, 0x16af5, BA),
+    (0x16b30, 0x16b36, CM),
+    (0x16b37, 0x16b39, BA),
+    (0x16b44, 0x16b44, BA),
+    (0x16b50, 0x16b59, NU),
+    (0x16e97, 0x16e98, BA),
+    (0x16f4f, 0x16f4f, CM),
+    (0x16f51, 0x16f92, CM),
+    (0x16fe0, 0x16fe3, NS),
+    (0x16fe4, 0x16fe4, GL),
+    (0x16ff0, 0x16ff1, CM),
+    (0x17000, 0x18aff, ID),
+    (0x18d00, 0x18d08, ID),
+    (0x1b000, 0x1b122, ID),
+    (0x1b132, 0x1b167, NS),
+    (0x1b170, 0x1b2fb, ID),
+    (0x1bc9d, 0x1bc9e, CM),
+    (0x1bc9f, 0x1bc9f, BA),
+    (0x1bca0, 0x1cf46, CM),
+    (0x1d165, 0x1d169, CM),
+    (0x1d16d, 0x1d182, CM),
+    (0x1d185, 0x1d18b, CM),
+    (0x1d1aa, 0x1d1ad, CM),
+    (0x1d242, 0x1d244, CM),
+    (0x1d7ce, 0x1d7ff, NU),
+    (0x1da00, 0x1da36, CM),
+    (0x1da3b, 0x1da6c, CM),
+    (0x1da75, 0x1da75, CM),
+    (0x1da84, 0x1da84, CM),
+    (0x1da87, 0x1da8a, BA),
+    (0x1da9b, 0x1daaf, CM),
+    (0x1e000, 0x1e02a, CM),
+    (0x1e08f, 0x1e08f, CM),
+    (0x1e130, 0x1e136, CM),
+    (0x1e140, 0x1e149, NU),
+    (0x1e2ae, 0x1e2ae, CM),
+    (0x1e2ec, 0x1e2ef, CM),
+    (0x1e2f0, 0x1e2f9, NU),
+    (0x1e2ff, 0x1e2ff, PR),
+    (0x1e4ec, 0x1e4ef, CM),
+    (0x1e4f0, 0x1e4f9, NU),
+    (0x1e8d0, 0x1e8d6, CM),
+    (0x1e944, 0x1e94a, CM),
+    (0x1e950, 0x1e959, NU),
+    (0x1e95e, 0x1e95f, OP),
+    (0x1ecac, 0x1ecac, PO),
+    (0x1ecb0, 0x1ecb0, PO),
+    (0x1f000, 0x1f0ff, ID),
+    (0x1f10d, 0x1f10f, ID),
+    (0x1f16d, 0x1f16f, ID),
+    (0x1f1ad, 0x1f1e5, ID),
+    (0x1f1e6, 0x1f1ff, RI),
+    (0x1f200, 0x1f384, ID),
+    (0x1f385, 0x1f385, EB),
+    (0x1f386, 0x1f39b, ID),
+    (0x1f39e, 0x1f3b4, ID),
+    (0x1f3b7, 0x1f3bb, ID),
+    (0x1f3bd, 0x1f3c1, ID),
+    (0x1f3c2, 0x1f3c4, EB),
+    (0x1f3c5, 0x1f3c6, ID),
+    (0x1f3c7, 0x1f3c7, EB),
+    (0x1f3c8, 0x1f3c9, ID),
+    (0x1f3ca, 0x1f3cc, EB),
+    (0x1f3cd, 0x1f3fa, ID),
+    (0x1f3fb, 0x1f3ff, EM),
+    (0x1f400, 0x1f441, ID),
+    (0x1f442, 0x1f443, EB),
+    (0x1f444, 0x1f445, ID),
+    (0x1f446, 0x1f450, EB),
+    (0x1f451, 0x1f465, ID),
+    (0x1f466, 0x1f478, EB),
+    (0x1f479, 0x1f47b, ID),
+    (0x1f47c, 0x1f47c, EB),
+    (0x1f47d, 0x1f480, ID),
+    (0x1f481, 0x1f483, EB),
+    (0x1f484, 0x1f484, ID),
+    (0x1f485, 0x1f487, EB),
+    (0x1f488, 0x1f48e, ID),
+    (0x1f48f, 0x1f48f, EB),
+    (0x1f490, 0x1f490, ID),
+    (0x1f491, 0x1f491, EB),
+    (0x1f492, 0x1f49f, ID),
+    (0x1f4a1, 0x1f4a1, ID),
+    (0x1f4a3, 0x1f4a3, ID),
+    (0x1f4a5, 0x1f4a9, ID),
+    (0x1f4aa, 0x1f4aa, EB),
+    (0x1f4ab, 0x1f4ae, ID),
+    (0x1f4b0, 0x1f4b0, ID),
+    (0x1f4b3, 0x1f4ff, ID),
+    (0x1f507, 0x1f516, ID),
+    (0x1f525, 0x1f531, ID),
+    (0x1f54a, 0x1f573, ID),
+    (0x1f574, 0x1f575, EB),
+    (0x1f576, 0x1f579, ID),
+    (0x1f57a, 0x1f57a, EB),
+    (0x1f57b, 0x1f58f, ID),
+    (0x1f590, 0x1f590, EB),
+    (0x1f591, 0x1f594, ID),
+    (0x1f595, 0x1f596, EB),
+    (0x1f597, 0x1f5d3, ID),
+    (0x1f5dc, 0x1f5f3, ID),
+    (0x1f5fa, 0x1f644, ID),
+    (0x1f645, 0x1f647, EB),
+    (0x1f648, 0x1f64a, ID),
+    (0x1f64b, 0x1f64f, EB),
+    (0x1f676, 0x1f678, QU),
+    (0x1f679, 0x1f67b, NS),
+    (0x1f680, 0x1f6a2, ID),
+    (0x1f6a3, 0x1f6a3, EB),
+    (0x1f6a4, 0x1f6b3, ID),
+    (0x1f6b4, 0x1f6b6, EB),
+    (0x1f6b7, 0x1f6bf, ID),
+    (0x1f6c0, 0x1f6c0, EB),
+    (0x1f6c1, 0x1f6cb, ID),
+    (0x1f6cc, 0x1f6cc, EB),
+    (0x1f6cd, 0x1f6ff, ID),
+    (0x1f774, 0x1f77f, ID),
+    (0x1f7d5, 0x1f7ff, ID),
+    (0x1f80c, 0x1f80f, ID),
+    (0x1f848, 0x1f84f, ID),
+    (0x1f85a, 0x1f85f, ID),
+    (0x1f888, 0x1f88f, ID),
+    (0x1f8ae, 0x1f8ff, ID),
+    (0x1f90c, 0x1f90c, EB),
+    (0x1f90d, 0x1f90e, ID),
+    (0x1f90f, 0x1f90f, EB),
+    (0x1f910, 0x1f917, ID),
+    (0x1f918, 0x1f91f, EB),
+    (0x1f920, 0x1f925, ID),
+    (0x1f926, 0x1f926, EB),
+    (0x1f927, 0x1f92f, ID),
+    (0x1f930, 0x1f939, EB),
+    (0x1f93a, 0x1f93b, ID),
+    (0x1f93c, 0x1f93e, EB),
+    (0x1f93f, 0x1f976, ID),
+    (0x1f977, 0x1f977, EB),
+    (0x1f978, 0x1f9b4, ID),
+    (0x1f9b5, 0x1f9b6, EB),
+    (0x1f9b7, 0x1f9b7, ID),
+    (0x1f9b8, 0x1f9b9, EB),
+    (0x1f9ba, 0x1f9ba, ID),
+    (0x1f9bb, 0x1f9bb, EB),
+    (0x1f9bc, 0x1f9cc, ID),
+    (0x1f9cd, 0x1f9cf, EB),
+    (0x1f9d0, 0x1f9d0, ID),
+    (0x1f9d1, 0x1f9dd, EB),
+    (0x1f9de, 0x1f9ff, ID),
+    (0x1fa54, 0x1fac2, ID),
+    (0x1fac3, 0x1fac5, EB),
+    (0x1fac6, 0x1faef, ID),
+    (0x1faf0, 0x1faf8, EB),
+    (0x1faf9, 0x1faff, ID),
+    (0x1fbf0, 0x1fbf9, NU),
+    (0x1fc00, 0x3fffd, ID),
+    (0xe0001, 0xe01ef, CM)
+];
+
+pub fn line_break_class(c: char) -> LineBreakClass {
+    match c as u32 {
+        // Hangul syllables go in blocks of 28 with a leading and vowel jamo first
+        c @ 0xac00..=0xd7a3 => if (c - 0xac00) % 28 == 0 { H2 } else { H3 },
+        _ => find_range(LINE_BREAK_CLASSES, c).unwrap_or(AL)
+    }
+}
+
+/// Whether a line may or must break at a place in the text
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Break {
+    /// The line has to end here, like after a newline
+    Mandatory,
+    /// The line can end here if it doesn't fit
+    Allowed
+}
+
+/// The places in `text` a line can start, as byte offsets, and whether a line has to start there.
+/// The end of the text is always a mandatory break
+pub fn line_breaks(text: &str) -> Vec<(usize, Break)> {
+    let mut breaks = Vec::new();
+    // the class of the last character, the class of the one before it, and the last class that
+    // wasn't a space. Combining marks take the class of the character they are on
+    let mut last: Option<LineBreakClass> = None;
+    let mut before_last: Option<LineBreakClass> = None;
+    let mut before_spaces: Option<LineBreakClass> = None;
+    // the class of the last character as it is, without combining marks being merged
+    let mut last_raw = AL;
+    let mut regional_indicators = 0;
+
+    for (offset, c) in text.char_indices() {
+        let class = line_break_class(c);
+        let previous = match last {
+            Some(previous) => previous,
+            None => {
+                // LB2: never break at the start of the text, and LB10: marks there are letters
+                let class = if class == CM || class == ZWJ { AL } else { class };
+                last = Some(class);
+                last_raw = class;
+                before_spaces = if class == SP { None } else { Some(class) };
+                regional_indicators = (class == RI) as usize;
+                continue;
+            }
+        };
+
+        // LB9: marks go with the character they are on, unless it's a space or a break
+        let attached = (class == CM || class == ZWJ) && !matches!(previous, BK | CR | LF | NL | SP | ZW);
+        let current = if class == CM || class == ZWJ { AL } else { class };
+        let opportunity = match (last_raw, class) {
+            // LB4 and LB5: breaks after newlines, keeping CR LF together
+            (CR, LF) => None,
+            (BK, _) | (CR, _) | (LF, _) | (NL, _) => Some(Break::Mandatory),
+            // LB6 and LB7: no breaks before newlines or spaces
+            (_, BK) | (_, CR) | (_, LF) | (_, NL) | (_, SP) | (_, ZW) => None,
+            // LB8: a break after a zero width space, even with spaces after it
+            _ if before_spaces == Some(ZW) => Some(Break::Allowed),
+            // LB8a: nothing breaks after a zero width joiner
+            (ZWJ, _) => None,
+            _ if attached => None,
+            _ => if can_break(before_last, previous, before_spaces, current, c, regional_indicators) {
+                Some(Break::Allowed)
+            } else {
+                None
+            }
+        };
+        if let Some(opportunity) = opportunity {
+            breaks.push((offset, opportunity));
+        }
+
+        last_raw = class;
+        if !attached {
+            before_last = last;
+            last = Some(current);
+            if current != SP {
+                before_spaces = Some(current);
+            }
+            regional_indicators = if current == RI { regional_indicators + 1 } else { 0 };
+        }
+    }
+
+    // LB3: the text always ends with a break
+    if !text.is_empty() {
+        breaks.push((text.len(), Break::Mandatory));
+    }
+    breaks
+}
+
+/// Rules LB11 to LB31, whether a line can break between two characters that aren't newlines,
+/// spaces or marks. `before_spaces` is the last class before any spaces in front of `current`
+fn can_break(before_last: Option<LineBreakClass>, last: LineBreakClass, before_spaces: Option<LineBreakClass>,
+             current: LineBreakClass, c: char, regional_indicators: usize) -> bool {
+    match (last, current) {
+        // LB11 and LB12: word joiners and glue hold the characters on either side together
+        (WJ, _) | (_, WJ) | (GL, _) => return false,
+        (SP, GL) | (BA, GL) | (HY, GL) => {},
+        (_, GL) => return false,
+        // LB13: no break before closing punctuation
+        (_, CL) | (_, CP) | (_, EX) | (_, IS) | (_, SY) => return false,
+        _ => {}
+    }
+    // LB14 to LB17, which hold even with spaces in between
+    match (before_spaces, current) {
+        (Some(OP), _) | (Some(QU), OP) | (Some(CL), NS) | (Some(CP), NS) | (Some(B2), B2) => return false,
+        _ => {}
+    }
+    match (last, current) {
+        // LB18: a break after spaces
+        (SP, _) => true,
+        // LB19 and LB20: quotes hold together, contingent breaks break
+        (QU, _) | (_, QU) => false,
+        (CB, _) | (_, CB) => true,
+        // LB21: no breaks before hyphens and small kana, or after a break-before character
+        (_, BA) | (_, HY) | (_, NS) | (BB, _) => false,
+        // LB21a and LB21b: Hebrew with a hyphen, and a slash before Hebrew
+        (HY, _) | (BA, _) if before_last == Some(HL) => false,
+        (SY, HL) => false,
+        // LB22: no break before leaders like the ellipsis
+        (_, IN) => false,
+        // LB23 to LB25: numbers with the letters, prefixes and postfixes around them
+        (AL, NU) | (HL, NU) | (NU, AL) | (NU, HL) => false,
+        (PR, ID) | (PR, EB) | (PR, EM) | (ID, PO) | (EB, PO) | (EM, PO) => false,
+        (PR, AL) | (PR, HL) | (PO, AL) | (PO, HL) | (AL, PR) | (AL, PO) | (HL, PR) | (HL, PO) => false,
+        (CL, PO) | (CP, PO) | (CL, PR) | (CP, PR) | (NU, PO) | (NU, PR) | (PO, OP) | (PO, NU) |
+        (PR, OP) | (PR, NU) | (HY, NU) | (IS, NU) | (NU, NU) | (SY, NU) => false,
+        // LB26 and LB27: Hangul syllables stay whole
+        (JL, JL) | (JL, JV) | (JL, H2) | (JL, H3) | (JV, JV) | (JV, JT) | (H2, JV) | (H2, JT) |
+        (JT, JT) | (H3, JT) => false,
+        (JL, PO) | (JV, PO) | (JT, PO) | (H2, PO) | (H3, PO) |
+        (PR, JL) | (PR, JV) | (PR, JT) | (PR, H2) | (PR, H3) => false,
+        // LB28 and LB29: words, and words after a full stop
+        (AL, AL) | (AL, HL) | (HL, AL) | (HL, HL) | (IS, AL) | (IS, HL) => false,
+        // LB30: parentheses hold on to the words on either side, except East Asian ones
+        (AL, OP) | (HL, OP) | (NU, OP) => is_wide(c),
+        (CP, AL) | (CP, HL) | (CP, NU) => false,
+        // LB30a and LB30b: flags are pairs of regional indicators, and emoji keep their modifiers
+        (RI, RI) => regional_indicators.is_multiple_of(2),
+        (EB, EM) => false,
+        // LB31: break everywhere else
+        _ => true
+    }
+}
+
+/// Whether an opening bracket is one of the full width East Asian ones, which LB30 lets a line
+/// break before
+fn is_wide(c: char) -> bool {
+    matches!(c as u32, 0x2e80..=0xa4cf | 0xf900..=0xfaff | 0xfe30..=0xfe4f | 0xff00..=0xff60 | 0xffe0..=0xffe6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<&str> {
+        let mut start = 0;
+        line_breaks(text).into_iter().map(|(end, _)| {
+            let line = &text[start..end];
+            start = end;
+            line
+        }).collect()
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(line_break_class('a'), AL);
+        assert_eq!(line_break_class(' '), SP);
+        assert_eq!(line_break_class('\n'), LF);
+        assert_eq!(line_break_class('('), OP);
+        assert_eq!(line_break_class(')'), CP);
+        assert_eq!(line_break_class('-'), HY);
+        assert_eq!(line_break_class('5'), NU);
+        assert_eq!(line_break_class('\u{a0}'), GL);
+        assert_eq!(line_break_class('\u{301}'), CM);
+        assert_eq!(line_break_class('\u{4e2d}'), ID);
+        assert_eq!(line_break_class('\u{5d0}'), HL);
+        assert_eq!(line_break_class('\u{ac00}'), H2);
+        assert_eq!(line_break_class('\u{ac01}'), H3);
+        assert_eq!(line_break_class('\u{e01}'), AL);
+    }
+
+    #[test]
+    fn breaks() {
+        assert_eq!(lines("The quick  brown fox"), vec!["The ", "quick  ", "brown ", "fox"]);
+        assert_eq!(line_breaks("a b"), vec![(2, Break::Allowed), (3, Break::Mandatory)]);
+        assert!(line_breaks("").is_empty());
+
+        // newlines force a break, and CR LF is a single one
+        assert_eq!(line_breaks("a\r\nb\nc"), vec![(3, Break::Mandatory), (5, Break::Mandatory), (6, Break::Mandatory)]);
+
+        // hyphens break after, punctuation and numbers stay with their words
+        assert_eq!(lines("well-known (see p. 12, $5.00)!"), vec!["well-", "known ", "(see ", "p. ", "12, ", "$5.00)!"]);
+        // no-break spaces and word joiners hold words together
+        assert_eq!(lines("a\u{a0}b c\u{2060}d"), vec!["a\u{a0}b ", "c\u{2060}d"]);
+        // accents don't split a word
+        assert_eq!(lines("e\u{301}t\u{e9}"), vec!["e\u{301}t\u{e9}"]);
+
+        // ideographs can be broken between, but not before closing punctuation
+        assert_eq!(lines("\u{4e2d}\u{6587}\u{3002}"), vec!["\u{4e2d}", "\u{6587}\u{3002}"]);
+        // a flag is two regional indicators
+        assert_eq!(lines("\u{1f1e8}\u{1f1e6}\u{1f1fa}\u{1f1f8}"), vec!["\u{1f1e8}\u{1f1e6}", "\u{1f1fa}\u{1f1f8}"]);
+    }
+}
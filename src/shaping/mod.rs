@@ -0,0 +1,572 @@
+//! Turns strings into positioned glyphs, using the font's 'cmap' to find glyphs, 'GSUB' to
+//! substitute them and 'GPOS' or 'kern' to position them
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use truetype_loader::*;
+use CharMap;
+
+mod script;
+mod arabic;
+mod indic;
+mod bidi;
+mod line_break;
+mod layout;
+pub use self::script::Script;
+pub use self::bidi::{paragraph_direction, Direction};
+pub use self::line_break::{line_breaks, Break};
+pub use self::layout::{layout_paragraph, Alignment, LayoutOptions, Line, LineGlyph, ParagraphLayout};
+use self::bidi::BidiParagraph;
+
+/// An OpenType feature to turn on or off while shaping. A value of 0 turns the feature off, 1 turns
+/// it on, and larger values pick an alternate for features that have them, counting from 1
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Feature {
+    pub tag: TableTag,
+    pub value: u32
+}
+
+impl Feature {
+    pub fn new(tag: &[u8; 4], value: u32) -> Feature {
+        Feature { tag: TableTag::new(tag), value }
+    }
+}
+
+/// A glyph in a shaped run, in pixels with y going up. The advances move the pen to where the next
+/// glyph goes and the offsets move this glyph without moving the pen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    pub glyph_index: usize,
+    /// The byte offset in the text of the first character this glyph came from. Glyphs made from
+    /// the same characters, like a base and the marks on it, can share a cluster
+    pub cluster: usize,
+    pub x_advance: f32,
+    pub y_advance: f32,
+    pub x_offset: f32,
+    pub y_offset: f32
+}
+
+/// A run of glyphs in a single script and direction, from a paragraph shaped by `shape_paragraph`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphRun {
+    /// The bytes of the paragraph the run was shaped from
+    pub range: Range<usize>,
+    pub script: Script,
+    /// The bidi embedding level of the run, which is odd for right to left text
+    pub level: u8,
+    /// The glyphs in visual order, with clusters counting from the start of the paragraph
+    pub glyphs: Vec<PositionedGlyph>
+}
+
+impl GlyphRun {
+    pub fn direction(&self) -> Direction {
+        Direction::of_level(self.level)
+    }
+}
+
+/// The 'GSUB' features that are on unless they are turned off, in stages. The lookups in a stage
+/// are applied in the order they are in the font, and each stage is finished before the next
+const DEFAULT_SUBSTITUTION_STAGES: &[&[&[u8; 4]]] = &[
+    &[b"ccmp", b"locl", b"rlig", b"liga", b"clig", b"calt"]
+];
+/// Joining scripts pick the positional forms first, then the ligatures that need them
+const JOINING_SUBSTITUTION_STAGES: &[&[&[u8; 4]]] = &[
+    &[b"ccmp", b"locl"],
+    &[b"isol"],
+    &[b"fina"],
+    &[b"medi"],
+    &[b"init"],
+    &[b"rlig"],
+    &[b"calt"],
+    &[b"liga", b"clig", b"mset"]
+];
+const DEFAULT_POSITIONING_STAGES: &[&[&[u8; 4]]] = &[
+    &[b"kern", b"mark", b"mkmk", b"curs", b"dist", b"abvm", b"blwm"]
+];
+
+/// Fonts without a 'head' table can still be shaped, they just get the most common em size
+const FALLBACK_UNITS_PER_EM: u16 = 1000;
+
+/// The mask bit every glyph has, for features that apply everywhere. Features that only apply to
+/// some glyphs, like the joining forms, get a bit each after it
+const GLOBAL_MASK: u32 = 1;
+
+fn feature_mask(tag: TableTag) -> u32 {
+    let masked = arabic::FORM_FEATURES.iter().chain(indic::MASKED_FEATURES.iter());
+    match masked.clone().position(|&t| TableTag::new(t) == tag) {
+        Some(i) => GLOBAL_MASK << (i + 1),
+        None => GLOBAL_MASK
+    }
+}
+
+/// Find the value for a character in a table of ranges sorted by their first character
+fn find_range<T: Copy>(ranges: &[(u32, u32, T)], c: char) -> Option<T> {
+    use std::cmp::Ordering;
+    let c = c as u32;
+    ranges.binary_search_by(|&(first, last, _)| {
+        if last < c { Ordering::Less } else if first > c { Ordering::Greater } else { Ordering::Equal }
+    }).ok().map(|i| ranges[i].2)
+}
+
+/// Nonspacing and enclosing marks, Unicode's general categories Mn and Me, in the scripts the
+/// shaper knows about. Sorted by their first character
+const MARK_RANGES: &[(u32, u32)] = &[
+    (0x0300, 0x036f), (0x0483, 0x0489), (0x0591, 0x05bd), (0x05bf, 0x05bf), (0x05c1, 0x05c2), (0x05c4, 0x05c5),
+    (0x05c7, 0x05c7), (0x0610, 0x061a), (0x064b, 0x065f), (0x0670, 0x0670), (0x06d6, 0x06dc), (0x06df, 0x06e4),
+    (0x06e7, 0x06e8), (0x06ea, 0x06ed), (0x0711, 0x0711), (0x0730, 0x074a), (0x07a6, 0x07b0), (0x07eb, 0x07f3),
+    (0x07fd, 0x07fd), (0x0816, 0x0819), (0x081b, 0x0823), (0x0825, 0x0827), (0x0829, 0x082d), (0x0859, 0x085b),
+    (0x0898, 0x089f), (0x08ca, 0x08e1), (0x08e3, 0x0902), (0x093a, 0x093a), (0x093c, 0x093c), (0x0941, 0x0948),
+    (0x094d, 0x094d), (0x0951, 0x0957), (0x0962, 0x0963), (0x0981, 0x0981), (0x09bc, 0x09bc), (0x09c1, 0x09c4),
+    (0x09cd, 0x09cd), (0x09e2, 0x09e3), (0x09fe, 0x09fe), (0x0a01, 0x0a02), (0x0a3c, 0x0a3c), (0x0a41, 0x0a42),
+    (0x0a47, 0x0a48), (0x0a4b, 0x0a4d), (0x0a51, 0x0a51), (0x0a70, 0x0a71), (0x0a75, 0x0a75), (0x0a81, 0x0a82),
+    (0x0abc, 0x0abc), (0x0ac1, 0x0ac5), (0x0ac7, 0x0ac8), (0x0acd, 0x0acd), (0x0ae2, 0x0ae3), (0x0afa, 0x0aff),
+    (0x0b01, 0x0b01), (0x0b3c, 0x0b3c), (0x0b3f, 0x0b3f), (0x0b41, 0x0b44), (0x0b4d, 0x0b4d), (0x0b55, 0x0b56),
+    (0x0b62, 0x0b63), (0x0b82, 0x0b82), (0x0bc0, 0x0bc0), (0x0bcd, 0x0bcd), (0x0c00, 0x0c00), (0x0c04, 0x0c04),
+    (0x0c3c, 0x0c3c), (0x0c3e, 0x0c40), (0x0c46, 0x0c48), (0x0c4a, 0x0c4d), (0x0c55, 0x0c56), (0x0c62, 0x0c63),
+    (0x0c81, 0x0c81), (0x0cbc, 0x0cbc), (0x0cbf, 0x0cbf), (0x0cc6, 0x0cc6), (0x0ccc, 0x0ccd), (0x0ce2, 0x0ce3),
+    (0x0d00, 0x0d01), (0x0d3b, 0x0d3c), (0x0d41, 0x0d44), (0x0d4d, 0x0d4d), (0x0d62, 0x0d63), (0x0d81, 0x0d81),
+    (0x0dca, 0x0dca), (0x0dd2, 0x0dd4), (0x0dd6, 0x0dd6), (0x0e31, 0x0e31), (0x0e34, 0x0e3a), (0x0e47, 0x0e4e),
+    (0x0eb1, 0x0eb1), (0x0eb4, 0x0ebc), (0x0ec8, 0x0ecd), (0x0f18, 0x0f19), (0x0f35, 0x0f35), (0x0f37, 0x0f37),
+    (0x0f39, 0x0f39), (0x0f71, 0x0f7e), (0x0f80, 0x0f84), (0x0f86, 0x0f87), (0x0f8d, 0x0f97), (0x0f99, 0x0fbc),
+    (0x0fc6, 0x0fc6), (0x102d, 0x1030), (0x1032, 0x1037), (0x1039, 0x103a), (0x103d, 0x103e), (0x1058, 0x1059),
+    (0x105e, 0x1060), (0x1071, 0x1074), (0x1082, 0x1082), (0x1085, 0x1086), (0x108d, 0x108d), (0x109d, 0x109d),
+    (0x135d, 0x135f), (0x1712, 0x1714), (0x1732, 0x1733), (0x1752, 0x1753), (0x1772, 0x1773), (0x17b4, 0x17b5),
+    (0x17b7, 0x17bd), (0x17c6, 0x17c6), (0x17c9, 0x17d3), (0x17dd, 0x17dd), (0x180b, 0x180d), (0x180f, 0x180f),
+    (0x1885, 0x1886), (0x18a9, 0x18a9), (0x1ab0, 0x1ace), (0x1dc0, 0x1dff), (0x20d0, 0x20f0), (0x302a, 0x302d),
+    (0x3099, 0x309a), (0xfe20, 0xfe2f)
+];
+
+fn is_mark(c: char) -> bool {
+    use std::cmp::Ordering;
+    let c = c as u32;
+    MARK_RANGES.binary_search_by(|&(first, last)| {
+        if last < c { Ordering::Less } else if first > c { Ordering::Greater } else { Ordering::Equal }
+    }).is_ok()
+}
+
+fn is_variation_selector(c: char) -> bool {
+    matches!(c as u32, 0x180b..=0x180d | 0xfe00..=0xfe0f | 0xe0100..=0xe01ef)
+}
+
+/// The extra work some scripts need on top of applying their features
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Shaper {
+    Default,
+    /// Cursive scripts whose letters change shape depending on their neighbours
+    Joining,
+    /// Scripts written in syllables that are reordered around a base consonant
+    Indic
+}
+
+impl Shaper {
+    fn for_script(script: Script) -> Shaper {
+        match script {
+            Script::Arabic | Script::Syriac | Script::Nko | Script::Mongolian => Shaper::Joining,
+            s if indic::model(s).is_some() => Shaper::Indic,
+            _ => Shaper::Default
+        }
+    }
+
+    fn substitution_stages(&self) -> &'static [&'static [&'static [u8; 4]]] {
+        match *self {
+            Shaper::Default => DEFAULT_SUBSTITUTION_STAGES,
+            Shaper::Joining => JOINING_SUBSTITUTION_STAGES,
+            Shaper::Indic => indic::SUBSTITUTION_STAGES
+        }
+    }
+
+    /// Splits a character into the ones the shaper wants to see, if it needs splitting
+    fn decompose(&self, c: char) -> Option<(char, char)> {
+        match *self {
+            Shaper::Indic => indic::decompose(c),
+            _ => None
+        }
+    }
+
+    /// Mark each glyph with the features that apply to it before substitution, reordering them
+    /// first if the script needs it. `chars` has the character each glyph was mapped from.
+    /// Syllabic scripts give back their syllables for the reordering after the basic features
+    fn setup_masks(&self, script: Script, chars: &[char], glyphs: &mut [LayoutGlyph]) -> Option<indic::Syllables> {
+        for g in glyphs.iter_mut() {
+            g.mask = GLOBAL_MASK;
+        }
+        match *self {
+            Shaper::Default => None,
+            Shaper::Joining => {
+                for (g, form) in glyphs.iter_mut().zip(arabic::joining_forms(chars)) {
+                    if let Some(form) = form {
+                        g.mask |= feature_mask(TableTag::new(form.feature()));
+                    }
+                }
+                None
+            },
+            Shaper::Indic => Some(indic::setup(script, chars, glyphs))
+        }
+    }
+}
+
+/// A lookup to apply while shaping, with the value of the feature it came from and the mask of
+/// the glyphs it applies to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct PlannedLookup {
+    index: u16,
+    value: u32,
+    mask: u32
+}
+
+/// Turn a string into glyphs positioned along a line at `size` pixels per em. The script and
+/// direction are worked out from the text, and right to left text comes back in visual order, so
+/// the glyphs are always drawn left to right. `features` are applied on top of the defaults, like
+/// 'liga' and 'kern'. Tables the font doesn't have are skipped, so a font with only 'cmap' and
+/// 'hmtx' still gets its glyphs laid out by their advance widths
+pub fn shape(font: &SfntFont, text: &str, size: f32, features: &[Feature]) -> Vec<PositionedGlyph> {
+    let script = Script::of_text(text);
+    let char_map = CharMap::from_truetype(font).ok();
+    shape_run(font, char_map.as_ref(), text, script, script.is_right_to_left(), size, features)
+}
+
+/// Shape a paragraph that can mix left to right and right to left text. The text is split into
+/// runs of one script and bidi level, which are shaped on their own and come back in the order
+/// they are drawn in, left to right. The paragraph's direction is worked out from its first strong
+/// character unless `direction` is given
+pub fn shape_paragraph(font: &SfntFont, text: &str, size: f32, features: &[Feature], direction: Option<Direction>) -> Vec<GlyphRun> {
+    let chars: Vec<char> = text.chars().collect();
+    let paragraph = BidiParagraph::new(&chars, direction);
+    let char_map = CharMap::from_truetype(font).ok();
+    shape_levels(font, char_map.as_ref(), text, &paragraph.line_levels(0, chars.len()), size, features)
+}
+
+/// Shape text with the bidi level of each of its characters already resolved, giving its runs in
+/// visual order. `char_map` is the font's, built once by whoever shapes a whole paragraph
+fn shape_levels(font: &SfntFont, char_map: Option<&CharMap>, text: &str, levels: &[u8], size: f32, features: &[Feature]) -> Vec<GlyphRun> {
+    let runs = itemize(text, levels);
+    let order = bidi::visual_order(&runs.iter().map(|&(_, _, level)| level).collect::<Vec<_>>());
+    order.into_iter().map(|n| {
+        let (ref range, script, level) = runs[n];
+        let mut glyphs = shape_run(font, char_map, &text[range.clone()], script, level & 1 == 1, size, features);
+        for g in &mut glyphs {
+            g.cluster += range.start;
+        }
+        GlyphRun { range: range.clone(), script, level, glyphs }
+    }).collect()
+}
+
+/// Split a paragraph into runs of characters with the same script and bidi level, in logical
+/// order. Common characters like spaces and punctuation join the run they are in, and runs of
+/// nothing but them are left as `Common`
+fn itemize(text: &str, levels: &[u8]) -> Vec<(Range<usize>, Script, u8)> {
+    let mut runs: Vec<(Range<usize>, Script, u8)> = Vec::new();
+    for ((offset, c), &level) in text.char_indices().zip(levels.iter()) {
+        let end = offset + c.len_utf8();
+        let script = match Script::of(c) {
+            Script::Inherited => Script::Common,
+            s => s
+        };
+        if let Some(&mut (ref mut range, ref mut run_script, run_level)) = runs.last_mut() {
+            if run_level == level && (script == Script::Common || *run_script == Script::Common || *run_script == script) {
+                range.end = end;
+                if script != Script::Common {
+                    *run_script = script;
+                }
+                continue;
+            }
+        }
+        runs.push((offset..end, script, level));
+    }
+    runs
+}
+
+/// Shape text that is all in one script and direction
+fn shape_run(font: &SfntFont, char_map: Option<&CharMap>, text: &str, script: Script, right_to_left: bool, size: f32, features: &[Feature]) -> Vec<PositionedGlyph> {
+    let shaper = Shaper::for_script(script);
+
+    let (mut glyphs, chars) = map_glyphs(char_map, text, shaper, right_to_left);
+    let syllables = shaper.setup_masks(script, &chars, &mut glyphs);
+
+    let stages = match font.gsub_table {
+        Some(ref gsub) => plan_lookups(&gsub.0, script, shaper.substitution_stages(), features),
+        None => vec![Vec::new(); shaper.substitution_stages().len()]
+    };
+    for (n, stage) in stages.into_iter().enumerate() {
+        if let Some(ref gsub) = font.gsub_table {
+            for lookup in stage {
+                gsub.apply_lookup_masked(lookup.index, &mut glyphs, font, lookup.value as usize - 1, lookup.mask);
+            }
+        }
+        if let (Some(syllables), true) = (syllables.as_ref(), n + 1 == indic::BASIC_STAGE_COUNT) {
+            syllables.reorder(&mut glyphs);
+        }
+    }
+
+    let mut positions: Vec<GlyphPosition> = glyphs.iter().map(|g| {
+        let advance = font.hmtx_table.as_ref().and_then(|hmtx| hmtx.metric(g.glyph as usize).ok()).map(|m| m.advance_width).unwrap_or(0);
+        GlyphPosition::new(advance as i32, 0)
+    }).collect();
+
+    let units_per_em = font.head_table.map(|h| h.units_per_em).unwrap_or(FALLBACK_UNITS_PER_EM);
+    let mut kerned = false;
+    if let Some(ref gpos) = font.gpos_table {
+        let options = PositioningOptions { ppem: size.round() as u16, units_per_em, right_to_left };
+        let stages = plan_lookups(&gpos.0, script, DEFAULT_POSITIONING_STAGES, features);
+        for lookup in stages.iter().flat_map(|s| s.iter()) {
+            gpos.apply_lookup(lookup.index, &glyphs, &mut positions, font, &options);
+        }
+        let kern_lookups = gpos.0.lookups_for_feature(script_tag(&gpos.0, script), None, TableTag::new(b"kern"));
+        kerned = stages.iter().flat_map(|s| s.iter()).any(|l| kern_lookups.contains(&l.index));
+    }
+    // marks are drawn over their base, so they take up no room on the line. This is done after the
+    // lookups since some fonts kern marks using their advances. Fonts without a 'GDEF' table don't
+    // say which glyphs are marks, so the characters they came from do instead
+    for (g, pos) in glyphs.iter().zip(positions.iter_mut()) {
+        let is_mark = match font.gdef_table {
+            Some(_) => font.glyph_class(g.glyph) == GLYPH_CLASS_MARK,
+            None => text[g.cluster..].chars().next().is_some_and(is_mark)
+        };
+        if is_mark {
+            pos.x_advance = 0;
+            pos.y_advance = 0;
+        }
+    }
+    resolve_attachments(&mut positions, right_to_left);
+
+    let mut run: Vec<(LayoutGlyph, GlyphPosition)> = glyphs.into_iter().zip(positions).collect();
+    if right_to_left {
+        run.reverse();
+    }
+
+    // fonts without 'GPOS' kerning may still have a 'kern' table, which is in visual order
+    let kern_enabled = features.iter().rev().find(|f| f.tag == TableTag::new(b"kern")).map(|f| f.value != 0).unwrap_or(true);
+    if !kerned && kern_enabled && font.kern_table.is_some() {
+        for i in 1..run.len() {
+            let kerning = font.kerning(run[i - 1].0.glyph as usize, run[i].0.glyph as usize);
+            run[i - 1].1.x_advance += kerning as i32;
+        }
+    }
+
+    let scale = size / units_per_em as f32;
+    run.into_iter().map(|(g, pos)| PositionedGlyph {
+        glyph_index: g.glyph as usize,
+        cluster: g.cluster,
+        x_advance: pos.x_advance as f32 * scale,
+        y_advance: pos.y_advance as f32 * scale,
+        x_offset: pos.x_offset as f32 * scale,
+        y_offset: pos.y_offset as f32 * scale
+    }).collect()
+}
+
+/// Look up a glyph for every character, giving the glyphs along with the character each came
+/// from. Variation selectors pick the glyph of the character before them and don't get one of
+/// their own, and in right to left text characters like brackets are swapped for their mirror
+/// image. Fonts without a usable 'cmap' get the missing glyph for everything
+fn map_glyphs(char_map: Option<&CharMap>, text: &str, shaper: Shaper, mirrored: bool) -> (Vec<LayoutGlyph>, Vec<char>) {
+    let map = |c| char_map.map(|cm| cm.map(c)).unwrap_or(0) as u16;
+    let mut glyphs: Vec<LayoutGlyph> = Vec::with_capacity(text.len());
+    let mut chars = Vec::with_capacity(text.len());
+    let mut last_char = None;
+    for (cluster, c) in text.char_indices() {
+        // a variation selector picks the glyph of the character before it, and on its own, like at
+        // the start of the text or after another selector, there is nothing for it to do
+        if is_variation_selector(c) {
+            if let (Some(cm), Some(base), Some(g)) = (char_map, last_char, glyphs.last_mut()) {
+                g.glyph = cm.map_variation(base, c) as u16;
+            }
+            last_char = None;
+            continue;
+        }
+        let c = if mirrored { bidi::mirror(c).unwrap_or(c) } else { c };
+        match shaper.decompose(c) {
+            Some((first, second)) => {
+                glyphs.push(LayoutGlyph::new(map(first), cluster));
+                glyphs.push(LayoutGlyph::new(map(second), cluster));
+                chars.push(first);
+                chars.push(second);
+            },
+            None => {
+                glyphs.push(LayoutGlyph::new(map(c), cluster));
+                chars.push(c);
+            }
+        }
+        last_char = Some(c);
+    }
+    (glyphs, chars)
+}
+
+/// The script tag to look for in a layout table, preferring the newer tags of scripts that have
+/// two when the font has the newer one
+fn script_tag<T>(table: &LayoutTable<T>, script: Script) -> TableTag {
+    script.script_tags().into_iter().find(|&t| table.scripts.iter().any(|s| s.tag == t)).unwrap_or(script.tag())
+}
+
+/// The lookups to apply in each stage. The features in `stages` are on unless `features` turns
+/// them off, and features that aren't in any stage are added to the last one. The required
+/// feature comes first
+fn plan_lookups<T>(table: &LayoutTable<T>, script: Script, stages: &[&[&[u8; 4]]], features: &[Feature]) -> Vec<Vec<PlannedLookup>> {
+    let tag = script_tag(table, script);
+    let in_stages = |t: TableTag| stages.iter().any(|s| s.iter().any(|&st| TableTag::new(st) == t));
+    let mut planned = Vec::with_capacity(stages.len());
+    for (n, stage) in stages.iter().enumerate() {
+        let mut values: Vec<(TableTag, u32)> = stage.iter().map(|&t| (TableTag::new(t), 1)).collect();
+        if n + 1 == stages.len() {
+            for f in features {
+                if !in_stages(f.tag) && !values.iter().any(|&(t, _)| t == f.tag) {
+                    values.push((f.tag, 1));
+                }
+            }
+        }
+        for &mut (t, ref mut value) in values.iter_mut() {
+            if let Some(f) = features.iter().rev().find(|f| f.tag == t) {
+                *value = f.value;
+            }
+        }
+
+        let mut lookups = BTreeMap::new();
+        if n == 0 {
+            for index in table.required_lookups(tag, None) {
+                lookups.insert(index, PlannedLookup { index, value: 1, mask: GLOBAL_MASK });
+            }
+        }
+        for (t, value) in values {
+            if value == 0 { continue; }
+            let mask = feature_mask(t);
+            for index in table.lookups_for_feature(tag, None, t) {
+                let lookup = lookups.entry(index).or_insert(PlannedLookup { index, value, mask: 0 });
+                lookup.value = value;
+                lookup.mask |= mask;
+            }
+        }
+        planned.push(lookups.into_values().collect());
+    }
+    planned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[cfg(target_os="windows")]
+    const FONT_PATH: &str =
+        "C:\\Windows\\Fonts\\arial.ttf";
+    #[cfg(target_os="macos")]
+    const FONT_PATH: &str =
+        "/Library/Fonts/Arial.ttf";
+    #[cfg(target_os="linux")]
+    const FONT_PATH: &str =
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+    #[test]
+    fn shape_latin() {
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+        let cm = CharMap::from_truetype(&font).expect("char map");
+
+        let glyphs = shape(&font, "AVATAR", 20.0, &[]);
+        assert_eq!(glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(glyphs.iter().map(|g| g.glyph_index).collect::<Vec<_>>(), "AVATAR".chars().map(|c| cm.map(c)).collect::<Vec<_>>());
+
+        // turning kerning off leaves the advances from 'hmtx', and A and V kern closer together
+        let unkerned = shape(&font, "AVATAR", 20.0, &[Feature::new(b"kern", 0)]);
+        let scale = 20.0 / font.head_table.unwrap().units_per_em as f32;
+        for g in &unkerned {
+            let advance = font.hmtx_table.as_ref().unwrap().metric(g.glyph_index).unwrap().advance_width as f32 * scale;
+            assert!((g.x_advance - advance).abs() < 1e-4);
+        }
+        assert!(glyphs[0].x_advance < unkerned[0].x_advance);
+        assert!(glyphs.iter().all(|g| g.y_advance == 0.0));
+    }
+
+    #[test]
+    fn shape_clusters() {
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+
+        // clusters are byte offsets, and variation selectors don't get a glyph
+        let glyphs = shape(&font, "\u{e9}a\u{fe00}b", 16.0, &[]);
+        assert_eq!(glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![0, 2, 6]);
+        let glyphs = shape(&font, "\u{fe00}a\u{fe00}\u{fe01}b", 16.0, &[]);
+        assert_eq!(glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![3, 10]);
+
+        // right to left text comes back in visual order
+        let glyphs = shape(&font, "\u{5d0}\u{5d1}\u{5d2}", 16.0, &[]);
+        assert_eq!(glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![4, 2, 0]);
+        assert!(glyphs.iter().all(|g| g.glyph_index != 0 && g.x_advance > 0.0));
+
+        assert!(shape(&font, "", 16.0, &[]).is_empty());
+    }
+
+    #[test]
+    fn shape_mixed_directions() {
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+        let cm = CharMap::from_truetype(&font).expect("char map");
+
+        // Hebrew in English is drawn right to left between the English on either side of it
+        let runs = shape_paragraph(&font, "ab \u{5d0}\u{5d1} cd", 16.0, &[], None);
+        assert_eq!(runs.iter().map(|r| (r.range.clone(), r.script, r.level)).collect::<Vec<_>>(),
+                   vec![(0..3, Script::Latin, 0), (3..7, Script::Hebrew, 1), (7..10, Script::Latin, 0)]);
+        let clusters: Vec<usize> = runs.iter().flat_map(|r| r.glyphs.iter().map(|g| g.cluster)).collect();
+        assert_eq!(clusters, vec![0, 1, 2, 5, 3, 7, 8, 9]);
+        assert_eq!(runs[1].direction(), Direction::RightToLeft);
+
+        // English in a Hebrew paragraph comes first when it is at the end
+        let runs = shape_paragraph(&font, "\u{5d0}\u{5d1} ab", 16.0, &[], None);
+        assert_eq!(runs.iter().map(|r| r.range.clone()).collect::<Vec<_>>(), vec![5..7, 0..5]);
+        let runs = shape_paragraph(&font, "\u{5d0}\u{5d1} ab", 16.0, &[], Some(Direction::LeftToRight));
+        assert_eq!(runs.iter().map(|r| r.range.clone()).collect::<Vec<_>>(), vec![0..4, 4..7]);
+
+        // brackets in right to left text are mirrored, so they still face the text inside them
+        let runs = shape_paragraph(&font, "\u{5d0}(\u{5d1})", 16.0, &[], None);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![5, 3, 2, 0]);
+        assert_eq!(runs[0].glyphs[0].glyph_index, cm.map('('));
+        assert_eq!(runs[0].glyphs[2].glyph_index, cm.map(')'));
+
+        assert!(shape_paragraph(&font, "", 16.0, &[], None).is_empty());
+    }
+
+    #[test]
+    fn shape_arabic() {
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let font = SfntFont::from_binary(&mut font_file).expect("load font data");
+        let cm = CharMap::from_truetype(&font).expect("char map");
+
+        // beh beh beh takes its final, medial and initial forms, drawn right to left
+        let beh = cm.map('\u{628}');
+        let glyphs = shape(&font, "\u{628}\u{628}\u{628}", 16.0, &[]);
+        assert_eq!(glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(), vec![4, 2, 0]);
+        assert!(glyphs.iter().all(|g| g.glyph_index != beh));
+        assert!(glyphs[0].glyph_index != glyphs[1].glyph_index && glyphs[1].glyph_index != glyphs[2].glyph_index);
+
+        // a letter on its own keeps the isolated form, and turning the forms off leaves them alone
+        assert_eq!(shape(&font, "\u{628} \u{628}", 16.0, &[])[0].glyph_index, shape(&font, "\u{628}", 16.0, &[])[0].glyph_index);
+        let plain = shape(&font, "\u{628}\u{628}", 16.0, &[Feature::new(b"init", 0), Feature::new(b"fina", 0)]);
+        assert!(plain.iter().all(|g| g.glyph_index == beh));
+
+        // lam alef is a required ligature
+        assert_eq!(shape(&font, "\u{644}\u{627}", 16.0, &[]).len(), 1);
+
+        // marks sit on their letter without taking up room
+        let glyphs = shape(&font, "\u{628}\u{64e}\u{628}", 16.0, &[]);
+        assert_eq!(glyphs.len(), 3);
+        assert_eq!(glyphs[1].cluster, 2);
+        assert_eq!(glyphs[1].x_advance, 0.0);
+    }
+
+    #[test]
+    fn shape_marks_without_gdef() {
+        let mut font_file = File::open(FONT_PATH).unwrap();
+        let mut font = SfntFont::from_binary(&mut font_file).expect("load font data");
+        let cm = CharMap::from_truetype(&font).expect("char map");
+        font.gdef_table = None;
+        font.gpos_table = None;
+
+        // the hundred thousands sign has an advance in 'hmtx', but is an enclosing mark so still
+        // takes up no room
+        let sign = cm.map('\u{488}');
+        assert!(font.hmtx_table.as_ref().unwrap().metric(sign).unwrap().advance_width > 0);
+        let glyphs = shape(&font, "\u{430}\u{488}\u{431}", 16.0, &[]);
+        assert_eq!(glyphs.iter().map(|g| g.glyph_index).collect::<Vec<_>>(), vec![cm.map('\u{430}'), sign, cm.map('\u{431}')]);
+        assert!(glyphs[0].x_advance > 0.0 && glyphs[2].x_advance > 0.0);
+        assert_eq!(glyphs[1].x_advance, 0.0);
+    }
+}
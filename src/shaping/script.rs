@@ -0,0 +1,256 @@
+use truetype_loader::TableTag;
+use super::find_range;
+
+/// The writing system a character belongs to, which decides the OpenType script tag, the text
+/// direction and how a run gets shaped
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Script {
+    /// Characters used by many scripts, like spaces, digits and punctuation
+    Common,
+    /// Combining marks that take the script of the character they are on
+    Inherited,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Syriac,
+    Thaana,
+    Nko,
+    Devanagari,
+    Bengali,
+    Gurmukhi,
+    Gujarati,
+    Oriya,
+    Tamil,
+    Telugu,
+    Kannada,
+    Malayalam,
+    Sinhala,
+    Thai,
+    Lao,
+    Tibetan,
+    Myanmar,
+    Georgian,
+    Hangul,
+    Ethiopic,
+    Khmer,
+    Mongolian,
+    Hiragana,
+    Katakana,
+    Han
+}
+
+/// Script ranges, sorted by their first character. Anything not in one of these is `Common`
+const SCRIPT_RANGES: &[(u32, u32, Script)] = &[
+    (0x0041, 0x005a, Script::Latin),
+    (0x0061, 0x007a, Script::Latin),
+    (0x00aa, 0x00aa, Script::Latin),
+    (0x00ba, 0x00ba, Script::Latin),
+    (0x00c0, 0x00d6, Script::Latin),
+    (0x00d8, 0x00f6, Script::Latin),
+    (0x00f8, 0x024f, Script::Latin),
+    (0x0250, 0x02af, Script::Latin),
+    (0x0300, 0x036f, Script::Inherited),
+    (0x0370, 0x03ff, Script::Greek),
+    (0x0400, 0x052f, Script::Cyrillic),
+    (0x0531, 0x058f, Script::Armenian),
+    (0x0591, 0x05ff, Script::Hebrew),
+    (0x0600, 0x0604, Script::Arabic),
+    (0x0606, 0x060b, Script::Arabic),
+    (0x060d, 0x061a, Script::Arabic),
+    (0x061c, 0x061e, Script::Arabic),
+    (0x0620, 0x063f, Script::Arabic),
+    (0x0641, 0x064a, Script::Arabic),
+    (0x064b, 0x0655, Script::Inherited),
+    (0x0656, 0x066f, Script::Arabic),
+    (0x0670, 0x0670, Script::Inherited),
+    (0x0671, 0x06dc, Script::Arabic),
+    (0x06de, 0x06ff, Script::Arabic),
+    (0x0700, 0x074f, Script::Syriac),
+    (0x0750, 0x077f, Script::Arabic),
+    (0x0780, 0x07bf, Script::Thaana),
+    (0x07c0, 0x07ff, Script::Nko),
+    (0x0860, 0x086f, Script::Syriac),
+    (0x0870, 0x08ff, Script::Arabic),
+    (0x0900, 0x0950, Script::Devanagari),
+    (0x0951, 0x0954, Script::Inherited),
+    (0x0955, 0x0963, Script::Devanagari),
+    (0x0966, 0x097f, Script::Devanagari),
+    (0x0980, 0x09ff, Script::Bengali),
+    (0x0a00, 0x0a7f, Script::Gurmukhi),
+    (0x0a80, 0x0aff, Script::Gujarati),
+    (0x0b00, 0x0b7f, Script::Oriya),
+    (0x0b80, 0x0bff, Script::Tamil),
+    (0x0c00, 0x0c7f, Script::Telugu),
+    (0x0c80, 0x0cff, Script::Kannada),
+    (0x0d00, 0x0d7f, Script::Malayalam),
+    (0x0d80, 0x0dff, Script::Sinhala),
+    (0x0e00, 0x0e3e, Script::Thai),
+    (0x0e40, 0x0e7f, Script::Thai),
+    (0x0e80, 0x0eff, Script::Lao),
+    (0x0f00, 0x0fd4, Script::Tibetan),
+    (0x0fd9, 0x0fff, Script::Tibetan),
+    (0x1000, 0x109f, Script::Myanmar),
+    (0x10a0, 0x10ff, Script::Georgian),
+    (0x1100, 0x11ff, Script::Hangul),
+    (0x1200, 0x139f, Script::Ethiopic),
+    (0x1780, 0x17ff, Script::Khmer),
+    (0x1800, 0x1801, Script::Mongolian),
+    (0x1804, 0x1804, Script::Mongolian),
+    (0x1806, 0x18af, Script::Mongolian),
+    (0x19e0, 0x19ff, Script::Khmer),
+    (0x1ab0, 0x1aff, Script::Inherited),
+    (0x1c80, 0x1c8f, Script::Cyrillic),
+    (0x1c90, 0x1cbf, Script::Georgian),
+    (0x1d00, 0x1d25, Script::Latin),
+    (0x1d26, 0x1d2a, Script::Greek),
+    (0x1d2c, 0x1d5c, Script::Latin),
+    (0x1d62, 0x1d65, Script::Latin),
+    (0x1d6b, 0x1d77, Script::Latin),
+    (0x1d79, 0x1dbe, Script::Latin),
+    (0x1dc0, 0x1dff, Script::Inherited),
+    (0x1e00, 0x1eff, Script::Latin),
+    (0x1f00, 0x1ffe, Script::Greek),
+    (0x200c, 0x200d, Script::Inherited),
+    (0x20d0, 0x20ff, Script::Inherited),
+    (0x2c00, 0x2c5f, Script::Latin),
+    (0x2c60, 0x2c7f, Script::Latin),
+    (0x2d00, 0x2d2f, Script::Georgian),
+    (0x2de0, 0x2dff, Script::Cyrillic),
+    (0x2e80, 0x2fdf, Script::Han),
+    (0x3005, 0x3005, Script::Han),
+    (0x3007, 0x3007, Script::Han),
+    (0x3021, 0x3029, Script::Han),
+    (0x302a, 0x302d, Script::Inherited),
+    (0x3038, 0x303b, Script::Han),
+    (0x3041, 0x3096, Script::Hiragana),
+    (0x3099, 0x309a, Script::Inherited),
+    (0x309d, 0x309f, Script::Hiragana),
+    (0x30a1, 0x30fa, Script::Katakana),
+    (0x30fd, 0x30ff, Script::Katakana),
+    (0x3131, 0x318e, Script::Hangul),
+    (0x31f0, 0x31ff, Script::Katakana),
+    (0x3400, 0x4dbf, Script::Han),
+    (0x4e00, 0x9fff, Script::Han),
+    (0xa640, 0xa69f, Script::Cyrillic),
+    (0xa720, 0xa721, Script::Common),
+    (0xa722, 0xa7ff, Script::Latin),
+    (0xa960, 0xa97f, Script::Hangul),
+    (0xa9e0, 0xa9ff, Script::Myanmar),
+    (0xaa60, 0xaa7f, Script::Myanmar),
+    (0xab30, 0xab5a, Script::Latin),
+    (0xac00, 0xd7ff, Script::Hangul),
+    (0xf900, 0xfaff, Script::Han),
+    (0xfb00, 0xfb06, Script::Latin),
+    (0xfb13, 0xfb17, Script::Armenian),
+    (0xfb1d, 0xfb4f, Script::Hebrew),
+    (0xfb50, 0xfdff, Script::Arabic),
+    (0xfe00, 0xfe0f, Script::Inherited),
+    (0xfe20, 0xfe2f, Script::Inherited),
+    (0xfe70, 0xfefc, Script::Arabic),
+    (0xff21, 0xff3a, Script::Latin),
+    (0xff41, 0xff5a, Script::Latin),
+    (0xff66, 0xff6f, Script::Katakana),
+    (0xff71, 0xff9d, Script::Katakana),
+    (0xffa0, 0xffdc, Script::Hangul),
+    (0x20000, 0x3134f, Script::Han),
+    (0xe0100, 0xe01ef, Script::Inherited)
+];
+
+impl Script {
+    pub fn of(c: char) -> Script {
+        find_range(SCRIPT_RANGES, c).unwrap_or(Script::Common)
+    }
+
+    /// The script most of a string is in, going by its first character that is in a particular
+    /// script. Strings of only common characters are `Common`
+    pub fn of_text(text: &str) -> Script {
+        text.chars().map(Script::of).find(|&s| s != Script::Common && s != Script::Inherited).unwrap_or(Script::Common)
+    }
+
+    /// The OpenType script tag. Scripts that were redesigned for OpenType use the newer tag, fonts
+    /// that only have the old one are found by `script_tags`
+    pub fn tag(&self) -> TableTag {
+        TableTag::new(match *self {
+            Script::Common | Script::Inherited => b"DFLT",
+            Script::Latin => b"latn",
+            Script::Greek => b"grek",
+            Script::Cyrillic => b"cyrl",
+            Script::Armenian => b"armn",
+            Script::Hebrew => b"hebr",
+            Script::Arabic => b"arab",
+            Script::Syriac => b"syrc",
+            Script::Thaana => b"thaa",
+            Script::Nko => b"nko ",
+            Script::Devanagari => b"dev2",
+            Script::Bengali => b"bng2",
+            Script::Gurmukhi => b"gur2",
+            Script::Gujarati => b"gjr2",
+            Script::Oriya => b"ory2",
+            Script::Tamil => b"tml2",
+            Script::Telugu => b"tel2",
+            Script::Kannada => b"knd2",
+            Script::Malayalam => b"mlm2",
+            Script::Sinhala => b"sinh",
+            Script::Thai => b"thai",
+            Script::Lao => b"lao ",
+            Script::Tibetan => b"tibt",
+            Script::Myanmar => b"mym2",
+            Script::Georgian => b"geor",
+            Script::Hangul => b"hang",
+            Script::Ethiopic => b"ethi",
+            Script::Khmer => b"khmr",
+            Script::Mongolian => b"mong",
+            Script::Hiragana | Script::Katakana => b"kana",
+            Script::Han => b"hani"
+        })
+    }
+
+    /// The tags to look for in a font, in order of preference. Indic scripts and Myanmar have an
+    /// older tag that fonts made before the current shaping model use
+    pub fn script_tags(&self) -> Vec<TableTag> {
+        let old = match *self {
+            Script::Devanagari => Some(b"deva"),
+            Script::Bengali => Some(b"beng"),
+            Script::Gurmukhi => Some(b"guru"),
+            Script::Gujarati => Some(b"gujr"),
+            Script::Oriya => Some(b"orya"),
+            Script::Tamil => Some(b"taml"),
+            Script::Telugu => Some(b"telu"),
+            Script::Kannada => Some(b"knda"),
+            Script::Malayalam => Some(b"mlym"),
+            Script::Myanmar => Some(b"mymr"),
+            _ => None
+        };
+        let mut tags = vec![self.tag()];
+        tags.extend(old.map(TableTag::new));
+        tags
+    }
+
+    pub fn is_right_to_left(&self) -> bool {
+        matches!(*self, Script::Hebrew | Script::Arabic | Script::Syriac | Script::Thaana | Script::Nko)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts() {
+        assert_eq!(Script::of('a'), Script::Latin);
+        assert_eq!(Script::of(' '), Script::Common);
+        assert_eq!(Script::of('7'), Script::Common);
+        assert_eq!(Script::of('\u{301}'), Script::Inherited);
+        assert_eq!(Script::of('\u{5d0}'), Script::Hebrew);
+        assert_eq!(Script::of('\u{628}'), Script::Arabic);
+        assert_eq!(Script::of('\u{915}'), Script::Devanagari);
+        assert_eq!(Script::of('\u{4e2d}'), Script::Han);
+        assert_eq!(Script::of_text("123 \u{5e9}\u{5dc}\u{5d5}\u{5dd} abc"), Script::Hebrew);
+        assert_eq!(Script::of_text("!?"), Script::Common);
+        assert!(Script::Arabic.is_right_to_left() && !Script::Latin.is_right_to_left());
+        assert_eq!(Script::Devanagari.script_tags(), vec![TableTag::new(b"dev2"), TableTag::new(b"deva")]);
+    }
+}
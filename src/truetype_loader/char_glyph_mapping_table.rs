@@ -17,6 +17,46 @@ pub struct HighByteMappingSubheader {
     id_range_offset: u16
 }
 
+/// A run of character codes that map to a run of glyphs, or all to the same glyph, in formats 12
+/// and 13
+#[derive(Copy, Clone, Debug)]
+pub struct SequentialMapGroup {
+    pub start_char_code: u32,
+    pub end_char_code: u32,
+    pub start_glyph_id: u32
+}
+
+/// A run of characters that use their default glyph with a variation selector
+#[derive(Copy, Clone, Debug)]
+pub struct UnicodeValueRange {
+    pub start_unicode_value: u32,
+    pub additional_count: u8
+}
+
+/// A character that uses a glyph of its own with a variation selector
+#[derive(Copy, Clone, Debug)]
+pub struct UvsMapping {
+    pub unicode_value: u32,
+    pub glyph_id: u16
+}
+
+/// The variation sequences for one variation selector in a format 14 subtable. Both lists are
+/// sorted by character
+#[derive(Clone, Debug)]
+pub struct VariationSelectorRecord {
+    pub var_selector: u32,
+    pub default_uvs: Vec<UnicodeValueRange>,
+    pub non_default_uvs: Vec<UvsMapping>
+}
+
+/// The glyph for a variation sequence
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VariationGlyph {
+    /// The sequence is displayed with the glyph the base character gets on its own
+    UseDefault,
+    Glyph(u16)
+}
+
 pub enum CharGlyphMappingEncodingTableFormat {
     ByteEncoding {
         glyph_ids: [u8; 256]
@@ -42,6 +82,15 @@ pub enum CharGlyphMappingEncodingTableFormat {
         first_code: u16,
         entry_count: u16,
         glyph_indices: Vec<u16>
+    },
+    SegmentedCoverage {
+        groups: Vec<SequentialMapGroup>
+    },
+    ManyToOneRange {
+        groups: Vec<SequentialMapGroup>
+    },
+    UnicodeVariationSequences {
+        records: Vec<VariationSelectorRecord>
     }
 }
 impl Debug for CharGlyphMappingEncodingTableFormat {
@@ -50,121 +99,597 @@ impl Debug for CharGlyphMappingEncodingTableFormat {
             CharGlyphMappingEncodingTableFormat::ByteEncoding {..} => write!(f, "ByteEncoding"),
             CharGlyphMappingEncodingTableFormat::HighByteMapping {..} => write!(f, "HighByteMapping"),
             CharGlyphMappingEncodingTableFormat::SegmentMapToDelta {..} => write!(f, "SegmentMapToDelta"),
-            CharGlyphMappingEncodingTableFormat::Trimmed {..} => write!(f, "Trimmed")
+            CharGlyphMappingEncodingTableFormat::Trimmed {..} => write!(f, "Trimmed"),
+            CharGlyphMappingEncodingTableFormat::SegmentedCoverage { ref groups } => write!(f, "SegmentedCoverage({} groups)", groups.len()),
+            CharGlyphMappingEncodingTableFormat::ManyToOneRange { ref groups } => write!(f, "ManyToOneRange({} groups)", groups.len()),
+            CharGlyphMappingEncodingTableFormat::UnicodeVariationSequences { ref records } => write!(f, "UnicodeVariationSequences({} selectors)", records.len())
         }
     }
 }
 
-#[derive(Debug)]
-pub struct CharGlyphMappingEncodingTable {
-    platform_id: u16,
-    platform_encoding_id: u16,
-    version: u16,
-    pub subtable: CharGlyphMappingEncodingTableFormat
+impl CharGlyphMappingEncodingTableFormat {
+    /// Look up the glyph for a character code in this subtable's encoding. Codes that aren't
+    /// mapped, or are mapped to the missing glyph, give `None`
+    pub fn glyph_index(&self, code: u32) -> Option<u16> {
+        use self::CharGlyphMappingEncodingTableFormat::*;
+        let gid = match *self {
+            ByteEncoding { ref glyph_ids } => {
+                if code > 0xff { return None; }
+                glyph_ids[code as usize] as u16
+            },
+            HighByteMapping { ref subheader_keys, ref subheaders, ref glyph_indices } => {
+                if code > 0xffff { return None; }
+                let (high, low) = if code <= 0xff { (code, code) } else { (code >> 8, code & 0xff) };
+                let k = (subheader_keys[high as usize] / 8) as usize;
+                // single bytes all use subheader 0, and each byte that starts a two byte code has
+                // a subheader of its own
+                if (code <= 0xff) != (k == 0) { return None; }
+                let sh = subheaders.get(k)?;
+                if low < sh.first_code as u32 || low >= sh.first_code as u32 + sh.entry_count as u32 { return None; }
+                // id_range_offset is a byte offset from the subheader's own id_range_offset field
+                // into the glyph index array that follows the subheaders
+                let index = (sh.id_range_offset as usize + 8*k + 6 + 2*(low - sh.first_code as u32) as usize)
+                    .checked_sub(8*subheaders.len())? / 2;
+                let gid = *glyph_indices.get(index)?;
+                if gid == 0 { return None; }
+                gid.wrapping_add(sh.id_delta as u16)
+            },
+            SegmentMapToDelta { ref end_count, ref start_count, ref id_delta, ref id_range_offset, ref glyph_indices, .. } => {
+                if code > 0xffff { return None; }
+                let c = code as u16;
+                // the segments are sorted by end code, so the first one that ends at or after c is
+                // the only one that could contain it
+                let i = match end_count.binary_search(&c) { Ok(i) => i, Err(i) => i };
+                if i >= end_count.len() || start_count[i] > c { return None; }
+                if id_range_offset[i] == 0 {
+                    c.wrapping_add(id_delta[i])
+                } else {
+                    // id_range_offset is a byte offset from its own entry into the glyph index
+                    // array that follows the id_range_offset array
+                    let index = (id_range_offset[i] / 2) as usize + (c - start_count[i]) as usize;
+                    let gid = *index.checked_sub(id_range_offset.len() - i).and_then(|j| glyph_indices.get(j))?;
+                    if gid == 0 { return None; }
+                    gid.wrapping_add(id_delta[i])
+                }
+            },
+            Trimmed { first_code, ref glyph_indices, .. } => {
+                if code < first_code as u32 { return None; }
+                *glyph_indices.get((code - first_code as u32) as usize)?
+            },
+            SegmentedCoverage { ref groups } => {
+                let g = find_group(groups, code)?;
+                // glyph ids are 16 bit, so codes a group would take past the last one have no glyph
+                let gid = g.start_glyph_id.checked_add(code - g.start_char_code)?;
+                if gid > 0xffff { return None; }
+                gid as u16
+            },
+            ManyToOneRange { ref groups } => {
+                let gid = find_group(groups, code)?.start_glyph_id;
+                if gid > 0xffff { return None; }
+                gid as u16
+            },
+            UnicodeVariationSequences { .. } => return None
+        };
+        if gid == 0 { None } else { Some(gid) }
+    }
+
+    /// Split the next character code off the front of a string of bytes in this subtable's
+    /// encoding, giving the code and how many bytes it took up. Format 2 subtables mark which bytes
+    /// start a two byte code, like they are used in Shift-JIS and Big5. Every other format takes
+    /// one byte at a time
+    pub fn next_code(&self, bytes: &[u8]) -> Option<(u32, usize)> {
+        let first = *bytes.first()?;
+        match *self {
+            CharGlyphMappingEncodingTableFormat::HighByteMapping { ref subheader_keys, .. } if subheader_keys[first as usize] != 0 => {
+                let second = *bytes.get(1)?;
+                Some(((first as u32) << 8 | second as u32, 2))
+            },
+            _ => Some((first as u32, 1))
+        }
+    }
+
+    /// Look up the glyph for a character followed by a variation selector. Only format 14
+    /// subtables have variation sequences, and sequences that aren't in the subtable give `None`
+    pub fn variation_glyph_index(&self, code: u32, selector: u32) -> Option<VariationGlyph> {
+        let records = match *self {
+            CharGlyphMappingEncodingTableFormat::UnicodeVariationSequences { ref records } => records,
+            _ => return None
+        };
+        let record = &records[records.binary_search_by_key(&selector, |r| r.var_selector).ok()?];
+        if let Ok(i) = record.non_default_uvs.binary_search_by_key(&code, |m| m.unicode_value) {
+            return Some(VariationGlyph::Glyph(record.non_default_uvs[i].glyph_id));
+        }
+        let i = match record.default_uvs.binary_search_by_key(&code, |r| r.start_unicode_value) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1
+        };
+        let range = record.default_uvs[i];
+        if code <= range.start_unicode_value + range.additional_count as u32 {
+            Some(VariationGlyph::UseDefault)
+        } else {
+            None
+        }
+    }
+}
+
+fn find_group(groups: &[SequentialMapGroup], code: u32) -> Option<&SequentialMapGroup> {
+    let i = match groups.binary_search_by_key(&code, |g| g.end_char_code) { Ok(i) => i, Err(i) => i };
+    groups.get(i).filter(|g| g.start_char_code <= code)
+}
+
+fn read_u24<R: ReadBytesExt>(reader: &mut R) -> Result<u32, FontError> {
+    Ok(reader.read_uint::<BigEndian>(3)? as u32)
+}
+
+/// Reads the format, length and language from the start of a subtable. Formats 8 and up have 32
+/// bit lengths, and format 14 doesn't have a language at all
+fn read_subtable_header<R: ReadBytesExt>(reader: &mut R) -> Result<(u16, u32, u32), FontError> {
+    let format = reader.read_u16::<BigEndian>()?;
+    Ok(match format {
+        14 => (format, reader.read_u32::<BigEndian>()?, 0),
+        8 | 10 | 12 | 13 => {
+            reader.read_u16::<BigEndian>()?; // reserved
+            (format, reader.read_u32::<BigEndian>()?, reader.read_u32::<BigEndian>()?)
+        },
+        _ => (format, reader.read_u16::<BigEndian>()? as u32, reader.read_u16::<BigEndian>()? as u32)
+    })
+}
+
+/// An encoding record from the 'cmap' table. The subtable itself is only decoded when it is asked for
+pub struct CharGlyphMappingEncodingTable<'a> {
+    pub platform_id: u16,
+    pub platform_encoding_id: u16,
+    pub format: u16,
+    version: u32,
+    data: FontData<'a>
+}
+
+impl<'a> Debug for CharGlyphMappingEncodingTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CharGlyphMappingEncodingTable {{ platform_id: {}, platform_encoding_id: {}, format: {}, version: {}, len: {} }}",
+               self.platform_id, self.platform_encoding_id, self.format, self.version, self.data.len())
+    }
+}
+
+impl<'a> CharGlyphMappingEncodingTable<'a> {
+    /// Decode the subtable for this encoding
+    pub fn subtable(&self) -> Result<CharGlyphMappingEncodingTableFormat, FontError> {
+        self.read_subtable().map_err(|e| e.in_table(TableTag::CharGlyphMapping))
+    }
+
+    fn read_subtable(&self) -> Result<CharGlyphMappingEncodingTableFormat, FontError> {
+        let mut reader = io::Cursor::new(&self.data[..]);
+        let (format, length, _) = read_subtable_header(&mut reader)?;
+        Ok(match format {
+            0 => {
+                let mut glyph_ids = [0u8; 256];
+                for glyph_id in glyph_ids.iter_mut() {
+                    *glyph_id = reader.read_u8()?;
+                }
+                CharGlyphMappingEncodingTableFormat::ByteEncoding { glyph_ids }
+            },
+            2 => {
+                let mut subheader_keys = [0u16; 256];
+                for key in subheader_keys.iter_mut() {
+                    *key = reader.read_u16::<BigEndian>()?;
+                }
+                // the keys are byte offsets into the subheaders, so the largest one says how many
+                // subheaders there are
+                let num_subheaders = subheader_keys.iter().map(|&k| k / 8).max().unwrap_or(0) as usize + 1;
+                let mut subheaders = Vec::with_capacity(num_subheaders);
+                for _ in 0..num_subheaders {
+                    subheaders.push(HighByteMappingSubheader {
+                        first_code: reader.read_u16::<BigEndian>()?,
+                        entry_count: reader.read_u16::<BigEndian>()?,
+                        id_delta: reader.read_i16::<BigEndian>()?,
+                        id_range_offset: reader.read_u16::<BigEndian>()?
+                    });
+                }
+                let glyph_indices_count = (self.data.len() - reader.position() as usize) / 2;
+                let mut glyph_indices = Vec::with_capacity(glyph_indices_count);
+                for _ in 0..glyph_indices_count {
+                    glyph_indices.push(reader.read_u16::<BigEndian>()?);
+                }
+                CharGlyphMappingEncodingTableFormat::HighByteMapping { subheader_keys, subheaders, glyph_indices }
+            },
+            4 => {
+                let segcount2 = reader.read_u16::<BigEndian>()?;
+                let segcount = (segcount2 / 2) as usize;
+                let search_range = reader.read_u16::<BigEndian>()?;
+                let entry_selector = reader.read_u16::<BigEndian>()?;
+                let range_shift = reader.read_u16::<BigEndian>()?;
+                let mut end_count = Vec::with_capacity(segcount);
+                for _ in 0..segcount {
+                    end_count.push(reader.read_u16::<BigEndian>()?);
+                }
+                reader.read_u16::<BigEndian>()?; //skip reserved padding u16
+                let mut start_count = Vec::with_capacity(segcount);
+                for _ in 0..segcount {
+                    start_count.push(reader.read_u16::<BigEndian>()?);
+                }
+                let mut id_delta = Vec::with_capacity(segcount);
+                for _ in 0..segcount {
+                    id_delta.push(reader.read_u16::<BigEndian>()?);
+                }
+                let mut id_range_offset = Vec::with_capacity(segcount);
+                for _ in 0..segcount {
+                    id_range_offset.push(reader.read_u16::<BigEndian>()?);
+                }
+                let glyph_indices_count = (length as usize).checked_sub(2 * (8 + 4*segcount))
+                    .ok_or(FontError::InconsistentCounts(TableTag::CharGlyphMapping, "segment count does not fit in subtable"))? / 2;
+                let mut glyph_indices = Vec::with_capacity(glyph_indices_count);
+                for _ in 0..glyph_indices_count {
+                    glyph_indices.push(reader.read_u16::<BigEndian>()?);
+                }
+                CharGlyphMappingEncodingTableFormat::SegmentMapToDelta {
+                    seg_countx2: segcount2,
+                    search_range,
+                    entry_selector,
+                    range_shift,
+                    reserved_pad: 0,
+                    start_count,
+                    end_count,
+                    id_delta,
+                    id_range_offset,
+                    glyph_indices
+                }
+            },
+            6 => {
+                let first_code = reader.read_u16::<BigEndian>()?;
+                let entry_count = reader.read_u16::<BigEndian>()?;
+                let mut glyph_indices = Vec::with_capacity(entry_count as usize);
+                for _ in 0..entry_count {
+                    glyph_indices.push(reader.read_u16::<BigEndian>()?);
+                }
+                CharGlyphMappingEncodingTableFormat::Trimmed {
+                    first_code,
+                    entry_count,
+                    glyph_indices
+                }
+            },
+            12 | 13 => {
+                let num_groups = reader.read_u32::<BigEndian>()? as usize;
+                if num_groups > self.data.len().saturating_sub(16) / 12 {
+                    return Err(FontError::Truncated(None));
+                }
+                let mut groups = Vec::with_capacity(num_groups);
+                for _ in 0..num_groups {
+                    let group = SequentialMapGroup {
+                        start_char_code: reader.read_u32::<BigEndian>()?,
+                        end_char_code: reader.read_u32::<BigEndian>()?,
+                        start_glyph_id: reader.read_u32::<BigEndian>()?
+                    };
+                    if group.end_char_code < group.start_char_code
+                        || groups.last().is_some_and(|g: &SequentialMapGroup| g.end_char_code >= group.start_char_code) {
+                        return Err(FontError::InconsistentCounts(TableTag::CharGlyphMapping, "map groups overlap or are out of order"));
+                    }
+                    groups.push(group);
+                }
+                if format == 12 {
+                    CharGlyphMappingEncodingTableFormat::SegmentedCoverage { groups }
+                } else {
+                    CharGlyphMappingEncodingTableFormat::ManyToOneRange { groups }
+                }
+            },
+            14 => {
+                let num_records = reader.read_u32::<BigEndian>()? as usize;
+                if num_records > self.data.len().saturating_sub(10) / 11 {
+                    return Err(FontError::Truncated(None));
+                }
+                let mut records = Vec::with_capacity(num_records);
+                for i in 0..num_records {
+                    reader.seek(io::SeekFrom::Start(10 + 11 * i as u64))?;
+                    let var_selector = read_u24(&mut reader)?;
+                    let default_offset = reader.read_u32::<BigEndian>()?;
+                    let non_default_offset = reader.read_u32::<BigEndian>()?;
+                    let mut default_uvs = Vec::new();
+                    if default_offset != 0 {
+                        reader.seek(io::SeekFrom::Start(default_offset as u64))?;
+                        let count = reader.read_u32::<BigEndian>()?;
+                        for _ in 0..count {
+                            default_uvs.push(UnicodeValueRange {
+                                start_unicode_value: read_u24(&mut reader)?,
+                                additional_count: reader.read_u8()?
+                            });
+                        }
+                    }
+                    let mut non_default_uvs = Vec::new();
+                    if non_default_offset != 0 {
+                        reader.seek(io::SeekFrom::Start(non_default_offset as u64))?;
+                        let count = reader.read_u32::<BigEndian>()?;
+                        for _ in 0..count {
+                            non_default_uvs.push(UvsMapping {
+                                unicode_value: read_u24(&mut reader)?,
+                                glyph_id: reader.read_u16::<BigEndian>()?
+                            });
+                        }
+                    }
+                    records.push(VariationSelectorRecord { var_selector, default_uvs, non_default_uvs });
+                }
+                CharGlyphMappingEncodingTableFormat::UnicodeVariationSequences { records }
+            },
+            _ => return Err(FontError::UnknownFormat(TableTag::CharGlyphMapping, format as u32))
+        })
+    }
+
+    /// How good a fit this subtable is for looking up Unicode characters, lower is better.
+    /// Subtables that can't be used for that give `None`
+    fn unicode_rank(&self) -> Option<u8> {
+        match (self.platform_id, self.platform_encoding_id, self.format) {
+            (_, _, 14) => None,
+            // many to one maps like last resort fonts' map everything in a range to one glyph, so
+            // they are only wanted if there's nothing else
+            (0, _, 13) | (3, 10, 13) => Some(4),
+            // the full Unicode repertoire
+            (0, 4, _) | (0, 6, _) | (3, 10, _) => Some(0),
+            // the Basic Multilingual Plane
+            (0, 0..=3, _) | (3, 1, _) => Some(1),
+            (3, 0, _) => Some(2),
+            (1, 0, _) => Some(3),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct CharGlyphMappingTable {
+pub struct CharGlyphMappingTable<'a> {
     table_version: u16,
-    pub encoding_tables: Vec<CharGlyphMappingEncodingTable>
+    pub encoding_tables: Vec<CharGlyphMappingEncodingTable<'a>>
 }
 
-impl CharGlyphMappingTable {
-    pub fn from_binary<R: Read + Seek>(reader: &mut R, table_offset: u64) -> io::Result<CharGlyphMappingTable> {
+impl<'a> CharGlyphMappingTable<'a> {
+    pub fn from_bytes<D: Into<FontData<'a>>>(data: D) -> Result<CharGlyphMappingTable<'a>, FontError> {
+        let data = data.into();
+        let mut reader = io::Cursor::new(&data[..]);
         let table_version = reader.read_u16::<BigEndian>()?;
         let num_encoding_tables = reader.read_u16::<BigEndian>()?;
         println!("cmap table ver={}, num_tables={}", table_version, num_encoding_tables);
         let mut encoding_tables = Vec::new();
         for i in 0..num_encoding_tables {
-            reader.seek(io::SeekFrom::Start(table_offset + 4 + (8*i) as u64))?;
+            reader.seek(io::SeekFrom::Start(4 + (8*i) as u64))?;
             let plat_id = reader.read_u16::<BigEndian>()?;
             let plat_encode_id = reader.read_u16::<BigEndian>()?;
             let offset = reader.read_u32::<BigEndian>()?;
-            reader.seek(io::SeekFrom::Start(offset as u64 + table_offset))?;
-            let format = reader.read_u16::<BigEndian>()?;
-            let length = reader.read_u16::<BigEndian>()?;
-            let ver = reader.read_u16::<BigEndian>()?;
-            println!("font data for table {}: offset={:X}h -> {:X}h; platid={}; plateid={}; version={}; format={}; len={}", i, offset, offset as u64 + table_offset, plat_id, plat_encode_id, ver, format, length);
+            if offset as usize + 6 > data.len() {
+                return Err(FontError::OffsetOutOfBounds(TableTag::CharGlyphMapping));
+            }
+            reader.seek(io::SeekFrom::Start(offset as u64))?;
+            let (format, length, ver) = read_subtable_header(&mut reader)?;
+            println!("font data for table {}: offset={:X}h; platid={}; plateid={}; version={}; format={}; len={}", i, offset, plat_id, plat_encode_id, ver, format, length);
+            let start = offset as usize;
+            let end = (start + length as usize).min(data.len());
             encoding_tables.push(
                 CharGlyphMappingEncodingTable {
                     platform_id: plat_id,
                     platform_encoding_id: plat_encode_id,
+                    format,
                     version: ver,
-                    subtable: match format {
-                        0 => {
-                            let mut glyph_ids = [0u8; 256];
-                            for i in 0..256 {
-                                glyph_ids[i] = reader.read_u8()?;
-                            }
-                            CharGlyphMappingEncodingTableFormat::ByteEncoding { glyph_ids: glyph_ids }
-                        },
-                        2 => {
-                            return Err(io::Error::new(io::ErrorKind::Other, "Format 2 Unimplemented"));
-                        },
-                        4 => {
-                            let segcount2 = reader.read_u16::<BigEndian>()?;
-                            let segcount = (segcount2 / 2) as usize;
-                            let search_range = reader.read_u16::<BigEndian>()?;
-                            let entry_selector = reader.read_u16::<BigEndian>()?;
-                            let range_shift = reader.read_u16::<BigEndian>()?;
-                            let mut end_count = Vec::with_capacity(segcount);
-                            for _ in 0..segcount {
-                                end_count.push(reader.read_u16::<BigEndian>()?);
-                            }
-                            reader.read_u16::<BigEndian>()?; //skip reserved padding u16
-                            let mut start_count = Vec::with_capacity(segcount);
-                            for _ in 0..segcount {
-                                start_count.push(reader.read_u16::<BigEndian>()?);
-                            }
-                            let mut id_delta = Vec::with_capacity(segcount);
-                            for _ in 0..segcount {
-                                id_delta.push(reader.read_u16::<BigEndian>()?);
-                            }
-                            let mut id_range_offset = Vec::with_capacity(segcount);
-                            for _ in 0..segcount {
-                                id_range_offset.push(reader.read_u16::<BigEndian>()?);
-                            }
-                            let glyph_indices_count = (length as usize - (2 * (8 + 4*segcount))) / 2;
-                            let mut glyph_indices = Vec::with_capacity(glyph_indices_count);
-                            for _ in 0..glyph_indices_count {
-                                glyph_indices.push(reader.read_u16::<BigEndian>()?);
-                            }
-                            CharGlyphMappingEncodingTableFormat::SegmentMapToDelta {
-                                seg_countx2: segcount2,
-                                search_range: search_range,
-                                entry_selector: entry_selector,
-                                range_shift: range_shift,
-                                reserved_pad: 0,
-                                start_count: start_count,
-                                end_count: end_count,
-                                id_delta: id_delta,
-                                id_range_offset: id_range_offset,
-                                glyph_indices: glyph_indices
-                            }
-                        },
-                        6 => {
-                            let first_code = reader.read_u16::<BigEndian>()?;
-                            let entry_count = reader.read_u16::<BigEndian>()?;
-                            let mut glyph_indices = Vec::with_capacity(entry_count as usize);
-                            for _ in 0..entry_count {
-                                glyph_indices.push(reader.read_u16::<BigEndian>()?);
-                            }
-                            CharGlyphMappingEncodingTableFormat::Trimmed {
-                                first_code: first_code,
-                                entry_count: entry_count,
-                                glyph_indices: glyph_indices
-                            }
-                        },
-                        _ => return Err(io::Error::new(io::ErrorKind::Other, "Unknown Format"))
-                    }
+                    data: data.slice(start, end)
                 });
         }
         Ok(CharGlyphMappingTable{table_version:table_version, encoding_tables:encoding_tables})
     }
+
+    /// Find and decode the subtable that is best for looking up Unicode characters. Full
+    /// repertoire subtables are preferred over BMP ones, which are preferred over symbol and Mac
+    /// Roman ones. Subtables that fail to decode are passed over
+    pub fn best_unicode_subtable(&self) -> Option<(&CharGlyphMappingEncodingTable<'a>, CharGlyphMappingEncodingTableFormat)> {
+        let mut candidates = self.encoding_tables.iter()
+            .filter_map(|t| t.unicode_rank().map(|r| (r, t)))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(r, _)| r);
+        candidates.into_iter().filter_map(|(_, t)| t.subtable().ok().map(|s| (t, s))).next()
+    }
+
+    /// Find and decode the subtable for a particular platform and encoding, like (3, 2) for
+    /// Shift-JIS or (3, 4) for Big5
+    pub fn encoding_subtable(&self, platform_id: u16, platform_encoding_id: u16) -> Option<Result<CharGlyphMappingEncodingTableFormat, FontError>> {
+        self.encoding_tables.iter()
+            .find(|t| t.platform_id == platform_id && t.platform_encoding_id == platform_encoding_id && t.format != 14)
+            .map(|t| t.subtable())
+    }
+
+    /// Find and decode the Unicode variation sequences subtable, if there is one
+    pub fn variation_subtable(&self) -> Option<CharGlyphMappingEncodingTableFormat> {
+        self.encoding_tables.iter()
+            .filter(|t| t.platform_id == 0 && t.platform_encoding_id == 5 && t.format == 14)
+            .filter_map(|t| t.subtable().ok())
+            .next()
+    }
 }
 
-impl Table for CharGlyphMappingTable {
+impl<'a> Table for CharGlyphMappingTable<'a> {
     fn tag(&self) -> TableTag { TableTag::CharGlyphMapping }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    // builds a 'cmap' table out of (platform id, encoding id, subtable) triples
+    fn cmap(subtables: &[(u16, u16, Vec<u8>)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(0).unwrap();
+        data.write_u16::<BigEndian>(subtables.len() as u16).unwrap();
+        let mut offset = 4 + 8 * subtables.len();
+        for &(plat, enc, ref st) in subtables {
+            data.write_u16::<BigEndian>(plat).unwrap();
+            data.write_u16::<BigEndian>(enc).unwrap();
+            data.write_u32::<BigEndian>(offset as u32).unwrap();
+            offset += st.len();
+        }
+        for (_, _, st) in subtables {
+            data.extend_from_slice(st);
+        }
+        data
+    }
+
+    fn format4(segments: &[(u16, u16, u16)]) -> Vec<u8> {
+        // (start, end, delta) segments, plus the 0xffff segment every format 4 subtable ends with
+        let mut segs = segments.to_vec();
+        segs.push((0xffff, 0xffff, 1));
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(4).unwrap();
+        data.write_u16::<BigEndian>((16 + 8 * segs.len()) as u16).unwrap();
+        data.write_u16::<BigEndian>(0).unwrap();
+        data.write_u16::<BigEndian>(2 * segs.len() as u16).unwrap();
+        for _ in 0..3 { data.write_u16::<BigEndian>(0).unwrap(); }
+        for s in &segs { data.write_u16::<BigEndian>(s.1).unwrap(); }
+        data.write_u16::<BigEndian>(0).unwrap();
+        for s in &segs { data.write_u16::<BigEndian>(s.0).unwrap(); }
+        for s in &segs { data.write_u16::<BigEndian>(s.2).unwrap(); }
+        for _ in &segs { data.write_u16::<BigEndian>(0).unwrap(); }
+        data
+    }
+
+    fn format12(format: u16, groups: &[(u32, u32, u32)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(format).unwrap();
+        data.write_u16::<BigEndian>(0).unwrap();
+        data.write_u32::<BigEndian>((16 + 12 * groups.len()) as u32).unwrap();
+        data.write_u32::<BigEndian>(0).unwrap();
+        data.write_u32::<BigEndian>(groups.len() as u32).unwrap();
+        for &(start, end, gid) in groups {
+            data.write_u32::<BigEndian>(start).unwrap();
+            data.write_u32::<BigEndian>(end).unwrap();
+            data.write_u32::<BigEndian>(gid).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn segment_map_lookup() {
+        let data = cmap(&[(3, 1, format4(&[(0x20, 0x7e, 0xffe3), (0x3b1, 0x3c9, 0xfd00)]))]);
+        let table = CharGlyphMappingTable::from_bytes(&data[..]).unwrap();
+        let (_, st) = table.best_unicode_subtable().unwrap();
+        assert_eq!(st.glyph_index(0x20), Some(3));
+        assert_eq!(st.glyph_index('A' as u32), Some(36));
+        assert_eq!(st.glyph_index(0x7f), None);
+        assert_eq!(st.glyph_index(0x3b1), Some(0xb1));
+        assert_eq!(st.glyph_index(0x1f600), None);
+    }
+
+    #[test]
+    fn segmented_coverage_lookup() {
+        let data = cmap(&[
+            (3, 1, format4(&[(0x20, 0x7e, 0xffe3)])),
+            (3, 10, format12(12, &[(0x20, 0x7e, 3), (0x1f600, 0x1f64f, 200)])),
+            (0, 6, format12(13, &[(0x4e00, 0x9fff, 7)]))
+        ]);
+        let table = CharGlyphMappingTable::from_bytes(&data[..]).unwrap();
+        // the first full repertoire subtable wins over the BMP one
+        let (enc, st) = table.best_unicode_subtable().unwrap();
+        assert_eq!((enc.platform_id, enc.platform_encoding_id, enc.format), (3, 10, 12));
+        assert_eq!(st.glyph_index('A' as u32), Some(36));
+        assert_eq!(st.glyph_index(0x1f600), Some(200));
+        assert_eq!(st.glyph_index(0x1f64f), Some(279));
+        assert_eq!(st.glyph_index(0x1f650), None);
+        let many = table.encoding_tables[2].subtable().unwrap();
+        assert_eq!(many.glyph_index(0x4e00), Some(7));
+        assert_eq!(many.glyph_index(0x9fff), Some(7));
+        assert_eq!(many.glyph_index(0xa000), None);
+
+        // groups that run past the last 16 bit glyph id stop mapping there
+        let data = cmap(&[
+            (3, 10, format12(12, &[(0x20, 0x7e, 0xfffe), (0x100, 0x1ff, 0xffff_fff0)])),
+            (0, 6, format12(13, &[(0x4e00, 0x9fff, 0x10000)]))
+        ]);
+        let table = CharGlyphMappingTable::from_bytes(&data[..]).unwrap();
+        let st = table.encoding_tables[0].subtable().unwrap();
+        assert_eq!(st.glyph_index(0x21), Some(0xffff));
+        assert_eq!(st.glyph_index(0x22), None);
+        assert_eq!(st.glyph_index(0x1ff), None);
+        assert_eq!(table.encoding_tables[1].subtable().unwrap().glyph_index(0x4e00), None);
+
+        // a many to one subtable loses to the others even when it comes first
+        let data = cmap(&[
+            (0, 6, format12(13, &[(0x4e00, 0x9fff, 7)])),
+            (3, 1, format4(&[(0x20, 0x7e, 0xffe3)])),
+            (3, 10, format12(12, &[(0x20, 0x7e, 3)]))
+        ]);
+        let table = CharGlyphMappingTable::from_bytes(&data[..]).unwrap();
+        let (enc, _) = table.best_unicode_subtable().unwrap();
+        assert_eq!((enc.platform_id, enc.platform_encoding_id, enc.format), (3, 10, 12));
+        let data = cmap(&[
+            (0, 6, format12(13, &[(0x4e00, 0x9fff, 7)])),
+            (3, 1, format4(&[(0x20, 0x7e, 0xffe3)]))
+        ]);
+        let table = CharGlyphMappingTable::from_bytes(&data[..]).unwrap();
+        let (enc, _) = table.best_unicode_subtable().unwrap();
+        assert_eq!((enc.platform_id, enc.platform_encoding_id, enc.format), (3, 1, 4));
+    }
+
+    #[test]
+    fn high_byte_mapping_lookup() {
+        let mut hb = Vec::new();
+        hb.write_u16::<BigEndian>(2).unwrap();
+        hb.write_u16::<BigEndian>(6 + 512 + 16 + 2 * 4).unwrap();
+        hb.write_u16::<BigEndian>(0).unwrap();
+        // 0x82 starts two byte codes and uses subheader 1, everything else is a single byte
+        for i in 0..256 {
+            hb.write_u16::<BigEndian>(if i == 0x82 { 8 } else { 0 }).unwrap();
+        }
+        // subheader 0: 0x41 and 0x42 are glyphs 10 and 11, pointing at glyph index 0
+        for &v in &[0x41u16, 2, 0, 2 + 8] { hb.write_u16::<BigEndian>(v).unwrap(); }
+        // subheader 1: 0x82 0x9f and 0x82 0xa0 are glyphs 20 and 21 through the delta, pointing at
+        // glyph index 2
+        for &v in &[0x9fu16, 2, 10, 2 + 4] { hb.write_u16::<BigEndian>(v).unwrap(); }
+        for &g in &[10u16, 11, 10, 11] { hb.write_u16::<BigEndian>(g).unwrap(); }
+        let data = cmap(&[(3, 2, hb)]);
+        let table = CharGlyphMappingTable::from_bytes(&data[..]).unwrap();
+        assert!(table.best_unicode_subtable().is_none());
+        let st = table.encoding_subtable(3, 2).unwrap().unwrap();
+        assert_eq!(st.glyph_index(0x41), Some(10));
+        assert_eq!(st.glyph_index(0x42), Some(11));
+        assert_eq!(st.glyph_index(0x43), None);
+        assert_eq!(st.glyph_index(0x829f), Some(20));
+        assert_eq!(st.glyph_index(0x82a0), Some(21));
+        assert_eq!(st.glyph_index(0x82a1), None);
+        // 0x82 is a lead byte, so it isn't a character on its own
+        assert_eq!(st.glyph_index(0x82), None);
+        assert_eq!(st.glyph_index(0x419f), None);
+
+        let bytes = [0x41, 0x82, 0xa0, 0x42, 0x82];
+        let mut codes = Vec::new();
+        let mut rest = &bytes[..];
+        while let Some((code, len)) = st.next_code(rest) {
+            codes.push(code);
+            rest = &rest[len..];
+        }
+        assert_eq!(codes, vec![0x41, 0x82a0, 0x42]);
+        assert_eq!(rest, &[0x82]);
+    }
+
+    #[test]
+    fn variation_sequence_lookup() {
+        let mut uvs = Vec::new();
+        uvs.write_u16::<BigEndian>(14).unwrap();
+        uvs.write_u32::<BigEndian>(39).unwrap();
+        uvs.write_u32::<BigEndian>(1).unwrap();
+        uvs.write_uint::<BigEndian>(0xfe0e, 3).unwrap();
+        uvs.write_u32::<BigEndian>(21).unwrap();
+        uvs.write_u32::<BigEndian>(29).unwrap();
+        // default UVS table: U+2600 to U+2603
+        uvs.write_u32::<BigEndian>(1).unwrap();
+        uvs.write_uint::<BigEndian>(0x2600, 3).unwrap();
+        uvs.write_u8(3).unwrap();
+        // non-default UVS table: U+263A gets glyph 12
+        uvs.write_u32::<BigEndian>(1).unwrap();
+        uvs.write_uint::<BigEndian>(0x263a, 3).unwrap();
+        uvs.write_u16::<BigEndian>(12).unwrap();
+        let data = cmap(&[(0, 3, format4(&[(0x2600, 0x26ff, 0)])), (0, 5, uvs)]);
+        let table = CharGlyphMappingTable::from_bytes(&data[..]).unwrap();
+        let vs = table.variation_subtable().unwrap();
+        assert_eq!(vs.variation_glyph_index(0x2603, 0xfe0e), Some(VariationGlyph::UseDefault));
+        assert_eq!(vs.variation_glyph_index(0x2604, 0xfe0e), None);
+        assert_eq!(vs.variation_glyph_index(0x263a, 0xfe0e), Some(VariationGlyph::Glyph(12)));
+        assert_eq!(vs.variation_glyph_index(0x263a, 0xfe0f), None);
+        // the variation sequences subtable is never picked for plain lookups
+        let (enc, _) = table.best_unicode_subtable().unwrap();
+        assert_eq!(enc.format, 4);
+    }
+}
@@ -31,17 +31,35 @@ bitflags! {
         const CGF_2X2_TRANSFORM         = 0b0000_0000_1000_0000,
         const CGF_INSTRUCTIONS_PRESENT  = 0b0000_0001_0000_0000,
         const CGF_USE_METRICS           = 0b0000_0010_0000_0000,
-        const CGF_OVERLAP_COMPOUND      = 0b0000_0100_0000_0000
+        const CGF_OVERLAP_COMPOUND      = 0b0000_0100_0000_0000,
+        const CGF_SCALED_OFFSET         = 0b0000_1000_0000_0000,
+        const CGF_UNSCALED_OFFSET       = 0b0001_0000_0000_0000
     }
 }
 
+impl Transformation {
+    fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        fn f(v: F2dot14) -> f32 { v.bits as f32 / 16384.0 }
+        match *self {
+            Transformation::Uniform(s) => (x*f(s), y*f(s)),
+            Transformation::XY(sx, sy) => (x*f(sx), y*f(sy)),
+            Transformation::Mat2x2 { xscale, scale01, scale10, yscale } =>
+                (x*f(xscale) + y*f(scale10), x*f(scale01) + y*f(yscale))
+        }
+    }
+}
+
+/// One component of a composite glyph. If the args are XY they are an offset in font units,
+/// otherwise they are the index of a point in the glyph so far and the index of a point in the
+/// component that should be moved onto it
 #[derive(Debug)]
 pub struct ComponentGlyphDescription {
-    glyph_index: u16,
-    arg1: u16,
-    arg2: u16,
-    transform: Transformation,
-    use_metrics: bool
+    pub glyph_index: u16,
+    pub arg1: i32,
+    pub arg2: i32,
+    pub transform: Transformation,
+    pub use_metrics: bool,
+    flags: ComponentGlyphFlags
 }
 
 
@@ -98,8 +116,8 @@ impl Debug for GlyphDescription {
 }
 
 impl GlyphDescription {
-    fn from_binary<R: Read+Seek>(reader: &mut R, num_points: usize, glyph_length: usize) -> io::Result<GlyphDescription> {
-        //println!("reading glyph p{} l{}", num_points, glyph_length);
+    fn from_binary<R: Read+Seek>(reader: &mut R, glyph_length: usize) -> Result<GlyphDescription, FontError> {
+        //println!("reading glyph l{}", glyph_length);
         if glyph_length == 0 { println!("0-len glyph?"); return Ok(GlyphDescription::None); }
         let num_contours = reader.read_i16::<BigEndian>()?;
         //println!("num contours = {}", num_contours);
@@ -117,22 +135,26 @@ impl GlyphDescription {
             let num_instr = reader.read_u16::<BigEndian>()?;
             let mut instr = vec![0u8; num_instr as usize];
             reader.read_exact(instr.as_mut_slice())?;
-            let mut data = vec![0u8; glyph_length];//-(10+epoc.len()*2+instr.len())]; // TODO: the loader seems to consistantly read more than what would be expected from this buffer, so it reads more than should be necessary to allow that. Questionable indeed.
-            reader.read_exact(data.as_mut_slice())?;
-            let mut points = Vec::new();
+            // the flags and coordinates take up the rest of the glyph
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            let n = (epoc[epoc.len()-1] as usize)+1;
+            if epoc.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(FontError::InconsistentCounts(TableTag::GlyphData, "contour end points are not increasing"));
+            }
+            let mut points = Vec::with_capacity(n);
             let mut i: usize = 0;
-            let n = (epoc[epoc.len()-1]+1) as usize; //this seems to be a guess found in STB's truetype loader
-            while i < data.len() {
-                let d0 = data[i];
+            while points.len() < n {
+                let d0 = *data.get(i).ok_or(FontError::Truncated(None))?;
                 let flag = GlyphPointFlags::from_bits_truncate(d0);
                 //println!("{} point [ flags = {:b}/{:?} ]", points.len(), d0, flag);
                 i += 1;
                 let repeat_count = 
                     if flag.intersects(GP_Repeat) {
-                        let v = data[i];
+                        let v = *data.get(i).ok_or(FontError::Truncated(None))?;
                         //println!("repeat = {}", v);
                         i += 1;
-                        v + 1
+                        v as usize + 1
                     } else {
                         1
                     };
@@ -140,38 +162,35 @@ impl GlyphDescription {
                     points.push(GlyphPoint{on_curve: flag.intersects(GP_OnCurve), x: 0, y: 0, flag: flag });
                     if points.len() >= n { break; }
                 }
-                if points.len() >= n { break; }
             }
             //println!("found {} points of {}, ifl = {}, d.l = {}, left={}", points.len(), n, i, data.len(), data.len()-i);
-            assert!(points.len() < data.len(), "absurd number of points!");
 
-            fn load_vec(data: &Vec<u8>, i: &mut usize, last: &mut i32, short_vec: bool, sameorsign: bool) -> i32 {
+            fn load_vec(data: &[u8], i: &mut usize, last: &mut i32, short_vec: bool, sameorsign: bool) -> Result<i32, FontError> {
                 if short_vec {
-                    let v = (data[*i] as i32) * if sameorsign {1} else {-1};
+                    let v = (*data.get(*i).ok_or(FontError::Truncated(None))? as i32) * if sameorsign {1} else {-1};
                     *last += v;
                     *i += 1;
                 } else if !sameorsign {
-                    let v = (data[*i] as u16)*256 + data[(*i) + 1] as u16;
-                    *last = last.wrapping_add((v as i16) as i32);
-                    assert!(last.abs() < 30000);
+                    let v = BigEndian::read_i16(data.get(*i..*i+2).ok_or(FontError::Truncated(None))?);
+                    *last = last.wrapping_add(v as i32);
                     *i += 2;
                     //print!("2");
                 } //else { print!("!!! "); }
                 //println!("i{} v{}", *i, *last); 
-                *last
+                Ok(*last)
             }
 
             let mut last: i32 = 0;
             for mut p in &mut points {
                 //if p.flag.intersects(GP_Repeat) { /*print!("REP ");*/ }
-                p.x = load_vec(&data, &mut i, &mut last, p.flag.intersects(GP_XShortVec), p.flag.intersects(GP_XSameOrVecSign));
+                p.x = load_vec(&data, &mut i, &mut last, p.flag.intersects(GP_XShortVec), p.flag.intersects(GP_XSameOrVecSign))?;
             }
             //println!("---");
             last = 0;
             let mut count = 0;
             for mut p in &mut points {
                 //if p.flag.intersects(GP_Repeat) { print!("REP "); }
-                p.y = load_vec(&data, &mut i, &mut last, p.flag.intersects(GP_YShortVec), p.flag.intersects(GP_YSameOrVecSign));
+                p.y = load_vec(&data, &mut i, &mut last, p.flag.intersects(GP_YShortVec), p.flag.intersects(GP_YSameOrVecSign))?;
                 count+=1;
                 //print!("c{} ", count);
             }
@@ -192,12 +211,13 @@ impl GlyphDescription {
                 let flags = ComponentGlyphFlags::from_bits_truncate(reader.read_u16::<BigEndian>()?);
                 //println!("flags = {:?}", flags);
                 let ix = reader.read_u16::<BigEndian>()?;
+                // offsets are signed, point numbers are not
                 let (arg1, arg2) =
-                    if flags.intersects(CGF_ARGS_ARE_WORDS) {
-                        (reader.read_u16::<BigEndian>()?, reader.read_u16::<BigEndian>()?)
-                    } else {
-                        let arg12 = reader.read_u8()?;
-                        (arg12 as u16 >> 8, arg12 as u16 & 0x00ff)
+                    match (flags.intersects(CGF_ARGS_ARE_WORDS), flags.intersects(CGF_ARGS_ARE_XY)) {
+                        (true, true) => (reader.read_i16::<BigEndian>()? as i32, reader.read_i16::<BigEndian>()? as i32),
+                        (true, false) => (reader.read_u16::<BigEndian>()? as i32, reader.read_u16::<BigEndian>()? as i32),
+                        (false, true) => (reader.read_i8()? as i32, reader.read_i8()? as i32),
+                        (false, false) => (reader.read_u8()? as i32, reader.read_u8()? as i32)
                     };
                 let tf = if flags.intersects(CGF_SIMPLE_SCALE) {
                     Transformation::Uniform(F2dot14::new(reader.read_i16::<BigEndian>()?))
@@ -221,7 +241,8 @@ impl GlyphDescription {
                     glyph_index: ix,
                     arg1: arg1, arg2: arg2,
                     transform: tf,
-                    use_metrics: flags.intersects(CGF_USE_METRICS)
+                    use_metrics: flags.intersects(CGF_USE_METRICS),
+                    flags
                 });
 
                 if flags.intersects(CGF_INSTRUCTIONS_PRESENT) {
@@ -237,7 +258,7 @@ impl GlyphDescription {
                 let mut i = vec![0u8; num_instr as usize];
                 reader.read_exact(i.as_mut_slice())?;
                 i
-            } else { /*println!("no instrs");*/ vec![0u8,0] };
+            } else { /*println!("no instrs");*/ Vec::new() };
             Ok(GlyphDescription::Composite{components:components,instructions:instr})
         } else { //this might be invalid, you might be supposed to read a single glyph anyway, but i fail to see how there
                  //could be glyph data if there are no contours
@@ -247,28 +268,140 @@ impl GlyphDescription {
     }
 }
 
-// apparently this table is useless
-#[derive(Debug)]
-pub struct GlyphDataTable {
-    pub glyphs: Vec<GlyphDescription>
+/// How deeply composite glyphs may nest whatever 'maxp' says, so a font can't make resolving a
+/// glyph recurse far enough to run out of stack
+pub const MAX_COMPONENT_DEPTH: u16 = 16;
 
+/// The 'glyf' table, left in the font data. Glyphs are decoded one at a time using the offsets
+/// in the 'loca' table
+#[derive(Clone)]
+pub struct GlyphDataTable<'a> {
+    data: FontData<'a>,
+    loca_table: LocationTable<'a>
 }
 
-impl Table for GlyphDataTable {
+impl<'a> Debug for GlyphDataTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GlyphDataTable [len = {}]", self.loca_table.num_glyphs())
+    }
+}
+
+impl<'a> Table for GlyphDataTable<'a> {
     fn tag(&self) -> TableTag { TableTag::GlyphData }
 }
 
 
-impl GlyphDataTable {
-    /// This function reads a 'glyf' table from a file, assymbling the glyphs togther as it goes
-    /// using data from the 'loca' table
-    pub fn from_binary<R: Read+Seek>(reader: &mut R, table_start: u64, maxp_table: MaxProfileTable, loca_table: &LocationTable) -> io::Result<GlyphDataTable> {
-        let mut glyphs = Vec::new();
-        for glyph_ix in loca_table.offsets.windows(2) {
-            //println!("glyph_ix = {:?}", glyph_ix);
-            reader.seek(io::SeekFrom::Start(table_start + glyph_ix[0] as u64))?;
-            glyphs.push(GlyphDescription::from_binary(reader, maxp_table.num_points as usize, (glyph_ix[1]-glyph_ix[0]) as usize)?);
+impl<'a> GlyphDataTable<'a> {
+    pub fn from_bytes(data: FontData<'a>, loca_table: LocationTable<'a>) -> GlyphDataTable<'a> {
+        GlyphDataTable { data, loca_table }
+    }
+
+    pub fn len(&self) -> usize {
+        self.loca_table.num_glyphs()
+    }
+
+    /// Decodes a single glyph, reading only the bytes that belong to it
+    pub fn glyph(&self, glyph_index: usize) -> Result<GlyphDescription, FontError> {
+        let (start, end) = self.loca_table.glyph_range(glyph_index)
+            .ok_or(FontError::GlyphOutOfRange(glyph_index))?;
+        if start > end || end > self.data.len() {
+            return Err(FontError::OffsetOutOfBounds(TableTag::GlyphData));
         }
-        Ok(GlyphDataTable {glyphs: glyphs})
+        GlyphDescription::from_binary(&mut io::Cursor::new(&self.data[start..end]), end-start)
+            .map_err(|e| e.in_table(TableTag::GlyphData))
+    }
+
+    /// Decodes a glyph, flattening composite glyphs into a single simple glyph by recursively
+    /// resolving their components. Components may themselves be composites up to `max_depth`
+    /// levels deep, but never deeper than `MAX_COMPONENT_DEPTH`, and may not contain the glyph
+    /// they are part of. `grid_scale` is the scale from font units to pixels, if known, and is used to
+    /// round component offsets to the pixel grid when a component asks for it
+    pub fn resolved_glyph(&self, glyph_index: usize, max_depth: u16, grid_scale: Option<f32>) -> Result<GlyphDescription, FontError> {
+        match self.glyph(glyph_index)? {
+            GlyphDescription::Composite { components, instructions } => {
+                let mut end_points_of_contours = Vec::new();
+                let mut points = Vec::new();
+                let mut path = vec![glyph_index];
+                self.append_components(&components, max_depth.min(MAX_COMPONENT_DEPTH), grid_scale, &mut path,
+                                       &mut end_points_of_contours, &mut points)?;
+                if points.is_empty() {
+                    return Ok(GlyphDescription::None);
+                }
+                Ok(GlyphDescription::Simple {
+                    num_contours: end_points_of_contours.len() as u16,
+                    x_min: points.iter().map(|p| p.x).min().unwrap_or(0) as i16,
+                    y_min: points.iter().map(|p| p.y).min().unwrap_or(0) as i16,
+                    x_max: points.iter().map(|p| p.x).max().unwrap_or(0) as i16,
+                    y_max: points.iter().map(|p| p.y).max().unwrap_or(0) as i16,
+                    end_points_of_contours, instructions, points
+                })
+            },
+            g => Ok(g)
+        }
+    }
+
+    /// `path` is the glyphs being resolved that lead to these components
+    fn append_components(&self, components: &[ComponentGlyphDescription], depth_left: u16, grid_scale: Option<f32>,
+                         path: &mut Vec<usize>, end_points: &mut Vec<u16>, points: &mut Vec<GlyphPoint>) -> Result<(), FontError> {
+        if depth_left == 0 {
+            return Err(FontError::InconsistentCounts(TableTag::GlyphData, "composite glyph nested deeper than maxp allows"));
+        }
+        for component in components {
+            let glyph_index = component.glyph_index as usize;
+            if path.contains(&glyph_index) {
+                return Err(FontError::InconsistentCounts(TableTag::GlyphData, "composite glyph contains itself"));
+            }
+            let mut cend_points = Vec::new();
+            let mut cpoints = Vec::new();
+            match self.glyph(glyph_index)? {
+                GlyphDescription::Simple { end_points_of_contours, points, .. } => {
+                    cend_points = end_points_of_contours;
+                    cpoints = points;
+                },
+                GlyphDescription::Composite { components, .. } => {
+                    path.push(glyph_index);
+                    self.append_components(&components, depth_left-1, grid_scale, path, &mut cend_points, &mut cpoints)?;
+                    path.pop();
+                },
+                GlyphDescription::None => {}
+            }
+
+            let transformed: Vec<(f32, f32)> = cpoints.iter().map(|p| component.transform.apply(p.x as f32, p.y as f32)).collect();
+            let (dx, dy) = if component.flags.intersects(CGF_ARGS_ARE_XY) {
+                let (mut dx, mut dy) = if component.flags.intersects(CGF_SCALED_OFFSET) && !component.flags.intersects(CGF_UNSCALED_OFFSET) {
+                    component.transform.apply(component.arg1 as f32, component.arg2 as f32)
+                } else {
+                    (component.arg1 as f32, component.arg2 as f32)
+                };
+                if let (true, Some(scale)) = (component.flags.intersects(CGF_ROUND_XY_TO_GRID), grid_scale) {
+                    dx = (dx*scale).round()/scale;
+                    dy = (dy*scale).round()/scale;
+                }
+                (dx, dy)
+            } else {
+                // point matching: move the component so that its point arg2 lands on point arg1 of
+                // the glyph so far
+                let parent = points.get(component.arg1 as usize)
+                    .ok_or(FontError::InconsistentCounts(TableTag::GlyphData, "component anchor point out of range"))?;
+                let child = transformed.get(component.arg2 as usize)
+                    .ok_or(FontError::InconsistentCounts(TableTag::GlyphData, "component anchor point out of range"))?;
+                (parent.x as f32 - child.0, parent.y as f32 - child.1)
+            };
+
+            let base = points.len();
+            if base + cpoints.len() > u16::MAX as usize {
+                return Err(FontError::InconsistentCounts(TableTag::GlyphData, "too many points in composite glyph"));
+            }
+            for (p, &(x, y)) in cpoints.iter().zip(transformed.iter()) {
+                points.push(GlyphPoint { x: (x+dx).round() as i32, y: (y+dy).round() as i32, .. *p });
+            }
+            end_points.extend(cend_points.iter().map(|&e| e + base as u16));
+        }
+        Ok(())
+    }
+
+    /// Decodes every glyph in the table at once
+    pub fn glyphs(&self) -> Result<Vec<GlyphDescription>, FontError> {
+        (0..self.len()).map(|i| self.glyph(i)).collect()
     }
 }
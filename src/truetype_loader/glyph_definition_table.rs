@@ -0,0 +1,185 @@
+use super::*;
+use super::layout_common::*;
+
+/// Where the caret goes between two components of a ligature
+#[derive(Clone, Debug)]
+pub enum CaretValue {
+    /// A position along the line in font units, which a device table may adjust
+    Coordinate { coordinate: i16, device: Option<Device> },
+    /// The position of a point in the glyph's outline
+    ContourPoint(u16)
+}
+
+impl CaretValue {
+    fn from_bytes(data: &[u8]) -> Result<CaretValue, FontError> {
+        match read_u16(data, 0)? {
+            1 => Ok(CaretValue::Coordinate { coordinate: read_i16(data, 2)?, device: None }),
+            2 => Ok(CaretValue::ContourPoint(read_u16(data, 2)?)),
+            3 => Ok(CaretValue::Coordinate {
+                coordinate: read_i16(data, 2)?,
+                device: Device::from_offset(data, read_u16(data, 4)? as usize)?
+            }),
+            f => Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        }
+    }
+}
+
+/// Reads a coverage followed by a list of offsets to something for each covered glyph, as in the
+/// attach and ligature caret lists
+fn read_coverage_and_offsets(data: &[u8]) -> Result<(Coverage, Vec<&[u8]>), FontError> {
+    let coverage = Coverage::from_bytes(subtable(data, read_u16(data, 0)? as usize)?)?;
+    let offsets = read_u16_array(data, 2)?;
+    let tables = offsets.into_iter().map(|o| subtable(data, o as usize)).collect::<Result<Vec<_>, _>>()?;
+    Ok((coverage, tables))
+}
+
+/// The 'GDEF' table, which sorts glyphs into the classes that layout lookup flags refer to, and
+/// has attachment points and ligature carets for each glyph
+#[derive(Clone, Debug)]
+pub struct GlyphDefinitionTable {
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// Classes are the `GLYPH_CLASS_` constants
+    pub glyph_classes: ClassDef,
+    /// Contour points on glyphs that other glyphs may be attached to, indexed by coverage
+    pub attach_coverage: Coverage,
+    pub attach_points: Vec<Vec<u16>>,
+    /// Caret positions inside ligatures, indexed by coverage
+    pub lig_caret_coverage: Coverage,
+    pub lig_carets: Vec<Vec<CaretValue>>,
+    pub mark_attach_classes: ClassDef,
+    /// Sets of marks that lookups with `LF_USE_MARK_FILTERING_SET` may be limited to. Only in
+    /// version 1.2 and later
+    pub mark_glyph_sets: Vec<Coverage>
+}
+
+impl Table for GlyphDefinitionTable {
+    fn tag(&self) -> TableTag { TableTag::GlyphDefinition }
+}
+
+impl GlyphDefinitionTable {
+    pub fn from_bytes(data: &[u8]) -> Result<GlyphDefinitionTable, FontError> {
+        let major_version = read_u16(data, 0)?;
+        let minor_version = read_u16(data, 2)?;
+        if major_version != 1 {
+            return Err(FontError::UnknownFormat(TableTag::GlyphDefinition, major_version as u32));
+        }
+
+        let (attach_coverage, attach_points) = match optional_subtable(data, read_u16(data, 6)? as usize)? {
+            Some(list) => {
+                let (coverage, tables) = read_coverage_and_offsets(list)?;
+                (coverage, tables.into_iter().map(|t| read_u16_array(t, 0)).collect::<Result<Vec<_>, _>>()?)
+            },
+            None => (Coverage::Glyphs(Vec::new()), Vec::new())
+        };
+
+        let (lig_caret_coverage, lig_carets) = match optional_subtable(data, read_u16(data, 8)? as usize)? {
+            Some(list) => {
+                let (coverage, tables) = read_coverage_and_offsets(list)?;
+                let mut carets = Vec::with_capacity(tables.len());
+                for lig in tables {
+                    carets.push(read_u16_array(lig, 0)?.into_iter()
+                                .map(|o| CaretValue::from_bytes(subtable(lig, o as usize)?))
+                                .collect::<Result<Vec<_>, _>>()?);
+                }
+                (coverage, carets)
+            },
+            None => (Coverage::Glyphs(Vec::new()), Vec::new())
+        };
+
+        let mut mark_glyph_sets = Vec::new();
+        if minor_version >= 2 {
+            if let Some(sets) = optional_subtable(data, read_u16(data, 12)? as usize)? {
+                let format = read_u16(sets, 0)?;
+                if format != 1 {
+                    return Err(FontError::UnknownFormat(TableTag::GlyphDefinition, format as u32));
+                }
+                // these offsets are 32 bits, unlike every other offset in the table
+                for i in 0..read_u16(sets, 2)? as usize {
+                    mark_glyph_sets.push(Coverage::from_bytes(subtable(sets, read_u32(sets, 4 + 4 * i)? as usize)?)?);
+                }
+            }
+        }
+
+        Ok(GlyphDefinitionTable {
+            major_version, minor_version,
+            glyph_classes: ClassDef::from_offset(data, read_u16(data, 4)? as usize)?,
+            attach_coverage, attach_points,
+            lig_caret_coverage, lig_carets,
+            mark_attach_classes: ClassDef::from_offset(data, read_u16(data, 10)? as usize)?,
+            mark_glyph_sets
+        })
+    }
+
+    /// The contour points other glyphs may be attached to on a glyph
+    pub fn attach_points(&self, glyph: u16) -> &[u16] {
+        self.attach_coverage.index(glyph).and_then(|i| self.attach_points.get(i)).map(|p| &p[..]).unwrap_or(&[])
+    }
+
+    /// The carets between the components of a ligature glyph, one less than the number of
+    /// components
+    pub fn lig_carets(&self, glyph: u16) -> &[CaretValue] {
+        self.lig_caret_coverage.index(glyph).and_then(|i| self.lig_carets.get(i)).map(|c| &c[..]).unwrap_or(&[])
+    }
+}
+
+impl GlyphClassifier for GlyphDefinitionTable {
+    fn glyph_class(&self, glyph: u16) -> u16 {
+        self.glyph_classes.class(glyph)
+    }
+
+    fn mark_attachment_class(&self, glyph: u16) -> u16 {
+        self.mark_attach_classes.class(glyph)
+    }
+
+    fn is_in_mark_glyph_set(&self, set: u16, glyph: u16) -> bool {
+        self.mark_glyph_sets.get(set as usize).map(|c| c.contains(glyph)).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::layout_common::test_tables::*;
+
+    #[test]
+    fn gdef() {
+        // version 1.2 with every list
+        let mut data = words(&[1, 2, 14, 28, 46, 72, 80]);
+        // glyph classes at 14: glyphs 1-2 are bases, 3 is a ligature, 4 is a mark
+        data.extend(words(&[1, 1, 4, 1, 1, 2, 3]));
+        // attach list at 28, glyph 1 has points 5 and 7
+        data.extend(words(&[6, 1, 12]));
+        data.extend(words(&[1, 1, 1]));
+        data.extend(words(&[2, 5, 7]));
+        // ligature caret list at 46, glyph 3 has a coordinate caret and a contour point caret
+        data.extend(words(&[6, 1, 12]));
+        data.extend(words(&[1, 1, 3]));
+        data.extend(words(&[2, 6, 10, 1, 250, 2, 9]));
+        // mark attachment classes at 72
+        data.extend(words(&[1, 4, 1, 2]));
+        // mark glyph sets at 80, with one set holding glyph 4
+        data.extend(words(&[1, 1, 0, 8]));
+        data.extend(words(&[1, 1, 4]));
+
+        let gdef = GlyphDefinitionTable::from_bytes(&data).unwrap();
+        let classes: Vec<_> = (0..6).map(|g| gdef.glyph_class(g)).collect();
+        assert_eq!(classes, vec![0, GLYPH_CLASS_BASE, GLYPH_CLASS_BASE, GLYPH_CLASS_LIGATURE, GLYPH_CLASS_MARK, 0]);
+        assert_eq!(gdef.attach_points(1), &[5, 7]);
+        assert!(gdef.attach_points(2).is_empty());
+        match gdef.lig_carets(3) {
+            &[CaretValue::Coordinate { coordinate: 250, device: None }, CaretValue::ContourPoint(9)] => {},
+            c => panic!("unexpected carets {:?}", c)
+        }
+        assert_eq!(gdef.mark_attachment_class(4), 2);
+        assert!(gdef.is_in_mark_glyph_set(0, 4));
+        assert!(!gdef.is_in_mark_glyph_set(0, 3));
+        assert!(!gdef.is_in_mark_glyph_set(1, 4));
+
+        // lookups that ignore marks or only use one attachment class skip glyphs by their class
+        let lookup: Lookup<()> = Lookup { lookup_type: 1, flags: LF_IGNORE_MARKS, mark_filtering_set: None, subtables: vec![] };
+        assert!(lookup.ignores(&gdef, 4) && !lookup.ignores(&gdef, 1));
+        let lookup: Lookup<()> = Lookup { lookup_type: 1, flags: LookupFlags::from_bits_truncate(0x0100), mark_filtering_set: None, subtables: vec![] };
+        assert!(lookup.ignores(&gdef, 4));
+    }
+}
@@ -0,0 +1,676 @@
+use super::*;
+use super::layout_common::*;
+
+bitflags! {
+    /// Which fields a value record has. Records only store the fields their format has, in this
+    /// order
+    pub flags ValueFormat: u16 {
+        const VF_X_PLACEMENT        = 0x0001,
+        const VF_Y_PLACEMENT        = 0x0002,
+        const VF_X_ADVANCE          = 0x0004,
+        const VF_Y_ADVANCE          = 0x0008,
+        const VF_X_PLACEMENT_DEVICE = 0x0010,
+        const VF_Y_PLACEMENT_DEVICE = 0x0020,
+        const VF_X_ADVANCE_DEVICE   = 0x0040,
+        const VF_Y_ADVANCE_DEVICE   = 0x0080
+    }
+}
+
+/// Where a glyph ends up after positioning, in font units. The advances start out as the glyph's
+/// metrics and the offsets move the glyph without moving the glyphs after it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlyphPosition {
+    pub x_advance: i32,
+    pub y_advance: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+    /// The glyph this one was attached to by a mark or cursive lookup. The offsets are from that
+    /// glyph until `resolve_attachments` is called
+    pub attachment: Option<Attachment>
+}
+
+impl GlyphPosition {
+    pub fn new(x_advance: i32, y_advance: i32) -> GlyphPosition {
+        GlyphPosition { x_advance, y_advance, ..GlyphPosition::default() }
+    }
+}
+
+/// The index of the glyph another glyph is attached to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Attachment {
+    Mark(usize),
+    Cursive(usize)
+}
+
+/// What positioning lookups need to know about the text they are applied to
+#[derive(Copy, Clone, Debug)]
+pub struct PositioningOptions {
+    /// The size in pixels per em that device tables adjust for, or 0 to leave them out
+    pub ppem: u16,
+    pub units_per_em: u16,
+    pub right_to_left: bool
+}
+
+fn device_adjustment(device: &Option<Device>, options: &PositioningOptions) -> i32 {
+    device.as_ref().map(|d| d.adjustment(options.ppem, options.units_per_em)).unwrap_or(0)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ValueRecord {
+    pub x_placement: i16,
+    pub y_placement: i16,
+    pub x_advance: i16,
+    pub y_advance: i16,
+    pub x_placement_device: Option<Device>,
+    pub y_placement_device: Option<Device>,
+    pub x_advance_device: Option<Device>,
+    pub y_advance_device: Option<Device>
+}
+
+impl ValueRecord {
+    fn size(format: ValueFormat) -> usize {
+        format.bits().count_ones() as usize * 2
+    }
+
+    /// Reads a record at `offset` in `base`, which is the subtable its device offsets are from
+    fn from_bytes(base: &[u8], offset: usize, format: ValueFormat) -> Result<ValueRecord, FontError> {
+        let mut fields = [0u16; 8];
+        let mut offset = offset;
+        for (bit, field) in fields.iter_mut().enumerate() {
+            if format.bits() & (1 << bit) != 0 {
+                *field = read_u16(base, offset)?;
+                offset += 2;
+            }
+        }
+        Ok(ValueRecord {
+            x_placement: fields[0] as i16,
+            y_placement: fields[1] as i16,
+            x_advance: fields[2] as i16,
+            y_advance: fields[3] as i16,
+            x_placement_device: Device::from_offset(base, fields[4] as usize)?,
+            y_placement_device: Device::from_offset(base, fields[5] as usize)?,
+            x_advance_device: Device::from_offset(base, fields[6] as usize)?,
+            y_advance_device: Device::from_offset(base, fields[7] as usize)?
+        })
+    }
+
+    pub fn apply(&self, pos: &mut GlyphPosition, options: &PositioningOptions) {
+        pos.x_offset += self.x_placement as i32 + device_adjustment(&self.x_placement_device, options);
+        pos.y_offset += self.y_placement as i32 + device_adjustment(&self.y_placement_device, options);
+        pos.x_advance += self.x_advance as i32 + device_adjustment(&self.x_advance_device, options);
+        pos.y_advance += self.y_advance as i32 + device_adjustment(&self.y_advance_device, options);
+    }
+}
+
+/// A point on a glyph that another glyph can be attached at
+#[derive(Clone, Debug)]
+pub struct Anchor {
+    pub x: i16,
+    pub y: i16,
+    /// An outline point that hinting may move the anchor with
+    pub anchor_point: Option<u16>,
+    pub x_device: Option<Device>,
+    pub y_device: Option<Device>
+}
+
+impl Anchor {
+    fn from_bytes(data: &[u8]) -> Result<Anchor, FontError> {
+        let format = read_u16(data, 0)?;
+        let (x, y) = (read_i16(data, 2)?, read_i16(data, 4)?);
+        match format {
+            1 => Ok(Anchor { x, y, anchor_point: None, x_device: None, y_device: None }),
+            2 => Ok(Anchor { x, y, anchor_point: Some(read_u16(data, 6)?), x_device: None, y_device: None }),
+            3 => Ok(Anchor {
+                x, y, anchor_point: None,
+                x_device: Device::from_offset(data, read_u16(data, 6)? as usize)?,
+                y_device: Device::from_offset(data, read_u16(data, 8)? as usize)?
+            }),
+            f => Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        }
+    }
+
+    fn from_offset(data: &[u8], offset: usize) -> Result<Option<Anchor>, FontError> {
+        match optional_subtable(data, offset)? {
+            Some(d) => Anchor::from_bytes(d).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Where the anchor is in font units. Outlines aren't hinted before layout, so the anchor
+    /// point is left out
+    pub fn position(&self, options: &PositioningOptions) -> (i32, i32) {
+        (self.x as i32 + device_adjustment(&self.x_device, options),
+         self.y as i32 + device_adjustment(&self.y_device, options))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MarkRecord {
+    pub class: u16,
+    pub anchor: Anchor
+}
+
+fn read_mark_array(data: &[u8]) -> Result<Vec<MarkRecord>, FontError> {
+    let count = read_u16(data, 0)? as usize;
+    let mut marks = Vec::with_capacity(count);
+    for i in 0..count {
+        marks.push(MarkRecord {
+            class: read_u16(data, 2 + 4 * i)?,
+            anchor: Anchor::from_bytes(subtable(data, read_u16(data, 4 + 4 * i)? as usize)?)?
+        });
+    }
+    Ok(marks)
+}
+
+/// Reads rows of anchors with one anchor per mark class, as in base and mark2 arrays and ligature
+/// attach tables. Missing anchors have zero offsets
+fn read_anchor_rows(data: &[u8], class_count: usize) -> Result<Vec<Vec<Option<Anchor>>>, FontError> {
+    let count = read_u16(data, 0)? as usize;
+    let offsets = read_u16s(data, 2, count * class_count)?;
+    let mut rows = Vec::with_capacity(count);
+    for row in offsets.chunks(class_count.max(1)).take(count) {
+        rows.push(row.iter().map(|&o| Anchor::from_offset(data, o as usize)).collect::<Result<Vec<_>, _>>()?);
+    }
+    Ok(rows)
+}
+
+#[derive(Clone, Debug)]
+pub struct PairValueRecord {
+    pub second_glyph: u16,
+    pub value1: ValueRecord,
+    pub value2: ValueRecord
+}
+
+#[derive(Clone, Debug)]
+pub enum PositioningSubtable {
+    /// Adjusts each covered glyph. Format 1 subtables adjust every glyph the same way and only
+    /// have one value
+    Single { coverage: Coverage, values: Vec<ValueRecord> },
+    /// Adjusts pairs of glyphs. Pair sets are indexed by the coverage index of the first glyph,
+    /// and are sorted by the second
+    PairGlyphs { coverage: Coverage, value_format2: ValueFormat, pair_sets: Vec<Vec<PairValueRecord>> },
+    /// Adjusts pairs of glyph classes. The values are in rows of `class2_count` for each class of
+    /// the first glyph
+    PairClasses {
+        coverage: Coverage,
+        value_format2: ValueFormat,
+        class_def1: ClassDef,
+        class_def2: ClassDef,
+        class2_count: u16,
+        values: Vec<(ValueRecord, ValueRecord)>
+    },
+    /// Connects the exit anchor of a glyph to the entry anchor of the next
+    Cursive { coverage: Coverage, entry_exits: Vec<(Option<Anchor>, Option<Anchor>)> },
+    MarkToBase { mark_coverage: Coverage, base_coverage: Coverage, marks: Vec<MarkRecord>, bases: Vec<Vec<Option<Anchor>>> },
+    /// Ligatures have a row of anchors for each of their components
+    MarkToLigature {
+        mark_coverage: Coverage,
+        ligature_coverage: Coverage,
+        marks: Vec<MarkRecord>,
+        ligatures: Vec<Vec<Vec<Option<Anchor>>>>
+    },
+    MarkToMark { mark1_coverage: Coverage, mark2_coverage: Coverage, marks: Vec<MarkRecord>, mark2s: Vec<Vec<Option<Anchor>>> },
+    Context(ContextLookup),
+    ChainedContext(ContextLookup),
+    /// Lookup types from later versions of the spec are kept so that lookup indices still line up
+    Unsupported(u16)
+}
+
+impl PositioningSubtable {
+    pub fn from_bytes(lookup_type: u16, data: &[u8]) -> Result<PositioningSubtable, FontError> {
+        let format = read_u16(data, 0)?;
+        let coverage_at = |offset: usize| -> Result<Coverage, FontError> {
+            Coverage::from_bytes(subtable(data, read_u16(data, offset)? as usize)?)
+        };
+        match (lookup_type, format) {
+            (1, 1) => {
+                let value_format = ValueFormat::from_bits_truncate(read_u16(data, 4)?);
+                Ok(PositioningSubtable::Single {
+                    coverage: coverage_at(2)?,
+                    values: vec![ValueRecord::from_bytes(data, 6, value_format)?]
+                })
+            },
+            (1, 2) => {
+                let value_format = ValueFormat::from_bits_truncate(read_u16(data, 4)?);
+                let size = ValueRecord::size(value_format);
+                let values = (0..read_u16(data, 6)? as usize)
+                    .map(|i| ValueRecord::from_bytes(data, 8 + size * i, value_format))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(PositioningSubtable::Single { coverage: coverage_at(2)?, values })
+            },
+            (2, 1) => {
+                let value_format1 = ValueFormat::from_bits_truncate(read_u16(data, 4)?);
+                let value_format2 = ValueFormat::from_bits_truncate(read_u16(data, 6)?);
+                let record_size = 2 + ValueRecord::size(value_format1) + ValueRecord::size(value_format2);
+                let mut pair_sets = Vec::new();
+                for set_offset in read_u16_array(data, 8)? {
+                    // device offsets in pair value records are from the start of the subtable,
+                    // not the pair set, so records are read from `data`
+                    let set_offset = set_offset as usize;
+                    let count = read_u16(data, set_offset)? as usize;
+                    let mut pairs = Vec::with_capacity(count);
+                    for i in 0..count {
+                        let offset = set_offset + 2 + record_size * i;
+                        pairs.push(PairValueRecord {
+                            second_glyph: read_u16(data, offset)?,
+                            value1: ValueRecord::from_bytes(data, offset + 2, value_format1)?,
+                            value2: ValueRecord::from_bytes(data, offset + 2 + ValueRecord::size(value_format1), value_format2)?
+                        });
+                    }
+                    // pairs are binary searched, and fonts don't always list them in order
+                    pairs.sort_by_key(|p| p.second_glyph);
+                    pair_sets.push(pairs);
+                }
+                Ok(PositioningSubtable::PairGlyphs { coverage: coverage_at(2)?, value_format2, pair_sets })
+            },
+            (2, 2) => {
+                let value_format1 = ValueFormat::from_bits_truncate(read_u16(data, 4)?);
+                let value_format2 = ValueFormat::from_bits_truncate(read_u16(data, 6)?);
+                let class1_count = read_u16(data, 12)? as usize;
+                let class2_count = read_u16(data, 14)?;
+                let size1 = ValueRecord::size(value_format1);
+                let record_size = size1 + ValueRecord::size(value_format2);
+                let mut values = Vec::with_capacity(class1_count * class2_count as usize);
+                for i in 0..class1_count * class2_count as usize {
+                    let offset = 16 + record_size * i;
+                    values.push((ValueRecord::from_bytes(data, offset, value_format1)?,
+                                 ValueRecord::from_bytes(data, offset + size1, value_format2)?));
+                }
+                Ok(PositioningSubtable::PairClasses {
+                    coverage: coverage_at(2)?,
+                    value_format2,
+                    class_def1: ClassDef::from_offset(data, read_u16(data, 8)? as usize)?,
+                    class_def2: ClassDef::from_offset(data, read_u16(data, 10)? as usize)?,
+                    class2_count, values
+                })
+            },
+            (3, 1) => {
+                let count = read_u16(data, 4)? as usize;
+                let mut entry_exits = Vec::with_capacity(count);
+                for i in 0..count {
+                    entry_exits.push((Anchor::from_offset(data, read_u16(data, 6 + 4 * i)? as usize)?,
+                                      Anchor::from_offset(data, read_u16(data, 8 + 4 * i)? as usize)?));
+                }
+                Ok(PositioningSubtable::Cursive { coverage: coverage_at(2)?, entry_exits })
+            },
+            (4, 1) | (5, 1) | (6, 1) => {
+                let class_count = read_u16(data, 6)? as usize;
+                let marks = read_mark_array(subtable(data, read_u16(data, 8)? as usize)?)?;
+                let attach_to = subtable(data, read_u16(data, 10)? as usize)?;
+                match lookup_type {
+                    4 => Ok(PositioningSubtable::MarkToBase {
+                        mark_coverage: coverage_at(2)?, base_coverage: coverage_at(4)?,
+                        marks, bases: read_anchor_rows(attach_to, class_count)?
+                    }),
+                    5 => {
+                        let mut ligatures = Vec::new();
+                        for offset in read_u16_array(attach_to, 0)? {
+                            ligatures.push(read_anchor_rows(subtable(attach_to, offset as usize)?, class_count)?);
+                        }
+                        Ok(PositioningSubtable::MarkToLigature {
+                            mark_coverage: coverage_at(2)?, ligature_coverage: coverage_at(4)?,
+                            marks, ligatures
+                        })
+                    },
+                    _ => Ok(PositioningSubtable::MarkToMark {
+                        mark1_coverage: coverage_at(2)?, mark2_coverage: coverage_at(4)?,
+                        marks, mark2s: read_anchor_rows(attach_to, class_count)?
+                    })
+                }
+            },
+            (7, _) => Ok(PositioningSubtable::Context(ContextLookup::from_bytes(data, false)?)),
+            (8, _) => Ok(PositioningSubtable::ChainedContext(ContextLookup::from_bytes(data, true)?)),
+            (t, _) if t == 0 || t > 9 => Ok(PositioningSubtable::Unsupported(t)),
+            (_, f) => Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        }
+    }
+}
+
+/// The 'GPOS' table, which moves glyphs relative to each other for kerning, mark placement and
+/// connecting scripts
+#[derive(Debug)]
+pub struct GlyphPositioningTable(pub LayoutTable<PositioningSubtable>);
+
+impl Table for GlyphPositioningTable {
+    fn tag(&self) -> TableTag { TableTag::GlyphPositioning }
+}
+
+impl GlyphPositioningTable {
+    pub fn from_bytes(data: &[u8]) -> Result<GlyphPositioningTable, FontError> {
+        LayoutTable::from_bytes(data, 9, PositioningSubtable::from_bytes).map(GlyphPositioningTable)
+    }
+
+    /// See `LayoutTable::lookups_for_features`
+    pub fn lookups_for_features(&self, script: TableTag, language: Option<TableTag>, features: &[TableTag]) -> Vec<u16> {
+        self.0.lookups_for_features(script, language, features)
+    }
+
+    /// Apply lookups in order to a run of glyphs. `positions` has a position for each glyph, with
+    /// the advances from the glyph metrics
+    pub fn apply<C: GlyphClassifier>(&self, lookups: &[u16], glyphs: &[LayoutGlyph], positions: &mut [GlyphPosition],
+                                     classes: &C, options: &PositioningOptions) {
+        for &lookup_index in lookups {
+            self.apply_lookup(lookup_index, glyphs, positions, classes, options);
+        }
+    }
+
+    /// Apply one lookup everywhere in a run of glyphs that it matches
+    pub fn apply_lookup<C: GlyphClassifier>(&self, lookup_index: u16, glyphs: &[LayoutGlyph], positions: &mut [GlyphPosition],
+                                            classes: &C, options: &PositioningOptions) {
+        let lookup = match self.0.lookups.get(lookup_index as usize) {
+            Some(l) => l,
+            None => return
+        };
+        let mut i = 0;
+        while i < glyphs.len() {
+            if !lookup.ignores(classes, glyphs[i].glyph) {
+                if let Some(next) = self.apply_at(lookup, glyphs, positions, i, classes, options, 0) {
+                    i = next.max(i + 1);
+                    continue;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    /// Try each subtable of a lookup at a glyph until one applies. Gives where to continue from
+    /// if one did
+    fn apply_at<C: GlyphClassifier>(&self, lookup: &Lookup<PositioningSubtable>, glyphs: &[LayoutGlyph], positions: &mut [GlyphPosition],
+                                    index: usize, classes: &C, options: &PositioningOptions, depth: usize) -> Option<usize> {
+        lookup.subtables.iter()
+            .filter_map(|st| self.apply_subtable(st, lookup, glyphs, positions, index, classes, options, depth))
+            .next()
+    }
+
+    fn apply_subtable<C: GlyphClassifier>(&self, st: &PositioningSubtable, lookup: &Lookup<PositioningSubtable>, glyphs: &[LayoutGlyph],
+                                          positions: &mut [GlyphPosition], index: usize, classes: &C,
+                                          options: &PositioningOptions, depth: usize) -> Option<usize> {
+        let ignored = |g| lookup.ignores(classes, g);
+        let glyph = glyphs[index].glyph;
+        // without glyph classes, anything a mark lookup has a mark anchor for is a mark
+        let is_mark = |g, mark_coverage: &Coverage| match classes.glyph_class(g) {
+            GLYPH_CLASS_MARK => true,
+            0 => mark_coverage.contains(g),
+            _ => false
+        };
+        match *st {
+            PositioningSubtable::Single { ref coverage, ref values } => {
+                let ci = coverage.index(glyph)?;
+                let value = if values.len() == 1 { &values[0] } else { values.get(ci)? };
+                value.apply(&mut positions[index], options);
+                Some(index + 1)
+            },
+            PositioningSubtable::PairGlyphs { ref coverage, value_format2, ref pair_sets } => {
+                let pairs = pair_sets.get(coverage.index(glyph)?)?;
+                let next = next_glyph(glyphs, index, &ignored)?;
+                let pair = &pairs[pairs.binary_search_by_key(&glyphs[next].glyph, |p| p.second_glyph).ok()?];
+                pair.value1.apply(&mut positions[index], options);
+                pair.value2.apply(&mut positions[next], options);
+                // a second glyph that was moved can't be the first glyph of another pair
+                Some(if value_format2.is_empty() { next } else { next + 1 })
+            },
+            PositioningSubtable::PairClasses { ref coverage, value_format2, ref class_def1, ref class_def2, class2_count, ref values } => {
+                coverage.index(glyph)?;
+                let next = next_glyph(glyphs, index, &ignored)?;
+                let class2 = class_def2.class(glyphs[next].glyph);
+                if class2 >= class2_count { return None; }
+                let (value1, value2) = values.get(class_def1.class(glyph) as usize * class2_count as usize + class2 as usize)?;
+                value1.apply(&mut positions[index], options);
+                value2.apply(&mut positions[next], options);
+                Some(if value_format2.is_empty() { next } else { next + 1 })
+            },
+            PositioningSubtable::Cursive { ref coverage, ref entry_exits } => {
+                let exit = entry_exits.get(coverage.index(glyph)?)?.1.as_ref()?;
+                let next = next_glyph(glyphs, index, &ignored)?;
+                let entry = entry_exits.get(coverage.index(glyphs[next].glyph)?)?.0.as_ref()?;
+                let (exit_x, exit_y) = exit.position(options);
+                let (entry_x, entry_y) = entry.position(options);
+                // the advances are changed so that the pen ends up on the next glyph's entry
+                if options.right_to_left {
+                    let d = exit_x + positions[index].x_offset;
+                    positions[index].x_advance -= d;
+                    positions[index].x_offset -= d;
+                    positions[next].x_advance = entry_x + positions[next].x_offset;
+                } else {
+                    positions[index].x_advance = exit_x + positions[index].x_offset;
+                    let d = entry_x + positions[next].x_offset;
+                    positions[next].x_advance -= d;
+                    positions[next].x_offset -= d;
+                }
+                // and one of the glyphs is moved so that the anchors line up vertically. The last
+                // glyph in the chain stays on the baseline unless the lookup says otherwise
+                let (child, parent, y_offset) = if lookup.flags.contains(LF_RIGHT_TO_LEFT) {
+                    (index, next, entry_y - exit_y)
+                } else {
+                    (next, index, exit_y - entry_y)
+                };
+                positions[child].y_offset = y_offset;
+                positions[child].attachment = Some(Attachment::Cursive(parent));
+                Some(next)
+            },
+            PositioningSubtable::MarkToBase { ref mark_coverage, ref base_coverage, ref marks, ref bases } => {
+                let mark = marks.get(mark_coverage.index(glyph)?)?;
+                let base = (0..index).rev().find(|&i| !is_mark(glyphs[i].glyph, mark_coverage))?;
+                let anchor = bases.get(base_coverage.index(glyphs[base].glyph)?)?.get(mark.class as usize)?.as_ref()?;
+                attach_mark(positions, index, base, &mark.anchor, anchor, options);
+                Some(index + 1)
+            },
+            PositioningSubtable::MarkToLigature { ref mark_coverage, ref ligature_coverage, ref marks, ref ligatures } => {
+                let mark = marks.get(mark_coverage.index(glyph)?)?;
+                let lig = (0..index).rev().find(|&i| !is_mark(glyphs[i].glyph, mark_coverage))?;
+                let components = ligatures.get(ligature_coverage.index(glyphs[lig].glyph)?)?;
+                if components.is_empty() { return None; }
+                // marks that were on a component before the ligature was formed go back on it,
+                // anything else goes on the last component
+                let (m, l) = (glyphs[index], glyphs[lig]);
+                let component = if m.ligature_id != 0 && m.ligature_id == l.ligature_id && m.ligature_component > 0 {
+                    (m.ligature_component as usize).min(components.len()) - 1
+                } else {
+                    components.len() - 1
+                };
+                let anchor = components[component].get(mark.class as usize)?.as_ref()?;
+                attach_mark(positions, index, lig, &mark.anchor, anchor, options);
+                Some(index + 1)
+            },
+            PositioningSubtable::MarkToMark { ref mark1_coverage, ref mark2_coverage, ref marks, ref mark2s } => {
+                let mark = marks.get(mark1_coverage.index(glyph)?)?;
+                let prev = prev_glyph(glyphs, index, &ignored)?;
+                // both marks have to be on the same ligature component
+                let (m1, m2) = (glyphs[index], glyphs[prev]);
+                if m1.ligature_id != m2.ligature_id || (m1.ligature_id != 0 && m1.ligature_component != m2.ligature_component) {
+                    return None;
+                }
+                let anchor = mark2s.get(mark2_coverage.index(m2.glyph)?)?.get(mark.class as usize)?.as_ref()?;
+                attach_mark(positions, index, prev, &mark.anchor, anchor, options);
+                Some(index + 1)
+            },
+            PositioningSubtable::Context(ref context) | PositioningSubtable::ChainedContext(ref context) => {
+                let m = context.match_at(glyphs, index, ignored)?;
+                if depth < MAX_NESTING_DEPTH {
+                    for sl in m.lookups {
+                        let nested = match self.0.lookups.get(sl.lookup_index as usize) {
+                            Some(l) => l,
+                            None => continue
+                        };
+                        if let Some(&pos) = m.positions.get(sl.sequence_index as usize) {
+                            if !nested.ignores(classes, glyphs[pos].glyph) {
+                                self.apply_at(nested, glyphs, positions, pos, classes, options, depth + 1);
+                            }
+                        }
+                    }
+                }
+                m.positions.last().map(|&last| last + 1)
+            },
+            PositioningSubtable::Unsupported(_) => None
+        }
+    }
+}
+
+fn attach_mark(positions: &mut [GlyphPosition], mark: usize, base: usize, mark_anchor: &Anchor, base_anchor: &Anchor,
+               options: &PositioningOptions) {
+    let (mx, my) = mark_anchor.position(options);
+    let (bx, by) = base_anchor.position(options);
+    let pos = &mut positions[mark];
+    pos.x_offset = bx - mx;
+    pos.y_offset = by - my;
+    pos.attachment = Some(Attachment::Mark(base));
+}
+
+/// Turns the offsets of attached glyphs, which are from the glyph they are attached to, into
+/// offsets from where the glyph would otherwise be drawn. Call this once all of the lookups have
+/// been applied, since they can still change the advances between attached glyphs
+pub fn resolve_attachments(positions: &mut [GlyphPosition], right_to_left: bool) {
+    let mut resolved = vec![false; positions.len()];
+    for i in 0..positions.len() {
+        resolve_attachment(positions, &mut resolved, i, right_to_left);
+    }
+}
+
+fn resolve_attachment(positions: &mut [GlyphPosition], resolved: &mut [bool], i: usize, right_to_left: bool) {
+    if resolved[i] { return; }
+    // marked before following the chain so that a font with a cycle in it can't loop forever
+    resolved[i] = true;
+    let (parent, is_mark) = match positions[i].attachment {
+        Some(Attachment::Mark(p)) => (p, true),
+        Some(Attachment::Cursive(p)) => (p, false),
+        None => return
+    };
+    if parent >= positions.len() { return; }
+    resolve_attachment(positions, resolved, parent, right_to_left);
+    let (px, py) = (positions[parent].x_offset, positions[parent].y_offset);
+    positions[i].x_offset += px;
+    positions[i].y_offset += py;
+    if is_mark {
+        // the mark was placed from its base's origin, so the advances between them are taken back
+        // off. Text is still in logical order, so right to left marks come after their base
+        // visually and before it in the run
+        if right_to_left {
+            for j in parent + 1..i + 1 {
+                positions[i].x_offset += positions[j].x_advance;
+                positions[i].y_offset += positions[j].y_advance;
+            }
+        } else {
+            for j in parent..i {
+                positions[i].x_offset -= positions[j].x_advance;
+                positions[i].y_offset -= positions[j].y_advance;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::layout_common::test_tables::*;
+
+    fn options() -> PositioningOptions {
+        PositioningOptions { ppem: 0, units_per_em: 1000, right_to_left: false }
+    }
+
+    fn run(glyphs: &[u16]) -> (Vec<LayoutGlyph>, Vec<GlyphPosition>) {
+        (glyph_run(glyphs), vec![GlyphPosition::new(500, 0); glyphs.len()])
+    }
+
+    #[test]
+    fn value_records() {
+        // x placement and x advance, with an x advance device table right after the record
+        let format = VF_X_PLACEMENT | VF_X_ADVANCE | VF_X_ADVANCE_DEVICE;
+        let mut data = words(&[10, (-20i16) as u16, 6]);
+        data.extend(words(&[12, 13, 1, 0b0111_0000_0000_0000]));
+        let value = ValueRecord::from_bytes(&data, 0, format).unwrap();
+        assert_eq!(ValueRecord::size(format), 6);
+        assert_eq!((value.x_placement, value.x_advance), (10, -20));
+
+        let mut pos = GlyphPosition::new(500, 0);
+        value.apply(&mut pos, &options());
+        assert_eq!(pos, GlyphPosition { x_advance: 480, x_offset: 10, ..GlyphPosition::default() });
+        // at 13ppem the device adds -1 pixel, which is 1000/13 units
+        let mut pos = GlyphPosition::new(500, 0);
+        value.apply(&mut pos, &PositioningOptions { ppem: 13, ..options() });
+        assert_eq!(pos.x_advance, 480 - 76);
+    }
+
+    #[test]
+    fn pair_positioning() {
+        // glyph pairs: 1 followed by 2 or 3
+        let mut pairs = words(&[1, 12, 4, 0, 1, 18]);
+        pairs.extend(words(&[1, 1, 1]));  // coverage
+        pairs.extend(words(&[2, 3, (-30i16) as u16, 2, (-20i16) as u16]));
+        // classes: glyph 4 is class 1 on the left, glyph 5 is class 1 on the right
+        let mut classes = words(&[2, 24, 4, 0, 30, 38, 2, 2]);
+        for &v in &[0i16, 0, 0, -50] { classes.extend(words(&[v as u16])); }
+        classes.extend(words(&[1, 1, 4]));
+        classes.extend(words(&[1, 4, 1, 1]));
+        classes.extend(words(&[1, 5, 1, 1]));
+        let table = GlyphPositioningTable::from_bytes(&layout_table(b"kern", &[(2, 0, pairs), (2, 0, classes)])).unwrap();
+        let lookups = table.lookups_for_features(TableTag::new(b"latn"), None, &[TableTag::new(b"kern")]);
+        assert_eq!(lookups, vec![0, 1]);
+
+        let (glyphs, mut positions) = run(&[1, 3, 1, 1, 2, 4, 5, 5]);
+        table.apply(&lookups, &glyphs, &mut positions, &NoGlyphClasses, &options());
+        let advances: Vec<_> = positions.iter().map(|p| p.x_advance).collect();
+        assert_eq!(advances, vec![470, 500, 500, 480, 500, 450, 500, 500]);
+    }
+
+    #[test]
+    fn mark_to_base() {
+        // glyph 1 is a base with its anchor at (300, 700), glyph 2 is a mark with its anchor at (50, 0)
+        let mut st = words(&[1, 12, 18, 1, 24, 36]);
+        st.extend(words(&[1, 1, 2]));
+        st.extend(words(&[1, 1, 1]));
+        st.extend(words(&[1, 0, 6, 1, 50, 0]));
+        st.extend(words(&[1, 4, 1, 300, 700]));
+        let table = GlyphPositioningTable::from_bytes(&layout_table(b"kern", &[(4, 0, st)])).unwrap();
+
+        let (glyphs, mut positions) = run(&[1, 2, 2, 3]);
+        positions[1].x_advance = 0;
+        table.apply_lookup(0, &glyphs, &mut positions, &NoGlyphClasses, &options());
+        assert_eq!(positions[1].attachment, Some(Attachment::Mark(0)));
+        assert_eq!(positions[2].attachment, Some(Attachment::Mark(0)));
+        assert_eq!(positions[3].attachment, None);
+        resolve_attachments(&mut positions, false);
+        assert_eq!((positions[1].x_offset, positions[1].y_offset), (250 - 500, 700));
+        assert_eq!((positions[2].x_offset, positions[2].y_offset), (250 - 500, 700));
+    }
+
+    #[test]
+    fn chained_context_in_extension() {
+        // a single adjustment of glyph 2, only when it comes after glyph 1
+        let mut single = words(&[1, 8, VF_Y_PLACEMENT.bits(), 100]);
+        single.extend(words(&[1, 1, 2]));
+        let mut chained = words(&[3, 1, 18, 1, 24, 0, 1, 0, 0]);
+        chained.extend(words(&[1, 1, 1]));
+        chained.extend(words(&[1, 1, 2]));
+        let mut extension = words(&[1, 8, 0, 8]);
+        extension.extend(chained);
+        let table = GlyphPositioningTable::from_bytes(&layout_table(b"kern", &[(1, 0, single), (9, 0, extension)])).unwrap();
+        assert_eq!(table.0.lookups[1].lookup_type, 8);
+
+        let (glyphs, mut positions) = run(&[2, 1, 2, 2]);
+        table.apply_lookup(1, &glyphs, &mut positions, &NoGlyphClasses, &options());
+        let offsets: Vec<_> = positions.iter().map(|p| p.y_offset).collect();
+        assert_eq!(offsets, vec![0, 0, 100, 0]);
+    }
+
+    #[test]
+    fn several_subtables_in_extension() {
+        // an extension subtable wrapping a single adjustment of one glyph
+        let extension = |lookup_type: u16, glyph: u16| {
+            let mut st = words(&[1, lookup_type, 0, 8]);
+            st.extend(words(&[1, 8, VF_Y_PLACEMENT.bits(), 100]));
+            st.extend(words(&[1, 1, glyph]));
+            st
+        };
+        let table = GlyphPositioningTable::from_bytes(&layout_table_with_subtables(b"kern", &[(9, 0, vec![extension(1, 2), extension(1, 3)])])).unwrap();
+        assert_eq!(table.0.lookups[0].lookup_type, 1);
+        assert_eq!(table.0.lookups[0].subtables.len(), 2);
+
+        let (glyphs, mut positions) = run(&[1, 2, 3]);
+        table.apply_lookup(0, &glyphs, &mut positions, &NoGlyphClasses, &options());
+        let offsets: Vec<_> = positions.iter().map(|p| p.y_offset).collect();
+        assert_eq!(offsets, vec![0, 100, 100]);
+
+        // the subtables of one extension lookup all have to wrap the same type
+        assert!(GlyphPositioningTable::from_bytes(&layout_table_with_subtables(b"kern", &[(9, 0, vec![extension(1, 2), extension(2, 3)])])).is_err());
+    }
+}
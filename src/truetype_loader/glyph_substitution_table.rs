@@ -0,0 +1,374 @@
+use super::*;
+use super::layout_common::*;
+
+/// A ligature and the glyphs that make it up, leaving out the first, which the coverage matched
+#[derive(Clone, Debug)]
+pub struct Ligature {
+    pub glyph: u16,
+    pub components: Vec<u16>
+}
+
+#[derive(Clone, Debug)]
+pub enum SubstitutionSubtable {
+    /// Replaces each covered glyph with the glyph `delta` after it, wrapping around
+    SingleDelta { coverage: Coverage, delta: i16 },
+    /// Replaces each covered glyph with the glyph at its coverage index
+    Single { coverage: Coverage, substitutes: Vec<u16> },
+    /// Replaces each covered glyph with a sequence of glyphs
+    Multiple { coverage: Coverage, sequences: Vec<Vec<u16>> },
+    /// Gives each covered glyph a set of alternates to choose from
+    Alternate { coverage: Coverage, alternate_sets: Vec<Vec<u16>> },
+    /// Ligature sets are indexed by the coverage index of the first glyph, and are in order of
+    /// preference
+    Ligature { coverage: Coverage, ligature_sets: Vec<Vec<Ligature>> },
+    Context(ContextLookup),
+    ChainedContext(ContextLookup),
+    /// A single substitution with a context, which is applied from the end of the text to the
+    /// start. Backtrack coverages start from the glyph before the input
+    ReverseChainedContext { coverage: Coverage, backtrack: Vec<Coverage>, lookahead: Vec<Coverage>, substitutes: Vec<u16> },
+    /// Lookup types from later versions of the spec are kept so that lookup indices still line up
+    Unsupported(u16)
+}
+
+fn read_glyph_sets(data: &[u8], offset: usize) -> Result<Vec<Vec<u16>>, FontError> {
+    read_u16_array(data, offset)?.into_iter()
+        .map(|o| read_u16_array(data, o as usize))
+        .collect()
+}
+
+fn read_coverage_list(data: &[u8], offset: usize) -> Result<Vec<Coverage>, FontError> {
+    read_u16_array(data, offset)?.into_iter()
+        .map(|o| Coverage::from_bytes(subtable(data, o as usize)?))
+        .collect()
+}
+
+impl SubstitutionSubtable {
+    pub fn from_bytes(lookup_type: u16, data: &[u8]) -> Result<SubstitutionSubtable, FontError> {
+        let format = read_u16(data, 0)?;
+        let coverage = || -> Result<Coverage, FontError> {
+            Coverage::from_bytes(subtable(data, read_u16(data, 2)? as usize)?)
+        };
+        match (lookup_type, format) {
+            (1, 1) => Ok(SubstitutionSubtable::SingleDelta { coverage: coverage()?, delta: read_i16(data, 4)? }),
+            (1, 2) => Ok(SubstitutionSubtable::Single { coverage: coverage()?, substitutes: read_u16_array(data, 4)? }),
+            (2, 1) => Ok(SubstitutionSubtable::Multiple { coverage: coverage()?, sequences: read_glyph_sets(data, 4)? }),
+            (3, 1) => Ok(SubstitutionSubtable::Alternate { coverage: coverage()?, alternate_sets: read_glyph_sets(data, 4)? }),
+            (4, 1) => {
+                let mut ligature_sets = Vec::new();
+                for set_offset in read_u16_array(data, 4)? {
+                    let set = subtable(data, set_offset as usize)?;
+                    let mut ligatures = Vec::new();
+                    for lig_offset in read_u16_array(set, 0)? {
+                        let lig = subtable(set, lig_offset as usize)?;
+                        let count = read_u16(lig, 2)? as usize;
+                        ligatures.push(Ligature { glyph: read_u16(lig, 0)?, components: read_u16s(lig, 4, count.saturating_sub(1))? });
+                    }
+                    ligature_sets.push(ligatures);
+                }
+                Ok(SubstitutionSubtable::Ligature { coverage: coverage()?, ligature_sets })
+            },
+            (5, _) => Ok(SubstitutionSubtable::Context(ContextLookup::from_bytes(data, false)?)),
+            (6, _) => Ok(SubstitutionSubtable::ChainedContext(ContextLookup::from_bytes(data, true)?)),
+            (8, 1) => {
+                let backtrack = read_coverage_list(data, 4)?;
+                let lookahead_at = 6 + 2 * backtrack.len();
+                let lookahead = read_coverage_list(data, lookahead_at)?;
+                Ok(SubstitutionSubtable::ReverseChainedContext {
+                    coverage: coverage()?, backtrack,
+                    substitutes: read_u16_array(data, lookahead_at + 2 + 2 * lookahead.len())?,
+                    lookahead
+                })
+            },
+            (t, _) if t == 0 || t > 8 => Ok(SubstitutionSubtable::Unsupported(t)),
+            (_, f) => Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        }
+    }
+}
+
+const REVERSE_CHAINED_CONTEXT: u16 = 8;
+
+/// The 'GSUB' table, which replaces glyphs with other glyphs for ligatures, alternate forms and
+/// the shapes that scripts need in context
+#[derive(Debug)]
+pub struct GlyphSubstitutionTable(pub LayoutTable<SubstitutionSubtable>);
+
+impl Table for GlyphSubstitutionTable {
+    fn tag(&self) -> TableTag { TableTag::GlyphSubstitution }
+}
+
+impl GlyphSubstitutionTable {
+    pub fn from_bytes(data: &[u8]) -> Result<GlyphSubstitutionTable, FontError> {
+        LayoutTable::from_bytes(data, 7, SubstitutionSubtable::from_bytes).map(GlyphSubstitutionTable)
+    }
+
+    /// See `LayoutTable::lookups_for_features`
+    pub fn lookups_for_features(&self, script: TableTag, language: Option<TableTag>, features: &[TableTag]) -> Vec<u16> {
+        self.0.lookups_for_features(script, language, features)
+    }
+
+    /// Apply lookups in order to a run of glyphs. Alternate substitutions pick the first alternate
+    pub fn apply<C: GlyphClassifier>(&self, lookups: &[u16], glyphs: &mut Vec<LayoutGlyph>, classes: &C) {
+        for &lookup_index in lookups {
+            self.apply_lookup(lookup_index, glyphs, classes, 0);
+        }
+    }
+
+    /// Apply one lookup everywhere in a run of glyphs that it matches. `alternate` is which glyph
+    /// alternate substitutions pick, counting from 0
+    pub fn apply_lookup<C: GlyphClassifier>(&self, lookup_index: u16, glyphs: &mut Vec<LayoutGlyph>, classes: &C, alternate: usize) {
+        self.apply_lookup_masked(lookup_index, glyphs, classes, alternate, !0);
+    }
+
+    /// Apply one lookup at the glyphs whose mask shares a bit with `mask`, like the glyphs a
+    /// positional form of a joining script is wanted for. Context and ligatures still match
+    /// against every glyph
+    pub fn apply_lookup_masked<C: GlyphClassifier>(&self, lookup_index: u16, glyphs: &mut Vec<LayoutGlyph>, classes: &C,
+                                                   alternate: usize, mask: u32) {
+        let lookup = match self.0.lookups.get(lookup_index as usize) {
+            Some(l) => l,
+            None => return
+        };
+        if lookup.lookup_type == REVERSE_CHAINED_CONTEXT {
+            for i in (0..glyphs.len()).rev() {
+                if glyphs[i].mask & mask != 0 && !lookup.ignores(classes, glyphs[i].glyph) {
+                    self.apply_at(lookup, glyphs, i, classes, alternate, 0);
+                }
+            }
+            return;
+        }
+        let mut i = 0;
+        while i < glyphs.len() {
+            if glyphs[i].mask & mask != 0 && !lookup.ignores(classes, glyphs[i].glyph) {
+                // a deletion gives back `i`, which is where the glyph after it is now
+                if let Some(next) = self.apply_at(lookup, glyphs, i, classes, alternate, 0) {
+                    i = next;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    /// Try each subtable of a lookup at a glyph until one applies. Gives where to continue from
+    /// if one did
+    fn apply_at<C: GlyphClassifier>(&self, lookup: &Lookup<SubstitutionSubtable>, glyphs: &mut Vec<LayoutGlyph>, index: usize,
+                                    classes: &C, alternate: usize, depth: usize) -> Option<usize> {
+        for st in &lookup.subtables {
+            if let Some(next) = self.apply_subtable(st, lookup, glyphs, index, classes, alternate, depth) {
+                return Some(next);
+            }
+        }
+        None
+    }
+
+    fn apply_subtable<C: GlyphClassifier>(&self, st: &SubstitutionSubtable, lookup: &Lookup<SubstitutionSubtable>,
+                                          glyphs: &mut Vec<LayoutGlyph>, index: usize, classes: &C,
+                                          alternate: usize, depth: usize) -> Option<usize> {
+        let ignored = |g| lookup.ignores(classes, g);
+        let glyph = glyphs[index].glyph;
+        match *st {
+            SubstitutionSubtable::SingleDelta { ref coverage, delta } => {
+                coverage.index(glyph)?;
+                glyphs[index].glyph = glyph.wrapping_add(delta as u16);
+                Some(index + 1)
+            },
+            SubstitutionSubtable::Single { ref coverage, ref substitutes } => {
+                glyphs[index].glyph = *substitutes.get(coverage.index(glyph)?)?;
+                Some(index + 1)
+            },
+            SubstitutionSubtable::Multiple { ref coverage, ref sequences } => {
+                let sequence = sequences.get(coverage.index(glyph)?)?;
+                let original = glyphs[index];
+                let replacement = sequence.iter().map(|&g| LayoutGlyph { glyph: g, ..original });
+                glyphs.splice(index..index + 1, replacement);
+                Some(index + sequence.len())
+            },
+            SubstitutionSubtable::Alternate { ref coverage, ref alternate_sets } => {
+                glyphs[index].glyph = *alternate_sets.get(coverage.index(glyph)?)?.get(alternate)?;
+                Some(index + 1)
+            },
+            SubstitutionSubtable::Ligature { ref coverage, ref ligature_sets } => {
+                for lig in ligature_sets.get(coverage.index(glyph)?)? {
+                    let mut positions = vec![index];
+                    for &component in &lig.components {
+                        match next_glyph(glyphs, *positions.last().unwrap(), &ignored) {
+                            Some(p) if glyphs[p].glyph == component => positions.push(p),
+                            _ => break
+                        }
+                    }
+                    if positions.len() == lig.components.len() + 1 {
+                        form_ligature(glyphs, &positions, lig.glyph);
+                        return Some(index + 1);
+                    }
+                }
+                None
+            },
+            SubstitutionSubtable::Context(ref context) | SubstitutionSubtable::ChainedContext(ref context) => {
+                let (mut positions, lookups) = {
+                    let m = context.match_at(glyphs, index, ignored)?;
+                    (m.positions, m.lookups.to_vec())
+                };
+                if depth < MAX_NESTING_DEPTH {
+                    for sl in lookups {
+                        let nested = match self.0.lookups.get(sl.lookup_index as usize) {
+                            Some(l) => l,
+                            None => continue
+                        };
+                        let seq = sl.sequence_index as usize;
+                        let pos = match positions.get(seq) {
+                            Some(&p) if p < glyphs.len() => p,
+                            _ => continue
+                        };
+                        if nested.ignores(classes, glyphs[pos].glyph) { continue; }
+                        let len = glyphs.len();
+                        self.apply_at(nested, glyphs, pos, classes, alternate, depth + 1);
+                        // move the positions after this one along by however many glyphs were
+                        // added or taken away
+                        let delta = glyphs.len() as isize - len as isize;
+                        if delta != 0 {
+                            for p in positions.iter_mut().skip(seq + 1) {
+                                *p = ((*p as isize + delta).max(pos as isize + 1)) as usize;
+                            }
+                        }
+                    }
+                }
+                positions.last().map(|&last| last + 1)
+            },
+            SubstitutionSubtable::ReverseChainedContext { ref coverage, ref backtrack, ref lookahead, ref substitutes } => {
+                let ci = coverage.index(glyph)?;
+                let mut j = index;
+                for c in backtrack {
+                    j = prev_glyph(glyphs, j, &ignored)?;
+                    if !c.contains(glyphs[j].glyph) { return None; }
+                }
+                let mut j = index;
+                for c in lookahead {
+                    j = next_glyph(glyphs, j, &ignored)?;
+                    if !c.contains(glyphs[j].glyph) { return None; }
+                }
+                glyphs[index].glyph = *substitutes.get(ci)?;
+                Some(index)
+            },
+            SubstitutionSubtable::Unsupported(_) => None
+        }
+    }
+}
+
+/// Replaces the glyphs at `positions` with a ligature. Glyphs in between that the lookup skipped,
+/// like marks, stay where they are and remember which component of the ligature they were on
+fn form_ligature(glyphs: &mut Vec<LayoutGlyph>, positions: &[usize], ligature: u16) {
+    let id = glyphs.iter().map(|g| g.ligature_id).max().unwrap_or(0).wrapping_add(1).max(1);
+    let first = positions[0];
+    let last = *positions.last().unwrap();
+    let mut component = 1;
+    for (i, g) in glyphs.iter_mut().enumerate().take(last).skip(first + 1) {
+        if positions.contains(&i) {
+            component += 1;
+        } else {
+            g.ligature_id = id;
+            g.ligature_component = component;
+        }
+    }
+    glyphs[first] = LayoutGlyph { glyph: ligature, ligature_id: id, ligature_component: 0, ..glyphs[first] };
+    for &p in positions[1..].iter().rev() {
+        glyphs.remove(p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::layout_common::test_tables::*;
+
+    fn ids(glyphs: &[LayoutGlyph]) -> Vec<u16> {
+        glyphs.iter().map(|g| g.glyph).collect()
+    }
+
+    struct MarkClasses;
+
+    impl GlyphClassifier for MarkClasses {
+        fn glyph_class(&self, glyph: u16) -> u16 { if glyph >= 100 { GLYPH_CLASS_MARK } else { GLYPH_CLASS_BASE } }
+        fn mark_attachment_class(&self, _: u16) -> u16 { 0 }
+        fn is_in_mark_glyph_set(&self, _: u16, _: u16) -> bool { false }
+    }
+
+    #[test]
+    fn single_multiple_and_alternate() {
+        let mut delta = words(&[1, 6, 10]);
+        delta.extend(words(&[1, 1, 1]));
+        let mut multiple = words(&[1, 8, 1, 14]);
+        multiple.extend(words(&[1, 1, 2]));
+        multiple.extend(words(&[3, 20, 21, 22]));
+        let mut alternate = words(&[1, 8, 1, 14]);
+        alternate.extend(words(&[1, 1, 3]));
+        alternate.extend(words(&[2, 30, 31]));
+        let table = GlyphSubstitutionTable::from_bytes(&layout_table(b"liga", &[(1, 0, delta), (2, 0, multiple), (3, 0, alternate)])).unwrap();
+
+        let mut glyphs = glyph_run(&[1, 2, 3, 4]);
+        table.apply(&[0, 1], &mut glyphs, &NoGlyphClasses);
+        assert_eq!(ids(&glyphs), vec![11, 20, 21, 22, 3, 4]);
+        assert!(glyphs[1..4].iter().all(|g| g.cluster == 1));
+        table.apply_lookup(2, &mut glyphs, &NoGlyphClasses, 1);
+        assert_eq!(ids(&glyphs), vec![11, 20, 21, 22, 31, 4]);
+
+        // masked lookups skip glyphs without the mask's bits, and glyphs that were copied or
+        // combined keep their masks
+        let mut glyphs = glyph_run(&[1, 2, 1]);
+        glyphs[0].mask = 2;
+        glyphs[1].mask = 2;
+        table.apply_lookup_masked(0, &mut glyphs, &NoGlyphClasses, 0, 1);
+        assert_eq!(ids(&glyphs), vec![1, 2, 11]);
+        table.apply_lookup_masked(1, &mut glyphs, &NoGlyphClasses, 0, 2);
+        assert_eq!(ids(&glyphs), vec![1, 20, 21, 22, 11]);
+        assert!(glyphs[1..4].iter().all(|g| g.mask == 2));
+    }
+
+    #[test]
+    fn deleted_glyphs() {
+        // glyph 5 becomes an empty sequence, which leaves the glyph after it to be looked at next
+        let mut multiple = words(&[1, 8, 1, 14]);
+        multiple.extend(words(&[1, 1, 5]));
+        multiple.extend(words(&[0]));
+        let table = GlyphSubstitutionTable::from_bytes(&layout_table(b"liga", &[(2, 0, multiple)])).unwrap();
+
+        let mut glyphs = glyph_run(&[5, 5, 1, 5]);
+        table.apply(&[0], &mut glyphs, &NoGlyphClasses);
+        assert_eq!(ids(&glyphs), vec![1]);
+        assert_eq!(glyphs[0].cluster, 2);
+    }
+
+    #[test]
+    fn ligatures() {
+        // f i -> fi and f f i -> ffi, skipping marks
+        let mut st = words(&[1, 8, 1, 14]);
+        st.extend(words(&[1, 1, 1]));
+        st.extend(words(&[2, 6, 14]));
+        st.extend(words(&[50, 3, 1, 2]));
+        st.extend(words(&[51, 2, 2]));
+        let table = GlyphSubstitutionTable::from_bytes(&layout_table(b"liga", &[(4, LF_IGNORE_MARKS.bits(), st)])).unwrap();
+
+        let mut glyphs = glyph_run(&[1, 1, 100, 2, 1, 1, 3]);
+        table.apply(&[0], &mut glyphs, &MarkClasses);
+        assert_eq!(ids(&glyphs), vec![50, 100, 1, 1, 3]);
+        assert_eq!((glyphs[1].ligature_id, glyphs[1].ligature_component), (glyphs[0].ligature_id, 2));
+        assert_eq!(glyphs[2].cluster, 4);
+
+        // without glyph classes the mark gets in the way
+        let mut glyphs = glyph_run(&[1, 100, 2]);
+        table.apply(&[0], &mut glyphs, &NoGlyphClasses);
+        assert_eq!(ids(&glyphs), vec![1, 100, 2]);
+    }
+
+    #[test]
+    fn reverse_chained_context() {
+        // glyph 1 becomes 10 when it comes before glyph 2, which is checked after earlier
+        // substitutions changed what comes after it
+        let mut st = words(&[1, 14, 0, 1, 20, 1, 10]);
+        st.extend(words(&[1, 1, 1]));
+        st.extend(words(&[1, 2, 2, 10]));
+        let table = GlyphSubstitutionTable::from_bytes(&layout_table(b"liga", &[(8, 0, st)])).unwrap();
+        let mut glyphs = glyph_run(&[1, 1, 2, 1]);
+        table.apply(&[0], &mut glyphs, &NoGlyphClasses);
+        assert_eq!(ids(&glyphs), vec![10, 10, 2, 1]);
+    }
+}
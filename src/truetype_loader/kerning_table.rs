@@ -0,0 +1,309 @@
+use std::io;
+use std::io::prelude::*;
+use std::fmt;
+use std::fmt::{Debug};
+use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
+
+use super::*;
+
+bitflags! {
+    /// What a kerning subtable applies to. Windows and Apple tables store these differently, both
+    /// are converted to these flags
+    pub flags KerningCoverage: u8 {
+        /// The values adjust advances along the line rather than across it
+        const KC_HORIZONTAL     = 0b0000_0001,
+        /// The values are minimums rather than adjustments
+        const KC_MINIMUM        = 0b0000_0010,
+        /// The values move glyphs across the line, perpendicular to the text direction
+        const KC_CROSS_STREAM   = 0b0000_0100,
+        /// The values replace what earlier subtables added up rather than adding to it
+        const KC_OVERRIDE       = 0b0000_1000,
+        /// The values are for a font variation, only in Apple tables
+        const KC_VARIATION      = 0b0001_0000
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KerningPair {
+    pub left: u16,
+    pub right: u16,
+    pub value: i16
+}
+
+/// Maps a range of glyphs to class values in a format 2 subtable
+#[derive(Clone, Debug)]
+pub struct KerningClassTable {
+    pub first_glyph: u16,
+    pub classes: Vec<u16>
+}
+
+impl KerningClassTable {
+    fn from_binary<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<KerningClassTable, FontError> {
+        reader.seek(io::SeekFrom::Start(offset))?;
+        let first_glyph = reader.read_u16::<BigEndian>()?;
+        let count = reader.read_u16::<BigEndian>()?;
+        let mut classes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            classes.push(reader.read_u16::<BigEndian>()?);
+        }
+        Ok(KerningClassTable { first_glyph, classes })
+    }
+
+    // glyphs outside of the table are in class 0
+    fn class(&self, glyph_index: u16) -> u16 {
+        glyph_index.checked_sub(self.first_glyph)
+            .and_then(|i| self.classes.get(i as usize).cloned())
+            .unwrap_or(0)
+    }
+}
+
+pub enum KerningSubtableFormat {
+    /// A sorted list of glyph pairs
+    OrderedPairs(Vec<KerningPair>),
+    /// A two dimensional array of values indexed by glyph classes. The class values are byte
+    /// offsets from the start of the subtable, so `values` remembers where it started
+    ClassTable {
+        left_classes: KerningClassTable,
+        right_classes: KerningClassTable,
+        array_offset: u16,
+        values: Vec<i16>
+    },
+    /// Apple's state table and indexed formats are not read
+    Unsupported(u8)
+}
+
+impl Debug for KerningSubtableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KerningSubtableFormat::OrderedPairs(ref pairs) => write!(f, "OrderedPairs [len = {}]", pairs.len()),
+            KerningSubtableFormat::ClassTable { ref values, .. } => write!(f, "ClassTable [len = {}]", values.len()),
+            KerningSubtableFormat::Unsupported(format) => write!(f, "Unsupported({})", format)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct KerningSubtable {
+    pub coverage: KerningCoverage,
+    pub format: KerningSubtableFormat
+}
+
+impl KerningSubtable {
+    fn from_bytes(data: &[u8], header_len: u64, format: u8, coverage: KerningCoverage) -> Result<KerningSubtable, FontError> {
+        let mut reader = io::Cursor::new(data);
+        reader.seek(io::SeekFrom::Start(header_len))?;
+        let format = match format {
+            0 => {
+                let num_pairs = reader.read_u16::<BigEndian>()?;
+                reader.seek(io::SeekFrom::Current(6))?; // search_range, entry_selector, range_shift
+                let mut pairs = Vec::with_capacity(num_pairs as usize);
+                for _ in 0..num_pairs {
+                    pairs.push(KerningPair {
+                        left: reader.read_u16::<BigEndian>()?,
+                        right: reader.read_u16::<BigEndian>()?,
+                        value: reader.read_i16::<BigEndian>()?
+                    });
+                }
+                // the binary search in `kerning` needs the pairs in order, which the font can't be
+                // trusted to have done
+                pairs.sort_by_key(|p| (p.left, p.right));
+                KerningSubtableFormat::OrderedPairs(pairs)
+            },
+            2 => {
+                reader.read_u16::<BigEndian>()?; // row width
+                let left_offset = reader.read_u16::<BigEndian>()?;
+                let right_offset = reader.read_u16::<BigEndian>()?;
+                let array_offset = reader.read_u16::<BigEndian>()?;
+                if array_offset as usize > data.len() {
+                    return Err(FontError::OffsetOutOfBounds(TableTag::Kerning));
+                }
+                let values = data[array_offset as usize..].chunks(2)
+                    .filter(|c| c.len() == 2)
+                    .map(BigEndian::read_i16)
+                    .collect();
+                KerningSubtableFormat::ClassTable {
+                    left_classes: KerningClassTable::from_binary(&mut reader, left_offset as u64)?,
+                    right_classes: KerningClassTable::from_binary(&mut reader, right_offset as u64)?,
+                    array_offset, values
+                }
+            },
+            f => KerningSubtableFormat::Unsupported(f)
+        };
+        Ok(KerningSubtable { coverage, format })
+    }
+
+    /// The value this subtable has for a pair of glyphs, if it has one
+    pub fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        match self.format {
+            KerningSubtableFormat::OrderedPairs(ref pairs) =>
+                pairs.binary_search_by_key(&(left, right), |p| (p.left, p.right)).ok().map(|i| pairs[i].value),
+            KerningSubtableFormat::ClassTable { ref left_classes, ref right_classes, array_offset, ref values } => {
+                let offset = left_classes.class(left) as usize + right_classes.class(right) as usize;
+                // class 0 on the left points at the subtable header, which means there's no value
+                let index = offset.checked_sub(array_offset as usize)? / 2;
+                values.get(index).cloned()
+            },
+            KerningSubtableFormat::Unsupported(_) => None
+        }
+    }
+}
+
+/// The legacy 'kern' table, in either the Windows or the Apple layout
+#[derive(Debug)]
+pub struct KerningTable {
+    pub subtables: Vec<KerningSubtable>
+}
+
+impl Table for KerningTable {
+    fn tag(&self) -> TableTag { TableTag::Kerning }
+}
+
+impl KerningTable {
+    pub fn from_bytes(data: &[u8]) -> Result<KerningTable, FontError> {
+        let mut reader = io::Cursor::new(data);
+        let mut subtables = Vec::new();
+        if reader.read_u16::<BigEndian>()? == 0 {
+            // Windows: 16 bit lengths, and the format is in the high byte of the coverage
+            let num_tables = reader.read_u16::<BigEndian>()?;
+            let mut offset = 4;
+            for _ in 0..num_tables {
+                reader.seek(io::SeekFrom::Start(offset as u64))?;
+                reader.read_u16::<BigEndian>()?; // version
+                let mut length = reader.read_u16::<BigEndian>()? as usize;
+                let coverage = reader.read_u16::<BigEndian>()?;
+                // the length is too small to hold large pair lists, some fonts leave it wrapped
+                // around and only have the one subtable
+                if num_tables == 1 { length = data.len() - offset; }
+                let end = offset + length;
+                if end > data.len() || length < 6 {
+                    return Err(FontError::OffsetOutOfBounds(TableTag::Kerning));
+                }
+                let flags = KerningCoverage::from_bits_truncate(coverage as u8 & 0x0f);
+                subtables.push(KerningSubtable::from_bytes(&data[offset..end], 6, (coverage >> 8) as u8, flags)?);
+                offset = end;
+            }
+        } else {
+            // Apple: a 32 bit version, 32 bit lengths, and the format in the low byte of the coverage
+            reader.seek(io::SeekFrom::Start(0))?;
+            let version = reader.read_u32::<BigEndian>()?;
+            if version != 0x00010000 {
+                return Err(FontError::UnknownFormat(TableTag::Kerning, version));
+            }
+            let num_tables = reader.read_u32::<BigEndian>()?;
+            let mut offset = 8;
+            for _ in 0..num_tables {
+                reader.seek(io::SeekFrom::Start(offset as u64))?;
+                let length = reader.read_u32::<BigEndian>()? as usize;
+                let coverage = reader.read_u16::<BigEndian>()?;
+                let end = offset + length;
+                if end > data.len() || length < 8 {
+                    return Err(FontError::OffsetOutOfBounds(TableTag::Kerning));
+                }
+                let mut flags = KerningCoverage::empty();
+                if coverage & 0x8000 == 0 { flags |= KC_HORIZONTAL; }
+                if coverage & 0x4000 != 0 { flags |= KC_CROSS_STREAM; }
+                if coverage & 0x2000 != 0 { flags |= KC_VARIATION; }
+                subtables.push(KerningSubtable::from_bytes(&data[offset..end], 8, coverage as u8, flags)?);
+                offset = end;
+            }
+        }
+        Ok(KerningTable { subtables })
+    }
+
+    /// How much to adjust the advance between two glyphs in horizontal text, in font units.
+    /// Subtables with minimums, cross-stream values or values for variations are left out
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        let mut total = 0i16;
+        for st in &self.subtables {
+            if st.coverage & (KC_HORIZONTAL | KC_MINIMUM | KC_CROSS_STREAM | KC_VARIATION) != KC_HORIZONTAL {
+                continue;
+            }
+            if let Some(value) = st.kerning(left, right) {
+                total = if st.coverage.contains(KC_OVERRIDE) { value } else { total.saturating_add(value) };
+            }
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    fn format0(pairs: &[(u16, u16, i16)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(pairs.len() as u16).unwrap();
+        for _ in 0..3 { data.write_u16::<BigEndian>(0).unwrap(); }
+        for &(l, r, v) in pairs {
+            data.write_u16::<BigEndian>(l).unwrap();
+            data.write_u16::<BigEndian>(r).unwrap();
+            data.write_i16::<BigEndian>(v).unwrap();
+        }
+        data
+    }
+
+    fn format2() -> Vec<u8> {
+        // glyphs 10 and 11 are left classes 1 and 2, glyphs 20 and 21 are right classes 1 and 2.
+        // Offsets are from the start of the subtable, which has an 8 byte Apple header
+        let mut data = Vec::new();
+        for &v in &[4u16, 16, 24, 32] { data.write_u16::<BigEndian>(v).unwrap(); }
+        // left class table at 16
+        for &v in &[10u16, 2, 32, 36] { data.write_u16::<BigEndian>(v).unwrap(); }
+        // right class table at 24
+        for &v in &[20u16, 2, 0, 2] { data.write_u16::<BigEndian>(v).unwrap(); }
+        // array at 32, two rows of two
+        for &v in &[-10i16, -20, -30, -40] { data.write_i16::<BigEndian>(v).unwrap(); }
+        data
+    }
+
+    #[test]
+    fn windows_kern() {
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(0).unwrap();
+        data.write_u16::<BigEndian>(2).unwrap();
+        for &(coverage, ref st) in &[(0x0001u16, format0(&[(5, 6, 7), (1, 2, -50), (1, 3, -60)])),
+                                     (0x0005u16, format0(&[(1, 2, 100)]))] {
+            data.write_u16::<BigEndian>(0).unwrap();
+            data.write_u16::<BigEndian>(6 + st.len() as u16).unwrap();
+            data.write_u16::<BigEndian>(coverage).unwrap();
+            data.extend_from_slice(st);
+        }
+        let table = KerningTable::from_bytes(&data).unwrap();
+        assert_eq!(table.subtables.len(), 2);
+        assert_eq!(table.subtables[1].coverage, KC_HORIZONTAL | KC_CROSS_STREAM);
+        assert_eq!(table.kerning(1, 2), -50);
+        assert_eq!(table.kerning(1, 3), -60);
+        assert_eq!(table.kerning(5, 6), 7);
+        assert_eq!(table.kerning(2, 1), 0);
+        assert_eq!(table.subtables[1].kerning(1, 2), Some(100));
+    }
+
+    #[test]
+    fn apple_kern() {
+        let mut data = Vec::new();
+        data.write_u32::<BigEndian>(0x00010000).unwrap();
+        data.write_u32::<BigEndian>(3).unwrap();
+        for &(coverage, ref st) in &[(0x0002u16, format2()),
+                                     (0x8000u16, format0(&[(10, 20, 5)])),
+                                     (0x0001u16, vec![0; 8])] {
+            data.write_u32::<BigEndian>(8 + st.len() as u32).unwrap();
+            data.write_u16::<BigEndian>(coverage).unwrap();
+            data.write_u16::<BigEndian>(0).unwrap();
+            data.extend_from_slice(&st[..]);
+        }
+        let table = KerningTable::from_bytes(&data).unwrap();
+        assert_eq!(table.subtables.len(), 3);
+        assert_eq!(table.subtables[1].coverage, KerningCoverage::empty());
+        match table.subtables[2].format {
+            KerningSubtableFormat::Unsupported(1) => {},
+            ref f => panic!("unexpected format {:?}", f)
+        }
+        assert_eq!(table.kerning(10, 20), -10);
+        assert_eq!(table.kerning(10, 21), -20);
+        assert_eq!(table.kerning(11, 20), -30);
+        assert_eq!(table.kerning(11, 21), -40);
+        assert_eq!(table.kerning(12, 21), 0);
+        assert_eq!(table.kerning(10, 19), -10);
+    }
+}
@@ -0,0 +1,786 @@
+//! Structures shared by the OpenType layout tables, 'GSUB', 'GPOS' and 'GDEF'. These tables are
+//! made of offsets from the start of whatever structure contains them, so everything here is read
+//! from a slice that starts at that structure. Errors are reported in `TableTag(0)`, which
+//! `FontError::in_table` replaces with the table that was being read
+
+use std::fmt;
+use std::fmt::{Debug};
+use byteorder::{ByteOrder, BigEndian};
+
+use super::*;
+
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16, FontError> {
+    data.get(offset..offset + 2).map(BigEndian::read_u16).ok_or(FontError::Truncated(None))
+}
+
+pub fn read_i16(data: &[u8], offset: usize) -> Result<i16, FontError> {
+    data.get(offset..offset + 2).map(BigEndian::read_i16).ok_or(FontError::Truncated(None))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32, FontError> {
+    data.get(offset..offset + 4).map(BigEndian::read_u32).ok_or(FontError::Truncated(None))
+}
+
+/// Reads a count followed by that many u16s
+pub fn read_u16_array(data: &[u8], offset: usize) -> Result<Vec<u16>, FontError> {
+    let count = read_u16(data, offset)? as usize;
+    read_u16s(data, offset + 2, count)
+}
+
+pub fn read_u16s(data: &[u8], offset: usize, count: usize) -> Result<Vec<u16>, FontError> {
+    let bytes = data.get(offset..offset + 2 * count).ok_or(FontError::Truncated(None))?;
+    Ok(bytes.chunks(2).map(BigEndian::read_u16).collect())
+}
+
+/// The part of `data` that an offset points to. Offsets are relative to the start of `data`
+pub fn subtable(data: &[u8], offset: usize) -> Result<&[u8], FontError> {
+    data.get(offset..).ok_or(FontError::Truncated(None))
+}
+
+/// Like `subtable`, but a zero offset means there is nothing there
+pub fn optional_subtable(data: &[u8], offset: usize) -> Result<Option<&[u8]>, FontError> {
+    if offset == 0 { Ok(None) } else { subtable(data, offset).map(Some) }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RangeRecord {
+    pub start_glyph: u16,
+    pub end_glyph: u16,
+    pub start_coverage_index: u16
+}
+
+/// The set of glyphs a subtable applies to. Each covered glyph has an index, which is used to look
+/// up the data for it in the subtable
+#[derive(Clone, Debug)]
+pub enum Coverage {
+    Glyphs(Vec<u16>),
+    Ranges(Vec<RangeRecord>)
+}
+
+impl Coverage {
+    pub fn from_bytes(data: &[u8]) -> Result<Coverage, FontError> {
+        match read_u16(data, 0)? {
+            1 => Ok(Coverage::Glyphs(read_u16_array(data, 2)?)),
+            2 => {
+                let count = read_u16(data, 2)? as usize;
+                let mut ranges = Vec::with_capacity(count);
+                for i in 0..count {
+                    let r = read_u16s(data, 4 + 6 * i, 3)?;
+                    ranges.push(RangeRecord { start_glyph: r[0], end_glyph: r[1], start_coverage_index: r[2] });
+                }
+                Ok(Coverage::Ranges(ranges))
+            },
+            f => Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        }
+    }
+
+    pub fn index(&self, glyph: u16) -> Option<usize> {
+        match *self {
+            Coverage::Glyphs(ref glyphs) => glyphs.binary_search(&glyph).ok(),
+            Coverage::Ranges(ref ranges) => {
+                let i = match ranges.binary_search_by_key(&glyph, |r| r.end_glyph) { Ok(i) => i, Err(i) => i };
+                let r = ranges.get(i)?;
+                if r.start_glyph <= glyph {
+                    Some(r.start_coverage_index as usize + (glyph - r.start_glyph) as usize)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    pub fn contains(&self, glyph: u16) -> bool {
+        self.index(glyph).is_some()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ClassRangeRecord {
+    pub start_glyph: u16,
+    pub end_glyph: u16,
+    pub class: u16
+}
+
+/// Sorts glyphs into classes. Glyphs that aren't mentioned are in class 0
+#[derive(Clone, Debug)]
+pub enum ClassDef {
+    Empty,
+    Array { start_glyph: u16, classes: Vec<u16> },
+    Ranges(Vec<ClassRangeRecord>)
+}
+
+impl ClassDef {
+    pub fn from_bytes(data: &[u8]) -> Result<ClassDef, FontError> {
+        match read_u16(data, 0)? {
+            1 => Ok(ClassDef::Array { start_glyph: read_u16(data, 2)?, classes: read_u16_array(data, 4)? }),
+            2 => {
+                let count = read_u16(data, 2)? as usize;
+                let mut ranges = Vec::with_capacity(count);
+                for i in 0..count {
+                    let r = read_u16s(data, 4 + 6 * i, 3)?;
+                    ranges.push(ClassRangeRecord { start_glyph: r[0], end_glyph: r[1], class: r[2] });
+                }
+                Ok(ClassDef::Ranges(ranges))
+            },
+            f => Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        }
+    }
+
+    /// Reads a class definition at an offset, where a zero offset means every glyph is in class 0
+    pub fn from_offset(data: &[u8], offset: usize) -> Result<ClassDef, FontError> {
+        match optional_subtable(data, offset)? {
+            Some(d) => ClassDef::from_bytes(d),
+            None => Ok(ClassDef::Empty)
+        }
+    }
+
+    pub fn class(&self, glyph: u16) -> u16 {
+        match *self {
+            ClassDef::Empty => 0,
+            ClassDef::Array { start_glyph, ref classes } =>
+                glyph.checked_sub(start_glyph).and_then(|i| classes.get(i as usize).cloned()).unwrap_or(0),
+            ClassDef::Ranges(ref ranges) => {
+                let i = match ranges.binary_search_by_key(&glyph, |r| r.end_glyph) { Ok(i) => i, Err(i) => i };
+                match ranges.get(i) {
+                    Some(r) if r.start_glyph <= glyph => r.class,
+                    _ => 0
+                }
+            }
+        }
+    }
+}
+
+/// Adjusts a value by a number of pixels at particular sizes, for hinting
+#[derive(Clone, Debug)]
+pub enum Device {
+    Hinting { start_size: u16, end_size: u16, deltas: Vec<i8> },
+    /// Variable fonts use these to point into an item variation store, which isn't read, so they
+    /// never change anything
+    VariationIndex { outer: u16, inner: u16 }
+}
+
+impl Device {
+    pub fn from_bytes(data: &[u8]) -> Result<Device, FontError> {
+        let start_size = read_u16(data, 0)?;
+        let end_size = read_u16(data, 2)?;
+        let format = read_u16(data, 4)?;
+        // the deltas are packed into u16s 2, 4 or 8 bits at a time, most significant first
+        let bits = match format {
+            1 => 2,
+            2 => 4,
+            3 => 8,
+            0x8000 => return Ok(Device::VariationIndex { outer: start_size, inner: end_size }),
+            f => return Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        };
+        let count = (end_size as usize + 1).saturating_sub(start_size as usize);
+        let per_word = 16 / bits;
+        let words = read_u16s(data, 6, count.div_ceil(per_word))?;
+        let deltas = (0..count).map(|i| {
+            let word = words[i / per_word];
+            let shift = 16 - bits * (i % per_word + 1);
+            // move the delta to the top of an i16 and shift it back down to sign extend it
+            (((word >> shift) << (16 - bits)) as i16 >> (16 - bits)) as i8
+        }).collect();
+        Ok(Device::Hinting { start_size, end_size, deltas })
+    }
+
+    pub fn from_offset(data: &[u8], offset: usize) -> Result<Option<Device>, FontError> {
+        match optional_subtable(data, offset)? {
+            Some(d) => Device::from_bytes(d).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// The adjustment in pixels at a size in pixels per em
+    pub fn delta(&self, ppem: u16) -> i8 {
+        match *self {
+            Device::Hinting { start_size, end_size, ref deltas } if ppem >= start_size && ppem <= end_size =>
+                deltas[(ppem - start_size) as usize],
+            _ => 0
+        }
+    }
+
+    /// The adjustment converted to font units
+    pub fn adjustment(&self, ppem: u16, units_per_em: u16) -> i32 {
+        if ppem == 0 { return 0; }
+        self.delta(ppem) as i32 * units_per_em as i32 / ppem as i32
+    }
+}
+
+/// The features a language system uses, by index into the feature list
+#[derive(Clone, Debug)]
+pub struct LangSys {
+    pub required_feature: Option<u16>,
+    pub feature_indices: Vec<u16>
+}
+
+impl LangSys {
+    fn from_bytes(data: &[u8]) -> Result<LangSys, FontError> {
+        let required = read_u16(data, 2)?;
+        Ok(LangSys {
+            required_feature: if required == 0xffff { None } else { Some(required) },
+            feature_indices: read_u16_array(data, 4)?
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub tag: TableTag,
+    pub default_lang_sys: Option<LangSys>,
+    pub lang_systems: Vec<(TableTag, LangSys)>
+}
+
+#[derive(Clone, Debug)]
+pub struct Feature {
+    pub tag: TableTag,
+    pub lookup_indices: Vec<u16>
+}
+
+bitflags! {
+    pub flags LookupFlags: u16 {
+        const LF_RIGHT_TO_LEFT          = 0x0001,
+        const LF_IGNORE_BASE_GLYPHS     = 0x0002,
+        const LF_IGNORE_LIGATURES       = 0x0004,
+        const LF_IGNORE_MARKS           = 0x0008,
+        const LF_USE_MARK_FILTERING_SET = 0x0010,
+        const LF_MARK_ATTACHMENT_TYPE   = 0xff00
+    }
+}
+
+/// How deeply contextual lookups may apply other contextual lookups
+pub const MAX_NESTING_DEPTH: usize = 8;
+
+/// Glyph classes from 'GDEF'
+pub const GLYPH_CLASS_BASE: u16 = 1;
+pub const GLYPH_CLASS_LIGATURE: u16 = 2;
+pub const GLYPH_CLASS_MARK: u16 = 3;
+pub const GLYPH_CLASS_COMPONENT: u16 = 4;
+
+/// Answers the questions about glyphs that lookup flags ask. Fonts with a 'GDEF' table answer
+/// these from it, `NoGlyphClasses` can be used for fonts without one
+pub trait GlyphClassifier {
+    /// One of the `GLYPH_CLASS_` constants, or 0 if the glyph isn't classified
+    fn glyph_class(&self, glyph: u16) -> u16;
+    fn mark_attachment_class(&self, glyph: u16) -> u16;
+    fn is_in_mark_glyph_set(&self, set: u16, glyph: u16) -> bool;
+}
+
+pub struct NoGlyphClasses;
+
+impl GlyphClassifier for NoGlyphClasses {
+    fn glyph_class(&self, _: u16) -> u16 { 0 }
+    fn mark_attachment_class(&self, _: u16) -> u16 { 0 }
+    fn is_in_mark_glyph_set(&self, _: u16, _: u16) -> bool { false }
+}
+
+/// A glyph in a run that layout lookups are applied to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LayoutGlyph {
+    pub glyph: u16,
+    /// Where the text this glyph came from starts
+    pub cluster: usize,
+    /// Ligatures and the marks that were on their components share a non-zero id
+    pub ligature_id: u16,
+    /// For a mark with a ligature id, which component of the ligature it was on, counting from 1
+    pub ligature_component: u16,
+    /// Which features apply to this glyph, as bits chosen by the shaper. Lookups applied with a
+    /// mask only start on glyphs that have one of its bits
+    pub mask: u32
+}
+
+impl LayoutGlyph {
+    pub fn new(glyph: u16, cluster: usize) -> LayoutGlyph {
+        LayoutGlyph { glyph, cluster, ligature_id: 0, ligature_component: 0, mask: !0 }
+    }
+}
+
+pub struct Lookup<T> {
+    pub lookup_type: u16,
+    pub flags: LookupFlags,
+    pub mark_filtering_set: Option<u16>,
+    pub subtables: Vec<T>
+}
+
+impl<T: Debug> Debug for Lookup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lookup {{ type: {}, flags: {:?}, subtables: {:?} }}", self.lookup_type, self.flags, self.subtables)
+    }
+}
+
+impl<T> Lookup<T> {
+    /// Whether this lookup skips over a glyph because of its flags
+    pub fn ignores<C: GlyphClassifier>(&self, classes: &C, glyph: u16) -> bool {
+        match classes.glyph_class(glyph) {
+            GLYPH_CLASS_BASE => self.flags.contains(LF_IGNORE_BASE_GLYPHS),
+            GLYPH_CLASS_LIGATURE => self.flags.contains(LF_IGNORE_LIGATURES),
+            GLYPH_CLASS_MARK => {
+                if self.flags.contains(LF_IGNORE_MARKS) {
+                    true
+                } else if let Some(set) = self.mark_filtering_set {
+                    !classes.is_in_mark_glyph_set(set, glyph)
+                } else {
+                    let attach_type = self.flags.bits() >> 8;
+                    attach_type != 0 && classes.mark_attachment_class(glyph) != attach_type
+                }
+            },
+            _ => false
+        }
+    }
+}
+
+/// The script, feature and lookup lists that 'GSUB' and 'GPOS' share. `T` is the kind of subtable
+/// the lookups have
+pub struct LayoutTable<T> {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub scripts: Vec<Script>,
+    pub features: Vec<Feature>,
+    pub lookups: Vec<Lookup<T>>
+}
+
+impl<T: Debug> Debug for LayoutTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LayoutTable {{ version: {}.{}, scripts: {:?}, features: [len = {}], lookups: [len = {}] }}",
+               self.major_version, self.minor_version, self.scripts.iter().map(|s| s.tag).collect::<Vec<_>>(),
+               self.features.len(), self.lookups.len())
+    }
+}
+
+pub const DEFAULT_SCRIPT: TableTag = TableTag(table_tag_code!('D', 'F', 'L', 'T'));
+const LATIN_SCRIPT: TableTag = TableTag(table_tag_code!('l', 'a', 't', 'n'));
+
+impl<T> LayoutTable<T> {
+    /// Reads the header and lists. `parse_subtable` reads a subtable given its lookup type, and
+    /// `extension_type` is the lookup type that wraps a subtable of another type behind a 32 bit
+    /// offset
+    pub fn from_bytes<F>(data: &[u8], extension_type: u16, parse_subtable: F) -> Result<LayoutTable<T>, FontError>
+        where F: Fn(u16, &[u8]) -> Result<T, FontError>
+    {
+        let major_version = read_u16(data, 0)?;
+        let minor_version = read_u16(data, 2)?;
+        if major_version != 1 {
+            return Err(FontError::UnknownFormat(TableTag(0), major_version as u32));
+        }
+
+        let script_list = subtable(data, read_u16(data, 4)? as usize)?;
+        let mut scripts = Vec::new();
+        for i in 0..read_u16(script_list, 0)? as usize {
+            let tag = TableTag(read_u32(script_list, 2 + 6 * i)?);
+            let script = subtable(script_list, read_u16(script_list, 6 + 6 * i)? as usize)?;
+            let default_lang_sys = match optional_subtable(script, read_u16(script, 0)? as usize)? {
+                Some(d) => Some(LangSys::from_bytes(d)?),
+                None => None
+            };
+            let mut lang_systems = Vec::new();
+            for j in 0..read_u16(script, 2)? as usize {
+                let lang_tag = TableTag(read_u32(script, 4 + 6 * j)?);
+                lang_systems.push((lang_tag, LangSys::from_bytes(subtable(script, read_u16(script, 8 + 6 * j)? as usize)?)?));
+            }
+            scripts.push(Script { tag, default_lang_sys, lang_systems });
+        }
+
+        let feature_list = subtable(data, read_u16(data, 6)? as usize)?;
+        let mut features = Vec::new();
+        for i in 0..read_u16(feature_list, 0)? as usize {
+            let tag = TableTag(read_u32(feature_list, 2 + 6 * i)?);
+            let feature = subtable(feature_list, read_u16(feature_list, 6 + 6 * i)? as usize)?;
+            features.push(Feature { tag, lookup_indices: read_u16_array(feature, 2)? });
+        }
+
+        let lookup_list = subtable(data, read_u16(data, 8)? as usize)?;
+        let mut lookups = Vec::new();
+        for i in 0..read_u16(lookup_list, 0)? as usize {
+            let lookup = subtable(lookup_list, read_u16(lookup_list, 2 + 2 * i)? as usize)?;
+            let declared_type = read_u16(lookup, 0)?;
+            let mut lookup_type = declared_type;
+            let flags = LookupFlags::from_bits_truncate(read_u16(lookup, 2)?);
+            let offsets = read_u16_array(lookup, 4)?;
+            let mark_filtering_set = if flags.contains(LF_USE_MARK_FILTERING_SET) {
+                Some(read_u16(lookup, 6 + 2 * offsets.len())?)
+            } else {
+                None
+            };
+            let mut subtables = Vec::with_capacity(offsets.len());
+            for (n, offset) in offsets.into_iter().enumerate() {
+                let mut st = subtable(lookup, offset as usize)?;
+                let mut subtable_type = declared_type;
+                if declared_type == extension_type {
+                    subtable_type = read_u16(st, 2)?;
+                    st = subtable(st, read_u32(st, 4)? as usize)?;
+                    // the lookup takes the type its subtables wrap, which has to be the same for all of them
+                    if n > 0 && subtable_type != lookup_type {
+                        return Err(FontError::InconsistentCounts(TableTag(0), "extension subtables wrap different lookup types"));
+                    }
+                    lookup_type = subtable_type;
+                }
+                subtables.push(parse_subtable(subtable_type, st)?);
+            }
+            lookups.push(Lookup { lookup_type, flags, mark_filtering_set, subtables });
+        }
+
+        Ok(LayoutTable { major_version, minor_version, scripts, features, lookups })
+    }
+
+    /// Find a script, falling back to the default script and then Latin like other layout engines
+    /// do for fonts that don't have the script that was asked for
+    pub fn script(&self, script: TableTag) -> Option<&Script> {
+        self.scripts.iter().find(|s| s.tag == script)
+            .or_else(|| self.scripts.iter().find(|s| s.tag == DEFAULT_SCRIPT))
+            .or_else(|| self.scripts.iter().find(|s| s.tag == LATIN_SCRIPT))
+    }
+
+    /// The language system for a language in a script, or the script's default one
+    fn lang_sys(&self, script: TableTag, language: Option<TableTag>) -> Option<&LangSys> {
+        let s = self.script(script)?;
+        match language.and_then(|l| s.lang_systems.iter().find(|&&(t, _)| t == l)) {
+            Some((_, ls)) => Some(ls),
+            None => s.default_lang_sys.as_ref()
+        }
+    }
+
+    /// The lookups to apply for a set of features in a script and language, in the order they
+    /// should be applied. The language system's required feature is always included
+    pub fn lookups_for_features(&self, script: TableTag, language: Option<TableTag>, features: &[TableTag]) -> Vec<u16> {
+        let lang_sys = match self.lang_sys(script, language) {
+            Some(ls) => ls,
+            None => return Vec::new()
+        };
+        let mut lookups = Vec::new();
+        for &fi in lang_sys.required_feature.iter().chain(lang_sys.feature_indices.iter()) {
+            if let Some(feature) = self.features.get(fi as usize) {
+                if Some(fi) == lang_sys.required_feature || features.contains(&feature.tag) {
+                    lookups.extend_from_slice(&feature.lookup_indices);
+                }
+            }
+        }
+        lookups.sort();
+        lookups.dedup();
+        lookups
+    }
+
+    /// The lookups for just one feature in a script and language, leaving out the required feature
+    /// unless it is the one asked for
+    pub fn lookups_for_feature(&self, script: TableTag, language: Option<TableTag>, feature: TableTag) -> Vec<u16> {
+        let mut lookups = Vec::new();
+        if let Some(lang_sys) = self.lang_sys(script, language) {
+            for &fi in lang_sys.required_feature.iter().chain(lang_sys.feature_indices.iter()) {
+                match self.features.get(fi as usize) {
+                    Some(f) if f.tag == feature => lookups.extend_from_slice(&f.lookup_indices),
+                    _ => {}
+                }
+            }
+        }
+        lookups.sort();
+        lookups.dedup();
+        lookups
+    }
+
+    /// The lookups of the language system's required feature, which are applied whatever features
+    /// were asked for
+    pub fn required_lookups(&self, script: TableTag, language: Option<TableTag>) -> Vec<u16> {
+        self.lang_sys(script, language)
+            .and_then(|ls| ls.required_feature)
+            .and_then(|fi| self.features.get(fi as usize))
+            .map(|f| f.lookup_indices.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Apply a lookup at this position in the sequence once the rest of a context has matched
+#[derive(Copy, Clone, Debug)]
+pub struct SequenceLookup {
+    pub sequence_index: u16,
+    pub lookup_index: u16
+}
+
+fn read_sequence_lookups(data: &[u8], offset: usize, count: usize) -> Result<Vec<SequenceLookup>, FontError> {
+    let values = read_u16s(data, offset, 2 * count)?;
+    Ok(values.chunks(2).map(|v| SequenceLookup { sequence_index: v[0], lookup_index: v[1] }).collect())
+}
+
+/// A rule in a glyph or class based context. The values are glyphs or classes depending on the
+/// subtable, and the input doesn't include the first glyph, which the coverage already matched.
+/// Backtrack values are in reverse order, starting from the glyph before the input
+#[derive(Clone, Debug)]
+pub struct ContextRule {
+    pub backtrack: Vec<u16>,
+    pub input: Vec<u16>,
+    pub lookahead: Vec<u16>,
+    pub lookups: Vec<SequenceLookup>
+}
+
+impl ContextRule {
+    fn from_bytes(data: &[u8], chained: bool) -> Result<ContextRule, FontError> {
+        if chained {
+            let backtrack = read_u16_array(data, 0)?;
+            let mut offset = 2 + 2 * backtrack.len();
+            let input_count = read_u16(data, offset)? as usize;
+            let input = read_u16s(data, offset + 2, input_count.saturating_sub(1))?;
+            offset += 2 + 2 * input.len();
+            let lookahead = read_u16_array(data, offset)?;
+            offset += 2 + 2 * lookahead.len();
+            let lookup_count = read_u16(data, offset)? as usize;
+            let lookups = read_sequence_lookups(data, offset + 2, lookup_count)?;
+            Ok(ContextRule { backtrack, input, lookahead, lookups })
+        } else {
+            let input_count = read_u16(data, 0)? as usize;
+            let lookup_count = read_u16(data, 2)? as usize;
+            let input = read_u16s(data, 4, input_count.saturating_sub(1))?;
+            let lookups = read_sequence_lookups(data, 4 + 2 * input.len(), lookup_count)?;
+            Ok(ContextRule { backtrack: Vec::new(), input, lookahead: Vec::new(), lookups })
+        }
+    }
+}
+
+fn read_rule_sets(data: &[u8], offset: usize, chained: bool) -> Result<Vec<Vec<ContextRule>>, FontError> {
+    let mut rule_sets = Vec::new();
+    for set_offset in read_u16_array(data, offset)? {
+        let mut rules = Vec::new();
+        if let Some(set) = optional_subtable(data, set_offset as usize)? {
+            for rule_offset in read_u16_array(set, 0)? {
+                rules.push(ContextRule::from_bytes(subtable(set, rule_offset as usize)?, chained)?);
+            }
+        }
+        rule_sets.push(rules);
+    }
+    Ok(rule_sets)
+}
+
+fn read_coverages(data: &[u8], offset: usize) -> Result<Vec<Coverage>, FontError> {
+    read_u16_array(data, offset)?.into_iter()
+        .map(|o| Coverage::from_bytes(subtable(data, o as usize)?))
+        .collect()
+}
+
+/// A contextual or chained contextual lookup subtable, which applies other lookups where a
+/// sequence of glyphs matches. Plain contexts are read as chained ones without a backtrack or
+/// lookahead
+#[derive(Clone, Debug)]
+pub enum ContextLookup {
+    /// Rule sets are indexed by the coverage index of the first glyph
+    Glyphs { coverage: Coverage, rule_sets: Vec<Vec<ContextRule>> },
+    /// Rule sets are indexed by the input class of the first glyph
+    Classes {
+        coverage: Coverage,
+        backtrack_classes: ClassDef,
+        input_classes: ClassDef,
+        lookahead_classes: ClassDef,
+        rule_sets: Vec<Vec<ContextRule>>
+    },
+    Coverages {
+        backtrack: Vec<Coverage>,
+        input: Vec<Coverage>,
+        lookahead: Vec<Coverage>,
+        lookups: Vec<SequenceLookup>
+    }
+}
+
+/// The positions of the glyphs a context matched, and the lookups to apply to them
+pub struct ContextMatch<'l> {
+    pub positions: Vec<usize>,
+    pub lookups: &'l [SequenceLookup]
+}
+
+impl ContextLookup {
+    pub fn from_bytes(data: &[u8], chained: bool) -> Result<ContextLookup, FontError> {
+        let format = read_u16(data, 0)?;
+        match (format, chained) {
+            (1, _) => Ok(ContextLookup::Glyphs {
+                coverage: Coverage::from_bytes(subtable(data, read_u16(data, 2)? as usize)?)?,
+                rule_sets: read_rule_sets(data, 4, chained)?
+            }),
+            (2, false) => {
+                let classes = ClassDef::from_offset(data, read_u16(data, 4)? as usize)?;
+                Ok(ContextLookup::Classes {
+                    coverage: Coverage::from_bytes(subtable(data, read_u16(data, 2)? as usize)?)?,
+                    backtrack_classes: ClassDef::Empty,
+                    lookahead_classes: ClassDef::Empty,
+                    input_classes: classes,
+                    rule_sets: read_rule_sets(data, 6, false)?
+                })
+            },
+            (2, true) => Ok(ContextLookup::Classes {
+                coverage: Coverage::from_bytes(subtable(data, read_u16(data, 2)? as usize)?)?,
+                backtrack_classes: ClassDef::from_offset(data, read_u16(data, 4)? as usize)?,
+                input_classes: ClassDef::from_offset(data, read_u16(data, 6)? as usize)?,
+                lookahead_classes: ClassDef::from_offset(data, read_u16(data, 8)? as usize)?,
+                rule_sets: read_rule_sets(data, 10, true)?
+            }),
+            (3, false) => {
+                let input_count = read_u16(data, 2)? as usize;
+                let lookup_count = read_u16(data, 4)? as usize;
+                let input = read_u16s(data, 6, input_count)?.into_iter()
+                    .map(|o| Coverage::from_bytes(subtable(data, o as usize)?))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(ContextLookup::Coverages {
+                    backtrack: Vec::new(), input, lookahead: Vec::new(),
+                    lookups: read_sequence_lookups(data, 6 + 2 * input_count, lookup_count)?
+                })
+            },
+            (3, true) => {
+                let backtrack = read_coverages(data, 2)?;
+                let mut offset = 4 + 2 * backtrack.len();
+                let input = read_coverages(data, offset)?;
+                offset += 2 + 2 * input.len();
+                let lookahead = read_coverages(data, offset)?;
+                offset += 2 + 2 * lookahead.len();
+                let lookup_count = read_u16(data, offset)? as usize;
+                Ok(ContextLookup::Coverages {
+                    backtrack, input, lookahead,
+                    lookups: read_sequence_lookups(data, offset + 2, lookup_count)?
+                })
+            },
+            (f, _) => Err(FontError::UnknownFormat(TableTag(0), f as u32))
+        }
+    }
+
+    /// Try to match the context with its first input glyph at `index`. Glyphs that `ignored` says
+    /// the lookup skips over are passed by while matching, but they are still kept in the sequence
+    pub fn match_at<F: Fn(u16) -> bool>(&self, glyphs: &[LayoutGlyph], index: usize, ignored: F) -> Option<ContextMatch<'_>> {
+        let first = glyphs[index].glyph;
+        match *self {
+            ContextLookup::Glyphs { ref coverage, ref rule_sets } => {
+                let rules = rule_sets.get(coverage.index(first)?)?;
+                rules.iter().filter_map(|rule| {
+                    match_rule(glyphs, index, &ignored, rule, |v, g| v == g, |v, g| v == g, |v, g| v == g)
+                }).next()
+            },
+            ContextLookup::Classes { ref coverage, ref backtrack_classes, ref input_classes, ref lookahead_classes, ref rule_sets } => {
+                coverage.index(first)?;
+                let rules = rule_sets.get(input_classes.class(first) as usize)?;
+                rules.iter().filter_map(|rule| {
+                    match_rule(glyphs, index, &ignored, rule,
+                               |v, g| v == backtrack_classes.class(g),
+                               |v, g| v == input_classes.class(g),
+                               |v, g| v == lookahead_classes.class(g))
+                }).next()
+            },
+            ContextLookup::Coverages { ref backtrack, ref input, ref lookahead, ref lookups } => {
+                if !input.first()?.contains(first) { return None; }
+                let positions = match_sequence(glyphs, index, &ignored, input[1..].iter(), |c, g| c.contains(g), backtrack.iter(), lookahead.iter())?;
+                Some(ContextMatch { positions, lookups })
+            }
+        }
+    }
+}
+
+fn match_rule<'r, F, B, I, L>(glyphs: &[LayoutGlyph], index: usize, ignored: &F, rule: &'r ContextRule, backtrack: B, input: I, lookahead: L) -> Option<ContextMatch<'r>>
+    where F: Fn(u16) -> bool, B: Fn(u16, u16) -> bool, I: Fn(u16, u16) -> bool, L: Fn(u16, u16) -> bool
+{
+    let mut positions = vec![index];
+    let mut i = index;
+    for &v in &rule.input {
+        i = next_glyph(glyphs, i, ignored)?;
+        if !input(v, glyphs[i].glyph) { return None; }
+        positions.push(i);
+    }
+    let mut j = i;
+    for &v in &rule.lookahead {
+        j = next_glyph(glyphs, j, ignored)?;
+        if !lookahead(v, glyphs[j].glyph) { return None; }
+    }
+    let mut j = index;
+    for &v in &rule.backtrack {
+        j = prev_glyph(glyphs, j, ignored)?;
+        if !backtrack(v, glyphs[j].glyph) { return None; }
+    }
+    Some(ContextMatch { positions, lookups: &rule.lookups })
+}
+
+fn match_sequence<'c, F, M, T: 'c, It>(glyphs: &[LayoutGlyph], index: usize, ignored: &F, input: It, matches: M, backtrack: It, lookahead: It) -> Option<Vec<usize>>
+    where F: Fn(u16) -> bool, M: Fn(&T, u16) -> bool, It: Iterator<Item=&'c T>
+{
+    let mut positions = vec![index];
+    let mut i = index;
+    for v in input {
+        i = next_glyph(glyphs, i, ignored)?;
+        if !matches(v, glyphs[i].glyph) { return None; }
+        positions.push(i);
+    }
+    let mut j = i;
+    for v in lookahead {
+        j = next_glyph(glyphs, j, ignored)?;
+        if !matches(v, glyphs[j].glyph) { return None; }
+    }
+    let mut j = index;
+    for v in backtrack {
+        j = prev_glyph(glyphs, j, ignored)?;
+        if !matches(v, glyphs[j].glyph) { return None; }
+    }
+    Some(positions)
+}
+
+/// The next glyph after `index` that isn't ignored
+pub fn next_glyph<F: Fn(u16) -> bool>(glyphs: &[LayoutGlyph], index: usize, ignored: &F) -> Option<usize> {
+    (index + 1..glyphs.len()).find(|&i| !ignored(glyphs[i].glyph))
+}
+
+/// The closest glyph before `index` that isn't ignored
+pub fn prev_glyph<F: Fn(u16) -> bool>(glyphs: &[LayoutGlyph], index: usize, ignored: &F) -> Option<usize> {
+    (0..index).rev().find(|&i| !ignored(glyphs[i].glyph))
+}
+
+/// Builders for the layout tables that the 'GSUB', 'GPOS' and 'GDEF' tests read
+#[cfg(test)]
+pub mod test_tables {
+    use byteorder::{BigEndian, WriteBytesExt};
+    use super::LayoutGlyph;
+
+    pub fn words(values: &[u16]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for &v in values { data.write_u16::<BigEndian>(v).unwrap(); }
+        data
+    }
+
+    /// A 'GSUB' or 'GPOS' table with a single script and feature that uses every lookup. Lookups
+    /// are given as their type, flags and one subtable
+    pub fn layout_table(feature: &[u8; 4], lookups: &[(u16, u16, Vec<u8>)]) -> Vec<u8> {
+        let lookups: Vec<_> = lookups.iter().map(|&(lookup_type, flags, ref st)| (lookup_type, flags, vec![st.clone()])).collect();
+        layout_table_with_subtables(feature, &lookups)
+    }
+
+    /// A table like `layout_table` makes, with any number of subtables in each lookup
+    pub fn layout_table_with_subtables(feature: &[u8; 4], lookups: &[(u16, u16, Vec<Vec<u8>>)]) -> Vec<u8> {
+        let script_list = {
+            let mut d = words(&[1]);
+            d.extend_from_slice(b"DFLT");
+            d.extend(words(&[8, 4, 0, 0, 0xffff, 1, 0]));
+            d
+        };
+        let feature_list = {
+            let mut d = words(&[1]);
+            d.extend_from_slice(feature);
+            d.extend(words(&[8, 0, lookups.len() as u16]));
+            d.extend(words(&(0..lookups.len() as u16).collect::<Vec<_>>()));
+            d
+        };
+        let mut lookup_list = words(&[lookups.len() as u16]);
+        let mut lookup_data = Vec::new();
+        let header = 2 + 2 * lookups.len();
+        for &(lookup_type, flags, ref sts) in lookups {
+            lookup_list.extend(words(&[(header + lookup_data.len()) as u16]));
+            lookup_data.extend(words(&[lookup_type, flags, sts.len() as u16]));
+            let mut offset = 6 + 2 * sts.len();
+            for st in sts {
+                lookup_data.extend(words(&[offset as u16]));
+                offset += st.len();
+            }
+            for st in sts {
+                lookup_data.extend_from_slice(st);
+            }
+        }
+        lookup_list.extend(lookup_data);
+
+        let mut data = words(&[1, 0, 10, 10 + script_list.len() as u16, (10 + script_list.len() + feature_list.len()) as u16]);
+        data.extend(script_list);
+        data.extend(feature_list);
+        data.extend(lookup_list);
+        data
+    }
+
+    /// Glyphs that are each their own cluster
+    pub fn glyph_run(glyphs: &[u16]) -> Vec<LayoutGlyph> {
+        glyphs.iter().enumerate().map(|(i, &g)| LayoutGlyph::new(g, i)).collect()
+    }
+}
@@ -7,6 +7,8 @@ use std::fmt;
 use std::fmt::{Debug};
 use std::mem;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::ops::Deref;
 use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
 
 
@@ -28,15 +30,80 @@ UFWORD	Unsigned 16-bit integer (USHORT) that describes a quantity in FUnits.
 F2DOT14	16-bit signed fixed number with the low 14 bits of fraction (2.14).
 */
 
-//TODO: Change this so that it just converts to float?
-#[derive(Copy, Clone, Debug)]
-pub struct Fixed {
-    int_part: u16,
-    frac_part: u16
+pub use numerics::Fixed;
+
+/// Errors that can occur while reading font data
+#[derive(Debug)]
+pub enum FontError {
+    /// The underlying reader failed
+    Io(io::Error),
+    /// The data ended before a structure in it did. The table is filled in once the error leaves
+    /// the table's parser
+    Truncated(Option<TableTag>),
+    /// The 'head' table magic number was wrong
+    BadMagic(u32),
+    /// A table or subtable is in a format this loader does not understand
+    UnknownFormat(TableTag, u32),
+    /// An offset points outside of the data it is supposed to point into
+    OffsetOutOfBounds(TableTag),
+    /// Counts or indices in a table disagree with each other or with the size of the data
+    InconsistentCounts(TableTag, &'static str),
+    /// A table that is needed to read another table is missing
+    MissingTable(TableTag),
+    /// A glyph index past the end of the font
+    GlyphOutOfRange(usize)
+}
+
+impl FontError {
+    /// Attach the table being read to errors that didn't know it
+    fn in_table(self, tag: TableTag) -> FontError {
+        match self {
+            FontError::Truncated(None) => FontError::Truncated(Some(tag)),
+            FontError::UnknownFormat(TableTag(0), f) => FontError::UnknownFormat(tag, f),
+            FontError::OffsetOutOfBounds(TableTag(0)) => FontError::OffsetOutOfBounds(tag),
+            FontError::InconsistentCounts(TableTag(0), m) => FontError::InconsistentCounts(tag, m),
+            e => e
+        }
+    }
 }
-impl Fixed {
-    pub fn from_binary<R: Read + Seek, E: ByteOrder>(r: &mut R) -> io::Result<Fixed> {
-        Ok(Fixed{ int_part: r.read_u16::<E>()?, frac_part: r.read_u16::<E>()? })
+
+impl From<io::Error> for FontError {
+    fn from(e: io::Error) -> FontError {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => FontError::Truncated(None),
+            _ => FontError::Io(e)
+        }
+    }
+}
+
+impl ::std::error::Error for FontError {
+    fn description(&self) -> &str {
+        match *self {
+            FontError::Io(_) => "I/O error",
+            FontError::Truncated(_) => "font data truncated",
+            FontError::BadMagic(_) => "bad magic number",
+            FontError::UnknownFormat(_,_) => "unknown table format",
+            FontError::OffsetOutOfBounds(_) => "offset out of bounds",
+            FontError::InconsistentCounts(_,_) => "inconsistent counts in table",
+            FontError::MissingTable(_) => "missing font data table",
+            FontError::GlyphOutOfRange(_) => "glyph index out of range"
+        }
+    }
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FontError::Io(ref e) => write!(f, "{}", e),
+            FontError::Truncated(Some(t)) => write!(f, "{:?} is truncated", t),
+            FontError::Truncated(None) => write!(f, "font data truncated"),
+            FontError::BadMagic(m) => write!(f, "bad magic number {:08x}", m),
+            FontError::UnknownFormat(t, fmt) => write!(f, "{:?} has unknown format {}", t, fmt),
+            FontError::OffsetOutOfBounds(t) => write!(f, "offset out of bounds in {:?}", t),
+            FontError::InconsistentCounts(t, what) => write!(f, "{:?}: {}", t, what),
+            FontError::MissingTable(t) => write!(f, "missing font data table {:?}", t),
+            FontError::GlyphOutOfRange(g) => write!(f, "glyph index {} out of range", g)
+        }
     }
 }
 
@@ -44,40 +111,56 @@ macro_rules! table_tag_code {
     ($a:expr, $b:expr, $c:expr, $d:expr) => (($a as u32) << 24 | ($b as u32) << 16 | ($c as u32) << 8 | ($d as u32));
 }
 
-#[repr(u32)]
-#[derive(Copy, Clone)]
-pub enum TableTag {
+/// A four character table tag. Any tag can be represented, the constants are just the tables that
+/// have names in this crate
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TableTag(pub u32);
+
+impl TableTag {
     //Required Tables
-    CharGlyphMapping = table_tag_code!('c','m','a','p'),
-    GlyphData = table_tag_code!('g','l','y','f'),
-    FontHeader = table_tag_code!('h', 'e', 'a', 'd'),
-    HorizHeader = table_tag_code!('h', 'h', 'e', 'a'),
-    HorizMetrics = table_tag_code!('h', 'm', 't', 'x'),
-    LocationIndex = table_tag_code!('l', 'o', 'c', 'a'),
-    MaxProfile = table_tag_code!('m', 'a', 'x', 'p'),
-    Nameing = table_tag_code!('n', 'a', 'm', 'e'),
-    PostScriptInfo = table_tag_code!('p', 'o', 's', 't'),
-    WindowsOS2SpecificMetric = table_tag_code!('O', 'S', '/', '2'),
+    pub const CharGlyphMapping: TableTag = TableTag(table_tag_code!('c','m','a','p'));
+    pub const GlyphData: TableTag = TableTag(table_tag_code!('g','l','y','f'));
+    pub const FontHeader: TableTag = TableTag(table_tag_code!('h', 'e', 'a', 'd'));
+    pub const HorizHeader: TableTag = TableTag(table_tag_code!('h', 'h', 'e', 'a'));
+    pub const HorizMetrics: TableTag = TableTag(table_tag_code!('h', 'm', 't', 'x'));
+    pub const LocationIndex: TableTag = TableTag(table_tag_code!('l', 'o', 'c', 'a'));
+    pub const MaxProfile: TableTag = TableTag(table_tag_code!('m', 'a', 'x', 'p'));
+    pub const Nameing: TableTag = TableTag(table_tag_code!('n', 'a', 'm', 'e'));
+    pub const PostScriptInfo: TableTag = TableTag(table_tag_code!('p', 'o', 's', 't'));
+    pub const WindowsOS2SpecificMetric: TableTag = TableTag(table_tag_code!('O', 'S', '/', '2'));
     //Optional Tables
-    ControlValue = table_tag_code!('c', 'v', 't', ' '),
-    EmbeddedBitmapData = table_tag_code!('E', 'B', 'D', 'T'),
-    EmbeddedBitmapLocationData = table_tag_code!('E', 'B', 'L', 'C'),
-    EmbeddedBitmapScalingData = table_tag_code!('E', 'B', 'S', 'C'),
-    FontProgram = table_tag_code!('f', 'p', 'g', 'm'),
-    GridFitAndScanConvertProc = table_tag_code!('g', 'a', 's', 'p'),
-    HorizDevMetric = table_tag_code!('h', 'd', 'm', 'x'),
-    Kerning = table_tag_code!('k', 'e', 'r', 'n'),
-    LinearThreshold = table_tag_code!('L', 'T', 'S', 'H'),
-    PreProgram = table_tag_code!('p', 'r', 'e', 'p'),
-    PCL5 = table_tag_code!('P', 'C', 'L', 'T'),
-    VertDevMetrics = table_tag_code!('V', 'D', 'M', 'X'),
-    VertHeader = table_tag_code!('v', 'h', 'e', 'a'),
-    VertMetrics = table_tag_code!('v', 'm', 't', 'x')
+    pub const ControlValue: TableTag = TableTag(table_tag_code!('c', 'v', 't', ' '));
+    pub const EmbeddedBitmapData: TableTag = TableTag(table_tag_code!('E', 'B', 'D', 'T'));
+    pub const EmbeddedBitmapLocationData: TableTag = TableTag(table_tag_code!('E', 'B', 'L', 'C'));
+    pub const EmbeddedBitmapScalingData: TableTag = TableTag(table_tag_code!('E', 'B', 'S', 'C'));
+    pub const FontProgram: TableTag = TableTag(table_tag_code!('f', 'p', 'g', 'm'));
+    pub const GridFitAndScanConvertProc: TableTag = TableTag(table_tag_code!('g', 'a', 's', 'p'));
+    pub const HorizDevMetric: TableTag = TableTag(table_tag_code!('h', 'd', 'm', 'x'));
+    pub const Kerning: TableTag = TableTag(table_tag_code!('k', 'e', 'r', 'n'));
+    pub const LinearThreshold: TableTag = TableTag(table_tag_code!('L', 'T', 'S', 'H'));
+    pub const PreProgram: TableTag = TableTag(table_tag_code!('p', 'r', 'e', 'p'));
+    pub const PCL5: TableTag = TableTag(table_tag_code!('P', 'C', 'L', 'T'));
+    pub const VertDevMetrics: TableTag = TableTag(table_tag_code!('V', 'D', 'M', 'X'));
+    pub const VertHeader: TableTag = TableTag(table_tag_code!('v', 'h', 'e', 'a'));
+    pub const VertMetrics: TableTag = TableTag(table_tag_code!('v', 'm', 't', 'x'));
+    //OpenType Tables
+    pub const CompactFontFormat: TableTag = TableTag(table_tag_code!('C', 'F', 'F', ' '));
+    pub const GlyphDefinition: TableTag = TableTag(table_tag_code!('G', 'D', 'E', 'F'));
+    pub const GlyphPositioning: TableTag = TableTag(table_tag_code!('G', 'P', 'O', 'S'));
+    pub const GlyphSubstitution: TableTag = TableTag(table_tag_code!('G', 'S', 'U', 'B'));
+
+    pub fn new(tag: &[u8; 4]) -> TableTag {
+        TableTag(table_tag_code!(tag[0], tag[1], tag[2], tag[3]))
+    }
+
+    pub fn to_bytes(self) -> [u8; 4] {
+        [(self.0 >> 24) as u8, (self.0 >> 16) as u8, (self.0 >> 8) as u8, self.0 as u8]
+    }
 }
 
 impl Debug for TableTag {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let n = *self as u32;
+        let n = self.0;
         write!(f, "Table:{}{}{}{}", ((n>>24) as u8) as char, ((n>>16) as u8) as char, ((n>>8) as u8) as char, (n as u8) as char)
     }
 }
@@ -90,6 +173,22 @@ mod char_glyph_mapping_table;
 pub use self::char_glyph_mapping_table::*;
 mod glyph_data_table;
 pub use self::glyph_data_table::*;
+mod naming_table;
+pub use self::naming_table::*;
+mod os2_table;
+pub use self::os2_table::*;
+mod post_table;
+pub use self::post_table::*;
+mod kerning_table;
+pub use self::kerning_table::*;
+mod layout_common;
+pub use self::layout_common::*;
+mod glyph_positioning_table;
+pub use self::glyph_positioning_table::*;
+mod glyph_substitution_table;
+pub use self::glyph_substitution_table::*;
+mod glyph_definition_table;
+pub use self::glyph_definition_table::*;
 
 pub struct ControlValueTable(pub Vec<i16>);
 
@@ -139,7 +238,7 @@ impl Table for GASPTable {
 }
 
 impl GASPTable {
-    fn from_binary<R: Read + Seek>(reader: &mut R) -> io::Result<GASPTable> {
+    fn from_binary<R: Read + Seek>(reader: &mut R) -> Result<GASPTable, FontError> {
         let ver = reader.read_u16::<BigEndian>()?;
         let num_ranges = reader.read_u16::<BigEndian>()?;
         let mut r = Vec::new();
@@ -182,12 +281,16 @@ pub struct HorizDeviceMetricsTable {
 }
 
 impl HorizDeviceMetricsTable {
-    fn from_binary<R: Read+Seek>(reader: &mut R, num_glyphs: usize) -> io::Result<HorizDeviceMetricsTable> {
+    fn from_binary<R: Read+Seek>(reader: &mut R, num_glyphs: usize) -> Result<HorizDeviceMetricsTable, FontError> {
         let v = reader.read_u16::<BigEndian>()?;
         let num_dr = reader.read_i16::<BigEndian>()?;
         let size_dr = reader.read_i32::<BigEndian>()?;
+        if (size_dr as i64) < 2 + num_glyphs as i64 {
+            return Err(FontError::InconsistentCounts(TableTag::HorizDevMetric, "device record smaller than its glyph widths"));
+        }
         let mut r = Vec::new();
-        for _ in 0..num_dr {
+        for i in 0..num_dr.max(0) {
+            reader.seek(io::SeekFrom::Start(8 + i as u64 * size_dr as u64))?;
             let ps = reader.read_u8()?;
             let mw = reader.read_u8()?;
             let mut w = vec![0u8; num_glyphs];
@@ -197,7 +300,6 @@ impl HorizDeviceMetricsTable {
                 max_width: mw,
                 widths: w
             }); // this requires knowing numGlyphs from the maxp table
-            reader.seek(io::SeekFrom::Current(size_dr as i64))?;
         }
         Ok(HorizDeviceMetricsTable {
             version: v,
@@ -210,6 +312,16 @@ impl Table for HorizDeviceMetricsTable {
     fn tag(&self) -> TableTag { TableTag::HorizDevMetric }
 }
 
+/// The vertical metrics of a line of text in font units, with y going up from the baseline, so the
+/// descender is usually negative
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LineMetrics {
+    pub ascender: i16,
+    pub descender: i16,
+    /// The extra space to leave between lines
+    pub line_gap: i16
+}
+
 #[derive(Debug)]
 pub struct HorizHeaderTable {
     version: Fixed,
@@ -228,7 +340,7 @@ pub struct HorizHeaderTable {
 }
 
 impl HorizHeaderTable {
-    fn from_binary<R: Read+Seek>(reader: &mut R) -> io::Result<Self> {
+    fn from_binary<R: Read+Seek>(reader: &mut R) -> Result<Self, FontError> {
         Ok(HorizHeaderTable {
             version: Fixed::from_binary::<_, BigEndian>(reader)?,
             ascent: reader.read_i16::<BigEndian>()?,
@@ -256,14 +368,14 @@ impl Table for HorizHeaderTable {
 }
 
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 pub struct LongHorizMetric {
     pub advance_width: u16,
     pub left_side_bearing: i16
 }
 
 impl LongHorizMetric {
-    fn from_binary<R: Read+Seek>(reader: &mut R) -> io::Result<Self> {
+    fn from_binary<R: Read+Seek>(reader: &mut R) -> Result<Self, FontError> {
         Ok(LongHorizMetric {
             advance_width: reader.read_u16::<BigEndian>()?,
             left_side_bearing: reader.read_i16::<BigEndian>()?
@@ -271,30 +383,98 @@ impl LongHorizMetric {
     }
 }
 
-#[derive(Debug)]
-pub struct HorizMetricsTable {
-    left_side_bearing: Vec<i16>,
-    pub metrics: Vec<LongHorizMetric>
+/// Some of the bytes of a font file. They are either borrowed from the caller, or owned by the font
+/// and shared with the tables that were left in the font data
+#[derive(Clone)]
+pub struct FontData<'a> {
+    bytes: FontBytes<'a>,
+    start: usize,
+    end: usize
 }
 
-impl HorizMetricsTable {
-    fn from_binary<R: Read+Seek>(reader: &mut R, num_glyphs: usize, num_long_horz_metrics: usize) -> io::Result<Self> {
-        let mut mt = Vec::new();
-        for _ in 0..num_long_horz_metrics {
-            mt.push(LongHorizMetric::from_binary(reader)?);
+#[derive(Clone)]
+enum FontBytes<'a> {
+    Borrowed(&'a [u8]),
+    Owned(Arc<Vec<u8>>)
+}
+
+impl<'a> FontData<'a> {
+    /// The bytes from `start` to `end` of these ones, which the caller has checked are in range
+    fn slice(&self, start: usize, end: usize) -> FontData<'a> {
+        assert!(start <= end && end <= self.len());
+        FontData { bytes: self.bytes.clone(), start: self.start + start, end: self.start + end }
+    }
+}
+
+impl<'a> From<&'a [u8]> for FontData<'a> {
+    fn from(data: &'a [u8]) -> FontData<'a> {
+        FontData { bytes: FontBytes::Borrowed(data), start: 0, end: data.len() }
+    }
+}
+
+impl From<Vec<u8>> for FontData<'static> {
+    fn from(data: Vec<u8>) -> FontData<'static> {
+        let end = data.len();
+        FontData { bytes: FontBytes::Owned(Arc::new(data)), start: 0, end }
+    }
+}
+
+impl<'a> Deref for FontData<'a> {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self.bytes {
+            FontBytes::Borrowed(data) => &data[self.start..self.end],
+            FontBytes::Owned(ref data) => &data[self.start..self.end]
+        }
+    }
+}
+
+/// The 'hmtx' table, left in the font data. Entries are decoded as they are asked for
+#[derive(Clone)]
+pub struct HorizMetricsTable<'a> {
+    data: FontData<'a>,
+    num_glyphs: usize,
+    num_long_horz_metrics: usize
+}
+
+impl<'a> HorizMetricsTable<'a> {
+    fn from_bytes(data: FontData<'a>, num_glyphs: usize, num_long_horz_metrics: usize) -> Result<Self, FontError> {
+        if num_long_horz_metrics == 0 {
+            return Err(FontError::InconsistentCounts(TableTag::HorizMetrics, "no long horizontal metrics"));
         }
-        let mut lsb = Vec::new();
-        for _ in 0..(num_glyphs-num_long_horz_metrics) {
-            lsb.push(reader.read_i16::<BigEndian>()?);
+        // glyphs past the last long metric only store their left side bearing
+        let len = num_long_horz_metrics*4 + num_glyphs.saturating_sub(num_long_horz_metrics)*2;
+        if data.len() < len {
+            return Err(FontError::Truncated(Some(TableTag::HorizMetrics)));
+        }
+        Ok(HorizMetricsTable { data, num_glyphs, num_long_horz_metrics })
+    }
+
+    pub fn metric(&self, glyph_index: usize) -> Result<LongHorizMetric, FontError> {
+        if glyph_index >= self.num_glyphs.max(self.num_long_horz_metrics) {
+            return Err(FontError::GlyphOutOfRange(glyph_index));
+        }
+        if glyph_index < self.num_long_horz_metrics {
+            LongHorizMetric::from_binary(&mut io::Cursor::new(&self.data[glyph_index*4..]))
+        } else {
+            // the last advance width applies to all remaining glyphs
+            let last = self.num_long_horz_metrics-1;
+            let lsb_offset = self.num_long_horz_metrics*4 + (glyph_index-self.num_long_horz_metrics)*2;
+            Ok(LongHorizMetric {
+                advance_width: BigEndian::read_u16(&self.data[last*4..]),
+                left_side_bearing: BigEndian::read_i16(&self.data[lsb_offset..])
+            })
         }
-        Ok(HorizMetricsTable {
-            left_side_bearing: lsb,
-            metrics: mt
-        })
     }
 }
 
-impl Table for HorizMetricsTable {
+impl<'a> Debug for HorizMetricsTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HorizMetricsTable [num_glyphs = {}, num_long_horz_metrics = {}]", self.num_glyphs, self.num_long_horz_metrics)
+    }
+}
+
+impl<'a> Table for HorizMetricsTable<'a> {
     fn tag(&self) -> TableTag { TableTag::HorizMetrics }
 }
 
@@ -319,12 +499,16 @@ pub struct FontHeader {
 }
 
 impl FontHeader {
-    fn from_binary<R: Read + Seek>(reader: &mut R) -> io::Result<FontHeader> {
-        Ok(FontHeader {
+    fn from_binary<R: Read + Seek>(reader: &mut R) -> Result<FontHeader, FontError> {
+        let head = FontHeader {
             version: Fixed::from_binary::<R,BigEndian>(reader)?,
             font_rev: Fixed::from_binary::<R,BigEndian>(reader)?,
             checksum: reader.read_u32::<BigEndian>()?,
-            flags: { assert_eq!(reader.read_u32::<BigEndian>()?, 0x5f0f3cf5, "invalid magic"); reader.read_u16::<BigEndian>()? },
+            flags: {
+                let magic = reader.read_u32::<BigEndian>()?;
+                if magic != 0x5f0f3cf5 { return Err(FontError::BadMagic(magic)); }
+                reader.read_u16::<BigEndian>()?
+            },
             units_per_em: reader.read_u16::<BigEndian>()?,
             created: reader.read_u64::<BigEndian>()?,
             modified: reader.read_u64::<BigEndian>()?,
@@ -337,7 +521,11 @@ impl FontHeader {
             font_direction_hint: reader.read_i16::<BigEndian>()?,
             index_to_locformat: reader.read_i16::<BigEndian>()?,
             glyph_data_format: reader.read_i16::<BigEndian>()?,
-        })
+        };
+        if head.index_to_locformat != 0 && head.index_to_locformat != 1 {
+            return Err(FontError::UnknownFormat(TableTag::FontHeader, head.index_to_locformat as u32));
+        }
+        Ok(head)
     }
 }
 
@@ -369,10 +557,21 @@ impl Table for MaxProfileTable {
 }
 
 impl MaxProfileTable {
-    fn from_binary<R: Read + Seek>(reader: &mut R) -> io::Result<MaxProfileTable> {
+    fn from_binary<R: Read + Seek>(reader: &mut R) -> Result<MaxProfileTable, FontError> {
+        let version = Fixed::from_binary::<R,BigEndian>(reader)?;
+        let num_glyphs = reader.read_u16::<BigEndian>()?;
+        if version.to_bits() == 0x0000_5000 {
+            // version 0.5 is used by fonts without TrueType outlines and only has the glyph count
+            return Ok(MaxProfileTable {
+                version, num_glyphs,
+                num_points: 0, max_contours: 0, max_composite_points: 0, max_composite_contours: 0,
+                max_zones: 0, max_twilight_points: 0, max_storage: 0, max_function_defs: 0,
+                max_instruction_defs: 0, max_stack: 0, max_instruction_size: 0,
+                max_component_elements: 0, max_component_depth: 0
+            });
+        }
         Ok(MaxProfileTable {
-            version: Fixed::from_binary::<R,BigEndian>(reader)?,
-            num_glyphs: reader.read_u16::<BigEndian>()?,
+            version, num_glyphs,
             num_points: reader.read_u16::<BigEndian>()?,
             max_contours: reader.read_u16::<BigEndian>()?,
             max_composite_points: reader.read_u16::<BigEndian>()?,
@@ -390,45 +589,67 @@ impl MaxProfileTable {
     }
 }
 
+/// The 'loca' table, left in the font data
 #[derive(Clone)]
-pub struct LocationTable {
-    offsets: Vec<u32>
+pub struct LocationTable<'a> {
+    data: FontData<'a>,
+    num_glyphs: usize,
+    format: i16
 }
 
-impl Debug for LocationTable {
+impl<'a> Debug for LocationTable<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "LocationTable len={}", self.offsets.len())
+        write!(f, "LocationTable len={}", self.num_glyphs+1)
     }
 }
 
-impl Table for LocationTable {
+impl<'a> Table for LocationTable<'a> {
     fn tag(&self) -> TableTag { TableTag::LocationIndex }
 }
 
-impl LocationTable {
-    fn from_binary<R: Read + Seek>(reader: &mut R, num_glyphs: usize, format: i16) -> io::Result<LocationTable> {
-        Ok(LocationTable {
-            offsets: {
-                let mut v = Vec::new();
-                for _ in 0..(num_glyphs+1) {
-                    v.push(if format == 1 { reader.read_u32::<BigEndian>()? } else { reader.read_u16::<BigEndian>()? as u32 *2 })
-                }
-                v
-            }
+impl<'a> LocationTable<'a> {
+    fn from_bytes(data: FontData<'a>, num_glyphs: usize, format: i16) -> Result<LocationTable<'a>, FontError> {
+        let entry_size = if format == 1 { 4 } else { 2 };
+        if data.len() < (num_glyphs+1)*entry_size {
+            return Err(FontError::Truncated(Some(TableTag::LocationIndex)));
+        }
+        Ok(LocationTable { data, num_glyphs, format })
+    }
+
+    pub fn num_glyphs(&self) -> usize {
+        self.num_glyphs
+    }
+
+    /// Offset of a glyph's data from the start of the 'glyf' table. There is one more offset than
+    /// there are glyphs so that the length of the last glyph is known
+    pub fn offset(&self, index: usize) -> Option<u32> {
+        if index > self.num_glyphs { return None; }
+        Some(if self.format == 1 {
+            BigEndian::read_u32(&self.data[index*4..])
+        } else {
+            BigEndian::read_u16(&self.data[index*2..]) as u32 * 2
         })
     }
+
+    /// The range of bytes in the 'glyf' table that make up a glyph
+    pub fn glyph_range(&self, glyph_index: usize) -> Option<(usize, usize)> {
+        match (self.offset(glyph_index), self.offset(glyph_index+1)) {
+            (Some(start), Some(end)) => Some((start as usize, end as usize)),
+            _ => None
+        }
+    }
 }
 
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct TableDirectoryEntry {
-    tag: TableTag, check_sum: u32, offset: u32, length: u32
+    pub tag: TableTag, pub check_sum: u32, pub offset: u32, pub length: u32
 }
 impl TableDirectoryEntry {
-    fn from_binary<R: Read + Seek>(reader: &mut R) -> io::Result<TableDirectoryEntry> {
+    fn from_binary<R: Read + Seek>(reader: &mut R) -> Result<TableDirectoryEntry, FontError> {
         Ok(TableDirectoryEntry {
-            tag: unsafe { mem::transmute(reader.read_u32::<BigEndian>()?) },
+            tag: TableTag(reader.read_u32::<BigEndian>()?),
             check_sum: reader.read_u32::<BigEndian>()?,
             offset: reader.read_u32::<BigEndian>()?,
             length: reader.read_u32::<BigEndian>()?
@@ -436,47 +657,102 @@ impl TableDirectoryEntry {
     }
 }
 
+/// A sfnt font, which either borrows the data it was loaded from or owns it. Only small fixed size
+/// tables are decoded up front, glyphs, metrics and character maps are decoded as they are requested
 #[repr(C)]
-#[derive(Debug)]
-pub struct SfntFont {
+pub struct SfntFont<'a> {
+    data: FontData<'a>,
     pub sfnt_version: Fixed,
     pub search_range: u16,
     pub entry_selector: u16,
     pub range_shift: u16,
     pub table_directory: Vec<TableDirectoryEntry>,
-    pub cmap_table: Option<CharGlyphMappingTable>,
+    pub cmap_table: Option<CharGlyphMappingTable<'a>>,
     pub cval_table: Option<ControlValueTable>,
     pub fprg_table: Option<FontProgram>,
     pub prep_table: Option<FontProgram>,
     pub gasp_table: Option<GASPTable>,
-    pub glyf_table: Option<GlyphDataTable>,
-    pub loca_table: Option<LocationTable>,
+    pub glyf_table: Option<GlyphDataTable<'a>>,
+    pub loca_table: Option<LocationTable<'a>>,
     pub hdmx_table: Option<HorizDeviceMetricsTable>,
     pub hhea_table: Option<HorizHeaderTable>,
-    pub hmtx_table: Option<HorizMetricsTable>,
+    pub hmtx_table: Option<HorizMetricsTable<'a>>,
     pub head_table: Option<FontHeader>,
-    pub maxp_table: Option<MaxProfileTable>
+    pub maxp_table: Option<MaxProfileTable>,
+    pub name_table: Option<NameTable>,
+    pub os2_table: Option<OS2Table>,
+    pub post_table: Option<PostScriptTable>,
+    pub kern_table: Option<KerningTable>,
+    pub gpos_table: Option<GlyphPositioningTable>,
+    pub gsub_table: Option<GlyphSubstitutionTable>,
+    pub gdef_table: Option<GlyphDefinitionTable>
 }
 
-impl SfntFont {
-    pub fn from_binary<R: Read + Seek>(reader : &mut R) -> io::Result<SfntFont> {
-        let version = Fixed::from_binary::<R,BigEndian>(reader)?;
+impl<'a> Debug for SfntFont<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SfntFont")
+            .field("len", &self.data.len())
+            .field("sfnt_version", &self.sfnt_version)
+            .field("table_directory", &self.table_directory)
+            .field("cmap_table", &self.cmap_table)
+            .field("cval_table", &self.cval_table)
+            .field("fprg_table", &self.fprg_table)
+            .field("prep_table", &self.prep_table)
+            .field("gasp_table", &self.gasp_table)
+            .field("glyf_table", &self.glyf_table)
+            .field("loca_table", &self.loca_table)
+            .field("hdmx_table", &self.hdmx_table)
+            .field("hhea_table", &self.hhea_table)
+            .field("hmtx_table", &self.hmtx_table)
+            .field("head_table", &self.head_table)
+            .field("maxp_table", &self.maxp_table)
+            .field("name_table", &self.name_table)
+            .field("os2_table", &self.os2_table)
+            .field("post_table", &self.post_table)
+            .field("kern_table", &self.kern_table)
+            .field("gpos_table", &self.gpos_table)
+            .field("gsub_table", &self.gsub_table)
+            .field("gdef_table", &self.gdef_table)
+            .finish()
+    }
+}
+
+impl SfntFont<'static> {
+    /// Reads an entire font and loads it, keeping the data in the font
+    pub fn from_binary<R: Read>(reader: &mut R) -> Result<SfntFont<'static>, FontError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        SfntFont::from_font_data(FontData::from(data))
+    }
+}
+
+impl<'a> SfntFont<'a> {
+    /// Loads a font from anything that can be viewed as bytes without copying it, for instance a
+    /// memory mapped file
+    pub fn from_data<D: AsRef<[u8]> + ?Sized>(data: &'a D) -> Result<SfntFont<'a>, FontError> {
+        SfntFont::from_bytes(data.as_ref())
+    }
+
+    /// Loads a font from a byte slice. The table data is not copied, so the font is only valid as
+    /// long as the slice is
+    pub fn from_bytes(data: &'a [u8]) -> Result<SfntFont<'a>, FontError> {
+        SfntFont::from_font_data(FontData::from(data))
+    }
+
+    fn from_font_data(data: FontData<'a>) -> Result<SfntFont<'a>, FontError> {
+        let mut reader = io::Cursor::new(&data[..]);
+        let version = Fixed::from_binary::<_,BigEndian>(&mut reader)?;
         let num_tables = reader.read_u16::<BigEndian>()?;
         let search_range = reader.read_u16::<BigEndian>()?;
         let entry_sel = reader.read_u16::<BigEndian>()?;
         let range_shift = reader.read_u16::<BigEndian>()?;
-        let mut table_directory = Vec::new();
+        let mut table_directory = Vec::with_capacity(num_tables as usize);
         for _ in 0..num_tables {
-            let tbe = TableDirectoryEntry::from_binary(reader)?;
-            match tbe.tag {
-                TableTag::MaxProfile => table_directory.insert(0, tbe),
-                TableTag::FontHeader => table_directory.insert(1, tbe),
-                TableTag::LocationIndex => table_directory.insert(2, tbe),
-                _ => table_directory.push(tbe)
-            }
+            table_directory.push(TableDirectoryEntry::from_binary(&mut reader)?);
         }
         //println!("table directory: {:?}", table_directory);
         let mut fnt = SfntFont {
+            data: data.clone(),
             sfnt_version: version,
             search_range: search_range,
             entry_selector: entry_sel,
@@ -494,64 +770,267 @@ impl SfntFont {
             hmtx_table: None,
             head_table: None,
             maxp_table: None,
+            name_table: None,
+            os2_table: None,
+            post_table: None,
+            kern_table: None,
+            gpos_table: None,
+            gsub_table: None,
+            gdef_table: None,
         };
-        for tde in &fnt.table_directory {
-            reader.seek(io::SeekFrom::Start(tde.offset as u64))?;
-            match tde.tag {
-                TableTag::CharGlyphMapping =>
-                    fnt.cmap_table = Some(char_glyph_mapping_table::CharGlyphMappingTable::from_binary(reader, tde.offset as u64)?),
-                TableTag::ControlValue => {
-                    let mut tbl = Vec::with_capacity((tde.length/2) as usize);
-                    for _ in 0..tde.length {
-                        tbl.push(reader.read_i16::<BigEndian>()?);
-                    }
-                    fnt.cval_table = Some(ControlValueTable(tbl))
-                },
-                TableTag::FontProgram => {
-                    let mut tbl = vec![0u8; tde.length as usize];
-                    reader.read_exact(tbl.as_mut_slice())?;
-                    fnt.fprg_table = Some(FontProgram(tbl))
-                },
-                TableTag::PreProgram => {
-                    let mut tbl = vec![0u8; tde.length as usize];
-                    reader.read_exact(tbl.as_mut_slice())?;
-                    fnt.prep_table = Some(FontProgram(tbl))
-                },
-                TableTag::GridFitAndScanConvertProc =>
-                    fnt.gasp_table = Some(GASPTable::from_binary(reader)?),
-                TableTag::GlyphData =>
-                    fnt.glyf_table = Some(GlyphDataTable::from_binary(reader, tde.offset as u64,
-                                                                      fnt.maxp_table.ok_or(io::Error::new(io::ErrorKind::Other, "Must load maxp table before glyf table!"))?,
-                                                                      fnt.loca_table.as_ref().ok_or(io::Error::new(io::ErrorKind::Other, "Must load loca table before glyf table!"))? )?),
-                TableTag::LocationIndex => {
-                    fnt.loca_table = Some(LocationTable::from_binary(reader,
-                                                                     fnt.maxp_table.ok_or(io::Error::new(io::ErrorKind::Other, "Must load maxp table before loca table!"))?.num_glyphs as usize,
-                                                                     fnt.head_table.ok_or(io::Error::new(io::ErrorKind::Other, "Must load head table before loca table!"))?.index_to_locformat)?);
-                },
-                TableTag::HorizDevMetric =>
-                    fnt.hdmx_table = Some(HorizDeviceMetricsTable::from_binary(reader,
-                                                                               fnt.maxp_table.ok_or(io::Error::new(io::ErrorKind::Other, "Must load maxp table before hdmx table!"))?.num_glyphs as usize)?),
-                TableTag::FontHeader =>
-                    fnt.head_table = Some({ let v = FontHeader::from_binary(reader)?; /*println!("got head table = {:?}", v);*/ v } ),
-                TableTag::MaxProfile => {
-                    fnt.maxp_table = Some(MaxProfileTable::from_binary(reader)?);
-
-                    //println!("got maxp table = {:?}", fnt.maxp_table);
-                }
-                TableTag::HorizHeader => {
-                    fnt.hhea_table = Some(HorizHeaderTable::from_binary(reader)?);
-                }
-                TableTag::HorizMetrics => {
-                    fnt.hmtx_table = Some(HorizMetricsTable::from_binary(reader,
-                                             fnt.maxp_table.as_ref().ok_or(io::Error::new(io::ErrorKind::Other, "Must load maxp table before loca table!"))?.num_glyphs as usize,
-                                             fnt.hhea_table.as_ref().ok_or(io::Error::new(io::ErrorKind::Other, "Must load hhea table before loca table!"))?.num_long_horz_metric as usize)?);
-                }
-                
-                _ =>  { /*println!("Unknown table tag: {:?}!", tde.tag);*/ continue; }
+
+        // tables are loaded in dependency order rather than directory order, since some tables
+        // need values from others to be read
+        if let Some(tbl) = fnt.table_data(TableTag::MaxProfile)? {
+            fnt.maxp_table = Some(MaxProfileTable::from_binary(&mut io::Cursor::new(tbl)).map_err(|e| e.in_table(TableTag::MaxProfile))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::FontHeader)? {
+            fnt.head_table = Some(FontHeader::from_binary(&mut io::Cursor::new(tbl)).map_err(|e| e.in_table(TableTag::FontHeader))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::HorizHeader)? {
+            fnt.hhea_table = Some(HorizHeaderTable::from_binary(&mut io::Cursor::new(tbl)).map_err(|e| e.in_table(TableTag::HorizHeader))?);
+        }
+        if let Some(tbl) = fnt.table_font_data(TableTag::LocationIndex)? {
+            fnt.loca_table = Some(LocationTable::from_bytes(tbl,
+                                                            fnt.maxp_table.ok_or(FontError::MissingTable(TableTag::MaxProfile))?.num_glyphs as usize,
+                                                            fnt.head_table.ok_or(FontError::MissingTable(TableTag::FontHeader))?.index_to_locformat)?);
+        }
+        if let Some(tbl) = fnt.table_font_data(TableTag::GlyphData)? {
+            fnt.glyf_table = Some(GlyphDataTable::from_bytes(tbl,
+                                                             fnt.loca_table.clone().ok_or(FontError::MissingTable(TableTag::LocationIndex))?));
+        }
+        if let Some(tbl) = fnt.table_font_data(TableTag::HorizMetrics)? {
+            fnt.hmtx_table = Some(HorizMetricsTable::from_bytes(tbl,
+                                     fnt.maxp_table.as_ref().ok_or(FontError::MissingTable(TableTag::MaxProfile))?.num_glyphs as usize,
+                                     fnt.hhea_table.as_ref().ok_or(FontError::MissingTable(TableTag::HorizHeader))?.num_long_horz_metric as usize)?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::HorizDevMetric)? {
+            fnt.hdmx_table = Some(HorizDeviceMetricsTable::from_binary(&mut io::Cursor::new(tbl),
+                                                                       fnt.maxp_table.ok_or(FontError::MissingTable(TableTag::MaxProfile))?.num_glyphs as usize)
+                                   .map_err(|e| e.in_table(TableTag::HorizDevMetric))?);
+        }
+        if let Some(tbl) = fnt.table_font_data(TableTag::CharGlyphMapping)? {
+            fnt.cmap_table = Some(CharGlyphMappingTable::from_bytes(tbl).map_err(|e| e.in_table(TableTag::CharGlyphMapping))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::ControlValue)? {
+            let mut reader = io::Cursor::new(tbl);
+            let mut cvt = Vec::with_capacity(tbl.len()/2);
+            for _ in 0..tbl.len()/2 {
+                cvt.push(reader.read_i16::<BigEndian>()?);
             }
+            fnt.cval_table = Some(ControlValueTable(cvt));
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::FontProgram)? {
+            fnt.fprg_table = Some(FontProgram(tbl.to_vec()));
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::PreProgram)? {
+            fnt.prep_table = Some(FontProgram(tbl.to_vec()));
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::GridFitAndScanConvertProc)? {
+            fnt.gasp_table = Some(GASPTable::from_binary(&mut io::Cursor::new(tbl)).map_err(|e| e.in_table(TableTag::GridFitAndScanConvertProc))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::Nameing)? {
+            fnt.name_table = Some(NameTable::from_bytes(tbl).map_err(|e| e.in_table(TableTag::Nameing))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::WindowsOS2SpecificMetric)? {
+            fnt.os2_table = Some(OS2Table::from_binary(&mut io::Cursor::new(tbl), tbl.len()).map_err(|e| e.in_table(TableTag::WindowsOS2SpecificMetric))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::PostScriptInfo)? {
+            fnt.post_table = Some(PostScriptTable::from_binary(&mut io::Cursor::new(tbl), tbl.len()).map_err(|e| e.in_table(TableTag::PostScriptInfo))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::Kerning)? {
+            fnt.kern_table = Some(KerningTable::from_bytes(tbl).map_err(|e| e.in_table(TableTag::Kerning))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::GlyphPositioning)? {
+            fnt.gpos_table = Some(GlyphPositioningTable::from_bytes(tbl).map_err(|e| e.in_table(TableTag::GlyphPositioning))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::GlyphSubstitution)? {
+            fnt.gsub_table = Some(GlyphSubstitutionTable::from_bytes(tbl).map_err(|e| e.in_table(TableTag::GlyphSubstitution))?);
+        }
+        if let Some(tbl) = fnt.table_data(TableTag::GlyphDefinition)? {
+            fnt.gdef_table = Some(GlyphDefinitionTable::from_bytes(tbl).map_err(|e| e.in_table(TableTag::GlyphDefinition))?);
         }
         Ok(fnt)
     }
+
+    /// The entries in the table directory, including tables this crate doesn't parse
+    pub fn tables(&self) -> &[TableDirectoryEntry] {
+        &self.table_directory
+    }
+
+    pub fn has_table(&self, tag: TableTag) -> bool {
+        self.table_directory.iter().any(|tde| tde.tag == tag)
+    }
+
+    /// The raw bytes of any table in the font, or `None` if the font doesn't have it
+    pub fn table_data(&self, tag: TableTag) -> Result<Option<&[u8]>, FontError> {
+        Ok(self.table_range(tag)?.map(|(start, end)| &self.data[start..end]))
+    }
+
+    /// A table's bytes that can be kept by a table that is left in the font data
+    fn table_font_data(&self, tag: TableTag) -> Result<Option<FontData<'a>>, FontError> {
+        Ok(self.table_range(tag)?.map(|(start, end)| self.data.slice(start, end)))
+    }
+
+    fn table_range(&self, tag: TableTag) -> Result<Option<(usize, usize)>, FontError> {
+        match self.table_directory.iter().find(|tde| tde.tag == tag) {
+            Some(tde) => {
+                let start = tde.offset as usize;
+                let end = start + tde.length as usize;
+                if end > self.data.len() {
+                    return Err(FontError::OffsetOutOfBounds(tag));
+                }
+                Ok(Some((start, end)))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Decodes a single glyph from the 'glyf' table
+    pub fn glyph(&self, glyph_index: usize) -> Result<GlyphDescription, FontError> {
+        self.glyf_table.as_ref().ok_or(FontError::MissingTable(TableTag::GlyphData))?.glyph(glyph_index)
+    }
+
+    /// Decodes a glyph with its components resolved, see `GlyphDataTable::resolved_glyph`
+    pub fn resolved_glyph(&self, glyph_index: usize, grid_scale: Option<f32>) -> Result<GlyphDescription, FontError> {
+        self.glyf_table.as_ref().ok_or(FontError::MissingTable(TableTag::GlyphData))?
+            .resolved_glyph(glyph_index, self.max_component_depth(), grid_scale)
+    }
+
+    /// Find a name in the 'name' table, see `NameTable::name`
+    pub fn name(&self, name_id: NameId) -> Option<&str> {
+        self.name_table.as_ref().and_then(|t| t.name(name_id))
+    }
+
+    pub fn family_name(&self) -> Option<&str> {
+        self.name(NameId::FontFamily)
+    }
+
+    pub fn subfamily_name(&self) -> Option<&str> {
+        self.name(NameId::FontSubfamily)
+    }
+
+    pub fn full_name(&self) -> Option<&str> {
+        self.name(NameId::FullName)
+    }
+
+    pub fn postscript_name(&self) -> Option<&str> {
+        self.name(NameId::PostScriptName)
+    }
+
+    pub fn version_string(&self) -> Option<&str> {
+        self.name(NameId::Version)
+    }
+
+    /// The family name without style words that fonts with more than four styles leave in the
+    /// legacy family name. Fonts that don't have one give the legacy family name instead
+    pub fn typographic_family_name(&self) -> Option<&str> {
+        self.name(NameId::TypographicFamily).or_else(|| self.family_name())
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.name(NameId::License)
+    }
+
+    /// The PostScript name of a glyph from the 'post' table
+    pub fn glyph_name(&self, glyph_index: usize) -> Option<&str> {
+        self.post_table.as_ref().and_then(|t| t.glyph_name(glyph_index))
+    }
+
+    /// Find a glyph by its PostScript name, see `PostScriptTable::glyph_by_name`
+    pub fn glyph_by_name(&self, name: &str) -> Option<usize> {
+        self.post_table.as_ref().and_then(|t| t.glyph_by_name(name))
+    }
+
+    /// The adjustment to the advance between two glyphs from the 'kern' table, in font units. Fonts
+    /// without one have no kerning
+    pub fn kerning(&self, left: usize, right: usize) -> i16 {
+        match self.kern_table {
+            Some(ref kern) if left <= 0xffff && right <= 0xffff => kern.kerning(left as u16, right as u16),
+            _ => 0
+        }
+    }
+
+    /// The ascender, descender and line gap that set the height of a line of text. These come
+    /// from the OS/2 typographic metrics when the font asks for them to be used, and from 'hhea'
+    /// otherwise
+    pub fn line_metrics(&self) -> Option<LineMetrics> {
+        // fonts without a metric leave it zero
+        let typo = match self.os2_table {
+            Some(ref os2) if os2.typo_ascender != 0 || os2.typo_descender != 0 =>
+                Some(LineMetrics { ascender: os2.typo_ascender, descender: os2.typo_descender, line_gap: os2.typo_line_gap }),
+            _ => None
+        };
+        let hhea = match self.hhea_table {
+            Some(ref hhea) if hhea.ascent != 0 || hhea.descent != 0 =>
+                Some(LineMetrics { ascender: hhea.ascent, descender: hhea.descent, line_gap: hhea.line_gap }),
+            _ => None
+        };
+        let use_typo = self.os2_table.as_ref().map(|os2| os2.fs_selection.contains(FS_USE_TYPO_METRICS)).unwrap_or(false);
+        if use_typo && typo.is_some() { typo } else { hhea.or(typo) }
+    }
+
+    /// The contour points on a glyph that other glyphs may be attached to, from 'GDEF'
+    pub fn attach_points(&self, glyph_index: usize) -> &[u16] {
+        match self.gdef_table {
+            Some(ref gdef) if glyph_index <= 0xffff => gdef.attach_points(glyph_index as u16),
+            _ => &[]
+        }
+    }
+
+    /// Where the caret goes between the components of a ligature glyph, in font units along the
+    /// line. Carets on contour points use the point's position in the unhinted outline
+    pub fn ligature_carets(&self, glyph_index: usize, ppem: u16) -> Result<Vec<i32>, FontError> {
+        let carets = match self.gdef_table {
+            Some(ref gdef) if glyph_index <= 0xffff => gdef.lig_carets(glyph_index as u16),
+            _ => return Ok(Vec::new())
+        };
+        let units_per_em = self.head_table.map(|h| h.units_per_em).unwrap_or(0);
+        let mut positions = Vec::with_capacity(carets.len());
+        for caret in carets {
+            positions.push(match *caret {
+                CaretValue::Coordinate { coordinate, ref device } =>
+                    coordinate as i32 + device.as_ref().map(|d| d.adjustment(ppem, units_per_em)).unwrap_or(0),
+                CaretValue::ContourPoint(point) => match self.resolved_glyph(glyph_index, None)? {
+                    GlyphDescription::Simple { ref points, .. } =>
+                        points.get(point as usize).ok_or(FontError::InconsistentCounts(TableTag::GlyphDefinition, "caret on a point the glyph doesn't have"))?.x,
+                    _ => 0
+                }
+            });
+        }
+        Ok(positions)
+    }
+
+    /// How deeply composite glyphs may nest, according to 'maxp'
+    pub fn max_component_depth(&self) -> u16 {
+        // simple components are one level deep, so never allow less than that
+        self.maxp_table.map(|m| m.max_component_depth).unwrap_or(1).max(1)
+    }
+
+    /// How many points the instructions may use in the twilight zone, according to 'maxp'
+    pub fn max_twilight_points(&self) -> u16 {
+        self.maxp_table.map(|m| m.max_twilight_points).unwrap_or(0)
+    }
+
+    /// How many values the instructions may keep in the storage area, according to 'maxp'
+    pub fn max_storage(&self) -> u16 {
+        self.maxp_table.map(|m| m.max_storage).unwrap_or(0)
+    }
+}
+
+/// Glyph classes come from 'GDEF'. Fonts without one leave every glyph unclassified
+impl<'a> GlyphClassifier for SfntFont<'a> {
+    fn glyph_class(&self, glyph: u16) -> u16 {
+        self.gdef_table.as_ref().map(|t| t.glyph_class(glyph)).unwrap_or(0)
+    }
+
+    fn mark_attachment_class(&self, glyph: u16) -> u16 {
+        self.gdef_table.as_ref().map(|t| t.mark_attachment_class(glyph)).unwrap_or(0)
+    }
+
+    fn is_in_mark_glyph_set(&self, set: u16, glyph: u16) -> bool {
+        self.gdef_table.as_ref().map(|t| t.is_in_mark_glyph_set(set, glyph)).unwrap_or(false)
+    }
 }
 
 #[cfg(test)]
@@ -564,17 +1043,42 @@ mod tests {
     use std::fs::File;
 
     #[cfg(target_os="windows")]
-    const FONT_PATH: &'static str = 
+    const FONT_PATH: &str = 
         "C:\\Windows\\Fonts\\arial.ttf";
     #[cfg(target_os="macos")]
-    const FONT_PATH: &'static str = 
+    const FONT_PATH: &str = 
         "/Library/Fonts/Arial.ttf";
+    #[cfg(target_os="linux")]
+    const FONT_PATH: &str = 
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
 
+    /// Loads the font at `FONT_PATH`. The tests that check parsed values expect DejaVu Sans
+    fn load_font() -> SfntFont<'static> {
+        SfntFont::from_binary(&mut File::open(FONT_PATH).expect("font file")).expect("load font")
+    }
 
     #[test]
     fn test_tabletag() {
-        println!("{:?} = {:X} = {:X}", TableTag::CharGlyphMapping, TableTag::CharGlyphMapping as u32, 0x636D6170);
-        assert_eq!(TableTag::CharGlyphMapping as u32, 0x636D6170);
+        println!("{:?} = {:X} = {:X}", TableTag::CharGlyphMapping, TableTag::CharGlyphMapping.0, 0x636D6170);
+        assert_eq!(TableTag::CharGlyphMapping.0, 0x636D6170);
+        assert_eq!(TableTag::new(b"cmap"), TableTag::CharGlyphMapping);
+        assert_eq!(&TableTag::new(b"GPOS").to_bytes(), b"GPOS");
+        assert_eq!(format!("{:?}", TableTag::new(b"CFF ")), "Table:CFF ");
+    }
+
+    #[test]
+    fn test_raw_tables() {
+        let mut font_data = Vec::new();
+        File::open(FONT_PATH).expect("font file").read_to_end(&mut font_data).unwrap();
+        let f = SfntFont::from_bytes(&font_data).unwrap();
+
+        for tde in f.tables() {
+            let data = f.table_data(tde.tag).unwrap().expect("table in directory");
+            assert_eq!(data.len(), tde.length as usize);
+        }
+        assert!(f.has_table(TableTag::Nameing));
+        assert_eq!(&f.table_data(TableTag::FontHeader).unwrap().unwrap()[12..16], &[0x5f, 0x0f, 0x3c, 0xf5]);
+        assert!(f.table_data(TableTag::new(b"zzzz")).unwrap().is_none());
     }
 
     #[test]
@@ -587,6 +1091,235 @@ mod tests {
         println!("SfntFont = {:?}", f);
     }
 
+    #[test]
+    fn test_names() {
+        let f = load_font();
+        assert_eq!(f.name_table.as_ref().expect("name table").format, 0);
+        assert_eq!(f.family_name(), Some("DejaVu Sans"));
+        assert_eq!(f.subfamily_name(), Some("Book"));
+        assert_eq!(f.full_name(), Some("DejaVu Sans"));
+        assert_eq!(f.postscript_name(), Some("DejaVuSans"));
+        assert_eq!(f.version_string(), Some("Version 2.37"));
+        assert_eq!(f.typographic_family_name(), Some("DejaVu Sans"));
+    }
+
+    #[test]
+    fn test_os2() {
+        let f = load_font();
+        let os2 = f.os2_table.expect("OS/2 table");
+        assert_eq!(os2.version, 1);
+        assert_eq!(os2.weight_class, 400);
+        assert_eq!(os2.width_class, 5);
+        assert_eq!(os2.fs_selection, FS_REGULAR);
+        assert_eq!(&os2.vendor_id, b"PfEd");
+        assert_eq!((os2.typo_ascender, os2.typo_descender, os2.typo_line_gap), (1556, -492, 410));
+        assert_eq!((os2.win_ascent, os2.win_descent), (1901, 483));
+        // only version 2 and later have these
+        assert_eq!(os2.x_height, None);
+        // Basic Latin
+        assert!(os2.supports_unicode_range(0));
+    }
+
+    #[test]
+    fn test_line_metrics() {
+        let f = load_font();
+        // the font doesn't ask for the typographic metrics, so they come from 'hhea'
+        let metrics = f.line_metrics().expect("line metrics");
+        assert_eq!((metrics.ascender, metrics.descender, metrics.line_gap), (1901, -483, 0));
+    }
+
+    #[test]
+    fn test_kerning() {
+        let f = load_font();
+        assert_eq!(f.kern_table.as_ref().expect("kern table").subtables.len(), 1);
+        let (a, v) = (f.glyph_by_name("A").unwrap(), f.glyph_by_name("V").unwrap());
+        assert_eq!(f.kerning(a, v), -131);
+        assert_eq!(f.kerning(v, a), -131);
+        assert_eq!(f.kerning(0, 0), 0);
+    }
+
+    #[test]
+    fn test_gpos() {
+        let f = load_font();
+        let gpos = f.gpos_table.as_ref().expect("GPOS table");
+        assert_eq!(gpos.0.lookups.len(), 16);
+        let lookups = gpos.lookups_for_features(TableTag::new(b"latn"), None, &[TableTag::new(b"kern")]);
+        assert_eq!(lookups, vec![14, 15]);
+
+        let (a, v) = (f.glyph_by_name("A").unwrap(), f.glyph_by_name("V").unwrap());
+        let glyphs = [LayoutGlyph::new(a as u16, 0), LayoutGlyph::new(v as u16, 1)];
+        let hmtx = f.hmtx_table.unwrap();
+        let mut positions: Vec<_> = [a, v].iter()
+            .map(|&g| GlyphPosition::new(hmtx.metric(g).unwrap().advance_width as i32, 0)).collect();
+        let options = PositioningOptions { ppem: 0, units_per_em: f.head_table.unwrap().units_per_em, right_to_left: false };
+        gpos.apply(&lookups, &glyphs, &mut positions, &NoGlyphClasses, &options);
+        // the same 131 units closer as in 'kern'
+        assert_eq!(hmtx.metric(a).unwrap().advance_width, 1401);
+        assert_eq!(positions[0].x_advance, 1270);
+    }
+
+    #[test]
+    fn test_gsub() {
+        let f = load_font();
+        let gsub = f.gsub_table.as_ref().expect("GSUB table");
+        assert_eq!(gsub.0.lookups.len(), 40);
+        let lookups = gsub.lookups_for_features(TableTag::new(b"latn"), None, &[TableTag::new(b"liga")]);
+        assert_eq!(lookups, vec![18]);
+        let fi = f.glyph_by_name("fi").expect("fi ligature glyph");
+        assert_eq!(fi, 5042);
+
+        let mut glyphs = vec![LayoutGlyph::new(f.glyph_by_name("f").unwrap() as u16, 0),
+                              LayoutGlyph::new(f.glyph_by_name("i").unwrap() as u16, 1)];
+        gsub.apply(&lookups, &mut glyphs, &NoGlyphClasses);
+        assert_eq!(glyphs, vec![LayoutGlyph { glyph: fi as u16, cluster: 0, ligature_id: 1, ligature_component: 0, mask: !0 }]);
+    }
+
+    #[test]
+    fn test_gdef() {
+        let f = load_font();
+        let gdef = f.gdef_table.as_ref().expect("GDEF table");
+        assert_eq!((gdef.major_version, gdef.minor_version), (1, 0));
+        assert!(gdef.lig_carets.is_empty() && gdef.attach_points.is_empty() && gdef.mark_glyph_sets.is_empty());
+        // A, and the combining acute
+        assert_eq!(f.glyph_class(36), GLYPH_CLASS_BASE);
+        assert_eq!(f.glyph_class(690), GLYPH_CLASS_MARK);
+        assert_eq!(f.mark_attachment_class(690), 1);
+        assert!(f.ligature_carets(0, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_glyph_names() {
+        let f = load_font();
+        let post = f.post_table.as_ref().expect("post table");
+        assert_eq!(post.version, Fixed::from(2.0));
+        assert!(!post.is_fixed_pitch);
+        assert_eq!(post.italic_angle, 0.0);
+        assert_eq!((post.underline_position, post.underline_thickness), (-40, 90));
+        // a standard Macintosh name and one from the font's own list
+        assert_eq!(f.glyph_name(0), Some(".notdef"));
+        assert_eq!(f.glyph_by_name("A"), Some(36));
+        assert_eq!(f.glyph_name(36), Some("A"));
+        assert_eq!(f.glyph_by_name("acutecomb"), Some(690));
+        assert_eq!(f.glyph_name(690), Some("acutecomb"));
+        assert_eq!(f.glyph_by_name("no such glyph"), None);
+    }
+
+    #[test]
+    fn test_loader_from_bytes() {
+        let mut font_data = Vec::new();
+        File::open(FONT_PATH).expect("font file").read_to_end(&mut font_data).unwrap();
+
+        let f = SfntFont::from_bytes(&font_data).unwrap();
+        let num_glyphs = f.maxp_table.unwrap().num_glyphs as usize;
+        let glyf = f.glyf_table.expect("glyf table");
+        assert_eq!(glyf.len(), num_glyphs);
+        assert!(glyf.glyph(num_glyphs).is_err());
+        let hmtx = f.hmtx_table.expect("hmtx table");
+        for i in 0..num_glyphs {
+            glyf.glyph(i).unwrap();
+            hmtx.metric(i).unwrap();
+        }
+        assert!(hmtx.metric(num_glyphs).is_err());
+    }
+
+    #[test]
+    fn test_resolve_composites() {
+        let mut font_data = Vec::new();
+        File::open(FONT_PATH).expect("font file").read_to_end(&mut font_data).unwrap();
+        let f = SfntFont::from_bytes(&font_data).unwrap();
+        let glyf = f.glyf_table.as_ref().unwrap();
+
+        fn num_points(g: &GlyphDescription) -> usize {
+            match *g {
+                GlyphDescription::Simple { ref points, .. } => points.len(),
+                _ => 0
+            }
+        }
+
+        let mut num_composites = 0;
+        for i in 0..glyf.len() {
+            let resolved = f.resolved_glyph(i, None).unwrap();
+            if let GlyphDescription::Composite { components, .. } = glyf.glyph(i).unwrap() {
+                num_composites += 1;
+                let expected: usize = components.iter()
+                    .map(|c| num_points(&f.resolved_glyph(c.glyph_index as usize, None).unwrap())).sum();
+                assert_eq!(num_points(&resolved), expected);
+            } else {
+                assert_eq!(num_points(&resolved), num_points(&glyf.glyph(i).unwrap()));
+            }
+        }
+        assert!(num_composites > 0);
+        // a composite glyph can't be resolved if no nesting is allowed
+        let composite = (0..glyf.len()).find(|&i| matches!(glyf.glyph(i).unwrap(), GlyphDescription::Composite { .. })).unwrap();
+        assert!(glyf.resolved_glyph(composite, 0, None).is_err());
+    }
+
+    #[test]
+    fn test_recursive_composites() {
+        use byteorder::WriteBytesExt;
+        // glyph 0 is made of itself, and glyphs 1 and 2 are made of each other
+        let mut glyf = Vec::new();
+        for &component in &[0u16, 2, 1] {
+            glyf.write_i16::<BigEndian>(-1).unwrap();
+            glyf.extend_from_slice(&[0; 8]);
+            glyf.write_u16::<BigEndian>(0x0003).unwrap(); // word sized x and y offsets
+            glyf.write_u16::<BigEndian>(component).unwrap();
+            glyf.extend_from_slice(&[0; 4]);
+        }
+        let loca = [0u8, 0, 0, 9, 0, 18, 0, 27];
+        let loca = LocationTable::from_bytes(FontData::from(&loca[..]), 3, 0).unwrap();
+        let glyf = GlyphDataTable::from_bytes(FontData::from(&glyf[..]), loca);
+        for i in 0..3 {
+            match glyf.resolved_glyph(i, u16::MAX, None) {
+                Err(FontError::InconsistentCounts(tag, _)) if tag == TableTag::GlyphData => {},
+                r => panic!("expected glyph {} to be rejected, got {:?}", i, r)
+            }
+        }
+    }
+
+    #[test]
+    fn test_loader_malformed() {
+        let mut font_data = Vec::new();
+        File::open(FONT_PATH).expect("font file").read_to_end(&mut font_data).unwrap();
+
+        for &len in &[0, 11, 100] {
+            assert!(SfntFont::from_bytes(&font_data[..len]).is_err());
+        }
+
+        let head_offset = SfntFont::from_bytes(&font_data).unwrap()
+            .table_directory.iter().find(|tde| tde.tag == TableTag::FontHeader).unwrap().offset as usize;
+        let mut bad_magic = font_data.clone();
+        bad_magic[head_offset+12] = 0;
+        match SfntFont::from_bytes(&bad_magic) {
+            Err(FontError::BadMagic(_)) => {},
+            r => panic!("expected bad magic, got {:?}", r)
+        }
+
+        // an optional table that is there but can't be read fails the load rather than going missing
+        let gpos_offset = SfntFont::from_bytes(&font_data).unwrap()
+            .table_directory.iter().find(|tde| tde.tag == TableTag::GlyphPositioning).unwrap().offset as usize;
+        let mut bad_gpos = font_data.clone();
+        bad_gpos[gpos_offset+1] = 9;
+        match SfntFont::from_bytes(&bad_gpos) {
+            Err(FontError::UnknownFormat(tag, 9)) if tag == TableTag::GlyphPositioning => {},
+            r => panic!("expected an unknown GPOS version, got {:?}", r)
+        }
+
+        // corrupting bytes anywhere in the font should result in errors, never panics
+        for i in (0..font_data.len()).filter(|i| i % 8191 == 0) {
+            let mut corrupt = font_data.clone();
+            corrupt[i] = 0xff;
+            if let Ok(f) = SfntFont::from_bytes(&corrupt) {
+                if let Some(glyf) = f.glyf_table {
+                    for g in 0..glyf.len() { let _ = glyf.glyph(g); }
+                }
+                if let Some(cmap) = f.cmap_table {
+                    for enc in &cmap.encoding_tables { let _ = enc.subtable(); }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_glyph_load_exp_svg() {
         use self::svg::Document;
@@ -648,7 +1381,7 @@ mod tests {
         
         let mut doc = Document::new();
         let mut gx : u64 = 0; let mut gy : u64 = 0; let mut limit = 0;
-        for (index, g) in font.glyf_table.unwrap().glyphs.iter().take(128).enumerate() {
+        for (index, g) in font.glyf_table.unwrap().glyphs().unwrap().iter().take(128).enumerate() {
             match g {
                 &GlyphDescription::Simple { 
                     num_contours: _, x_max, x_min, y_max, y_min,
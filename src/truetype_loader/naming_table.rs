@@ -0,0 +1,205 @@
+use std::io;
+use std::io::prelude::*;
+use std::fmt;
+use std::fmt::{Debug};
+use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
+
+use super::*;
+
+/// Identifies what a name in the 'name' table is for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NameId(pub u16);
+
+impl NameId {
+    pub const Copyright: NameId = NameId(0);
+    pub const FontFamily: NameId = NameId(1);
+    pub const FontSubfamily: NameId = NameId(2);
+    pub const UniqueId: NameId = NameId(3);
+    pub const FullName: NameId = NameId(4);
+    pub const Version: NameId = NameId(5);
+    pub const PostScriptName: NameId = NameId(6);
+    pub const Trademark: NameId = NameId(7);
+    pub const Manufacturer: NameId = NameId(8);
+    pub const Designer: NameId = NameId(9);
+    pub const Description: NameId = NameId(10);
+    pub const VendorUrl: NameId = NameId(11);
+    pub const DesignerUrl: NameId = NameId(12);
+    pub const License: NameId = NameId(13);
+    pub const LicenseUrl: NameId = NameId(14);
+    pub const TypographicFamily: NameId = NameId(16);
+    pub const TypographicSubfamily: NameId = NameId(17);
+    pub const CompatibleFullName: NameId = NameId(18);
+    pub const SampleText: NameId = NameId(19);
+    pub const PostScriptCidName: NameId = NameId(20);
+    pub const WwsFamily: NameId = NameId(21);
+    pub const WwsSubfamily: NameId = NameId(22);
+}
+
+/// A name from the 'name' table. `value` is `None` for names in encodings that can't be decoded,
+/// which are anything other than Unicode or Mac Roman
+#[derive(Clone, Debug)]
+pub struct NameRecord {
+    pub platform_id: u16,
+    pub platform_encoding_id: u16,
+    pub language_id: u16,
+    pub name_id: NameId,
+    pub value: Option<String>
+}
+
+impl NameRecord {
+    /// How good a fit this record is for showing to an English speaking user, lower is better
+    fn rank(&self) -> u8 {
+        match (self.platform_id, self.platform_encoding_id, self.language_id) {
+            (3, 1, 0x0409) | (3, 10, 0x0409) => 0,
+            (3, 1, _) | (3, 10, _) => 2,
+            (0, _, _) => 1,
+            (1, 0, 0) => 3,
+            _ => 4
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NameTable {
+    /// 0, or 1 for tables that also have language tags
+    pub format: u16,
+    pub records: Vec<NameRecord>,
+    /// BCP 47 language tags, which format 1 tables refer to with language IDs from 0x8000 up
+    pub language_tags: Vec<String>
+}
+
+impl Table for NameTable {
+    fn tag(&self) -> TableTag { TableTag::Nameing }
+}
+
+impl NameTable {
+    pub fn from_bytes(data: &[u8]) -> Result<NameTable, FontError> {
+        let mut reader = io::Cursor::new(data);
+        let format = reader.read_u16::<BigEndian>()?;
+        if format > 1 {
+            return Err(FontError::UnknownFormat(TableTag::Nameing, format as u32));
+        }
+        let count = reader.read_u16::<BigEndian>()?;
+        let string_offset = reader.read_u16::<BigEndian>()? as usize;
+        let string_data = |length: u16, offset: u16| {
+            let start = string_offset + offset as usize;
+            data.get(start..start + length as usize).ok_or(FontError::OffsetOutOfBounds(TableTag::Nameing))
+        };
+        let mut records = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let platform_id = reader.read_u16::<BigEndian>()?;
+            let platform_encoding_id = reader.read_u16::<BigEndian>()?;
+            let language_id = reader.read_u16::<BigEndian>()?;
+            let name_id = NameId(reader.read_u16::<BigEndian>()?);
+            let length = reader.read_u16::<BigEndian>()?;
+            let offset = reader.read_u16::<BigEndian>()?;
+            let bytes = string_data(length, offset)?;
+            let value = match (platform_id, platform_encoding_id) {
+                (0, _) | (3, 0) | (3, 1) | (3, 10) => Some(decode_utf16_be(bytes)),
+                (1, 0) => Some(decode_mac_roman(bytes)),
+                _ => None
+            };
+            records.push(NameRecord { platform_id, platform_encoding_id, language_id, name_id, value });
+        }
+        let mut language_tags = Vec::new();
+        if format == 1 {
+            let count = reader.read_u16::<BigEndian>()?;
+            for _ in 0..count {
+                let length = reader.read_u16::<BigEndian>()?;
+                let offset = reader.read_u16::<BigEndian>()?;
+                language_tags.push(decode_utf16_be(string_data(length, offset)?));
+            }
+        }
+        Ok(NameTable { format, records, language_tags })
+    }
+
+    /// The BCP 47 language tag a record declares, if it uses one. Older records use platform
+    /// specific language IDs instead
+    pub fn language_tag(&self, record: &NameRecord) -> Option<&str> {
+        if record.language_id < 0x8000 { return None; }
+        self.language_tags.get((record.language_id - 0x8000) as usize).map(|s| &s[..])
+    }
+
+    /// Find a name, preferring US English Windows names, then Unicode names, then Windows names in
+    /// any language, then Mac Roman names
+    pub fn name(&self, name_id: NameId) -> Option<&str> {
+        self.records.iter()
+            .filter(|r| r.name_id == name_id && r.value.is_some())
+            .min_by_key(|r| r.rank())
+            .and_then(|r| r.value.as_ref().map(|s| &s[..]))
+    }
+}
+
+fn decode_utf16_be(bytes: &[u8]) -> String {
+    let units = bytes.chunks(2).filter(|c| c.len() == 2).map(BigEndian::read_u16).collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// The characters for Mac Roman bytes 0x80 to 0xff. The lower half is the same as ASCII
+const MAC_ROMAN_HIGH: &str =
+    "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø\
+     ¿¡¬√ƒ≈∆«»…\u{a0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{f8ff}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+
+pub fn decode_mac_roman(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| {
+        if b < 0x80 { b as char } else { MAC_ROMAN_HIGH.chars().nth((b - 0x80) as usize).unwrap() }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    #[test]
+    fn mac_roman() {
+        assert_eq!(MAC_ROMAN_HIGH.chars().count(), 128);
+        assert_eq!(decode_mac_roman(b"Caf\x8e \xa9 \xd2x\xd3"), "Café © “x”");
+    }
+
+    #[test]
+    fn name_table() {
+        // (platform, encoding, language, name id, bytes)
+        let names: Vec<(u16, u16, u16, u16, Vec<u8>)> = vec![
+            (1, 0, 0, 1, b"Mac Family".to_vec()),
+            (3, 1, 0x0407, 1, "Familie".encode_utf16().flat_map(|u| vec![(u >> 8) as u8, u as u8]).collect()),
+            (3, 1, 0x0409, 1, "Family".encode_utf16().flat_map(|u| vec![(u >> 8) as u8, u as u8]).collect()),
+            (3, 1, 0x8000, 4, "Ünïcode".encode_utf16().flat_map(|u| vec![(u >> 8) as u8, u as u8]).collect()),
+            (1, 1, 0, 6, vec![0x82, 0xa0])
+        ];
+        let tag: Vec<u8> = "de-CH".encode_utf16().flat_map(|u| vec![(u >> 8) as u8, u as u8]).collect();
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(1).unwrap();
+        data.write_u16::<BigEndian>(names.len() as u16).unwrap();
+        data.write_u16::<BigEndian>((6 + 12 * names.len() + 2 + 4) as u16).unwrap();
+        let mut offset = 0;
+        for &(plat, enc, lang, id, ref bytes) in &names {
+            for &v in &[plat, enc, lang, id, bytes.len() as u16, offset] {
+                data.write_u16::<BigEndian>(v).unwrap();
+            }
+            offset += bytes.len() as u16;
+        }
+        data.write_u16::<BigEndian>(1).unwrap();
+        data.write_u16::<BigEndian>(tag.len() as u16).unwrap();
+        data.write_u16::<BigEndian>(offset).unwrap();
+        for (_, _, _, _, bytes) in &names {
+            data.extend_from_slice(bytes);
+        }
+        data.extend_from_slice(&tag);
+
+        let table = NameTable::from_bytes(&data).unwrap();
+        assert_eq!(table.format, 1);
+        assert_eq!(table.records.len(), 5);
+        assert_eq!(table.name(NameId::FontFamily), Some("Family"));
+        assert_eq!(table.name(NameId::FullName), Some("Ünïcode"));
+        assert_eq!(table.language_tag(&table.records[3]), Some("de-CH"));
+        assert_eq!(table.language_tag(&table.records[1]), None);
+        // Mac Japanese isn't decoded
+        assert!(table.records[4].value.is_none());
+        assert_eq!(table.name(NameId::PostScriptName), None);
+
+        // a string that runs off the end of the table
+        let len = data.len();
+        assert!(NameTable::from_bytes(&data[..len - 4]).is_err());
+    }
+}
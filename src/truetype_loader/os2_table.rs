@@ -0,0 +1,251 @@
+use std::io;
+use std::io::prelude::*;
+use byteorder::{BigEndian, ReadBytesExt};
+
+use super::*;
+
+bitflags! {
+    pub flags FontSelectionFlags: u16 {
+        const FS_ITALIC             = 0b0000_0000_0000_0001,
+        const FS_UNDERSCORE         = 0b0000_0000_0000_0010,
+        const FS_NEGATIVE           = 0b0000_0000_0000_0100,
+        const FS_OUTLINED           = 0b0000_0000_0000_1000,
+        const FS_STRIKEOUT          = 0b0000_0000_0001_0000,
+        const FS_BOLD               = 0b0000_0000_0010_0000,
+        const FS_REGULAR            = 0b0000_0000_0100_0000,
+        const FS_USE_TYPO_METRICS   = 0b0000_0000_1000_0000,
+        const FS_WWS                = 0b0000_0001_0000_0000,
+        const FS_OBLIQUE            = 0b0000_0010_0000_0000
+    }
+}
+
+/// What the font's license lets documents that embed it do with it, from the low bits of `fsType`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmbeddingPermission {
+    /// The font may be embedded and installed permanently
+    Installable,
+    /// The font must not be embedded without permission from the owner
+    Restricted,
+    /// The font may be embedded to view and print a document, but the document can't be edited
+    PreviewAndPrint,
+    /// The font may be embedded in documents that are then edited
+    Editable
+}
+
+/// The 'OS/2' table. Fields that were added in later versions of the table are `None` when the
+/// table is older than that
+#[derive(Clone, Debug)]
+pub struct OS2Table {
+    pub version: u16,
+    pub x_avg_char_width: i16,
+    pub weight_class: u16,
+    pub width_class: u16,
+    pub fs_type: u16,
+    pub subscript_x_size: i16,
+    pub subscript_y_size: i16,
+    pub subscript_x_offset: i16,
+    pub subscript_y_offset: i16,
+    pub superscript_x_size: i16,
+    pub superscript_y_size: i16,
+    pub superscript_x_offset: i16,
+    pub superscript_y_offset: i16,
+    pub strikeout_size: i16,
+    pub strikeout_position: i16,
+    pub family_class: i16,
+    pub panose: [u8; 10],
+    pub unicode_ranges: [u32; 4],
+    pub vendor_id: [u8; 4],
+    pub fs_selection: FontSelectionFlags,
+    pub first_char_index: u16,
+    pub last_char_index: u16,
+    // the original Apple version 0 table ends before the typographic metrics, these are zero then
+    pub typo_ascender: i16,
+    pub typo_descender: i16,
+    pub typo_line_gap: i16,
+    pub win_ascent: u16,
+    pub win_descent: u16,
+    // version 1
+    pub code_page_ranges: Option<[u32; 2]>,
+    // version 2
+    pub x_height: Option<i16>,
+    pub cap_height: Option<i16>,
+    pub default_char: Option<u16>,
+    pub break_char: Option<u16>,
+    pub max_context: Option<u16>,
+    // version 5, in twentieths of a point
+    pub lower_optical_point_size: Option<u16>,
+    pub upper_optical_point_size: Option<u16>
+}
+
+impl Table for OS2Table {
+    fn tag(&self) -> TableTag { TableTag::WindowsOS2SpecificMetric }
+}
+
+impl OS2Table {
+    pub fn from_binary<R: Read + Seek>(reader: &mut R, length: usize) -> Result<OS2Table, FontError> {
+        let version = reader.read_u16::<BigEndian>()?;
+        if version > 5 {
+            return Err(FontError::UnknownFormat(TableTag::WindowsOS2SpecificMetric, version as u32));
+        }
+        let mut table = OS2Table {
+            version,
+            x_avg_char_width: reader.read_i16::<BigEndian>()?,
+            weight_class: reader.read_u16::<BigEndian>()?,
+            width_class: reader.read_u16::<BigEndian>()?,
+            fs_type: reader.read_u16::<BigEndian>()?,
+            subscript_x_size: reader.read_i16::<BigEndian>()?,
+            subscript_y_size: reader.read_i16::<BigEndian>()?,
+            subscript_x_offset: reader.read_i16::<BigEndian>()?,
+            subscript_y_offset: reader.read_i16::<BigEndian>()?,
+            superscript_x_size: reader.read_i16::<BigEndian>()?,
+            superscript_y_size: reader.read_i16::<BigEndian>()?,
+            superscript_x_offset: reader.read_i16::<BigEndian>()?,
+            superscript_y_offset: reader.read_i16::<BigEndian>()?,
+            strikeout_size: reader.read_i16::<BigEndian>()?,
+            strikeout_position: reader.read_i16::<BigEndian>()?,
+            family_class: reader.read_i16::<BigEndian>()?,
+            panose: {
+                let mut panose = [0u8; 10];
+                reader.read_exact(&mut panose)?;
+                panose
+            },
+            unicode_ranges: [reader.read_u32::<BigEndian>()?, reader.read_u32::<BigEndian>()?,
+                             reader.read_u32::<BigEndian>()?, reader.read_u32::<BigEndian>()?],
+            vendor_id: {
+                let mut vendor_id = [0u8; 4];
+                reader.read_exact(&mut vendor_id)?;
+                vendor_id
+            },
+            fs_selection: FontSelectionFlags::from_bits_truncate(reader.read_u16::<BigEndian>()?),
+            first_char_index: reader.read_u16::<BigEndian>()?,
+            last_char_index: reader.read_u16::<BigEndian>()?,
+            typo_ascender: 0, typo_descender: 0, typo_line_gap: 0, win_ascent: 0, win_descent: 0,
+            code_page_ranges: None,
+            x_height: None, cap_height: None, default_char: None, break_char: None, max_context: None,
+            lower_optical_point_size: None, upper_optical_point_size: None
+        };
+        if version == 0 && length <= 68 {
+            return Ok(table);
+        }
+        table.typo_ascender = reader.read_i16::<BigEndian>()?;
+        table.typo_descender = reader.read_i16::<BigEndian>()?;
+        table.typo_line_gap = reader.read_i16::<BigEndian>()?;
+        table.win_ascent = reader.read_u16::<BigEndian>()?;
+        table.win_descent = reader.read_u16::<BigEndian>()?;
+        if version >= 1 {
+            table.code_page_ranges = Some([reader.read_u32::<BigEndian>()?, reader.read_u32::<BigEndian>()?]);
+        }
+        if version >= 2 {
+            table.x_height = Some(reader.read_i16::<BigEndian>()?);
+            table.cap_height = Some(reader.read_i16::<BigEndian>()?);
+            table.default_char = Some(reader.read_u16::<BigEndian>()?);
+            table.break_char = Some(reader.read_u16::<BigEndian>()?);
+            table.max_context = Some(reader.read_u16::<BigEndian>()?);
+        }
+        if version >= 5 {
+            table.lower_optical_point_size = Some(reader.read_u16::<BigEndian>()?);
+            table.upper_optical_point_size = Some(reader.read_u16::<BigEndian>()?);
+        }
+        Ok(table)
+    }
+
+    pub fn embedding_permission(&self) -> EmbeddingPermission {
+        // only one of the bits should be set, and the least restrictive one wins if there are more
+        if self.fs_type & 0x000f == 0 {
+            EmbeddingPermission::Installable
+        } else if self.fs_type & 0x0008 != 0 {
+            EmbeddingPermission::Editable
+        } else if self.fs_type & 0x0004 != 0 {
+            EmbeddingPermission::PreviewAndPrint
+        } else {
+            EmbeddingPermission::Restricted
+        }
+    }
+
+    /// Whether the font can only be embedded as a whole rather than a subset of its glyphs
+    pub fn no_subsetting(&self) -> bool {
+        self.fs_type & 0x0100 != 0
+    }
+
+    /// Whether only the font's embedded bitmaps may be embedded, not its outlines
+    pub fn bitmap_embedding_only(&self) -> bool {
+        self.fs_type & 0x0200 != 0
+    }
+
+    /// Whether the font claims to cover a Unicode range, by its bit number in `ulUnicodeRange`
+    pub fn supports_unicode_range(&self, bit: usize) -> bool {
+        bit < 128 && self.unicode_ranges[bit / 32] & (1 << (bit % 32)) != 0
+    }
+
+    /// Whether the font claims to work with a code page, by its bit number in `ulCodePageRange`
+    pub fn supports_code_page(&self, bit: usize) -> bool {
+        match self.code_page_ranges {
+            Some(ranges) => bit < 64 && ranges[bit / 32] & (1 << (bit % 32)) != 0,
+            None => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    fn os2(version: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_u16::<BigEndian>(version).unwrap();
+        data.write_i16::<BigEndian>(500).unwrap();
+        data.write_u16::<BigEndian>(700).unwrap();
+        data.write_u16::<BigEndian>(5).unwrap();
+        data.write_u16::<BigEndian>(0x0104).unwrap();
+        for _ in 0..11 { data.write_i16::<BigEndian>(0).unwrap(); }
+        data.extend_from_slice(&[2, 11, 6, 3, 3, 8, 4, 2, 2, 4]);
+        for &r in &[0x8000_0003u32, 0, 1, 0] { data.write_u32::<BigEndian>(r).unwrap(); }
+        data.extend_from_slice(b"TEST");
+        data.write_u16::<BigEndian>(0x00a1).unwrap();
+        data.write_u16::<BigEndian>(0x20).unwrap();
+        data.write_u16::<BigEndian>(0xfffd).unwrap();
+        for &v in &[800i16, -200, 90] { data.write_i16::<BigEndian>(v).unwrap(); }
+        for &v in &[900u16, 250] { data.write_u16::<BigEndian>(v).unwrap(); }
+        if version >= 1 {
+            for &r in &[1u32, 0x8000_0000] { data.write_u32::<BigEndian>(r).unwrap(); }
+        }
+        if version >= 2 {
+            for &v in &[520u16, 700, 0, 0x20, 3] { data.write_u16::<BigEndian>(v).unwrap(); }
+        }
+        if version >= 5 {
+            for &v in &[160u16, 480] { data.write_u16::<BigEndian>(v).unwrap(); }
+        }
+        data
+    }
+
+    #[test]
+    fn os2_versions() {
+        for version in 0..6 {
+            let data = os2(version);
+            let t = OS2Table::from_binary(&mut io::Cursor::new(&data), data.len()).unwrap();
+            assert_eq!(t.weight_class, 700);
+            assert_eq!(t.width_class, 5);
+            assert_eq!(t.fs_selection, FS_ITALIC | FS_BOLD | FS_USE_TYPO_METRICS);
+            assert_eq!(t.embedding_permission(), EmbeddingPermission::PreviewAndPrint);
+            assert!(t.no_subsetting());
+            assert!(t.supports_unicode_range(0) && t.supports_unicode_range(31) && t.supports_unicode_range(64));
+            assert!(!t.supports_unicode_range(2));
+            assert_eq!((t.typo_ascender, t.typo_descender, t.typo_line_gap), (800, -200, 90));
+            assert_eq!((t.win_ascent, t.win_descent), (900, 250));
+            assert_eq!(t.supports_code_page(63), version >= 1);
+            assert_eq!(t.x_height, if version >= 2 { Some(520) } else { None });
+            assert_eq!(t.cap_height, if version >= 2 { Some(700) } else { None });
+            assert_eq!(t.upper_optical_point_size, if version >= 5 { Some(480) } else { None });
+            assert!(OS2Table::from_binary(&mut io::Cursor::new(&data[..data.len() - 1]), data.len() - 1).is_err());
+        }
+
+        // Apple's version 0 tables stop before the typographic metrics
+        let data = os2(0);
+        let t = OS2Table::from_binary(&mut io::Cursor::new(&data[..68]), 68).unwrap();
+        assert_eq!((t.typo_ascender, t.win_ascent), (0, 0));
+
+        let mut data = os2(6);
+        assert!(OS2Table::from_binary(&mut io::Cursor::new(&mut data), 100).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::io;
+use std::io::prelude::*;
+use byteorder::{BigEndian, ReadBytesExt};
+
+use super::*;
+
+/// The names of the 258 glyphs in the standard Macintosh character set, which 'post' tables refer
+/// to by index instead of storing the names again
+const STANDARD_MAC_GLYPH_NAMES: [&str; 258] = [
+    ".notdef", ".null", "nonmarkingreturn", "space", "exclam", "quotedbl", "numbersign", "dollar",
+    "percent", "ampersand", "quotesingle", "parenleft", "parenright", "asterisk", "plus", "comma",
+    "hyphen", "period", "slash", "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question", "at", "A", "B",
+    "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U",
+    "V", "W", "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum",
+    "underscore", "grave", "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n",
+    "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y", "z", "braceleft", "bar", "braceright",
+    "asciitilde", "Adieresis", "Aring", "Ccedilla", "Eacute", "Ntilde", "Odieresis", "Udieresis",
+    "aacute", "agrave", "acircumflex", "adieresis", "atilde", "aring", "ccedilla", "eacute",
+    "egrave", "ecircumflex", "edieresis", "iacute", "igrave", "icircumflex", "idieresis", "ntilde",
+    "oacute", "ograve", "ocircumflex", "odieresis", "otilde", "uacute", "ugrave", "ucircumflex",
+    "udieresis", "dagger", "degree", "cent", "sterling", "section", "bullet", "paragraph",
+    "germandbls", "registered", "copyright", "trademark", "acute", "dieresis", "notequal", "AE",
+    "Oslash", "infinity", "plusminus", "lessequal", "greaterequal", "yen", "mu", "partialdiff",
+    "summation", "product", "pi", "integral", "ordfeminine", "ordmasculine", "Omega", "ae",
+    "oslash", "questiondown", "exclamdown", "logicalnot", "radical", "florin", "approxequal",
+    "Delta", "guillemotleft", "guillemotright", "ellipsis", "nonbreakingspace", "Agrave", "Atilde",
+    "Otilde", "OE", "oe", "endash", "emdash", "quotedblleft", "quotedblright", "quoteleft",
+    "quoteright", "divide", "lozenge", "ydieresis", "Ydieresis", "fraction", "currency",
+    "guilsinglleft", "guilsinglright", "fi", "fl", "daggerdbl", "periodcentered", "quotesinglbase",
+    "quotedblbase", "perthousand", "Acircumflex", "Ecircumflex", "Aacute", "Edieresis", "Egrave",
+    "Iacute", "Icircumflex", "Idieresis", "Igrave", "Oacute", "Ocircumflex", "apple", "Ograve",
+    "Uacute", "Ucircumflex", "Ugrave", "dotlessi", "circumflex", "tilde", "macron", "breve",
+    "dotaccent", "ring", "cedilla", "hungarumlaut", "ogonek", "caron", "Lslash", "lslash", "Scaron",
+    "scaron", "Zcaron", "zcaron", "brokenbar", "Eth", "eth", "Yacute", "yacute", "Thorn", "thorn",
+    "minus", "multiply", "onesuperior", "twosuperior", "threesuperior", "onehalf", "onequarter",
+    "threequarters", "franc", "Gbreve", "gbreve", "Idotaccent", "Scedilla", "scedilla", "Cacute",
+    "cacute", "Ccaron", "ccaron", "dcroat"
+];
+
+/// The 'post' table
+#[derive(Debug)]
+pub struct PostScriptTable {
+    pub version: Fixed,
+    /// Degrees counter-clockwise from vertical, negative for fonts that lean to the right
+    pub italic_angle: f32,
+    pub underline_position: i16,
+    pub underline_thickness: i16,
+    pub is_fixed_pitch: bool,
+    pub min_mem_type42: u32,
+    pub max_mem_type42: u32,
+    pub min_mem_type1: u32,
+    pub max_mem_type1: u32,
+    /// For each glyph, the index of its name. Indices below 258 are standard Macintosh names,
+    /// the rest are into `custom_names`. Empty for version 3 tables, which don't have names
+    glyph_name_indices: Vec<u16>,
+    custom_names: Vec<String>
+}
+
+impl Table for PostScriptTable {
+    fn tag(&self) -> TableTag { TableTag::PostScriptInfo }
+}
+
+impl PostScriptTable {
+    pub fn from_binary<R: Read + Seek>(reader: &mut R, length: usize) -> Result<PostScriptTable, FontError> {
+        let version = Fixed::from_binary::<R, BigEndian>(reader)?;
+        let mut table = PostScriptTable {
+            version,
+            italic_angle: reader.read_i32::<BigEndian>()? as f32 / 65536.0,
+            underline_position: reader.read_i16::<BigEndian>()?,
+            underline_thickness: reader.read_i16::<BigEndian>()?,
+            is_fixed_pitch: reader.read_u32::<BigEndian>()? != 0,
+            min_mem_type42: reader.read_u32::<BigEndian>()?,
+            max_mem_type42: reader.read_u32::<BigEndian>()?,
+            min_mem_type1: reader.read_u32::<BigEndian>()?,
+            max_mem_type1: reader.read_u32::<BigEndian>()?,
+            glyph_name_indices: Vec::new(),
+            custom_names: Vec::new()
+        };
+        match version.to_bits() {
+            0x0001_0000 => {
+                table.glyph_name_indices = (0..STANDARD_MAC_GLYPH_NAMES.len() as u16).collect();
+            },
+            0x0002_0000 => {
+                let num_glyphs = reader.read_u16::<BigEndian>()?;
+                let mut num_custom = 0;
+                for _ in 0..num_glyphs {
+                    let index = reader.read_u16::<BigEndian>()?;
+                    if index as usize >= STANDARD_MAC_GLYPH_NAMES.len() {
+                        num_custom = num_custom.max(index as usize - STANDARD_MAC_GLYPH_NAMES.len() + 1);
+                    }
+                    table.glyph_name_indices.push(index);
+                }
+                // the names are Pascal strings that run to the end of the table
+                let mut position = 34 + 2 * num_glyphs as usize;
+                while position < length {
+                    let len = reader.read_u8()? as usize;
+                    let mut name = vec![0u8; len];
+                    reader.read_exact(&mut name)?;
+                    table.custom_names.push(decode_mac_roman(&name));
+                    position += len + 1;
+                }
+                if table.custom_names.len() < num_custom {
+                    return Err(FontError::InconsistentCounts(TableTag::PostScriptInfo, "glyph name index past the end of the names"));
+                }
+            },
+            0x0002_5000 => {
+                // each glyph's name is a standard name, offset from its glyph index
+                let num_glyphs = reader.read_u16::<BigEndian>()?;
+                for gid in 0..num_glyphs {
+                    let index = gid as i32 + reader.read_i8()? as i32;
+                    if index < 0 || index as usize >= STANDARD_MAC_GLYPH_NAMES.len() {
+                        return Err(FontError::InconsistentCounts(TableTag::PostScriptInfo, "glyph name offset outside of the standard names"));
+                    }
+                    table.glyph_name_indices.push(index as u16);
+                }
+            },
+            0x0003_0000 => {},
+            v => return Err(FontError::UnknownFormat(TableTag::PostScriptInfo, v as u32))
+        }
+        Ok(table)
+    }
+
+    /// The PostScript name of a glyph, if the table has names
+    pub fn glyph_name(&self, glyph_index: usize) -> Option<&str> {
+        let index = *self.glyph_name_indices.get(glyph_index)? as usize;
+        if index < STANDARD_MAC_GLYPH_NAMES.len() {
+            Some(STANDARD_MAC_GLYPH_NAMES[index])
+        } else {
+            self.custom_names.get(index - STANDARD_MAC_GLYPH_NAMES.len()).map(|s| &s[..])
+        }
+    }
+
+    /// Find the first glyph with a name. This searches every glyph, so it is meant for occasional
+    /// lookups rather than mapping whole strings
+    pub fn glyph_by_name(&self, name: &str) -> Option<usize> {
+        (0..self.glyph_name_indices.len()).find(|&gid| self.glyph_name(gid) == Some(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    fn post_header(version: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_u32::<BigEndian>(version).unwrap();
+        data.write_i32::<BigEndian>(-12 << 16 | 0x8000).unwrap();
+        data.write_i16::<BigEndian>(-100).unwrap();
+        data.write_i16::<BigEndian>(50).unwrap();
+        data.write_u32::<BigEndian>(1).unwrap();
+        for _ in 0..4 { data.write_u32::<BigEndian>(0).unwrap(); }
+        data
+    }
+
+    #[test]
+    fn post_versions() {
+        assert_eq!(STANDARD_MAC_GLYPH_NAMES[257], "dcroat");
+
+        let data = post_header(0x00010000);
+        let t = PostScriptTable::from_binary(&mut io::Cursor::new(&data), data.len()).unwrap();
+        assert_eq!(t.italic_angle, -11.5);
+        assert_eq!((t.underline_position, t.underline_thickness, t.is_fixed_pitch), (-100, 50, true));
+        assert_eq!(t.glyph_name(36), Some("A"));
+        assert_eq!(t.glyph_name(258), None);
+        assert_eq!(t.glyph_by_name("dcroat"), Some(257));
+
+        let mut data = post_header(0x00020000);
+        data.write_u16::<BigEndian>(4).unwrap();
+        for &i in &[0u16, 259, 36, 258] { data.write_u16::<BigEndian>(i).unwrap(); }
+        for name in &["uni2603", "A.alt"] {
+            data.write_u8(name.len() as u8).unwrap();
+            data.extend_from_slice(name.as_bytes());
+        }
+        let t = PostScriptTable::from_binary(&mut io::Cursor::new(&data), data.len()).unwrap();
+        assert_eq!(t.glyph_name(0), Some(".notdef"));
+        assert_eq!(t.glyph_name(1), Some("A.alt"));
+        assert_eq!(t.glyph_name(2), Some("A"));
+        assert_eq!(t.glyph_name(3), Some("uni2603"));
+        assert_eq!(t.glyph_name(4), None);
+        assert_eq!(t.glyph_by_name("uni2603"), Some(3));
+        assert_eq!(t.glyph_by_name("B"), None);
+        // the last name is missing
+        let len = data.len() - 6;
+        assert!(PostScriptTable::from_binary(&mut io::Cursor::new(&data[..len]), len).is_err());
+
+        let mut data = post_header(0x00025000);
+        data.write_u16::<BigEndian>(2).unwrap();
+        data.write_i8(3).unwrap();
+        data.write_i8(35).unwrap();
+        let t = PostScriptTable::from_binary(&mut io::Cursor::new(&data), data.len()).unwrap();
+        assert_eq!(t.glyph_name(0), Some("space"));
+        assert_eq!(t.glyph_name(1), Some("A"));
+
+        let data = post_header(0x00030000);
+        let t = PostScriptTable::from_binary(&mut io::Cursor::new(&data), data.len()).unwrap();
+        assert_eq!(t.glyph_name(0), None);
+
+        let data = post_header(0x00040000);
+        assert!(PostScriptTable::from_binary(&mut io::Cursor::new(&data), data.len()).is_err());
+    }
+}